@@ -0,0 +1,83 @@
+//! Counts heap allocations on the GraphQL basic auth check.
+//!
+//! Compares the previous per-request approach (decode the header and split the configured
+//! credentials on every request) with the precomputed `BasicAuthCredentials`.
+//!
+//! Run with `cargo bench --bench graphql_basic_auth`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use axum::http::HeaderValue;
+use base64::{engine::general_purpose, Engine};
+use server::common::middlewares::basic_auth::BasicAuthCredentials;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: usize = 100_000;
+const CONFIG: &str = "admin:secret";
+
+/// The check as it was done before, on every request.
+fn legacy_matches(config: &str, header_value: &HeaderValue) -> bool {
+  if let Ok(auth_str) = header_value.to_str() {
+    if let Some(encoded) = auth_str.strip_prefix("Basic ") {
+      if let Ok(decoded) = general_purpose::STANDARD.decode(encoded) {
+        if let Ok(decoded_str) = String::from_utf8(decoded) {
+          let parts: Vec<&str> = decoded_str.splitn(2, ':').collect();
+          let config_parts: Vec<&str> = config.split(':').collect();
+          let username = config_parts[0].to_string();
+          let password = config_parts[1].to_string();
+          return parts.len() == 2 && parts[0] == username && parts[1] == password;
+        }
+      }
+    }
+  }
+  false
+}
+
+fn measure(name: &str, f: impl Fn() -> bool) {
+  let before = ALLOCATIONS.load(Ordering::Relaxed);
+  let start = Instant::now();
+  for _ in 0..ITERATIONS {
+    assert!(black_box(f()));
+  }
+  let elapsed = start.elapsed();
+  let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+  println!(
+    "{:<12} {:>8.2} allocations/request {:>10.1} ns/request",
+    name,
+    allocations as f64 / ITERATIONS as f64,
+    elapsed.as_nanos() as f64 / ITERATIONS as f64,
+  );
+}
+
+fn main() {
+  let header_value = HeaderValue::from_static("Basic YWRtaW46c2VjcmV0");
+  let credentials = BasicAuthCredentials::parse(CONFIG).expect("valid credentials");
+
+  measure("legacy", || {
+    legacy_matches(black_box(CONFIG), black_box(&header_value))
+  });
+  measure("precomputed", || {
+    credentials.matches(black_box(&header_value))
+  });
+}
@@ -1,6 +1,14 @@
+use std::sync::Arc;
+
 use axum::Router;
 
-use crate::common::{api_doc, config::telemetry, config::Config, graphql, middlewares};
+use crate::common::background::BackgroundTasks;
+use crate::common::config::shutdown::Readiness;
+use crate::common::events::UserEvents;
+use crate::common::notifier::{LogNotifier, SharedNotifier};
+use crate::common::storage::{LocalStorage, SharedStorage};
+use crate::common::streams::StreamLimit;
+use crate::common::{api_doc, config::telemetry, config::Config, graphql, metrics, middlewares};
 use crate::database::Db;
 use crate::modules;
 
@@ -8,28 +16,82 @@ use crate::modules;
 pub struct AppState {
   pub db: Db,
   pub cfg: Config,
+  pub notifier: SharedNotifier,
+  pub readiness: Readiness,
+  pub background: BackgroundTasks,
+  pub streams: StreamLimit,
+  pub events: UserEvents,
+  pub storage: SharedStorage,
 }
 
 pub fn router(cfg: Config, db: Db) -> Router {
-  let app_state = AppState { db, cfg };
+  router_with_notifier(cfg, db, Arc::new(LogNotifier))
+}
+
+/// Same as `router`, but with a custom `Notifier` used to deliver emails to users.
+pub fn router_with_notifier(cfg: Config, db: Db, notifier: SharedNotifier) -> Router {
+  let app_state = AppState {
+    db,
+    background: BackgroundTasks::new(cfg.background_concurrency),
+    streams: StreamLimit::new(cfg.max_stream_connections),
+    events: UserEvents::default(),
+    storage: Arc::new(LocalStorage::new(&cfg.storage_dir)),
+    cfg,
+    notifier,
+    readiness: Readiness::default(),
+  };
+  let schemas = graphql::build_schema(&app_state).expect("Failed to build the GraphQL schema");
+  router_with_state(app_state, schemas)
+}
 
+/// Same as `router`, from a prepared state, e.g. to keep a handle on its `Readiness`, and the
+/// GraphQL schemas built with `graphql::build_schema`.
+pub fn router_with_state(app_state: AppState, schemas: graphql::Schemas) -> Router {
   // Middleware that adds high level tracing to a Service.
   // Trace comes with good defaults but also supports customizing many aspects of the output:
   // https://docs.rs/tower-http/latest/tower_http/trace/index.html
   let trace_layer = telemetry::trace_layer();
 
+  // Logs one line per request, REST and GraphQL alike, with its latency, request id and user.
+  let access_log_layer = axum::middleware::from_fn(middlewares::access_log);
+
+  // Logs the invalid fields, not their values, of payloads failing validation when
+  // LOG_VALIDATION_FAILURES is enabled, to spot misbehaving clients.
+  let validation_log_layer = axum::middleware::from_fn_with_state(
+    app_state.cfg.clone(),
+    middlewares::log_validation_failures,
+  );
+
   // Sets 'x-request-id' header with randomly generated uuid v7.
   let request_id_layer = middlewares::request_id_layer();
 
   // Propagates 'x-request-id' header from the request to the response.
   let propagate_request_id_layer = middlewares::propagate_request_id_layer();
 
-  // Layer that applies the Cors middleware which adds headers for CORS.
-  let cors_layer = middlewares::cors_layer();
+  // Continues the W3C trace of the 'traceparent' and 'tracestate' headers, or starts a new one,
+  // for the trace layer to record on the request span, and echoes them on the response.
+  let trace_context_layer = axum::middleware::from_fn(middlewares::trace_context);
 
-  // Layer that applies the Timeout middleware, which sets a timeout for requests.
-  // The default value is 15 seconds.
-  let timeout_layer = middlewares::timeout_layer();
+  // Drops an incoming 'x-request-id' that isn't a UUID when REQUEST_ID_VALIDATE is enabled, for
+  // the request id layer to generate a fresh one.
+  let validate_request_id_layer =
+    axum::middleware::from_fn_with_state(app_state.cfg.clone(), middlewares::validate_request_id);
+
+  // Layer that applies the Cors middleware which adds headers for CORS, restricted to
+  // CORS_ALLOWED_ORIGINS when set.
+  let cors_layer = middlewares::cors_layer(&app_state.cfg.cors_allowed_origins, &app_state.cfg.env);
+
+  // Layer that fails requests with 408 Request Timeout after REQUEST_TIMEOUT_SECONDS, heavy
+  // routes set their own HEAVY_REQUEST_TIMEOUT_SECONDS.
+  let timeout_layer =
+    axum::middleware::from_fn_with_state(app_state.cfg.request_timeout, middlewares::timeout);
+
+  // Layer that rejects request bodies larger than MAX_BODY_BYTES with 413 Payload Too Large.
+  let body_limit_layer = middlewares::body_limit_layer(app_state.cfg.max_body_bytes);
+
+  // Layer that compresses responses of at least COMPRESSION_MIN_BYTES, as negotiated from the
+  // 'Accept-Encoding' header.
+  let compression_layer = middlewares::compression_layer(app_state.cfg.compression_min_bytes);
 
   // Any trailing slashes from request paths will be removed. For example, a request with `/foo/`
   // will be changed to `/foo` before reaching the internal service.
@@ -39,10 +101,13 @@ pub fn router(cfg: Config, db: Db) -> Router {
   let router = modules::router(axum::extract::State(app_state.clone()));
 
   // Create the API documentation using OpenAPI and Swagger UI.
-  let api_doc = api_doc::swagger_ui(&app_state.cfg);
+  let api_doc = api_doc::router(&app_state.cfg);
 
   // Create the GraphQL router with playground and query handler.
-  let graphql_router = graphql::router(app_state.clone());
+  let graphql_router = graphql::router(app_state.clone(), schemas);
+
+  // Expose the Prometheus metrics.
+  let metrics_router = metrics::router(&app_state.cfg);
 
   // Combine all the routes and apply the middleware layers.
   // The order of the layers is important. The first layer is the outermost layer.
@@ -50,11 +115,20 @@ pub fn router(cfg: Config, db: Db) -> Router {
     .merge(router)
     .merge(api_doc)
     .merge(graphql_router)
+    .merge(metrics_router)
+    .layer(axum::middleware::from_fn(middlewares::track_metrics))
+    .layer(axum::middleware::from_fn(middlewares::rate_limit_headers))
     .layer(normalize_path_layer)
+    .layer(body_limit_layer)
+    .layer(compression_layer)
     .layer(cors_layer)
     .layer(timeout_layer)
     .layer(propagate_request_id_layer)
+    .layer(validation_log_layer)
+    .layer(access_log_layer)
     .layer(trace_layer)
+    .layer(trace_context_layer)
     .layer(request_id_layer)
+    .layer(validate_request_id_layer)
     .with_state(app_state)
 }
@@ -0,0 +1,47 @@
+use server::common::api_doc;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+fn print_usage() {
+  eprintln!("Usage: spec [PATH]");
+  eprintln!();
+  eprintln!("Writes the OpenAPI spec served at /docs/api-doc/openapi.json to PATH, or to stdout");
+  eprintln!("when PATH is missing or '-'. Needs neither a database nor a port.");
+  eprintln!();
+  eprintln!("Examples:");
+  eprintln!("  cargo run --bin spec");
+  eprintln!("  cargo run --bin spec -- openapi.json");
+}
+
+fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  let path = match args.as_slice() {
+    [] => None,
+    [flag] if flag == "-h" || flag == "--help" => {
+      print_usage();
+      return;
+    }
+    [path] if path == "-" => None,
+    [path] => Some(path.clone()),
+    _ => {
+      print_usage();
+      process::exit(1);
+    }
+  };
+
+  let spec = api_doc::openapi_spec();
+  let result = match &path {
+    Some(path) => fs::write(path, &spec),
+    None => io::stdout().write_all(spec.as_bytes()),
+  };
+  if let Err(e) = result {
+    eprintln!(
+      "Failed to write the OpenAPI spec to {}: {}",
+      path.as_deref().unwrap_or("stdout"),
+      e
+    );
+    process::exit(1);
+  }
+}
@@ -1,3 +1,16 @@
+use std::sync::{Arc, OnceLock};
+
+use axum::{
+  body::Bytes,
+  http::{
+    header::{CACHE_CONTROL, CONTENT_TYPE, ETAG},
+    HeaderMap, HeaderValue,
+  },
+  response::{IntoResponse, Response},
+  routing::get,
+  Router,
+};
+use hyper::StatusCode;
 use utoipa::{
   openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme},
   Modify, OpenApi,
@@ -6,6 +19,11 @@ use utoipa_swagger_ui::{BasicAuth, Config as SwaggerConfig, SwaggerUi};
 use utoipauto::utoipauto;
 
 use super::config::Config;
+use super::etag;
+use super::middlewares::{self, basic_auth::BasicAuthCredentials};
+
+/// The spec only changes with the build, clients revalidate it daily with its `ETag`.
+const OPENAPI_CACHE_CONTROL: &str = "public, max-age=86400";
 
 #[utoipauto]
 #[derive(OpenApi)]
@@ -35,28 +53,143 @@ impl Modify for SecurityAddon {
   }
 }
 
-/// Create the API documentation using OpenAPI and Swagger UI.
+/// Create the API documentation router, serving the Swagger UI and the cached OpenAPI JSON.
+pub fn router<S>(cfg: &Config) -> Router<S>
+where
+  S: Clone + Send + Sync + 'static,
+{
+  if !cfg.features.swagger_enabled {
+    return Router::new();
+  }
+
+  let mut openapi_router = Router::new().route(&openapi_url(cfg), get(openapi_json));
+  if !cfg.swagger_basic_auth.is_empty() {
+    // The format is checked by `swagger_ui`
+    if let Some(credentials) = BasicAuthCredentials::parse(cfg.swagger_basic_auth.expose()) {
+      openapi_router = openapi_router.layer(axum::middleware::from_fn_with_state(
+        Arc::new(credentials),
+        middlewares::basic_auth::basic_auth_layer,
+      ));
+    }
+  }
+
+  Router::new().merge(swagger_ui(cfg)).merge(openapi_router)
+}
+
+fn openapi_url(cfg: &Config) -> String {
+  cfg.swagger_endpoint.clone() + "/api-doc/openapi.json"
+}
+
+/// Serves the OpenAPI JSON, answering `304 Not Modified` when `If-None-Match` matches its `ETag`.
+async fn openapi_json(headers: HeaderMap) -> Response {
+  let (json, etag) = cached_openapi_json();
+  let cache_headers = [
+    (ETAG, etag.clone()),
+    (
+      CACHE_CONTROL,
+      HeaderValue::from_static(OPENAPI_CACHE_CONTROL),
+    ),
+  ];
+
+  if etag::is_fresh(&headers, etag) {
+    return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+  }
+
+  (
+    cache_headers,
+    [(CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+    json.clone(),
+  )
+    .into_response()
+}
+
+/// Serializes the OpenAPI spec as served at runtime, with the security schemes of `SecurityAddon`.
+/// Also dumped by the `spec` binary, which needs neither a database nor a port.
+pub fn openapi_spec() -> String {
+  ApiDoc::openapi()
+    .to_json()
+    .expect("OpenAPI spec should serialize to JSON")
+}
+
+/// Returns the serialized spec and its `ETag`, computed on first use.
+fn cached_openapi_json() -> &'static (Bytes, HeaderValue) {
+  static OPENAPI_JSON: OnceLock<(Bytes, HeaderValue)> = OnceLock::new();
+  OPENAPI_JSON.get_or_init(|| {
+    let json = openapi_spec();
+    let etag = etag::etag(json.as_bytes());
+    (Bytes::from(json), etag)
+  })
+}
+
+/// Create the Swagger UI, pointing to the OpenAPI JSON served by `router`.
 pub fn swagger_ui(cfg: &Config) -> SwaggerUi {
-  SwaggerUi::new(cfg.swagger_endpoint.clone())
-    .url(
-      cfg.swagger_endpoint.clone() + "/api-doc/openapi.json",
-      ApiDoc::openapi(),
-    )
-    .config({
-      let mut config = SwaggerConfig::default().persist_authorization(true);
-      if !cfg.swagger_basic_auth.is_empty() {
-        let parts: Vec<&str> = cfg.swagger_basic_auth.split(':').collect();
-        if parts.len() == 2 {
-          config = config.basic_auth(BasicAuth {
-            username: parts[0].to_string(),
-            password: parts[1].to_string(),
-          });
-        } else {
-          // We're immediately panicking here because this is a configuration error that should be
-          // caught during application startup.
-          panic!("Invalid format for swagger_basic_auth. Expected 'username:password'.");
-        }
+  SwaggerUi::new(cfg.swagger_endpoint.clone()).config({
+    let mut config = SwaggerConfig::from(openapi_url(cfg)).persist_authorization(true);
+    if !cfg.swagger_basic_auth.is_empty() {
+      // Split like `BasicAuthCredentials::parse`, passwords may contain colons
+      if let Some((username, password)) = cfg.swagger_basic_auth.expose().split_once(':') {
+        config = config.basic_auth(BasicAuth {
+          username: username.to_string(),
+          password: password.to_string(),
+        });
+      } else {
+        // We're immediately panicking here because this is a configuration error that should be
+        // caught during application startup.
+        panic!("Invalid format for swagger_basic_auth. Expected 'username:password'.");
       }
-      config
-    })
+    }
+    config
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, extract::Request};
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::common::config::Configuration;
+
+  async fn get_openapi(if_none_match: Option<&HeaderValue>) -> Response {
+    let app: Router = router(&Configuration::for_tests());
+    let mut req = Request::builder().uri("/docs/api-doc/openapi.json");
+    if let Some(etag) = if_none_match {
+      req = req.header(IF_NONE_MATCH, etag);
+    }
+    app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_openapi_json_is_cacheable() {
+    let response = get_openapi(None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    assert_eq!(response.headers()[CACHE_CONTROL], OPENAPI_CACHE_CONTROL);
+    assert!(response.headers().contains_key(ETAG));
+  }
+
+  #[tokio::test]
+  async fn test_openapi_json_not_modified() {
+    let response = get_openapi(None).await;
+    let etag = response.headers()[ETAG].clone();
+
+    let response = get_openapi(Some(&etag)).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[ETAG], etag);
+
+    let response = get_openapi(Some(&HeaderValue::from_static("\"stale\""))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn test_dumped_spec_matches_the_served_one() {
+    let response = get_openapi(None).await;
+    let served = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert_eq!(openapi_spec().as_bytes(), served);
+
+    let spec: serde_json::Value = serde_json::from_str(&openapi_spec()).unwrap();
+    assert!(spec["components"]["securitySchemes"]["bearerAuth"].is_object());
+  }
 }
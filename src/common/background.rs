@@ -0,0 +1,65 @@
+use std::{future::Future, sync::Arc};
+
+use tokio::{sync::Semaphore, task::JoinHandle};
+
+/// Runs background work, e.g. emails, on `tokio` tasks with at most `BACKGROUND_CONCURRENCY`
+/// of them running at once.
+///
+/// Tasks are spawned right away and wait for a slot, so callers never block on a busy executor.
+#[derive(Clone, Debug)]
+pub struct BackgroundTasks(Arc<Semaphore>);
+
+impl BackgroundTasks {
+  pub fn new(concurrency: usize) -> Self {
+    Self(Arc::new(Semaphore::new(concurrency.max(1))))
+  }
+
+  /// Spawns `task`, which starts once fewer than `concurrency` tasks are running.
+  pub fn spawn<F>(&self, task: F) -> JoinHandle<()>
+  where
+    F: Future<Output = ()> + Send + 'static,
+  {
+    let semaphore = self.0.clone();
+    tokio::spawn(async move {
+      // The semaphore is never closed
+      let _permit = semaphore
+        .acquire_owned()
+        .await
+        .expect("background semaphore closed");
+      task.await;
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::time::Duration;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_concurrency_limit_is_respected() {
+    let tasks = BackgroundTasks::new(2);
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+
+    let handles: Vec<_> = (0..8)
+      .map(|_| {
+        let running = running.clone();
+        let max_running = max_running.clone();
+        tasks.spawn(async move {
+          let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+          max_running.fetch_max(now, Ordering::SeqCst);
+          tokio::time::sleep(Duration::from_millis(10)).await;
+          running.fetch_sub(1, Ordering::SeqCst);
+        })
+      })
+      .collect();
+    for handle in handles {
+      handle.await.unwrap();
+    }
+
+    assert_eq!(max_running.load(Ordering::SeqCst), 2);
+  }
+}
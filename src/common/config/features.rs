@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::config::{ConfigError, Vars};
+
+/// Boolean toggles of the application, read once from environment variables on startup.
+///
+/// Admins can inspect them with `GET /api/v1/admin/features`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Features {
+  /// Whether new users can sign up through `POST /api/v1/auth/register` (default: true)
+  pub registration_enabled: bool,
+
+  /// Whether the API answers 503 Service Unavailable, except for the health and admin
+  /// endpoints (default: false)
+  pub maintenance_mode: bool,
+
+  /// Whether the Swagger UI and the OpenAPI JSON are served (default: true)
+  pub swagger_enabled: bool,
+
+  /// Whether the GraphiQL playground is served on `GET` of the GraphQL endpoint (default: true)
+  pub graphql_playground_enabled: bool,
+}
+
+impl Default for Features {
+  fn default() -> Self {
+    Features {
+      registration_enabled: true,
+      maintenance_mode: false,
+      swagger_enabled: true,
+      graphql_playground_enabled: true,
+    }
+  }
+}
+
+impl Features {
+  /// Reads the feature flags from the settings, falling back to the defaults.
+  pub fn from_vars(vars: &Vars) -> Result<Self, ConfigError> {
+    let defaults = Features::default();
+    Ok(Features {
+      registration_enabled: flag(vars, "REGISTRATION_ENABLED", defaults.registration_enabled)?,
+      maintenance_mode: flag(vars, "MAINTENANCE_MODE", defaults.maintenance_mode)?,
+      swagger_enabled: flag(vars, "SWAGGER_ENABLED", defaults.swagger_enabled)?,
+      graphql_playground_enabled: flag(
+        vars,
+        "GRAPHQL_PLAYGROUND_ENABLED",
+        defaults.graphql_playground_enabled,
+      )?,
+    })
+  }
+}
+
+fn flag(vars: &Vars, name: &'static str, default: bool) -> Result<bool, ConfigError> {
+  vars.parse(name, &default.to_string(), "a valid boolean")
+}
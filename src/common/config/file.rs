@@ -0,0 +1,149 @@
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+
+/// Supported formats of the `CONFIG_FILE`, picked by file extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+  Toml,
+  Yaml,
+}
+
+impl FileFormat {
+  fn from_path(path: &Path) -> Result<Self, String> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+      Some("toml") => Ok(FileFormat::Toml),
+      Some("yaml" | "yml") => Ok(FileFormat::Yaml),
+      _ => Err("unsupported extension, expected .toml, .yaml or .yml".to_string()),
+    }
+  }
+}
+
+/// Settings of the `CONFIG_FILE`, by uppercased environment variable name.
+///
+/// Keys are the environment variable names, e.g. `DATABASE_URL` or `database_url`, and lists
+/// such as `GRAPHQL_ENTITIES` may be arrays. `Configuration::try_new` only falls back to them
+/// for the variables that aren't set, so the precedence is env > file > defaults.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(try_from = "BTreeMap<String, serde_json::Value>")]
+pub struct FileSettings(BTreeMap<String, String>);
+
+impl FileSettings {
+  /// Value of the setting `name`, as it would be read from the environment.
+  pub fn get(&self, name: &str) -> Option<&str> {
+    self.0.get(name).map(String::as_str)
+  }
+}
+
+impl TryFrom<BTreeMap<String, serde_json::Value>> for FileSettings {
+  type Error = String;
+
+  fn try_from(values: BTreeMap<String, serde_json::Value>) -> Result<Self, Self::Error> {
+    values
+      .into_iter()
+      .map(|(key, value)| {
+        let value = to_env_value(&value).ok_or_else(|| {
+          format!(
+            "{}: expected a string, number, boolean or array of them",
+            key
+          )
+        })?;
+        Ok((key.to_uppercase(), value))
+      })
+      .collect::<Result<_, String>>()
+      .map(FileSettings)
+  }
+}
+
+/// Loads the settings of the TOML or YAML file at `path`.
+pub fn load(path: &str) -> Result<FileSettings, String> {
+  let path = Path::new(path);
+  let format = FileFormat::from_path(path)?;
+  let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+  parse(&content, format)
+}
+
+fn parse(content: &str, format: FileFormat) -> Result<FileSettings, String> {
+  match format {
+    FileFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+    FileFormat::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+  }
+}
+
+fn to_env_value(value: &serde_json::Value) -> Option<String> {
+  match value {
+    serde_json::Value::String(value) => Some(value.clone()),
+    serde_json::Value::Number(value) => Some(value.to_string()),
+    serde_json::Value::Bool(value) => Some(value.to_string()),
+    serde_json::Value::Array(values) => values
+      .iter()
+      .map(|value| match value {
+        serde_json::Value::Array(_) => None,
+        value => to_env_value(value),
+      })
+      .collect::<Option<Vec<_>>>()
+      .map(|values| values.join(",")),
+    serde_json::Value::Null | serde_json::Value::Object(_) => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_toml() {
+    let values = parse(
+      r#"
+        database_url = "postgres://localhost/app"
+        PORT = 8080
+        DATABASE_RUN_SEEDS = false
+        graphql_entities = ["users", "password_reset_tokens"]
+      "#,
+      FileFormat::Toml,
+    )
+    .unwrap();
+    assert_eq!(values.get("DATABASE_URL"), Some("postgres://localhost/app"));
+    assert_eq!(values.get("PORT"), Some("8080"));
+    assert_eq!(values.get("DATABASE_RUN_SEEDS"), Some("false"));
+    assert_eq!(
+      values.get("GRAPHQL_ENTITIES"),
+      Some("users,password_reset_tokens")
+    );
+  }
+
+  #[test]
+  fn test_parse_yaml() {
+    let values = parse(
+      "APP_ENV: production\nJWT_EXPIRATION: 15m\nBCRYPT_COST: 10\n",
+      FileFormat::Yaml,
+    )
+    .unwrap();
+    assert_eq!(values.get("APP_ENV"), Some("production"));
+    assert_eq!(values.get("JWT_EXPIRATION"), Some("15m"));
+    assert_eq!(values.get("BCRYPT_COST"), Some("10"));
+  }
+
+  #[test]
+  fn test_parse_errors_are_reported() {
+    let err = parse("PORT = ", FileFormat::Toml).err().unwrap();
+    assert!(err.contains("line 1"), "{}", err);
+
+    let err = parse("[database]\nurl = \"x\"\n", FileFormat::Toml)
+      .err()
+      .unwrap();
+    assert!(err.contains("database: expected"), "{}", err);
+  }
+
+  #[test]
+  fn test_format_from_extension() {
+    assert_eq!(
+      FileFormat::from_path(Path::new("config.toml")),
+      Ok(FileFormat::Toml)
+    );
+    assert_eq!(
+      FileFormat::from_path(Path::new("config.yml")),
+      Ok(FileFormat::Yaml)
+    );
+    assert!(FileFormat::from_path(Path::new("config.json")).is_err());
+  }
+}
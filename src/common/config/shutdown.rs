@@ -1,5 +1,30 @@
+use std::{
+  future::Future,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+
 use tokio::signal;
 
+/// Whether the instance should keep receiving traffic, reported by the readiness probe.
+#[derive(Clone, Debug, Default)]
+pub struct Readiness(Arc<AtomicBool>);
+
+impl Readiness {
+  /// Returns true once the shutdown has started.
+  pub fn is_shutting_down(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+
+  /// Makes the readiness probe fail, so that load balancers stop routing to the instance.
+  pub fn shut_down(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+}
+
 pub async fn shutdown_signal() {
   let ctrl_c = async {
     signal::ctrl_c()
@@ -25,3 +50,41 @@ pub async fn shutdown_signal() {
 
   println!("Shutdown signal received. Shutting down...");
 }
+
+/// Waits for the shutdown signal, then reports the instance as not ready for `delay` before
+/// letting the graceful drain start (`PRE_SHUTDOWN_DELAY_SECS`).
+pub async fn shutdown_signal_with_delay(readiness: Readiness, delay: Duration) {
+  pre_shutdown(shutdown_signal(), readiness, delay).await
+}
+
+async fn pre_shutdown(signal: impl Future<Output = ()>, readiness: Readiness, delay: Duration) {
+  signal.await;
+  readiness.shut_down();
+  if !delay.is_zero() {
+    tracing::info!("Readiness is failing, draining connections in {:?}", delay);
+    tokio::time::sleep(delay).await;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_readiness_fails_during_pre_shutdown_delay() {
+    let readiness = Readiness::default();
+    assert!(!readiness.is_shutting_down());
+
+    let handle = tokio::spawn(pre_shutdown(
+      async {},
+      readiness.clone(),
+      Duration::from_millis(200),
+    ));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(readiness.is_shutting_down());
+    assert!(!handle.is_finished());
+
+    handle.await.unwrap();
+    assert!(readiness.is_shutting_down());
+  }
+}
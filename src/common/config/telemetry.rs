@@ -1,29 +1,196 @@
+use std::{io::Write, thread, time::Duration};
+
+use axum::http::Request;
 use tower_http::{
   classify::{ServerErrorsAsFailures, SharedClassifier},
-  trace::{DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, TraceLayer},
+  trace::{DefaultOnRequest, DefaultOnResponse, MakeSpan, TraceLayer},
 };
-use tracing::Level;
+use tracing::{Level, Span};
+use tracing_appender::non_blocking::{NonBlocking, NonBlockingBuilder, WorkerGuard};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+use crate::common::config::Config;
+use crate::common::middlewares::TraceContext;
+
+/// How often dropped log lines are reported.
+const DROPPED_LINES_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
 /// The `EnvFilter` type is used to filter log events based on the value of an environment variable.
 /// In this case, we are using the `try_from_default_env` method to attempt to read the `RUST_LOG` environment variable,
 /// which is used to set the log level for the application.
 /// If the environment variable is not set, we default to the log level of `debug`.
 /// The `RUST_LOG` environment variable is set in the Dockerfile and .env files.
-pub fn setup_tracing() {
+///
+/// Logs are written by a background worker through a bounded queue (`LOG_BUFFER_LINES`), so a
+/// slow or stuck log sink never blocks request handling. When `LOG_LOSSY` is true (the default),
+/// lines are dropped once the queue is full and a warning reports how many were lost.
+///
+/// The configuration is logged once tracing is set up, it is loaded before.
+///
+/// The returned guard flushes pending logs when dropped, keep it alive for the whole program.
+pub fn setup_tracing(cfg: &Config) -> WorkerGuard {
+  let (writer, guard) = non_blocking_writer(std::io::stdout(), cfg.log_buffer_lines, cfg.log_lossy);
+
   let env_filter_layer = EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into());
-  let formatting_layer = fmt::layer().json();
+  let formatting_layer = fmt::layer().json().with_writer(writer.clone());
   tracing_subscriber::registry()
     .with(env_filter_layer)
     .with(formatting_layer)
-    .init()
+    .init();
+
+  if cfg.log_lossy {
+    report_dropped_lines(writer);
+  }
+
+  // Log the current configuration
+  tracing::info!(?cfg, "Application configuration loaded");
+
+  guard
+}
+
+/// Wraps `writer` so that writes are queued and performed by a background worker thread.
+pub fn non_blocking_writer<W: Write + Send + 'static>(
+  writer: W,
+  buffered_lines: usize,
+  lossy: bool,
+) -> (NonBlocking, WorkerGuard) {
+  NonBlockingBuilder::default()
+    .buffered_lines_limit(buffered_lines)
+    .lossy(lossy)
+    .finish(writer)
+}
+
+/// Periodically logs a warning with the number of log lines dropped because the queue was full.
+fn report_dropped_lines(writer: NonBlocking) {
+  let error_counter = writer.error_counter();
+  thread::Builder::new()
+    .name("log-drop-reporter".to_string())
+    .spawn(move || {
+      let mut reported = 0;
+      loop {
+        thread::sleep(DROPPED_LINES_REPORT_INTERVAL);
+        let dropped = error_counter.dropped_lines();
+        if dropped > reported {
+          tracing::warn!(
+            dropped = dropped - reported,
+            "Log sink is falling behind, log lines were dropped"
+          );
+          reported = dropped;
+        }
+      }
+    })
+    .expect("Failed to spawn log drop reporter");
 }
 
 /// Returns a `TraceLayer` for HTTP requests and responses.
 /// The `TraceLayer` is used to trace requests and responses in the application.
-pub fn trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>> {
+pub fn trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>, RequestSpan> {
   TraceLayer::new_for_http()
-    .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+    .make_span_with(RequestSpan)
     .on_request(DefaultOnRequest::new().level(Level::INFO))
     .on_response(DefaultOnResponse::new().level(Level::INFO))
 }
+
+/// Makes the span of each request like `DefaultMakeSpan` does, with the W3C trace context read
+/// by the `trace_context` middleware so that the logs of a trace correlate across services.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequestSpan;
+
+impl<B> MakeSpan<B> for RequestSpan {
+  fn make_span(&mut self, request: &Request<B>) -> Span {
+    let context = request.extensions().get::<TraceContext>();
+    tracing::info_span!(
+      "request",
+      method = %request.method(),
+      uri = %request.uri(),
+      version = ?request.version(),
+      trace_id = context.map(|context| context.trace_id.as_str()),
+      span_id = context.map(|context| context.span_id.as_str()),
+      parent_id = context.and_then(|context| context.parent_id.as_deref()),
+    )
+  }
+}
+
+/// Log lines written while the guard returned by `capture_logs` is alive, as JSON.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl CapturedLogs {
+  pub(crate) fn contents(&self) -> String {
+    String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+  }
+}
+
+#[cfg(test)]
+impl Write for CapturedLogs {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.0.lock().unwrap().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Captures the logs of the current thread, e.g. of a `#[tokio::test]`, until the guard is dropped.
+#[cfg(test)]
+pub(crate) fn capture_logs() -> (CapturedLogs, tracing::subscriber::DefaultGuard) {
+  let logs = CapturedLogs::default();
+  let writer = logs.clone();
+  let subscriber =
+    tracing_subscriber::registry().with(fmt::layer().json().with_writer(move || writer.clone()));
+  (logs, tracing::subscriber::set_default(subscriber))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Instant;
+
+  use super::*;
+
+  /// A sink that takes a long time for every write, like an unreachable collector.
+  struct SlowWriter;
+
+  impl Write for SlowWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      thread::sleep(Duration::from_millis(50));
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_slow_sink_does_not_block_writers() {
+    let (mut writer, _guard) = non_blocking_writer(SlowWriter, 8, true);
+
+    let start = Instant::now();
+    for _ in 0..1_000 {
+      writer.write_all(b"log line\n").unwrap();
+    }
+
+    // Writing directly to the slow sink would take 50 seconds.
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(writer.error_counter().dropped_lines() > 0);
+  }
+
+  #[test]
+  fn test_request_span_records_the_trace_context() {
+    let (logs, _guard) = capture_logs();
+    let context = TraceContext::from_headers(&axum::http::HeaderMap::new());
+    let mut request = Request::new(());
+    request.extensions_mut().insert(context.clone());
+
+    RequestSpan
+      .make_span(&request)
+      .in_scope(|| tracing::info!("Handling"));
+    let logs = logs.contents();
+    assert!(logs.contains(&format!(r#""trace_id":"{}""#, context.trace_id)));
+    assert!(logs.contains(&format!(r#""span_id":"{}""#, context.span_id)));
+  }
+}
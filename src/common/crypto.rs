@@ -0,0 +1,224 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Result};
+use argon2::{
+  password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+  Argon2,
+};
+use serde::Deserialize;
+
+/// Algorithm used to hash new passwords.
+///
+/// Stored hashes are self-describing (`$2b$...` for bcrypt, `$argon2id$...` for argon2), so
+/// passwords hashed with either algorithm can always be verified.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum PasswordHashAlgo {
+  Bcrypt,
+  Argon2,
+}
+
+impl FromStr for PasswordHashAlgo {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "bcrypt" => Ok(PasswordHashAlgo::Bcrypt),
+      "argon2" => Ok(PasswordHashAlgo::Argon2),
+      _ => Err(format!(
+        "Invalid password hash algorithm: {}. Please make sure it is either \"bcrypt\" or \"argon2\".",
+        s
+      )),
+    }
+  }
+}
+
+/// Algorithm used to sign the JWTs, HMAC with `JWT_SECRET` or RSA with a key pair so that other
+/// services can verify the tokens with the public key only.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum JwtAlgorithm {
+  HS256,
+  RS256,
+}
+
+impl FromStr for JwtAlgorithm {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "HS256" => Ok(JwtAlgorithm::HS256),
+      "RS256" => Ok(JwtAlgorithm::RS256),
+      _ => Err(format!(
+        "Invalid JWT algorithm: {}. Please make sure it is either \"HS256\" or \"RS256\".",
+        s
+      )),
+    }
+  }
+}
+
+impl From<JwtAlgorithm> for jsonwebtoken::Algorithm {
+  fn from(algo: JwtAlgorithm) -> Self {
+    match algo {
+      JwtAlgorithm::HS256 => jsonwebtoken::Algorithm::HS256,
+      JwtAlgorithm::RS256 => jsonwebtoken::Algorithm::RS256,
+    }
+  }
+}
+
+/// Hashes `password` with `algo`. `bcrypt_cost` is only used by bcrypt.
+pub fn hash_password(password: &str, algo: PasswordHashAlgo, bcrypt_cost: u32) -> Result<String> {
+  match algo {
+    PasswordHashAlgo::Bcrypt => bcrypt::hash(password.as_bytes(), bcrypt_cost)
+      .map_err(|e| anyhow!("Failed to hash password: {}", e)),
+    PasswordHashAlgo::Argon2 => {
+      let salt = SaltString::generate(&mut OsRng);
+      Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow!("Failed to hash password: {}", e))
+    }
+  }
+}
+
+/// Algorithm and bcrypt cost of the configuration, for the passwords hashed when users are
+/// saved outside of the services, e.g. through the GraphQL mutations.
+static PASSWORD_HASHING: OnceLock<(PasswordHashAlgo, u32)> = OnceLock::new();
+
+/// Sets the algorithm and bcrypt cost of `hash_password_as_configured`, once on startup from
+/// `PASSWORD_HASH_ALGO` and `BCRYPT_COST`. Later calls are ignored.
+pub fn configure_password_hashing(algo: PasswordHashAlgo, bcrypt_cost: u32) {
+  let _ = PASSWORD_HASHING.set((algo, bcrypt_cost));
+}
+
+/// Hashes `password` with the algorithm set by `configure_password_hashing`, failing rather
+/// than guessing one when it wasn't called.
+pub fn hash_password_as_configured(password: &str) -> Result<String> {
+  let (algo, bcrypt_cost) = PASSWORD_HASHING
+    .get()
+    .ok_or_else(|| anyhow!("Failed to hash password: password hashing is not configured"))?;
+  hash_password(password, *algo, *bcrypt_cost)
+}
+
+/// Verifies `password` against `password_hash`, whichever algorithm produced it.
+pub fn verify_password(password: &str, password_hash: &str) -> Result<bool> {
+  match algo_of(password_hash) {
+    Some(PasswordHashAlgo::Bcrypt) => bcrypt::verify(password.as_bytes(), password_hash)
+      .map_err(|e| anyhow!("Failed to verify password: {}", e)),
+    Some(PasswordHashAlgo::Argon2) => {
+      let parsed = PasswordHash::new(password_hash)
+        .map_err(|e| anyhow!("Failed to verify password: {}", e))?;
+      Ok(
+        Argon2::default()
+          .verify_password(password.as_bytes(), &parsed)
+          .is_ok(),
+      )
+    }
+    None => Err(anyhow!("Failed to verify password: unknown hash format")),
+  }
+}
+
+/// Returns whether `password_hash` was produced by another algorithm than `algo`, and should
+/// be replaced after the next successful login.
+pub fn needs_rehash(password_hash: &str, algo: PasswordHashAlgo) -> bool {
+  algo_of(password_hash) != Some(algo)
+}
+
+/// Returns whether `value` is already a bcrypt or argon2 hash rather than a plain text password.
+pub fn is_password_hash(value: &str) -> bool {
+  match algo_of(value) {
+    Some(PasswordHashAlgo::Bcrypt) => {
+      // $2b$12$ followed by 53 characters of salt and hash
+      let bytes = value.as_bytes();
+      bytes.len() == 60
+        && matches!(bytes[2], b'a' | b'b' | b'x' | b'y')
+        && bytes[3] == b'$'
+        && bytes[4..6].iter().all(u8::is_ascii_digit)
+        && bytes[6] == b'$'
+        && bytes[7..]
+          .iter()
+          .all(|b| b.is_ascii_alphanumeric() || *b == b'.' || *b == b'/')
+    }
+    Some(PasswordHashAlgo::Argon2) => PasswordHash::new(value).is_ok(),
+    None => false,
+  }
+}
+
+fn algo_of(password_hash: &str) -> Option<PasswordHashAlgo> {
+  if password_hash.starts_with("$argon2") {
+    Some(PasswordHashAlgo::Argon2)
+  } else if password_hash.starts_with("$2") {
+    Some(PasswordHashAlgo::Bcrypt)
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_bcrypt_roundtrip() {
+    let hash = hash_password("password123", PasswordHashAlgo::Bcrypt, 4).unwrap();
+    assert!(hash.starts_with("$2b$04$"));
+    assert!(verify_password("password123", &hash).unwrap());
+    assert!(!verify_password("wrong-password", &hash).unwrap());
+  }
+
+  #[test]
+  fn test_argon2_roundtrip() {
+    let hash = hash_password("password123", PasswordHashAlgo::Argon2, 4).unwrap();
+    assert!(hash.starts_with("$argon2id$"));
+    assert!(verify_password("password123", &hash).unwrap());
+    assert!(!verify_password("wrong-password", &hash).unwrap());
+  }
+
+  #[test]
+  fn test_hash_password_as_configured() {
+    configure_password_hashing(PasswordHashAlgo::Bcrypt, 4);
+    let hash = hash_password_as_configured("password123").unwrap();
+    assert!(hash.starts_with("$2b$04$"));
+    assert!(verify_password("password123", &hash).unwrap());
+  }
+
+  #[test]
+  fn test_unknown_hash_format() {
+    assert!(verify_password("password123", "plain-text").is_err());
+  }
+
+  #[test]
+  fn test_needs_rehash() {
+    let bcrypt_hash = hash_password("password123", PasswordHashAlgo::Bcrypt, 4).unwrap();
+    assert!(needs_rehash(&bcrypt_hash, PasswordHashAlgo::Argon2));
+    assert!(!needs_rehash(&bcrypt_hash, PasswordHashAlgo::Bcrypt));
+    assert!(!needs_rehash(
+      "$argon2id$v=19$...",
+      PasswordHashAlgo::Argon2
+    ));
+  }
+
+  #[test]
+  fn test_is_password_hash() {
+    let bcrypt_hash = hash_password("password123", PasswordHashAlgo::Bcrypt, 4).unwrap();
+    let argon2_hash = hash_password("password123", PasswordHashAlgo::Argon2, 4).unwrap();
+    assert!(is_password_hash(&bcrypt_hash));
+    assert!(is_password_hash(&argon2_hash));
+    assert!(!is_password_hash("password123"));
+    assert!(!is_password_hash("$2b$04$short"));
+    assert!(!is_password_hash("$argon2id$not-a-hash"));
+  }
+
+  #[test]
+  fn test_algo_from_str() {
+    assert_eq!("bcrypt".parse(), Ok(PasswordHashAlgo::Bcrypt));
+    assert_eq!("argon2".parse(), Ok(PasswordHashAlgo::Argon2));
+    assert!("md5".parse::<PasswordHashAlgo>().is_err());
+  }
+
+  #[test]
+  fn test_jwt_algorithm_from_str() {
+    assert_eq!("HS256".parse(), Ok(JwtAlgorithm::HS256));
+    assert_eq!("RS256".parse(), Ok(JwtAlgorithm::RS256));
+    assert!("none".parse::<JwtAlgorithm>().is_err());
+  }
+}
@@ -1,5 +1,8 @@
+use std::collections::HashMap;
+
 use axum::{
   extract::rejection::JsonRejection,
+  http::{header::RETRY_AFTER, HeaderValue},
   response::{IntoResponse, Response},
   Json,
 };
@@ -9,6 +12,8 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::error;
 
+use crate::common::redact::{is_sensitive, REDACTED};
+
 /// Custom error type for the API.
 /// The `#[from]` attribute allows for easy conversion from other error types.
 #[derive(Error, Debug)]
@@ -21,6 +26,10 @@ pub enum ApiError {
   #[error("Invalid request: {0}")]
   InvalidRequest(String),
 
+  /// For request payloads that fail validation, holds the messages keyed by field.
+  #[error("Validation failed")]
+  Validation(HashMap<String, Vec<String>>),
+
   /// For errors that occur during manual validation.
   #[error("Not Found: {0}")]
   NotFound(String),
@@ -33,6 +42,27 @@ pub enum ApiError {
   #[error("Unauthorized: {0}")]
   Unauthorized(String),
 
+  /// For requests whose `Accept` header excludes every type the API can produce.
+  #[error("Not acceptable, only application/json can be produced")]
+  NotAcceptable,
+
+  /// For request bodies larger than the configured limit.
+  #[error("Payload too large")]
+  PayloadTooLarge,
+
+  /// For requests not answered within the timeout of their route.
+  #[error("Request timed out")]
+  RequestTimeout,
+
+  /// For errors that occur when a client exceeds the rate limit.
+  /// Holds the number of seconds after which the client may retry.
+  #[error("Too many requests, retry after {0} seconds")]
+  TooManyRequests(u64),
+
+  /// For requests refused while the service is unavailable, e.g. in maintenance mode.
+  #[error("Service unavailable: {0}")]
+  ServiceUnavailable(String),
+
   /// Converts from `sea_orm::DbErr`.
   #[error("A database error has occurred.")]
   DatabaseError(#[from] DbErr),
@@ -42,10 +72,37 @@ pub enum ApiError {
   InternalError(#[from] anyhow::Error),
 }
 
+/// Drops the value quoted by a JSON data error on a sensitive field, e.g. the password in
+/// `password: invalid type: integer `1234`, expected a string`, so that it isn't logged.
+fn redact_data_error(text: String) -> String {
+  const PREFIX: &str = "Failed to deserialize the JSON body into the target type: ";
+  let Some(detail) = text.strip_prefix(PREFIX) else {
+    return text;
+  };
+  match detail.split_once(": ") {
+    Some((path, _)) if path.split(['.', '[']).any(is_sensitive) => {
+      format!("{}{}: {}", PREFIX, path, REDACTED)
+    }
+    _ => text,
+  }
+}
+
+/// Names of the fields that failed validation, set on the response extensions of
+/// `ApiError::Validation` for `log_validation_failures`. Never holds the submitted values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvalidFields(pub Vec<String>);
+
 #[derive(Serialize, Deserialize)]
 pub struct ApiErrorResp {
   pub status: u16,
+  /// Stable identifier of the error type, e.g. `NOT_FOUND`, clients should branch on this
+  /// rather than on `message`.
+  #[serde(default)]
+  pub code: String,
   pub message: String,
+  /// Field-level validation messages, only present for validation errors.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub errors: Option<HashMap<String, Vec<String>>>,
 }
 
 // The IntoResponse implementation for ApiError logs the error message.
@@ -57,7 +114,7 @@ impl IntoResponse for ApiError {
     // Log detailed error for telemetry.
     let error_to_log = match &self {
       ApiError::InvalidJsonBody(ref err) => match err {
-        JsonRejection::JsonDataError(e) => e.body_text(),
+        JsonRejection::JsonDataError(e) => redact_data_error(e.body_text()),
         JsonRejection::JsonSyntaxError(e) => e.body_text(),
         JsonRejection::MissingJsonContentType(_) => {
           "Missing `Content-Type: application/json` header".to_string()
@@ -66,30 +123,65 @@ impl IntoResponse for ApiError {
         _ => "Unknown error".to_string(),
       },
       ApiError::InvalidRequest(_) => format!("{}", self),
+      ApiError::Validation(ref errors) => format!("{}: {:?}", self, errors),
       ApiError::NotFound(_) => format!("{}", self),
       ApiError::Forbidden(_) => format!("{}", self),
       ApiError::Unauthorized(_) => format!("{}", self),
+      ApiError::NotAcceptable => format!("{}", self),
+      ApiError::PayloadTooLarge => format!("{}", self),
+      ApiError::RequestTimeout => format!("{}", self),
+      ApiError::TooManyRequests(_) => format!("{}", self),
+      ApiError::ServiceUnavailable(_) => format!("{}", self),
       ApiError::DatabaseError(ref err) => format!("{}", err),
       ApiError::InternalError(ref err) => format!("{}", err),
     };
     error!("{}", error_to_log);
 
-    // Determine the appropriate status code.
-    let status = match self {
-      ApiError::InvalidJsonBody(_) | ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
-      ApiError::NotFound(_) => StatusCode::NOT_FOUND,
-      ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
-      ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
-      ApiError::DatabaseError(_) | ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    // Determine the appropriate status code and error code.
+    let (status, code) = match &self {
+      ApiError::InvalidJsonBody(_) => (StatusCode::BAD_REQUEST, "INVALID_JSON_BODY"),
+      ApiError::InvalidRequest(_) => (StatusCode::BAD_REQUEST, "INVALID_REQUEST"),
+      ApiError::Validation(_) => (StatusCode::BAD_REQUEST, "VALIDATION_FAILED"),
+      ApiError::NotFound(_) => (StatusCode::NOT_FOUND, "NOT_FOUND"),
+      ApiError::Forbidden(_) => (StatusCode::FORBIDDEN, "FORBIDDEN"),
+      ApiError::Unauthorized(_) => (StatusCode::UNAUTHORIZED, "UNAUTHORIZED"),
+      ApiError::NotAcceptable => (StatusCode::NOT_ACCEPTABLE, "NOT_ACCEPTABLE"),
+      ApiError::PayloadTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "PAYLOAD_TOO_LARGE"),
+      ApiError::RequestTimeout => (StatusCode::REQUEST_TIMEOUT, "REQUEST_TIMEOUT"),
+      ApiError::TooManyRequests(_) => (StatusCode::TOO_MANY_REQUESTS, "TOO_MANY_REQUESTS"),
+      ApiError::ServiceUnavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, "SERVICE_UNAVAILABLE"),
+      ApiError::DatabaseError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "DATABASE_ERROR"),
+      ApiError::InternalError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR"),
     };
 
     // Create a generic response to hide specific implementation details.
     let resp = ApiErrorResp {
       status: status.as_u16(),
+      code: code.to_string(),
       message: self.to_string(),
+      errors: match &self {
+        ApiError::Validation(errors) => Some(errors.clone()),
+        _ => None,
+      },
     };
 
-    (status, Json(resp)).into_response()
+    let mut response = (status, Json(resp)).into_response();
+
+    // Tell rate limited clients when they can retry.
+    if let ApiError::TooManyRequests(retry_after) = self {
+      response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    }
+
+    // Let the middlewares tell which fields were invalid without parsing the body.
+    if let ApiError::Validation(errors) = &self {
+      let mut fields: Vec<String> = errors.keys().cloned().collect();
+      fields.sort();
+      response.extensions_mut().insert(InvalidFields(fields));
+    }
+
+    response
   }
 }
 
@@ -97,6 +189,37 @@ impl IntoResponse for ApiError {
 mod tests {
   use super::*;
 
+  #[tokio::test]
+  async fn test_data_error_on_a_password_is_logged_redacted() {
+    #[derive(Debug, Deserialize)]
+    struct Payload {
+      #[allow(dead_code)]
+      password: String,
+    }
+
+    let (logs, _guard) = crate::common::config::telemetry::capture_logs();
+    let request = axum::http::Request::builder()
+      .header("content-type", "application/json")
+      .body(axum::body::Body::from(r#"{"password":12345678}"#))
+      .unwrap();
+    let rejection = <Json<Payload> as axum::extract::FromRequest<()>>::from_request(request, &())
+      .await
+      .err()
+      .unwrap();
+    ApiError::from(rejection).into_response();
+
+    let logs = logs.contents();
+    assert!(logs.contains("password: [REDACTED]"));
+    assert!(!logs.contains("12345678"));
+  }
+
+  #[test]
+  fn test_data_error_on_other_fields_is_kept() {
+    let text =
+      "Failed to deserialize the JSON body into the target type: role: unknown variant `Root`";
+    assert_eq!(redact_data_error(text.to_string()), text);
+  }
+
   #[test]
   fn test_api_error_invalid_request() {
     let error = ApiError::InvalidRequest("Test error".to_string());
@@ -140,16 +263,57 @@ mod tests {
     assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
   }
 
+  #[test]
+  fn test_api_error_too_many_requests() {
+    let error = ApiError::TooManyRequests(30);
+    assert_eq!(
+      error.to_string(),
+      "Too many requests, retry after 30 seconds"
+    );
+
+    let response = error.into_response();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[RETRY_AFTER], "30");
+  }
+
   #[test]
   fn test_api_error_resp_serialization() {
     let error_resp = ApiErrorResp {
       status: 400,
+      code: "INVALID_REQUEST".to_string(),
       message: "Bad Request".to_string(),
+      errors: None,
     };
 
     let json = serde_json::to_string(&error_resp).unwrap();
     assert!(json.contains("\"status\":400"));
+    assert!(json.contains("\"code\":\"INVALID_REQUEST\""));
     assert!(json.contains("\"message\":\"Bad Request\""));
+    assert!(!json.contains("errors"));
+  }
+
+  #[tokio::test]
+  async fn test_api_error_validation_body() {
+    use http_body_util::BodyExt;
+
+    let error = ApiError::Validation(HashMap::from([(
+      "email".to_string(),
+      vec!["invalid email format".to_string()],
+    )]));
+    let response = error.into_response();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+      json,
+      serde_json::json!({
+        "status": 400,
+        "code": "VALIDATION_FAILED",
+        "message": "Validation failed",
+        "errors": { "email": ["invalid email format"] }
+      })
+    );
   }
 
   #[test]
@@ -157,6 +321,43 @@ mod tests {
     let json = r#"{"status":404,"message":"Not Found"}"#;
     let error_resp: ApiErrorResp = serde_json::from_str(json).unwrap();
     assert_eq!(error_resp.status, 404);
+    assert_eq!(error_resp.code, "");
     assert_eq!(error_resp.message, "Not Found");
   }
+
+  async fn response_json(error: ApiError) -> serde_json::Value {
+    use http_body_util::BodyExt;
+
+    let body = error.into_response().into_body().collect().await.unwrap();
+    serde_json::from_slice(&body.to_bytes()).unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_api_error_codes() {
+    let cases = [
+      (
+        ApiError::InvalidRequest("Test".to_string()),
+        "INVALID_REQUEST",
+      ),
+      (ApiError::Validation(HashMap::new()), "VALIDATION_FAILED"),
+      (ApiError::NotFound("Test".to_string()), "NOT_FOUND"),
+      (ApiError::Forbidden("Test".to_string()), "FORBIDDEN"),
+      (ApiError::Unauthorized("Test".to_string()), "UNAUTHORIZED"),
+      (ApiError::PayloadTooLarge, "PAYLOAD_TOO_LARGE"),
+      (ApiError::RequestTimeout, "REQUEST_TIMEOUT"),
+      (ApiError::TooManyRequests(1), "TOO_MANY_REQUESTS"),
+      (
+        ApiError::DatabaseError(DbErr::Custom("Test".to_string())),
+        "DATABASE_ERROR",
+      ),
+      (
+        ApiError::InternalError(anyhow::anyhow!("Test")),
+        "INTERNAL_ERROR",
+      ),
+    ];
+
+    for (error, code) in cases {
+      assert_eq!(response_json(error).await["code"], code);
+    }
+  }
 }
@@ -0,0 +1,51 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::http::{header::IF_NONE_MATCH, HeaderMap, HeaderValue};
+
+/// Returns a strong `ETag` of `content`, which changes whenever the content does.
+pub fn etag(content: &[u8]) -> HeaderValue {
+  let mut hasher = DefaultHasher::new();
+  content.hash(&mut hasher);
+  HeaderValue::from_str(&format!("\"{:016x}\"", hasher.finish()))
+    .expect("ETag should be a valid header value")
+}
+
+/// Whether the `If-None-Match` header matches `etag`, i.e. the client's copy is still fresh and
+/// `304 Not Modified` can be answered instead.
+pub fn is_fresh(headers: &HeaderMap, etag: &HeaderValue) -> bool {
+  headers
+    .get(IF_NONE_MATCH)
+    .and_then(|value| value.to_str().ok())
+    .is_some_and(|value| {
+      value
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/").as_bytes() == etag.as_bytes())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn if_none_match(value: &str) -> HeaderMap {
+    HeaderMap::from_iter([(IF_NONE_MATCH, HeaderValue::from_str(value).unwrap())])
+  }
+
+  #[test]
+  fn test_etag_changes_with_content() {
+    assert_eq!(etag(b"{\"name\":\"A\"}"), etag(b"{\"name\":\"A\"}"));
+    assert_ne!(etag(b"{\"name\":\"A\"}"), etag(b"{\"name\":\"B\"}"));
+  }
+
+  #[test]
+  fn test_is_fresh() {
+    let tag = etag(b"content");
+    let weak = format!("W/\"other\", W/{}", tag.to_str().unwrap());
+
+    assert!(is_fresh(&if_none_match(&weak), &tag));
+    assert!(is_fresh(&if_none_match("*"), &tag));
+    assert!(!is_fresh(&if_none_match("\"other\""), &tag));
+    assert!(!is_fresh(&HeaderMap::new(), &tag));
+  }
+}
@@ -0,0 +1,63 @@
+use futures_util::{stream, Stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::modules::users::dto::UserDto;
+
+/// Events kept for subscribers lagging behind, the oldest ones are skipped past that.
+const CAPACITY: usize = 256;
+
+/// Broadcasts the created users to the subscribers, e.g. GraphQL subscriptions.
+#[derive(Clone, Debug)]
+pub struct UserEvents(broadcast::Sender<UserDto>);
+
+impl Default for UserEvents {
+  fn default() -> Self {
+    Self(broadcast::channel(CAPACITY).0)
+  }
+}
+
+impl UserEvents {
+  /// Publishes a created user, dropped when nobody is subscribed.
+  pub fn user_created(&self, user: UserDto) {
+    let _ = self.0.send(user);
+  }
+
+  /// Streams the users created from now on, until the stream is dropped.
+  pub fn created_users(&self) -> impl Stream<Item = UserDto> {
+    stream::unfold(self.0.subscribe(), |mut receiver| async move {
+      loop {
+        match receiver.recv().await {
+          Ok(user) => return Some((user, receiver)),
+          Err(RecvError::Lagged(_)) => continue,
+          Err(RecvError::Closed) => return None,
+        }
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use futures_util::StreamExt;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_subscribers_receive_users_created_after_subscribing() {
+    let events = UserEvents::default();
+    events.user_created(UserDto {
+      email: "before@example.com".to_string(),
+      ..Default::default()
+    });
+
+    let created = events.created_users();
+    events.user_created(UserDto {
+      email: "after@example.com".to_string(),
+      ..Default::default()
+    });
+    drop(events);
+
+    let emails: Vec<String> = created.map(|user| user.email).collect().await;
+    assert_eq!(emails, vec!["after@example.com"]);
+  }
+}
@@ -0,0 +1,135 @@
+use std::{
+  convert::Infallible,
+  net::{IpAddr, SocketAddr},
+};
+
+use axum::{
+  extract::{ConnectInfo, FromRequestParts},
+  http::{header::HOST, request::Parts, HeaderMap},
+};
+
+use crate::app::AppState;
+
+/// Extracts the public base URL of the API (e.g. `https://api.example.com`), to build absolute
+/// links such as `Location` headers.
+///
+/// `X-Forwarded-Proto` and `X-Forwarded-Host` are only honored when the peer is one of the
+/// `TRUSTED_PROXIES`, so that clients can't spoof them. Otherwise the `Host` header is used
+/// with the `http` scheme of the local connection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaseUrl(pub String);
+
+impl BaseUrl {
+  /// Returns the absolute URL of `path`, which must start with a `/`.
+  pub fn join(&self, path: &str) -> String {
+    format!("{}{}", self.0, path)
+  }
+}
+
+impl FromRequestParts<AppState> for BaseUrl {
+  type Rejection = Infallible;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &AppState,
+  ) -> Result<Self, Self::Rejection> {
+    let peer = parts
+      .extensions
+      .get::<ConnectInfo<SocketAddr>>()
+      .map(|ConnectInfo(addr)| addr.ip());
+    Ok(base_url(&parts.headers, peer, &state.cfg.trusted_proxies))
+  }
+}
+
+fn base_url(headers: &HeaderMap, peer: Option<IpAddr>, trusted_proxies: &[IpAddr]) -> BaseUrl {
+  let trusted = peer.is_some_and(|ip| trusted_proxies.contains(&ip));
+  let forwarded = |name: &str| {
+    headers
+      .get(name)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.split(',').next())
+      .map(|value| value.trim().to_ascii_lowercase())
+      .filter(|value| !value.is_empty())
+  };
+
+  let scheme = trusted
+    .then(|| forwarded("x-forwarded-proto"))
+    .flatten()
+    .filter(|proto| proto == "http" || proto == "https")
+    .unwrap_or_else(|| "http".to_string());
+  let host = trusted
+    .then(|| forwarded("x-forwarded-host"))
+    .flatten()
+    .or_else(|| {
+      headers
+        .get(HOST)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+    })
+    .unwrap_or_else(|| "localhost".to_string());
+
+  BaseUrl(format!("{}://{}", scheme, host))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PROXY: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+  const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 7));
+
+  fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(HOST, "api.internal:8080".parse().unwrap());
+    for (name, value) in pairs {
+      headers.insert(*name, value.parse().unwrap());
+    }
+    headers
+  }
+
+  #[test]
+  fn test_forwarded_proto_from_trusted_proxy() {
+    let headers = headers(&[
+      ("x-forwarded-proto", "HTTPS"),
+      ("x-forwarded-host", "api.example.com"),
+    ]);
+    assert_eq!(
+      base_url(&headers, Some(PROXY), &[PROXY]).0,
+      "https://api.example.com"
+    );
+  }
+
+  #[test]
+  fn test_forwarded_proto_from_untrusted_peer_is_ignored() {
+    let headers = headers(&[
+      ("x-forwarded-proto", "https"),
+      ("x-forwarded-host", "evil.example.com"),
+    ]);
+    assert_eq!(
+      base_url(&headers, Some(CLIENT), &[PROXY]).0,
+      "http://api.internal:8080"
+    );
+    assert_eq!(
+      base_url(&headers, None, &[PROXY]).0,
+      "http://api.internal:8080"
+    );
+  }
+
+  #[test]
+  fn test_unknown_forwarded_proto_is_ignored() {
+    let headers = headers(&[("x-forwarded-proto", "gopher")]);
+    assert_eq!(
+      base_url(&headers, Some(PROXY), &[PROXY]).0,
+      "http://api.internal:8080"
+    );
+  }
+
+  #[test]
+  fn test_join() {
+    let base_url = BaseUrl("https://api.example.com".to_string());
+    assert_eq!(
+      base_url.join("/api/v1/users/1"),
+      "https://api.example.com/api/v1/users/1"
+    );
+  }
+}
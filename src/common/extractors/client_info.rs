@@ -0,0 +1,72 @@
+use std::convert::Infallible;
+
+use axum::{
+  extract::FromRequestParts,
+  http::{header::USER_AGENT, request::Parts},
+};
+
+use crate::common::middlewares::client_ip;
+
+/// Extracts the client IP address and user agent, e.g. to record them in audit trails.
+///
+/// The IP comes from the first `X-Forwarded-For` entry, or else the peer address.
+/// Either value is `None` when it can't be determined.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+  pub ip: Option<String>,
+  pub user_agent: Option<String>,
+}
+
+impl<S> FromRequestParts<S> for ClientInfo
+where
+  S: Send + Sync,
+{
+  type Rejection = Infallible;
+
+  async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+    let ip = client_ip(&parts.headers, &parts.extensions);
+    let user_agent = parts
+      .headers
+      .get(USER_AGENT)
+      .and_then(|value| value.to_str().ok())
+      .map(|value| value.to_string());
+
+    Ok(ClientInfo {
+      ip: (!ip.is_unspecified()).then(|| ip.to_string()),
+      user_agent,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, extract::Request};
+
+  use super::*;
+
+  async fn extract(req: Request) -> ClientInfo {
+    let (mut parts, _) = req.into_parts();
+    ClientInfo::from_request_parts(&mut parts, &())
+      .await
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_client_info_from_headers() {
+    let req = Request::builder()
+      .header("x-forwarded-for", "203.0.113.7")
+      .header(USER_AGENT, "curl/8.0")
+      .body(Body::empty())
+      .unwrap();
+    let client = extract(req).await;
+    assert_eq!(client.ip.as_deref(), Some("203.0.113.7"));
+    assert_eq!(client.user_agent.as_deref(), Some("curl/8.0"));
+  }
+
+  #[tokio::test]
+  async fn test_client_info_unknown() {
+    let client = extract(Request::builder().body(Body::empty()).unwrap()).await;
+    assert!(client.ip.is_none());
+    assert!(client.user_agent.is_none());
+  }
+}
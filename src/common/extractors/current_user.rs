@@ -0,0 +1,73 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use sea_orm::ActiveEnum;
+use uuid::Uuid;
+
+use crate::common::errors::ApiError;
+use crate::modules::users::dto::UserDto;
+use crate::modules::users::enums::UserRole;
+
+/// Extracts the authenticated user stored in the request extensions by `auth_guard`.
+///
+/// Rejects the request with `401 Unauthorized` on routes not protected by `auth_guard`.
+pub struct CurrentUser(pub UserDto);
+
+impl CurrentUser {
+  pub fn id(&self) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(&self.0.id)
+      .map_err(|_| ApiError::Unauthorized("Invalid user in token".to_string()))
+  }
+
+  pub fn is_admin(&self) -> bool {
+    self.0.role == UserRole::Admin.to_value()
+  }
+}
+
+impl<S> FromRequestParts<S> for CurrentUser
+where
+  S: Send + Sync,
+{
+  type Rejection = ApiError;
+
+  async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+    parts
+      .extensions
+      .get::<UserDto>()
+      .cloned()
+      .map(CurrentUser)
+      .ok_or_else(|| ApiError::Unauthorized("User not found in request".to_string()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, extract::Request};
+
+  use super::*;
+
+  async fn extract(user: Option<UserDto>) -> Result<CurrentUser, ApiError> {
+    let mut req = Request::builder().body(Body::empty()).unwrap();
+    if let Some(user) = user {
+      req.extensions_mut().insert(user);
+    }
+    let (mut parts, _) = req.into_parts();
+    CurrentUser::from_request_parts(&mut parts, &()).await
+  }
+
+  #[tokio::test]
+  async fn test_extracts_user_from_extensions() {
+    let user = UserDto {
+      id: Uuid::nil().to_string(),
+      role: "Admin".to_string(),
+      ..Default::default()
+    };
+    let current_user = extract(Some(user)).await.ok().unwrap();
+    assert_eq!(current_user.id().unwrap(), Uuid::nil());
+    assert!(current_user.is_admin());
+  }
+
+  #[tokio::test]
+  async fn test_rejects_when_unauthenticated() {
+    let err = extract(None).await.err().unwrap();
+    assert!(matches!(err, ApiError::Unauthorized(_)));
+  }
+}
@@ -1,15 +1,22 @@
 use axum::{
-  extract::{rejection::JsonRejection, FromRequest, Request},
+  body::{Body, Bytes},
+  extract::{rejection::JsonRejection, FromRequest, OptionalFromRequest, Request},
+  http::StatusCode,
   Json,
 };
 use serde::de::DeserializeOwned;
+use tracing::debug;
 use validator::Validate;
 
 use crate::common::errors::ApiError;
+use crate::common::redact::redact_body;
 
 /// A custom JSON extractor that validates the request body after deserialization.
 ///
-/// Use this instead of `Json<T>` when `T` implements `Validate`.
+/// Use this instead of `Json<T>` when `T` implements `Validate`. An empty body is rejected
+/// with "Request body is required", use `Option<ValidatedJson<T>>` when the body is optional.
+///
+/// Rejected payloads are logged at debug level with their passwords, tokens and secrets redacted.
 pub struct ValidatedJson<T>(pub T);
 
 impl<S, T> FromRequest<S> for ValidatedJson<T>
@@ -21,23 +28,201 @@ where
   type Rejection = ApiError;
 
   async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
-    let Json(value) = Json::<T>::from_request(req, state).await?;
+    <Self as OptionalFromRequest<S>>::from_request(req, state)
+      .await?
+      .ok_or_else(|| ApiError::InvalidRequest("Request body is required".to_string()))
+  }
+}
+
+impl<S, T> OptionalFromRequest<S> for ValidatedJson<T>
+where
+  T: DeserializeOwned + Validate,
+  S: Send + Sync,
+  Json<T>: FromRequest<S, Rejection = JsonRejection>,
+{
+  type Rejection = ApiError;
+
+  async fn from_request(req: Request, state: &S) -> Result<Option<Self>, Self::Rejection> {
+    // Buffer the body first, an empty body means there's no payload rather than bad JSON
+    let headers = req.headers().clone();
+    let extensions = req.extensions().clone();
+    let bytes = Bytes::from_request(req, state).await.map_err(|rejection| {
+      // Bodies over the size limit are rejected as such, not as invalid JSON
+      if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        ApiError::PayloadTooLarge
+      } else {
+        ApiError::InvalidJsonBody(rejection.into())
+      }
+    })?;
+    if bytes.is_empty() {
+      return Ok(None);
+    }
+
+    let rejected = |bytes: &Bytes| debug!(body = %redact_body(bytes), "Rejected JSON payload");
+    let mut req = Request::new(Body::from(bytes.clone()));
+    *req.headers_mut() = headers;
+    *req.extensions_mut() = extensions;
+    let Json(value) = Json::<T>::from_request(req, state)
+      .await
+      .inspect_err(|_| rejected(&bytes))?;
     value.validate().map_err(|e| {
-      let messages: Vec<String> = e
+      rejected(&bytes);
+      let errors = e
         .field_errors()
         .into_iter()
-        .flat_map(|(field, errors)| {
-          errors.iter().map(move |err| {
-            err
-              .message
-              .as_ref()
-              .map(|m| format!("{}: {}", field, m))
-              .unwrap_or_else(|| format!("{}: validation failed", field))
-          })
+        .map(|(field, errors)| {
+          let messages = errors
+            .iter()
+            .map(|err| {
+              err
+                .message
+                .as_ref()
+                .map(|m| m.to_string())
+                .unwrap_or_else(|| "validation failed".to_string())
+            })
+            .collect();
+          (field.to_string(), messages)
         })
         .collect();
-      ApiError::InvalidRequest(messages.join(", "))
+      ApiError::Validation(errors)
     })?;
-    Ok(ValidatedJson(value))
+    Ok(Some(ValidatedJson(value)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, http::header::CONTENT_TYPE, response::IntoResponse};
+  use http_body_util::Limited;
+  use serde::Deserialize;
+
+  use super::*;
+  use crate::common::config::telemetry::capture_logs;
+
+  #[derive(Debug, Deserialize, Validate)]
+  struct Payload {
+    #[validate(email(message = "invalid email format"))]
+    email: String,
+    #[validate(length(min = 8, message = "too short"))]
+    password: String,
+  }
+
+  fn request(body: &'static str) -> Request {
+    Request::builder()
+      .method("POST")
+      .header(CONTENT_TYPE, "application/json")
+      .body(Body::from(body))
+      .unwrap()
+  }
+
+  async fn extract(body: &'static str) -> Result<ValidatedJson<Payload>, ApiError> {
+    <ValidatedJson<Payload> as FromRequest<()>>::from_request(request(body), &()).await
+  }
+
+  async fn extract_optional(
+    body: &'static str,
+  ) -> Result<Option<ValidatedJson<Payload>>, ApiError> {
+    <ValidatedJson<Payload> as OptionalFromRequest<()>>::from_request(request(body), &()).await
+  }
+
+  fn assert_body_required(result: Result<ValidatedJson<Payload>, ApiError>) {
+    match result.err().unwrap() {
+      ApiError::InvalidRequest(message) => assert_eq!(message, "Request body is required"),
+      err => panic!("unexpected error: {}", err),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_valid_payload() {
+    let ValidatedJson(payload) =
+      extract(r#"{"email":"user@example.com","password":"password123"}"#)
+        .await
+        .unwrap();
+    assert_eq!(payload.email, "user@example.com");
+  }
+
+  #[tokio::test]
+  async fn test_empty_body_is_required() {
+    assert_body_required(extract("").await);
+  }
+
+  #[tokio::test]
+  async fn test_missing_body_is_required() {
+    let req = Request::builder()
+      .method("POST")
+      .body(Body::empty())
+      .unwrap();
+    assert_body_required(<ValidatedJson<Payload> as FromRequest<()>>::from_request(req, &()).await);
+  }
+
+  #[tokio::test]
+  async fn test_rejected_payload_is_logged_redacted() {
+    let (logs, _guard) = capture_logs();
+    let err = extract(r#"{"email":"bad","password":"hunter22"}"#)
+      .await
+      .err()
+      .unwrap();
+    assert!(matches!(err, ApiError::Validation(_)));
+    err.into_response();
+
+    let logs = logs.contents();
+    assert!(logs.contains("Rejected JSON payload"));
+    assert!(logs.contains("[REDACTED]"));
+    assert!(!logs.contains("hunter22"));
+  }
+
+  #[tokio::test]
+  async fn test_malformed_body_is_invalid_json() {
+    let err = extract("{").await.err().unwrap();
+    assert!(matches!(err, ApiError::InvalidJsonBody(_)));
+  }
+
+  #[tokio::test]
+  async fn test_body_over_limit_is_payload_too_large() {
+    let body = r#"{"email":"user@example.com","password":"password123"}"#;
+    let req = Request::builder()
+      .method("POST")
+      .header(CONTENT_TYPE, "application/json")
+      .body(Body::new(Limited::new(Body::from(body), 16)))
+      .unwrap();
+    let err = <ValidatedJson<Payload> as FromRequest<()>>::from_request(req, &())
+      .await
+      .err()
+      .unwrap();
+    assert!(matches!(err, ApiError::PayloadTooLarge));
+  }
+
+  #[tokio::test]
+  async fn test_optional_empty_body() {
+    assert!(extract_optional("").await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn test_optional_body_is_still_validated() {
+    let ValidatedJson(payload) =
+      extract_optional(r#"{"email":"user@example.com","password":"password123"}"#)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(payload.email, "user@example.com");
+
+    let err = extract_optional(r#"{"email":"bad","password":"password123"}"#)
+      .await
+      .err()
+      .unwrap();
+    assert!(matches!(err, ApiError::Validation(_)));
+  }
+
+  #[tokio::test]
+  async fn test_invalid_payload_returns_errors_by_field() {
+    let err = extract(r#"{"email":"bad","password":"short"}"#)
+      .await
+      .err()
+      .unwrap();
+    let ApiError::Validation(errors) = err else {
+      panic!("expected a validation error");
+    };
+    assert_eq!(errors["email"], vec!["invalid email format"]);
+    assert_eq!(errors["password"], vec!["too short"]);
   }
 }
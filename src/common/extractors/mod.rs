@@ -1,5 +1,17 @@
+mod base_url;
+mod client_info;
+mod current_user;
 mod json;
 mod path;
+mod query;
+mod tenant;
+mod tx;
 
+pub use base_url::BaseUrl;
+pub use client_info::ClientInfo;
+pub use current_user::CurrentUser;
 pub use json::ValidatedJson;
 pub use path::ValidatedPath;
+pub use query::ListQuery;
+pub use tenant::TenantContext;
+pub use tx::Tx;
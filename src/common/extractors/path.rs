@@ -1,5 +1,11 @@
+use std::collections::HashMap;
+
 use axum::{
-  extract::{rejection::PathRejection, FromRequestParts, Path},
+  extract::{
+    path::{ErrorKind, FailedToDeserializePathParams},
+    rejection::PathRejection,
+    FromRequestParts, Path,
+  },
   http::request::Parts,
 };
 use serde::de::DeserializeOwned;
@@ -9,7 +15,9 @@ use crate::common::errors::ApiError;
 /// A custom Path extractor that returns `ApiError` on rejection.
 ///
 /// Use this instead of `Path<T>` to get consistent error responses
-/// through the `ApiError` system.
+/// through the `ApiError` system. Composite params such as `ValidatedPath<(Uuid, Uuid)>` for
+/// `/users/{user_id}/sessions/{session_id}` are supported, a param that fails to parse is
+/// reported as a validation error keyed by its name.
 pub struct ValidatedPath<T>(pub T);
 
 impl<S, T> FromRequestParts<S> for ValidatedPath<T>
@@ -29,10 +37,99 @@ where
 
 fn path_rejection_to_api_error(rejection: PathRejection) -> ApiError {
   match rejection {
-    PathRejection::FailedToDeserializePathParams(inner) => {
-      ApiError::InvalidRequest(inner.body_text())
-    }
+    PathRejection::FailedToDeserializePathParams(inner) => deserialize_error_to_api_error(inner),
     PathRejection::MissingPathParams(inner) => ApiError::InvalidRequest(inner.body_text()),
     _ => ApiError::InvalidRequest("Invalid path parameter".to_string()),
   }
 }
+
+fn deserialize_error_to_api_error(inner: FailedToDeserializePathParams) -> ApiError {
+  let (param, message) = match inner.kind() {
+    ErrorKind::ParseErrorAtKey {
+      key,
+      value,
+      expected_type,
+    } => (
+      key.clone(),
+      format!("cannot parse `{}` as {}", value, expected_type),
+    ),
+    ErrorKind::ParseErrorAtIndex {
+      index,
+      value,
+      expected_type,
+    } => (
+      index.to_string(),
+      format!("cannot parse `{}` as {}", value, expected_type),
+    ),
+    ErrorKind::DeserializeError {
+      key,
+      value,
+      message,
+    } => (
+      key.clone(),
+      format!("invalid value `{}`: {}", value, message),
+    ),
+    ErrorKind::InvalidUtf8InPathParam { key } => (key.clone(), "invalid UTF-8".to_string()),
+    _ => return ApiError::InvalidRequest(inner.body_text()),
+  };
+  ApiError::Validation(HashMap::from([(param, vec![message])]))
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    routing::get,
+    Router,
+  };
+  use http_body_util::BodyExt;
+  use hyper::StatusCode;
+  use tower::ServiceExt;
+  use uuid::Uuid;
+
+  use super::*;
+  use crate::common::errors::ApiErrorResp;
+
+  const USER_ID: &str = "123e4567-e89b-12d3-a456-426614174000";
+  const SESSION_ID: &str = "0192b2a8-8f3e-7c1a-9d2b-5f6e7a8b9c0d";
+
+  async fn show_session(
+    ValidatedPath((user_id, session_id)): ValidatedPath<(Uuid, Uuid)>,
+  ) -> String {
+    format!("{}/{}", user_id, session_id)
+  }
+
+  async fn request(uri: &str) -> (StatusCode, Bytes) {
+    let app: Router =
+      Router::new().route("/users/{user_id}/sessions/{session_id}", get(show_session));
+    let response = app
+      .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    let status = response.status();
+    (
+      status,
+      response.into_body().collect().await.unwrap().to_bytes(),
+    )
+  }
+
+  #[tokio::test]
+  async fn test_composite_path_params() {
+    let (status, body) = request(&format!("/users/{}/sessions/{}", USER_ID, SESSION_ID)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, format!("{}/{}", USER_ID, SESSION_ID));
+  }
+
+  #[tokio::test]
+  async fn test_invalid_composite_path_param_is_reported_by_name() {
+    let (status, body) = request(&format!("/users/{}/sessions/not-a-uuid", USER_ID)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let body: ApiErrorResp = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body.code, "VALIDATION_FAILED");
+    let errors = body.errors.unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(errors["session_id"][0].contains("not-a-uuid"));
+  }
+}
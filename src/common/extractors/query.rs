@@ -0,0 +1,88 @@
+use axum::{
+  extract::{rejection::QueryRejection, FromRequestParts, Query},
+  http::request::Parts,
+};
+use serde::de::DeserializeOwned;
+
+use crate::common::errors::ApiError;
+use crate::common::pagination::PaginationParams;
+
+/// Query parameters of a list endpoint: the pagination, sort and search params, and a filter
+/// `F` specific to the listed resource.
+///
+/// Use this instead of `Query<PaginationParams>` and `Query<F>`, malformed params are rejected
+/// as `ApiError::InvalidRequest`. The page sizes are clamped by the `PaginationParams` accessors.
+#[derive(Debug, Default)]
+pub struct ListQuery<F> {
+  pub params: PaginationParams,
+  pub filter: F,
+}
+
+impl<S, F> FromRequestParts<S> for ListQuery<F>
+where
+  F: DeserializeOwned + Send,
+  S: Send + Sync,
+{
+  type Rejection = ApiError;
+
+  async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+    // Both are read from the whole query string, `#[serde(flatten)]` would lose the number types
+    let Query(params) = Query::<PaginationParams>::from_request_parts(parts, state)
+      .await
+      .map_err(query_rejection_to_api_error)?;
+    let Query(filter) = Query::<F>::from_request_parts(parts, state)
+      .await
+      .map_err(query_rejection_to_api_error)?;
+    Ok(ListQuery { params, filter })
+  }
+}
+
+fn query_rejection_to_api_error(rejection: QueryRejection) -> ApiError {
+  ApiError::InvalidRequest(rejection.body_text())
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::Request;
+  use serde::Deserialize;
+
+  use super::*;
+  use crate::common::config::Configuration;
+
+  #[derive(Debug, Default, Deserialize)]
+  struct Filter {
+    active: Option<bool>,
+  }
+
+  async fn extract(uri: &str) -> Result<ListQuery<Filter>, ApiError> {
+    let (mut parts, _) = Request::builder().uri(uri).body(()).unwrap().into_parts();
+    ListQuery::<Filter>::from_request_parts(&mut parts, &()).await
+  }
+
+  #[tokio::test]
+  async fn test_params_and_filter_are_read_from_the_query() {
+    let query = extract("/users?page=2&per_page=500&active=true")
+      .await
+      .unwrap();
+    assert_eq!(query.params.page(), 2);
+    assert_eq!(query.params.per_page_with(&Configuration::for_tests()), 100);
+    assert_eq!(query.filter.active, Some(true));
+
+    let query = extract("/users").await.unwrap();
+    assert_eq!(query.params.page(), 1);
+    assert_eq!(query.filter.active, None);
+  }
+
+  #[tokio::test]
+  async fn test_malformed_params_are_invalid_requests() {
+    for uri in ["/users?per_page=many", "/users?active=maybe"] {
+      let result = extract(uri).await;
+      assert!(
+        matches!(result, Err(ApiError::InvalidRequest(_))),
+        "{}: {:?}",
+        uri,
+        result
+      );
+    }
+  }
+}
@@ -0,0 +1,107 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+
+use crate::common::errors::ApiError;
+
+/// Header selecting the tenant of a request.
+pub const TENANT_HEADER: &str = "x-tenant-id";
+
+/// Extracts the tenant of the request from the `X-Tenant-Id` header, if any.
+///
+/// Authenticated requests are bound to the tenant of their token instead, which `auth_guard`
+/// puts in the request extensions, so that a header can't reach into another tenant. Pass it to
+/// `Db::conn_for` to get the connection to the tenant's database. Tenant ids are made of
+/// lowercase letters, digits, `-` and `_`, up to 63 characters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TenantContext(pub Option<String>);
+
+impl TenantContext {
+  pub fn id(&self) -> Option<&str> {
+    self.0.as_deref()
+  }
+}
+
+impl<S> FromRequestParts<S> for TenantContext
+where
+  S: Send + Sync,
+{
+  type Rejection = ApiError;
+
+  async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+    if let Some(tenant) = parts.extensions.get::<TenantContext>() {
+      return Ok(tenant.clone());
+    }
+    let Some(value) = parts.headers.get(TENANT_HEADER) else {
+      return Ok(TenantContext(None));
+    };
+    let tenant = value
+      .to_str()
+      .ok()
+      .map(str::trim)
+      .filter(|tenant| is_valid_tenant(tenant))
+      .ok_or_else(|| ApiError::InvalidRequest("Invalid tenant id".to_string()))?;
+    Ok(TenantContext(Some(tenant.to_string())))
+  }
+}
+
+/// Tenant ids end up in database URLs, so only a safe subset of characters is allowed.
+fn is_valid_tenant(tenant: &str) -> bool {
+  !tenant.is_empty()
+    && tenant.len() <= 63
+    && tenant
+      .bytes()
+      .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'-' | b'_'))
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::Request;
+
+  use super::*;
+
+  async fn extract(request: Request<()>) -> Result<TenantContext, ApiError> {
+    let (mut parts, _) = request.into_parts();
+    TenantContext::from_request_parts(&mut parts, &()).await
+  }
+
+  #[tokio::test]
+  async fn test_tenant_from_header() {
+    let request = Request::builder()
+      .header(TENANT_HEADER, "acme")
+      .body(())
+      .unwrap();
+    assert_eq!(extract(request).await.unwrap().id(), Some("acme"));
+  }
+
+  #[tokio::test]
+  async fn test_token_tenant_wins_over_header() {
+    let request = Request::builder()
+      .header(TENANT_HEADER, "other")
+      .extension(TenantContext(Some("acme".to_string())))
+      .body(())
+      .unwrap();
+    assert_eq!(extract(request).await.unwrap().id(), Some("acme"));
+
+    // Users outside of any tenant can't pick one either
+    let request = Request::builder()
+      .header(TENANT_HEADER, "other")
+      .extension(TenantContext(None))
+      .body(())
+      .unwrap();
+    assert_eq!(extract(request).await.unwrap(), TenantContext(None));
+  }
+
+  #[test]
+  fn test_valid_tenant() {
+    assert!(is_valid_tenant("acme"));
+    assert!(is_valid_tenant("acme-eu_1"));
+  }
+
+  #[test]
+  fn test_invalid_tenant() {
+    assert!(!is_valid_tenant(""));
+    assert!(!is_valid_tenant("Acme"));
+    assert!(!is_valid_tenant("acme/../other"));
+    assert!(!is_valid_tenant("acme?sslmode=disable"));
+    assert!(!is_valid_tenant(&"a".repeat(64)));
+  }
+}
@@ -0,0 +1,44 @@
+use std::{ops::Deref, sync::Arc};
+
+use anyhow::anyhow;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use sea_orm::DatabaseTransaction;
+
+use crate::common::errors::ApiError;
+
+/// Extracts the request-scoped transaction opened by the `transaction` middleware.
+///
+/// Derefs to `DatabaseTransaction`, so `&*tx` can be passed to services expecting a
+/// `ConnectionTrait`. Writes are committed when the handler returns a 2xx response and
+/// rolled back otherwise.
+#[derive(Clone)]
+pub struct Tx(Arc<DatabaseTransaction>);
+
+impl Tx {
+  pub(crate) fn new(txn: Arc<DatabaseTransaction>) -> Self {
+    Self(txn)
+  }
+}
+
+impl Deref for Tx {
+  type Target = DatabaseTransaction;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl<S> FromRequestParts<S> for Tx
+where
+  S: Send + Sync,
+{
+  type Rejection = ApiError;
+
+  async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+    parts.extensions.get::<Tx>().cloned().ok_or_else(|| {
+      ApiError::InternalError(anyhow!(
+        "Tx extractor used on a route without the transaction middleware"
+      ))
+    })
+  }
+}
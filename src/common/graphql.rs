@@ -1,18 +1,39 @@
-use async_graphql::{dynamic::*, http::GraphiQLSource};
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use async_graphql::{
+  dynamic::*,
+  http::{GraphiQLSource, ALL_WEBSOCKET_PROTOCOLS},
+  Data,
+};
+use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
 use axum::{
-  extract::State,
-  response::Html,
+  body::Bytes,
+  extract::{State, WebSocketUpgrade},
+  response::{Html, Response},
   routing::{get, post},
   Router,
 };
-use sea_orm::DatabaseConnection;
-use seaography::{async_graphql, lazy_static, Builder, BuilderContext};
+use futures_util::StreamExt;
+use sea_orm::{ActiveEnum, DatabaseConnection, EntityTrait};
+use seaography::{async_graphql, lazy_static, Builder, BuilderContext, LifecycleHooks};
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
 
 use crate::app::AppState;
-use crate::common::middlewares;
-use crate::modules::auth::guards::{auth_guard, graphql_guards};
-use crate::modules::users::{self, entities as usersEntities};
+use crate::common::config::Config;
+use crate::common::events::UserEvents;
+use crate::common::extractors::{CurrentUser, TenantContext};
+use crate::common::middlewares::{self, basic_auth::BasicAuthCredentials};
+use crate::common::storage::SharedStorage;
+use crate::modules::auth::guards::{
+  auth_guard,
+  graphql_guards::{self, QueryMaxRows},
+};
+use crate::modules::users::{
+  self,
+  dto::{UserCreate, UserDeleteMode, UserDto, UserPatch},
+  entities as usersEntities,
+  enums::UserRole,
+};
 
 lazy_static::lazy_static! {
   static ref CONTEXT: BuilderContext = {
@@ -21,67 +42,328 @@ lazy_static::lazy_static! {
 
     BuilderContext {
       guards,
+      hooks: LifecycleHooks::new(graphql_guards::NotDeletedHooks),
       ..context
     }
   };
 }
 
+/// Entities that can be exposed through GraphQL, by table name.
+///
+/// Only the ones listed in the `GRAPHQL_ENTITIES` allowlist are registered in the schema,
+/// so new tables are never exposed by default. Tables holding secrets, like the email
+/// verification and password reset tokens, are never available.
+pub const AVAILABLE_ENTITIES: &[&str] = &["users"];
+
 pub fn schema(
   database: DatabaseConnection,
+  entities: &[String],
   depth: Option<usize>,
   complexity: Option<usize>,
+  max_rows: Option<u64>,
 ) -> Result<Schema, SchemaError> {
+  for entity in entities {
+    if !AVAILABLE_ENTITIES.contains(&entity.as_str()) {
+      tracing::warn!("Unknown GraphQL entity '{}' in allowlist, ignoring", entity);
+    }
+  }
+  let is_exposed = |table: &str| entities.iter().any(|entity| entity == table);
+
   // Create a new schema builder with the provided database connection
   let mut builder = Builder::new(&CONTEXT, database.clone());
 
-  // Register the entities
-  seaography::register_entities!(builder, [usersEntities]);
+  // Register the allowlisted entities. The users have no relations, and their mutations
+  // go through the users service instead of the generated ones, see `users_mutations`
+  if is_exposed("users") {
+    builder.register_entity::<usersEntities::Entity>(vec![]);
+    let (inputs, mutations) = users_mutations();
+    builder.inputs.extend(inputs);
+    builder.mutations.extend(mutations);
+  }
 
   // Register the active enums
   builder.register_enumeration::<users::enums::UserStatus>();
   builder.register_enumeration::<users::enums::UserRole>();
 
   // Register the custom scalars
-  builder
+  let mut schema_builder = builder
     .set_depth_limit(depth)
     .set_complexity_limit(complexity)
     .schema_builder()
-    .data(database)
+    .data(database);
+
+  // Entity queries must then be paginated, see `graphql_guards::bounded_query_guard`
+  if let Some(max_rows) = max_rows {
+    schema_builder = schema_builder.data(QueryMaxRows(max_rows));
+  }
+  schema_builder.finish()
+}
+
+/// Mutations of the users: `usersCreateOne`, `usersUpdate` and `usersDelete`.
+///
+/// They call the users service like the REST routes, so passwords are hashed, immutable fields
+/// and the last admin are protected, the audit fields and log are written, and deleting
+/// anonymizes the user. Admins manage every user, the others only their own profile.
+fn users_mutations() -> (Vec<InputObject>, Vec<Field>) {
+  let required = || TypeRef::named_nn(TypeRef::STRING);
+  let inputs = vec![
+    InputObject::new("UserCreateInput")
+      .field(InputValue::new("email", required()))
+      .field(InputValue::new("password", required()))
+      .field(InputValue::new("name", required())),
+    InputObject::new("UserUpdateInput")
+      .field(InputValue::new("name", TypeRef::named(TypeRef::STRING)))
+      .field(InputValue::new("email", TypeRef::named(TypeRef::STRING))),
+  ];
+
+  let create = Field::new("usersCreateOne", TypeRef::named_nn("Users"), |ctx| {
+    FieldFuture::new(async move {
+      let actor = mutation_actor(&ctx)?;
+      if !actor.is_admin() {
+        return Err("Admin role required".into());
+      }
+      let user = ctx.args.try_get("data")?.deserialize::<UserCreate>()?;
+      user.validate()?;
+      let db = ctx.data::<DatabaseConnection>()?;
+      let created = users::service::create(
+        db,
+        ctx.data::<Config>()?,
+        ctx.data::<UserEvents>()?,
+        &TenantContext::default(),
+        user.email,
+        user.password,
+        user.name,
+        Some(actor.id()?),
+      )
+      .await?;
+      find_user(db, Uuid::parse_str(&created.id)?).await
+    })
+  })
+  .argument(InputValue::new(
+    "data",
+    TypeRef::named_nn("UserCreateInput"),
+  ));
+
+  let update = Field::new("usersUpdate", TypeRef::named_nn("Users"), |ctx| {
+    FieldFuture::new(async move {
+      let actor = mutation_actor(&ctx)?;
+      let id = owned_user_id(&ctx, &actor)?;
+      let patch = ctx.args.try_get("data")?.deserialize::<UserPatch>()?;
+      patch.validate()?;
+      let db = ctx.data::<DatabaseConnection>()?;
+      users::service::update(
+        db,
+        ctx.data::<Config>()?,
+        &TenantContext::default(),
+        id,
+        patch,
+        actor.is_admin(),
+        actor.id()?,
+      )
+      .await?;
+      find_user(db, id).await
+    })
+  })
+  .argument(InputValue::new("id", required()))
+  .argument(InputValue::new(
+    "data",
+    TypeRef::named_nn("UserUpdateInput"),
+  ));
+
+  let delete = Field::new("usersDelete", TypeRef::named_nn(TypeRef::BOOLEAN), |ctx| {
+    FieldFuture::new(async move {
+      let actor = mutation_actor(&ctx)?;
+      let id = owned_user_id(&ctx, &actor)?;
+      users::service::destroy(
+        ctx.data::<DatabaseConnection>()?,
+        ctx.data::<SharedStorage>()?.as_ref(),
+        &TenantContext::default(),
+        id,
+        UserDeleteMode::Anonymize,
+        actor.id()?,
+      )
+      .await?;
+      Ok(Some(FieldValue::value(true)))
+    })
+  })
+  .argument(InputValue::new("id", required()));
+
+  (inputs, vec![create, update, delete])
+}
+
+/// The user running a mutation, the users of a tenant are blocked like for the queries.
+fn mutation_actor(ctx: &ResolverContext) -> async_graphql::Result<CurrentUser> {
+  if let seaography::GuardAction::Block(reason) = graphql_guards::tenant_guard(ctx) {
+    return Err(reason.unwrap_or_default().into());
+  }
+  Ok(CurrentUser(ctx.data::<UserDto>()?.clone()))
+}
+
+/// The `id` argument of a mutation, which must be the actor's own unless they are an admin.
+fn owned_user_id(ctx: &ResolverContext, actor: &CurrentUser) -> async_graphql::Result<Uuid> {
+  let id = Uuid::parse_str(ctx.args.try_get("id")?.string()?)?;
+  if !actor.is_admin() && actor.id()? != id {
+    return Err("You can only access your own resource".into());
+  }
+  Ok(id)
+}
+
+/// Resolves the `Users` object of a mutation from the stored user.
+async fn find_user(
+  db: &DatabaseConnection,
+  id: Uuid,
+) -> async_graphql::Result<Option<FieldValue<'static>>> {
+  Ok(
+    usersEntities::Entity::find_by_id(id)
+      .one(db)
+      .await?
+      .map(FieldValue::owned_any),
+  )
+}
+
+/// Schema of the subscriptions, served over WebSocket at `{GRAPHQL_ENDPOINT}/ws`.
+///
+/// Seaography only builds queries and mutations, so the subscriptions have their own schema,
+/// with `me` as the query root that GraphQL requires.
+pub fn subscription_schema(events: UserEvents) -> Result<Schema, SchemaError> {
+  let required = || TypeRef::named_nn(TypeRef::STRING);
+  let user = Object::new("User")
+    .field(user_field("id", required(), |user| Some(&user.id)))
+    .field(user_field("email", required(), |user| Some(&user.email)))
+    .field(user_field("name", required(), |user| Some(&user.name)))
+    .field(user_field("status", required(), |user| Some(&user.status)))
+    .field(user_field("role", required(), |user| Some(&user.role)))
+    .field(user_field(
+      "createdAt",
+      TypeRef::named(TypeRef::STRING),
+      |user| user.created_at.as_ref(),
+    ));
+
+  let query = Object::new("Query").field(Field::new("me", TypeRef::named_nn("User"), |ctx| {
+    FieldFuture::new(async move {
+      let user = ctx.data::<UserDto>()?;
+      Ok(Some(FieldValue::owned_any(user.clone())))
+    })
+  }));
+
+  // Users created from the REST API, as creating them involves hashing and the audit fields
+  let subscription = Subscription::new("Subscription").field(SubscriptionField::new(
+    "userCreated",
+    TypeRef::named_nn("User"),
+    |ctx| {
+      SubscriptionFieldFuture::new(async move {
+        let user = ctx.data::<UserDto>()?;
+        if UserRole::try_from_value(&user.role).ok() != Some(UserRole::Admin) {
+          return Err(async_graphql::Error::new("Admin role required"));
+        }
+        // Only the users of the subscriber's tenant are sent
+        let tenant = user.tenant_id.clone();
+        let created_users = ctx.data::<UserEvents>()?.created_users();
+        Ok(
+          created_users
+            .filter(move |user| std::future::ready(user.tenant_id == tenant))
+            .map(|user| Ok(FieldValue::owned_any(user))),
+        )
+      })
+    },
+  ));
+
+  Schema::build("Query", None, Some("Subscription"))
+    .register(user)
+    .register(query)
+    .register(subscription)
+    .data(events)
     .finish()
 }
 
+/// A field of the `User` object, read from the `UserDto` it resolves.
+fn user_field(name: &str, type_ref: TypeRef, value: fn(&UserDto) -> Option<&String>) -> Field {
+  Field::new(name, type_ref, move |ctx| {
+    FieldFuture::new(async move {
+      let user = ctx.parent_value.try_downcast_ref::<UserDto>()?;
+      Ok(value(user).cloned().map(async_graphql::Value::from))
+    })
+  })
+}
+
+/// The GraphQL schemas, built once at startup by `build_schema`.
+#[derive(Clone)]
+pub struct Schemas {
+  /// Queries and mutations, served at `GRAPHQL_ENDPOINT`
+  pub query: Schema,
+  /// Subscriptions, served at `{GRAPHQL_ENDPOINT}/ws`
+  pub subscription: Schema,
+}
+
+/// Builds the schemas from the configuration, failing with the reason when they are invalid so
+/// that startup can report it.
+pub fn build_schema(app_state: &AppState) -> Result<Schemas, SchemaError> {
+  let depth = app_state.cfg.graphql_depth_limit;
+  let complexity = app_state.cfg.graphql_complexity_limit;
+  tracing::info!(?depth, ?complexity, "GraphQL query limits");
+  Ok(Schemas {
+    query: schema(
+      app_state.db.conn.clone(),
+      &app_state.cfg.graphql_entities,
+      depth,
+      complexity,
+      Some(app_state.cfg.query_max_rows),
+    )?,
+    subscription: subscription_schema(app_state.events.clone())?,
+  })
+}
+
 /// Create the GraphQL router with playground and query handler.
-pub fn router(app_state: AppState) -> Router<AppState> {
-  let schema = schema(app_state.db.conn.clone(), None, None).unwrap();
+///
+/// The schemas, the playground page and the basic auth credentials are built once and shared
+/// across requests, so the request path only clones reference-counted handles.
+pub fn router(app_state: AppState, schemas: Schemas) -> Router<AppState> {
+  // The playground and its basic auth are only built when the playground is served
+  let mut playground_router = Router::new();
+  if app_state.cfg.features.graphql_playground_enabled {
+    // Render the playground page once, `Bytes` clones are reference-counted.
+    let playground = Bytes::from(
+      GraphiQLSource::build()
+        .endpoint(&app_state.cfg.graphql_endpoint)
+        .finish(),
+    );
+
+    playground_router = playground_router.route(
+      "/",
+      get(move || {
+        let playground = playground.clone();
+        async move { Html(playground) }
+      }),
+    );
+    if !app_state.cfg.graphql_basic_auth.is_empty() {
+      let credentials = match BasicAuthCredentials::parse(app_state.cfg.graphql_basic_auth.expose())
+      {
+        Some(credentials) => Arc::new(credentials),
+        // We're immediately panicking here because this is a configuration error that should be
+        // caught during application startup.
+        None => panic!("Invalid format for graphql_basic_auth. Expected 'username:password'."),
+      };
+      playground_router = playground_router.layer(axum::middleware::from_fn_with_state(
+        credentials,
+        middlewares::basic_auth::basic_auth_layer,
+      ));
+    }
+  }
+
+  // Authenticated on connection init, browsers can't set headers on WebSocket requests
+  let ws_router = Router::new()
+    .route("/ws", get(graphql_ws_handler))
+    .with_state((schemas.subscription, app_state.clone()));
+
   Router::new().nest(
     &app_state.cfg.graphql_endpoint,
     Router::new()
-      .merge({
-        let mut router = Router::new().route("/", get(graphql_playground));
-        if !app_state.cfg.graphql_basic_auth.is_empty() {
-          let parts: Vec<&str> = app_state.cfg.graphql_basic_auth.split(':').collect();
-          if parts.len() == 2 {
-            router = router.layer(axum::middleware::from_fn({
-              let auth_config = app_state.clone();
-              move |req, next| {
-                let auth_config = auth_config.clone();
-                async move {
-                  middlewares::basic_auth::basic_auth_layer(State(auth_config), req, next).await
-                }
-              }
-            }));
-          } else {
-            // We're immediately panicking here because this is a configuration error that should be
-            // caught during application startup.
-            panic!("Invalid format for graphql_basic_auth. Expected 'username:password'.");
-          }
-        }
-        router
-      })
+      .merge(playground_router)
+      .merge(ws_router)
       .merge(
         Router::new()
           .route("/", post(graphql_handler))
-          .with_state(schema)
+          .with_state((schemas.query, app_state.clone()))
           .layer(axum::middleware::from_fn_with_state(
             app_state.clone(),
             auth_guard,
@@ -90,11 +372,489 @@ pub fn router(app_state: AppState) -> Router<AppState> {
   )
 }
 
-async fn graphql_handler(schema: State<Schema>, req: GraphQLRequest) -> GraphQLResponse {
-  schema.execute(req.into_inner()).await.into()
+async fn graphql_handler(
+  State((schema, state)): State<(Schema, AppState)>,
+  CurrentUser(user): CurrentUser,
+  req: GraphQLRequest,
+) -> GraphQLResponse {
+  // Expose the authenticated user and its tenant to the guards, and what the users service
+  // needs to the mutations
+  let mut request = req
+    .into_inner()
+    .data(TenantContext(user.tenant_id.clone()))
+    .data(state.cfg)
+    .data(state.events)
+    .data(state.storage);
+  if let Ok(role) = UserRole::try_from_value(&user.role) {
+    request = request.data(role);
+  }
+  schema.execute(request.data(user)).await.into()
 }
 
-async fn graphql_playground(State(state): State<AppState>) -> Html<String> {
-  let endpoint = &state.cfg.graphql_endpoint;
-  Html(GraphiQLSource::build().endpoint(endpoint).finish())
+async fn graphql_ws_handler(
+  State((schema, state)): State<(Schema, AppState)>,
+  protocol: GraphQLProtocol,
+  upgrade: WebSocketUpgrade,
+) -> Response {
+  upgrade
+    .protocols(ALL_WEBSOCKET_PROTOCOLS)
+    .on_upgrade(move |stream| {
+      GraphQLWebSocket::new(stream, schema, protocol)
+        .on_connection_init(move |payload| connection_init(state, payload))
+        .serve()
+    })
+}
+
+/// Authenticates a WebSocket connection from the `Authorization: Bearer <token>` or the
+/// `token` entry of its init payload, the connection is closed when it fails.
+async fn connection_init(
+  state: AppState,
+  payload: serde_json::Value,
+) -> async_graphql::Result<Data> {
+  let entry = |name: &str| payload.get(name).and_then(serde_json::Value::as_str);
+  let token = match (
+    entry("Authorization").or(entry("authorization")),
+    entry("token"),
+  ) {
+    (Some(header), _) => auth_guard::bearer_token(header).map_err(|e| e.to_string())?,
+    (None, Some(token)) => token,
+    (None, None) => return Err("Missing authorization in the connection init payload".into()),
+  };
+  let user = auth_guard::authenticate_token(&state, token)
+    .await
+    .map_err(|e| e.to_string())?;
+
+  let mut data = Data::default();
+  data.insert(user);
+  Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use sea_orm::{DbBackend, MockDatabase};
+
+  use super::*;
+  use crate::common::config::Secret;
+
+  fn mock_database() -> DatabaseConnection {
+    MockDatabase::new(DbBackend::Postgres).into_connection()
+  }
+
+  fn user() -> usersEntities::Model {
+    let now = chrono::Utc::now();
+    usersEntities::Model {
+      id: uuid::Uuid::new_v4(),
+      email: "user@example.com".to_string(),
+      name: "user".to_string(),
+      password: "$2b$04$hash".to_string(),
+      status: users::enums::UserStatus::Active,
+      role: UserRole::User,
+      created_at: Some(now),
+      updated_at: Some(now),
+      created_by: None,
+      updated_by: None,
+      deleted_at: None,
+      last_login_at: None,
+      avatar_url: None,
+      tenant_id: None,
+      token_version: 0,
+    }
+  }
+
+  fn state() -> AppState {
+    let cfg = crate::common::config::Configuration::for_tests();
+    AppState {
+      db: crate::database::Db {
+        conn: mock_database(),
+        tenants: None,
+      },
+      background: crate::common::background::BackgroundTasks::new(cfg.background_concurrency),
+      streams: crate::common::streams::StreamLimit::new(cfg.max_stream_connections),
+      events: UserEvents::default(),
+      storage: Arc::new(crate::common::storage::LocalStorage::new(&cfg.storage_dir)),
+      cfg,
+      notifier: Arc::new(crate::common::notifier::LogNotifier),
+      readiness: Default::default(),
+    }
+  }
+
+  fn count(num_items: i64) -> BTreeMap<&'static str, sea_orm::Value> {
+    BTreeMap::from([("num_items", num_items.into())])
+  }
+
+  #[test]
+  fn test_schema_exposes_allowlisted_entities() {
+    let entities = vec!["users".to_string()];
+    let sdl = schema(mock_database(), &entities, None, None, None)
+      .unwrap()
+      .sdl();
+    assert!(sdl.contains("users("));
+  }
+
+  #[test]
+  fn test_schema_hides_entities_not_allowlisted() {
+    let entities = vec!["users".to_string()];
+    let sdl = schema(mock_database(), &entities, None, None, None)
+      .unwrap()
+      .sdl();
+    assert!(!sdl.contains("emailVerificationTokens("));
+    assert!(!sdl.contains("passwordResetTokens("));
+  }
+
+  #[test]
+  fn test_schema_exposes_users_mutations() {
+    let entities = vec!["users".to_string()];
+    let sdl = schema(mock_database(), &entities, None, None, None)
+      .unwrap()
+      .sdl();
+    assert!(sdl.contains("usersCreateOne("));
+    assert!(sdl.contains("usersUpdate("));
+    assert!(sdl.contains("usersDelete("));
+    assert!(!sdl.contains("usersCreateBatch("));
+  }
+
+  #[tokio::test]
+  async fn test_users_are_blocked_for_tenant_admins() {
+    let entities = vec!["users".to_string()];
+    let schema = schema(mock_database(), &entities, None, None, None).unwrap();
+    let request = async_graphql::Request::new("{ users { nodes { email } } }")
+      .data(UserRole::Admin)
+      .data(TenantContext(Some("acme".to_string())));
+    let response = schema.execute(request).await;
+    assert_eq!(
+      response.errors[0].message,
+      "Not available to the users of a tenant"
+    );
+  }
+
+  #[tokio::test]
+  async fn test_users_mutations_require_admin() {
+    let entities = vec!["users".to_string()];
+    let schema = schema(mock_database(), &entities, None, None, None).unwrap();
+    let request = mutation(
+      r#"mutation { usersCreateOne(data: { email: "new@example.com", password: "password123", name: "new" }) { id } }"#,
+      UserDto::from(user()),
+    );
+    let response = schema.execute(request).await;
+    assert_eq!(response.errors[0].message, "Admin role required");
+  }
+
+  #[tokio::test]
+  async fn test_users_mutations_are_limited_to_the_owner() {
+    let entities = vec!["users".to_string()];
+    let schema = schema(mock_database(), &entities, None, None, None).unwrap();
+    let request = mutation(
+      &format!(
+        r#"mutation {{ usersDelete(id: "{}") }}"#,
+        uuid::Uuid::new_v4()
+      ),
+      UserDto::from(user()),
+    );
+    let response = schema.execute(request).await;
+    assert_eq!(
+      response.errors[0].message,
+      "You can only access your own resource"
+    );
+  }
+
+  #[tokio::test]
+  async fn test_users_update_applies_the_service_rules() {
+    let owner = user();
+    let database = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![owner.clone()]])
+      .into_connection();
+    let entities = vec!["users".to_string()];
+    let schema = schema(database, &entities, None, None, None).unwrap();
+    let request = mutation(
+      &format!(
+        r#"mutation {{ usersUpdate(id: "{}", data: {{ email: "new@example.com" }}) {{ id }} }}"#,
+        owner.id
+      ),
+      UserDto::from(owner),
+    );
+    let response = schema.execute(request).await;
+    assert!(response.errors[0].message.contains("email"));
+  }
+
+  /// A mutation request of `user`, with the data the GraphQL handler adds.
+  fn mutation(query: &str, user: UserDto) -> async_graphql::Request {
+    let mut request = async_graphql::Request::new(query)
+      .data(TenantContext(user.tenant_id.clone()))
+      .data(crate::common::config::Configuration::for_tests())
+      .data(UserEvents::default())
+      .data(state().storage);
+    if let Ok(role) = UserRole::try_from_value(&user.role) {
+      request = request.data(role);
+    }
+    request.data(user)
+  }
+
+  #[tokio::test]
+  async fn test_password_is_write_only() {
+    let database = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user()], vec![user()]])
+      .into_connection();
+    let entities = vec!["users".to_string()];
+    let schema = schema(database, &entities, None, None, None).unwrap();
+
+    let response = schema
+      .execute(async_graphql::Request::new("{ users { nodes { email } } }").data(UserRole::Admin))
+      .await;
+    assert!(response.errors.is_empty());
+
+    let response = schema
+      .execute(
+        async_graphql::Request::new("{ users { nodes { email password } } }").data(UserRole::Admin),
+      )
+      .await;
+    assert!(response.errors[0].message.contains("write-only"));
+  }
+
+  #[tokio::test]
+  async fn test_depth_limit_rejects_nested_queries() {
+    let entities = vec!["users".to_string()];
+    let schema = schema(mock_database(), &entities, Some(2), None, None).unwrap();
+    let response = schema
+      .execute(async_graphql::Request::new("{ users { nodes { email } } }").data(UserRole::Admin))
+      .await;
+    assert!(response.errors[0].message.contains("nested too deep"));
+  }
+
+  #[tokio::test]
+  async fn test_complexity_limit_rejects_complex_queries() {
+    let entities = vec!["users".to_string()];
+    let schema = schema(mock_database(), &entities, None, Some(2), None).unwrap();
+    let response = schema
+      .execute(
+        async_graphql::Request::new("{ users { nodes { id email name } } }").data(UserRole::Admin),
+      )
+      .await;
+    assert!(response.errors[0].message.contains("too complex"));
+  }
+
+  #[tokio::test]
+  async fn test_unbounded_query_is_rejected() {
+    let entities = vec!["users".to_string()];
+    let schema = schema(mock_database(), &entities, None, None, Some(100)).unwrap();
+    let response = schema
+      .execute(async_graphql::Request::new("{ users { nodes { email } } }").data(UserRole::Admin))
+      .await;
+    assert!(response.errors[0].message.contains("Pagination"));
+
+    let response = schema
+      .execute(
+        async_graphql::Request::new(
+          "{ users(pagination: { page: { page: 0, limit: 1000 } }) { nodes { email } } }",
+        )
+        .data(UserRole::Admin),
+      )
+      .await;
+    assert!(response.errors[0].message.contains("must not exceed 100"));
+  }
+
+  #[tokio::test]
+  async fn test_paginated_query_succeeds() {
+    let database = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![count(1)]])
+      .append_query_results([vec![user()]])
+      .into_connection();
+    let entities = vec!["users".to_string()];
+    let schema = schema(database, &entities, None, None, Some(100)).unwrap();
+    let response = schema
+      .execute(
+        async_graphql::Request::new(
+          "{ users(pagination: { page: { page: 0, limit: 10 } }) { nodes { email } } }",
+        )
+        .data(UserRole::Admin),
+      )
+      .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+  }
+
+  #[tokio::test]
+  async fn test_users_query_hides_deleted_users() {
+    let database = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![count(1)]])
+      .append_query_results([vec![user()]])
+      .into_connection();
+    let entities = vec!["users".to_string()];
+    let schema = schema(database.clone(), &entities, None, None, Some(100)).unwrap();
+    let response = schema
+      .execute(
+        async_graphql::Request::new(
+          "{ users(pagination: { page: { page: 0, limit: 10 } }) { nodes { email } } }",
+        )
+        .data(UserRole::Admin),
+      )
+      .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    let log = format!("{:?}", database.into_transaction_log());
+    assert!(log.contains("\\\"deleted_at\\\" IS NULL"));
+    assert!(log.contains("\\\"status\\\" <> "));
+  }
+
+  fn created_user(role: UserRole) -> UserDto {
+    UserDto {
+      email: "created@example.com".to_string(),
+      role: role.to_value(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_subscription_schema_exposes_user_created() {
+    let sdl = subscription_schema(UserEvents::default()).unwrap().sdl();
+    assert!(sdl.contains("userCreated: User!"));
+  }
+
+  #[tokio::test]
+  async fn test_user_created_streams_published_users() {
+    let events = UserEvents::default();
+    let schema = subscription_schema(events.clone()).unwrap();
+    let mut stream = schema.execute_stream(
+      async_graphql::Request::new("subscription { userCreated { email role } }")
+        .data(created_user(UserRole::Admin)),
+    );
+
+    // The subscription starts listening on the first poll
+    let next = tokio::spawn(async move { stream.next().await.unwrap() });
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    events.user_created(created_user(UserRole::User));
+
+    let response = next.await.unwrap();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    let data = response.data.into_json().unwrap();
+    assert_eq!(data["userCreated"]["email"], "created@example.com");
+    assert_eq!(data["userCreated"]["role"], "User");
+  }
+
+  #[tokio::test]
+  async fn test_user_created_skips_other_tenants() {
+    let events = UserEvents::default();
+    let schema = subscription_schema(events.clone()).unwrap();
+    let admin = UserDto {
+      tenant_id: Some("acme".to_string()),
+      ..created_user(UserRole::Admin)
+    };
+    let mut stream = schema.execute_stream(
+      async_graphql::Request::new("subscription { userCreated { email } }").data(admin),
+    );
+
+    let next = tokio::spawn(async move { stream.next().await.unwrap() });
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    events.user_created(UserDto {
+      email: "other@example.com".to_string(),
+      tenant_id: Some("other".to_string()),
+      ..Default::default()
+    });
+    events.user_created(UserDto {
+      email: "acme@example.com".to_string(),
+      tenant_id: Some("acme".to_string()),
+      ..Default::default()
+    });
+
+    let data = next.await.unwrap().data.into_json().unwrap();
+    assert_eq!(data["userCreated"]["email"], "acme@example.com");
+  }
+
+  #[tokio::test]
+  async fn test_user_created_requires_admin() {
+    let schema = subscription_schema(UserEvents::default()).unwrap();
+    let mut stream = schema.execute_stream(
+      async_graphql::Request::new("subscription { userCreated { email } }")
+        .data(created_user(UserRole::User)),
+    );
+    let response = stream.next().await.unwrap();
+    assert_eq!(response.errors[0].message, "Admin role required");
+  }
+
+  #[tokio::test]
+  async fn test_connection_init_requires_a_token() {
+    let state = state();
+    let error = connection_init(state.clone(), serde_json::json!({}))
+      .await
+      .unwrap_err();
+    assert!(error.message.contains("Missing authorization"));
+
+    let error = connection_init(state, serde_json::json!({ "Authorization": "Basic abc" }))
+      .await
+      .unwrap_err();
+    assert!(error.message.contains("Invalid authorization format"));
+  }
+
+  async fn status(state: AppState, method: &str) -> axum::http::StatusCode {
+    use tower::ServiceExt;
+
+    let schemas = build_schema(&state).unwrap();
+    let app: Router = router(state.clone(), schemas).with_state(state);
+    let request = axum::http::Request::builder()
+      .method(method)
+      .uri("/graphql")
+      .header("content-type", "application/json")
+      .body(axum::body::Body::from(r#"{"query":"{ __typename }"}"#))
+      .unwrap();
+    app.oneshot(request).await.unwrap().status()
+  }
+
+  #[tokio::test]
+  async fn test_playground_is_public_and_queries_require_auth() {
+    assert_eq!(status(state(), "GET").await, axum::http::StatusCode::OK);
+    assert_eq!(
+      status(state(), "POST").await,
+      axum::http::StatusCode::UNAUTHORIZED
+    );
+  }
+
+  #[tokio::test]
+  async fn test_playground_requires_configured_basic_auth() {
+    let mut state = state();
+    Arc::get_mut(&mut state.cfg).unwrap().graphql_basic_auth = Secret::new("admin:secret");
+    assert_eq!(
+      status(state, "GET").await,
+      axum::http::StatusCode::UNAUTHORIZED
+    );
+  }
+
+  #[tokio::test]
+  async fn test_disabled_playground_ignores_basic_auth() {
+    let mut state = state();
+    let cfg = Arc::get_mut(&mut state.cfg).unwrap();
+    cfg.features.graphql_playground_enabled = false;
+    cfg.graphql_basic_auth = Secret::new("invalid");
+    assert_eq!(
+      status(state, "GET").await,
+      axum::http::StatusCode::METHOD_NOT_ALLOWED
+    );
+  }
+
+  #[test]
+  fn test_build_schema_builds_both_schemas() {
+    let schemas = build_schema(&state()).unwrap();
+    assert!(schemas.query.sdl().contains("users("));
+    assert!(schemas.subscription.sdl().contains("userCreated"));
+  }
+
+  #[tokio::test]
+  async fn test_token_tables_cannot_be_allowlisted() {
+    let entities = vec!["users".to_string(), "password_reset_tokens".to_string()];
+    let schema = schema(mock_database(), &entities, None, None, None).unwrap();
+    assert!(!schema.sdl().contains("passwordResetTokens("));
+
+    let request = async_graphql::Request::new("{ passwordResetTokens { nodes { token } } }")
+      .data(UserRole::User);
+    let response = schema.execute(request).await;
+    assert!(!response.errors.is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_non_allowlisted_entity_is_not_queryable() {
+    let entities = vec!["users".to_string()];
+    let schema = schema(mock_database(), &entities, None, None, None).unwrap();
+    let response = schema
+      .execute("{ passwordResetTokens { nodes { token } } }")
+      .await;
+    assert!(!response.errors.is_empty());
+  }
 }
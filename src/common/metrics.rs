@@ -0,0 +1,59 @@
+use std::sync::{Arc, OnceLock};
+
+use axum::{routing::get, Router};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::common::config::Config;
+use crate::common::middlewares::{self, basic_auth::BasicAuthCredentials};
+
+/// Latency buckets in seconds, from 5ms to 10s.
+const LATENCY_BUCKETS: &[f64] = &[
+  0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Returns the handle of the Prometheus recorder, installing it on first use.
+pub fn handle() -> PrometheusHandle {
+  static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+  HANDLE
+    .get_or_init(|| {
+      PrometheusBuilder::new()
+        .set_buckets_for_metric(
+          Matcher::Full("http_request_duration_seconds".to_string()),
+          LATENCY_BUCKETS,
+        )
+        .expect("Latency buckets should not be empty")
+        .install_recorder()
+        .expect("Failed to install the Prometheus recorder")
+    })
+    .clone()
+}
+
+/// Create the `GET /metrics` router, exposing the metrics in the Prometheus text format.
+///
+/// The endpoint is protected with basic auth when `METRICS_BASIC_AUTH` is set.
+pub fn router<S>(cfg: &Config) -> Router<S>
+where
+  S: Clone + Send + Sync + 'static,
+{
+  let handle = handle();
+  let mut router = Router::new().route(
+    "/metrics",
+    get(move || {
+      let handle = handle.clone();
+      async move { handle.render() }
+    }),
+  );
+  if !cfg.metrics_basic_auth.is_empty() {
+    let credentials = match BasicAuthCredentials::parse(cfg.metrics_basic_auth.expose()) {
+      Some(credentials) => Arc::new(credentials),
+      // We're immediately panicking here because this is a configuration error that should be
+      // caught during application startup.
+      None => panic!("Invalid format for metrics_basic_auth. Expected 'username:password'."),
+    };
+    router = router.layer(axum::middleware::from_fn_with_state(
+      credentials,
+      middlewares::basic_auth::basic_auth_layer,
+    ));
+  }
+  router
+}
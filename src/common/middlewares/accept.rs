@@ -0,0 +1,112 @@
+use axum::{
+  extract::{Request, State},
+  http::header::ACCEPT,
+  middleware::Next,
+  response::Response,
+};
+
+use crate::common::config::Config;
+use crate::common::errors::ApiError;
+
+/// Media ranges matching the JSON responses of the API, NDJSON being used by streamed lists.
+const PRODUCIBLE_RANGES: &[&str] = &[
+  "application/json",
+  "application/x-ndjson",
+  "application/*",
+  "*/*",
+];
+
+/// Answers 406 Not Acceptable when `STRICT_ACCEPT` is enabled and the `Accept` header excludes
+/// JSON, e.g. `Accept: application/xml`. Requests without an `Accept` header are served.
+pub async fn accept_guard(
+  State(cfg): State<Config>,
+  req: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  if cfg.strict_accept {
+    let accept = req
+      .headers()
+      .get_all(ACCEPT)
+      .iter()
+      .filter_map(|value| value.to_str().ok())
+      .collect::<Vec<_>>()
+      .join(",");
+    if !accepts_json(&accept) {
+      return Err(ApiError::NotAcceptable);
+    }
+  }
+  Ok(next.run(req).await)
+}
+
+/// Returns whether the `Accept` header value allows JSON, ranges with `q=0` being excluded.
+fn accepts_json(accept: &str) -> bool {
+  if accept.trim().is_empty() {
+    return true;
+  }
+  accept.split(',').any(|range| {
+    let mut parts = range.split(';');
+    let media_range = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let excluded = parts.any(|param| {
+      let param = param.trim().to_ascii_lowercase();
+      param
+        .strip_prefix("q=")
+        .and_then(|q| q.parse::<f32>().ok())
+        .is_some_and(|q| q <= 0.0)
+    });
+    !excluded && PRODUCIBLE_RANGES.contains(&media_range.as_str())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use axum::{body::Body, routing::get, Router};
+  use hyper::StatusCode;
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::common::config::Configuration;
+
+  async fn status(accept: &str) -> StatusCode {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().strict_accept = true;
+    let app = Router::new()
+      .route("/", get(|| async { "{}" }))
+      .layer(axum::middleware::from_fn_with_state(cfg, accept_guard));
+    app
+      .oneshot(
+        Request::builder()
+          .uri("/")
+          .header(ACCEPT, accept)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap()
+      .status()
+  }
+
+  #[tokio::test]
+  async fn test_acceptable_accept_header() {
+    assert_eq!(status("application/json").await, StatusCode::OK);
+    assert_eq!(
+      status("text/html, application/xml;q=0.9, */*;q=0.8").await,
+      StatusCode::OK
+    );
+  }
+
+  #[tokio::test]
+  async fn test_unacceptable_accept_header() {
+    assert_eq!(status("application/xml").await, StatusCode::NOT_ACCEPTABLE);
+    assert_eq!(
+      status("application/xml, application/json;q=0").await,
+      StatusCode::NOT_ACCEPTABLE
+    );
+  }
+
+  #[test]
+  fn test_missing_accept_header_accepts_json() {
+    assert!(accepts_json(""));
+  }
+}
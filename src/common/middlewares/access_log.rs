@@ -0,0 +1,92 @@
+use std::time::Instant;
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use tracing::info;
+
+use super::request_id::X_REQUEST_ID;
+use crate::modules::users::dto::UserDto;
+
+/// Logs one line per request with its method, path, status, latency, request id and the id of
+/// the authenticated user.
+///
+/// The user is the one `auth_guard` copies to the response extensions, so the field is left out
+/// of the public routes. Must run inside `request_id_layer` to see the generated ids.
+pub async fn access_log(req: Request, next: Next) -> Response {
+  let start = Instant::now();
+  let method = req.method().clone();
+  let path = req.uri().path().to_string();
+  let request_id = req
+    .headers()
+    .get(X_REQUEST_ID)
+    .and_then(|id| id.to_str().ok())
+    .map(str::to_string);
+
+  let response = next.run(req).await;
+
+  let user_id = response
+    .extensions()
+    .get::<UserDto>()
+    .map(|user| user.id.as_str());
+  info!(
+    method = %method,
+    path = %path,
+    status = response.status().as_u16(),
+    latency_ms = start.elapsed().as_millis() as u64,
+    request_id = request_id.as_deref(),
+    user_id,
+    "Request completed"
+  );
+
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, routing::get, Router};
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::common::config::telemetry::capture_logs;
+
+  async fn as_user(req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    response.extensions_mut().insert(UserDto {
+      id: "user-123".to_string(),
+      ..Default::default()
+    });
+    response
+  }
+
+  #[tokio::test]
+  async fn test_request_is_logged_with_user() {
+    let (logs, _guard) = capture_logs();
+    let app: Router = Router::new()
+      .route(
+        "/private",
+        get(|| async { "ok" }).layer(axum::middleware::from_fn(as_user)),
+      )
+      .route("/public", get(|| async { "ok" }))
+      .layer(axum::middleware::from_fn(access_log));
+
+    for uri in ["/private", "/public"] {
+      let request = Request::builder()
+        .uri(uri)
+        .header(X_REQUEST_ID, format!("id-of{}", uri))
+        .body(Body::empty())
+        .unwrap();
+      app.clone().oneshot(request).await.unwrap();
+    }
+
+    let logs = logs.contents();
+    let lines: Vec<&str> = logs.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains(r#""method":"GET""#));
+    assert!(lines[0].contains(r#""path":"/private""#));
+    assert!(lines[0].contains(r#""status":200"#));
+    assert!(lines[0].contains(r#""latency_ms":"#));
+    assert!(lines[0].contains(r#""request_id":"id-of/private""#));
+    assert!(lines[0].contains(r#""user_id":"user-123""#));
+    assert!(lines[1].contains(r#""path":"/public""#));
+    assert!(!lines[1].contains("user_id"));
+  }
+}
@@ -1,42 +1,101 @@
+use std::sync::Arc;
+
 use axum::{
   body::Body,
   extract::State,
+  http::HeaderValue,
   response::{IntoResponse, Response},
 };
 use base64::{engine::general_purpose, Engine};
 use hyper::StatusCode;
+use sha2::{Digest, Sha256};
+
+/// Basic auth credentials, parsed once at startup and shared across requests.
+///
+/// The digest of the expected `Authorization` header value is precomputed, so that checking a
+/// request compares digests of equal length in constant time, leaking neither the
+/// credentials nor their length through timing.
+#[derive(Debug, Clone)]
+pub struct BasicAuthCredentials {
+  expected_digest: [u8; 32],
+}
+
+impl BasicAuthCredentials {
+  /// Parses credentials in the format "username:password".
+  /// Returns `None` if the value doesn't contain a colon.
+  pub fn parse(value: &str) -> Option<Self> {
+    let (username, password) = value.split_once(':')?;
+    let encoded = general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+    let expected_digest = Sha256::digest(format!("Basic {}", encoded)).into();
+    Some(Self { expected_digest })
+  }
+
+  /// Returns true if the `Authorization` header value matches these credentials.
+  pub fn matches(&self, header_value: &HeaderValue) -> bool {
+    let digest = Sha256::digest(header_value.as_bytes());
+    digest
+      .iter()
+      .zip(self.expected_digest.iter())
+      .fold(0, |diff, (a, b)| diff | (a ^ b))
+      == 0
+  }
+}
 
 /// Middleware that applies basic authentication.
 pub async fn basic_auth_layer(
-  State(state): State<crate::app::AppState>,
+  State(credentials): State<Arc<BasicAuthCredentials>>,
   req: axum::http::Request<Body>,
   next: axum::middleware::Next,
 ) -> Result<Response<Body>, StatusCode> {
-  let auth_header = req.headers().get("authorization");
-
-  if let Some(header_value) = auth_header {
-    if let Ok(auth_str) = header_value.to_str() {
-      if auth_str.starts_with("Basic ") {
-        let encoded = &auth_str[6..];
-        if let Ok(decoded) = general_purpose::STANDARD.decode(encoded) {
-          if let Ok(decoded_str) = String::from_utf8(decoded) {
-            let parts: Vec<&str> = decoded_str.splitn(2, ':').collect();
-            let config_parts: Vec<&str> = state.cfg.graphql_basic_auth.split(':').collect();
-            let username = config_parts[0].to_string();
-            let password = config_parts[1].to_string();
-            if parts.len() == 2 && parts[0] == username && parts[1] == password {
-              return Ok(next.run(req).await);
-            }
-          }
-        }
-      }
+  if let Some(header_value) = req.headers().get("authorization") {
+    if credentials.matches(header_value) {
+      return Ok(next.run(req).await);
     }
   }
 
   let mut response = StatusCode::UNAUTHORIZED.into_response();
   response.headers_mut().insert(
     "WWW-Authenticate",
-    "Basic realm=\"Restricted\"".parse().unwrap(),
+    HeaderValue::from_static("Basic realm=\"Restricted\""),
   );
   Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_valid_credentials() {
+    let credentials = BasicAuthCredentials::parse("admin:secret").unwrap();
+    assert!(credentials.matches(&HeaderValue::from_static("Basic YWRtaW46c2VjcmV0")));
+  }
+
+  #[test]
+  fn test_parse_without_colon() {
+    assert!(BasicAuthCredentials::parse("admin").is_none());
+  }
+
+  #[test]
+  fn test_matches_rejects_wrong_credentials() {
+    let credentials = BasicAuthCredentials::parse("admin:secret").unwrap();
+    let wrong = format!("Basic {}", general_purpose::STANDARD.encode("admin:wrong"));
+    assert!(!credentials.matches(&HeaderValue::from_str(&wrong).unwrap()));
+  }
+
+  #[test]
+  fn test_password_may_contain_colons() {
+    let credentials = BasicAuthCredentials::parse("admin:se:cret").unwrap();
+    let header = format!(
+      "Basic {}",
+      general_purpose::STANDARD.encode("admin:se:cret")
+    );
+    assert!(credentials.matches(&HeaderValue::from_str(&header).unwrap()));
+  }
+
+  #[test]
+  fn test_matches_rejects_other_schemes() {
+    let credentials = BasicAuthCredentials::parse("admin:secret").unwrap();
+    assert!(!credentials.matches(&HeaderValue::from_static("Bearer YWRtaW46c2VjcmV0")));
+  }
+}
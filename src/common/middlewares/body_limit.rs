@@ -0,0 +1,56 @@
+use tower_http::limit::RequestBodyLimitLayer;
+
+/// Layer that rejects request bodies larger than `max_bytes` with `413 Payload Too Large`.
+///
+/// Bodies announcing a larger `Content-Length` are rejected upfront, others once the limit is
+/// reached while reading them.
+pub fn body_limit_layer(max_bytes: usize) -> RequestBodyLimitLayer {
+  RequestBodyLimitLayer::new(max_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, extract::Request, routing::post, Router};
+  use hyper::StatusCode;
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::common::extractors::ValidatedJson;
+
+  #[derive(serde::Deserialize, validator::Validate)]
+  struct Payload {
+    #[allow(dead_code)]
+    name: String,
+  }
+
+  fn app() -> Router {
+    Router::new()
+      .route("/", post(|_: ValidatedJson<Payload>| async {}))
+      .layer(body_limit_layer(32))
+  }
+
+  fn request(body: String) -> Request {
+    Request::builder()
+      .method("POST")
+      .header("content-type", "application/json")
+      .header("content-length", body.len())
+      .body(Body::from(body))
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_oversized_body_is_rejected() {
+    let body = format!(r#"{{"name":"{}"}}"#, "a".repeat(64));
+    let response = app().oneshot(request(body)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+  }
+
+  #[tokio::test]
+  async fn test_body_within_limit_is_accepted() {
+    let response = app()
+      .oneshot(request(r#"{"name":"a"}"#.to_string()))
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+  }
+}
@@ -0,0 +1,84 @@
+use tower_http::compression::{
+  predicate::{NotForContentType, Predicate, SizeAbove},
+  CompressionLayer,
+};
+
+/// Layer that compresses responses with gzip, brotli or deflate, as negotiated from the
+/// `Accept-Encoding` header.
+///
+/// Responses smaller than `min_bytes` are sent as is, as are images, gRPC and server-sent
+/// events, which don't benefit from it.
+pub fn compression_layer(min_bytes: u16) -> CompressionLayer<impl Predicate> {
+  let predicate = SizeAbove::new(min_bytes)
+    .and(NotForContentType::GRPC)
+    .and(NotForContentType::IMAGES)
+    .and(NotForContentType::SSE);
+  CompressionLayer::new().compress_when(predicate)
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{
+    body::Body,
+    extract::Request,
+    http::header::{ACCEPT_ENCODING, CONTENT_ENCODING},
+    response::Html,
+    routing::get,
+    Router,
+  };
+  use tower::ServiceExt;
+
+  use super::*;
+
+  fn app() -> Router {
+    Router::new()
+      .route("/large", get(|| async { Html("<p>users</p>".repeat(200)) }))
+      .route("/small", get(|| async { "ok" }))
+      .layer(compression_layer(1024))
+  }
+
+  async fn content_encoding(uri: &str, accept_encoding: &str) -> Option<String> {
+    let response = app()
+      .oneshot(
+        Request::builder()
+          .uri(uri)
+          .header(ACCEPT_ENCODING, accept_encoding)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    response
+      .headers()
+      .get(CONTENT_ENCODING)
+      .map(|value| value.to_str().unwrap().to_string())
+  }
+
+  #[tokio::test]
+  async fn test_gzip_when_requested() {
+    assert_eq!(
+      content_encoding("/large", "gzip").await.as_deref(),
+      Some("gzip")
+    );
+  }
+
+  #[tokio::test]
+  async fn test_brotli_when_preferred() {
+    assert_eq!(
+      content_encoding("/large", "br;q=1.0, gzip;q=0.5")
+        .await
+        .as_deref(),
+      Some("br")
+    );
+  }
+
+  #[tokio::test]
+  async fn test_small_responses_are_not_compressed() {
+    assert_eq!(content_encoding("/small", "gzip").await, None);
+  }
+
+  #[tokio::test]
+  async fn test_identity_without_accept_encoding() {
+    assert_eq!(content_encoding("/large", "identity").await, None);
+  }
+}
@@ -1,12 +1,113 @@
 use std::time::Duration;
 
-use tower_http::cors::{AllowHeaders, Any, CorsLayer};
+use axum::http::HeaderValue;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
+
+use crate::common::config::Environment;
 
 /// Layer that applies the Cors middleware which adds headers for CORS.
-pub fn cors_layer() -> CorsLayer {
-  CorsLayer::new()
-    .allow_origin(Any)
-    .allow_methods(Any)
+///
+/// Only `allowed_origins` are allowed, with credentials. Without them, any origin is allowed in
+/// development, without credentials since browsers reject `*` with credentials, and none in
+/// production.
+///
+/// # Panics
+///
+/// Panics if an origin is not a valid header value, `CORS_ALLOWED_ORIGINS` is checked when the
+/// configuration is loaded.
+pub fn cors_layer(allowed_origins: &[String], env: &Environment) -> CorsLayer {
+  let layer = CorsLayer::new()
     .allow_headers(AllowHeaders::mirror_request())
-    .max_age(Duration::from_secs(600))
+    .max_age(Duration::from_secs(600));
+
+  if allowed_origins.is_empty() {
+    return match env {
+      Environment::Development => layer.allow_origin(Any).allow_methods(Any),
+      Environment::Production => layer.allow_origin(AllowOrigin::list([])).allow_methods(Any),
+    };
+  }
+
+  let origins = allowed_origins
+    .iter()
+    .map(|origin| HeaderValue::from_str(origin).expect("Invalid CORS origin"));
+  layer
+    .allow_origin(AllowOrigin::list(origins))
+    .allow_methods(AllowMethods::mirror_request())
+    .allow_credentials(true)
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{
+    body::Body,
+    extract::Request,
+    http::header::{ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_ORIGIN, ORIGIN},
+    response::Response,
+    routing::get,
+    Router,
+  };
+  use tower::ServiceExt;
+
+  use super::*;
+
+  async fn request(layer: CorsLayer, origin: &str) -> Response {
+    Router::new()
+      .route("/", get(|| async { "ok" }))
+      .layer(layer)
+      .oneshot(
+        Request::builder()
+          .uri("/")
+          .header(ORIGIN, origin)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap()
+  }
+
+  fn allowed_origins() -> Vec<String> {
+    vec!["https://app.example.com".to_string()]
+  }
+
+  #[tokio::test]
+  async fn test_allowed_origin() {
+    let layer = cors_layer(&allowed_origins(), &Environment::Production);
+    let response = request(layer, "https://app.example.com").await;
+    assert_eq!(
+      response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+      "https://app.example.com"
+    );
+    assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+  }
+
+  #[tokio::test]
+  async fn test_disallowed_origin_gets_no_allow_origin_header() {
+    let layer = cors_layer(&allowed_origins(), &Environment::Production);
+    let response = request(layer, "https://evil.example.com").await;
+    assert!(response
+      .headers()
+      .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+      .is_none());
+  }
+
+  #[tokio::test]
+  async fn test_any_origin_in_development_without_origins() {
+    let layer = cors_layer(&[], &Environment::Development);
+    let response = request(layer, "http://localhost:3000").await;
+    assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    assert!(response
+      .headers()
+      .get(ACCESS_CONTROL_ALLOW_CREDENTIALS)
+      .is_none());
+  }
+
+  #[tokio::test]
+  async fn test_no_origin_in_production_without_origins() {
+    let layer = cors_layer(&[], &Environment::Production);
+    let response = request(layer, "http://localhost:3000").await;
+    assert!(response
+      .headers()
+      .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+      .is_none());
+  }
 }
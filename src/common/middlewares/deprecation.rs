@@ -0,0 +1,125 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use axum::{
+  extract::Request,
+  http::{HeaderName, HeaderValue},
+  response::Response,
+};
+use tower::{Layer, Service};
+
+pub const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+pub const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// Layer that marks the routes it is applied to as deprecated.
+///
+/// Every response gets a `Deprecation: true` header and, when `sunset` is given, a `Sunset`
+/// header with the HTTP date after which the route may be removed, e.g.
+/// `Sat, 01 May 2027 00:00:00 GMT`. Remember to also mark the handler `#[deprecated]` so
+/// that the operation is flagged in the OpenAPI document.
+///
+/// # Panics
+///
+/// Panics if `sunset` is not a valid header value.
+pub fn deprecation_layer(sunset: Option<&str>) -> DeprecationLayer {
+  DeprecationLayer {
+    sunset: sunset.map(|sunset| HeaderValue::from_str(sunset).expect("Invalid sunset date")),
+  }
+}
+
+#[derive(Clone)]
+pub struct DeprecationLayer {
+  sunset: Option<HeaderValue>,
+}
+
+impl<S> Layer<S> for DeprecationLayer {
+  type Service = Deprecation<S>;
+
+  fn layer(&self, inner: S) -> Self::Service {
+    Deprecation {
+      inner,
+      sunset: self.sunset.clone(),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct Deprecation<S> {
+  inner: S,
+  sunset: Option<HeaderValue>,
+}
+
+impl<S> Service<Request> for Deprecation<S>
+where
+  S: Service<Request, Response = Response> + Send + 'static,
+  S::Future: Send + 'static,
+{
+  type Response = Response;
+  type Error = S::Error;
+  type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+  fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    self.inner.poll_ready(cx)
+  }
+
+  fn call(&mut self, req: Request) -> Self::Future {
+    let sunset = self.sunset.clone();
+    let future = self.inner.call(req);
+    Box::pin(async move {
+      let mut response = future.await?;
+      let headers = response.headers_mut();
+      headers.insert(DEPRECATION, HeaderValue::from_static("true"));
+      if let Some(sunset) = sunset {
+        headers.insert(SUNSET, sunset);
+      }
+      Ok(response)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, routing::get, Router};
+  use tower::ServiceExt;
+
+  use super::*;
+
+  async fn call(app: Router) -> Response {
+    app
+      .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+      .await
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_sets_deprecation_and_sunset_headers() {
+    let app = Router::new().route(
+      "/",
+      get(|| async { "ok" }).layer(deprecation_layer(Some("Sat, 01 May 2027 00:00:00 GMT"))),
+    );
+
+    let response = call(app).await;
+    assert_eq!(response.headers()[DEPRECATION], "true");
+    assert_eq!(response.headers()[SUNSET], "Sat, 01 May 2027 00:00:00 GMT");
+  }
+
+  #[tokio::test]
+  async fn test_sunset_is_optional() {
+    let app = Router::new().route("/", get(|| async { "ok" }).layer(deprecation_layer(None)));
+
+    let response = call(app).await;
+    assert_eq!(response.headers()[DEPRECATION], "true");
+    assert!(response.headers().get(SUNSET).is_none());
+  }
+
+  #[tokio::test]
+  async fn test_untagged_route_has_no_headers() {
+    let app = Router::new().route("/", get(|| async { "ok" }));
+
+    let response = call(app).await;
+    assert!(response.headers().get(DEPRECATION).is_none());
+  }
+}
@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Response of the first request sent with an `Idempotency-Key`, replayed for the repeated ones.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "idempotency_keys")]
+pub struct Model {
+  /// Method and path of the request, e.g. `POST /api/v1/users`, and the id of the
+  /// authenticated user, if any
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub scope: String,
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub key: String,
+  /// SHA-256 of the request body, to refuse the key with another payload
+  pub request_hash: String,
+  pub status_code: i16,
+  #[sea_orm(column_type = "Text")]
+  pub response_body: String,
+  /// `Location` header of the response, e.g. of a created resource
+  pub location: Option<String>,
+  #[sea_orm(column_type = "TimestampWithTimeZone")]
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,282 @@
+pub mod entity;
+
+use axum::{
+  body::{Body, Bytes},
+  extract::{FromRequest, Request, State},
+  http::{
+    header::{CONTENT_TYPE, LOCATION},
+    HeaderName, HeaderValue, StatusCode,
+  },
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use sea_orm::{sea_query::OnConflict, ColumnTrait, EntityTrait, QueryFilter, Set};
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use crate::app::AppState;
+use crate::common::errors::ApiError;
+use crate::modules::users::dto::UserDto;
+
+/// Header carrying the client's key for a request that may be retried.
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Header set on replayed responses.
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+/// Longest accepted `Idempotency-Key`.
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Runs a request sent with an `Idempotency-Key` header once, and replays its response to the
+/// requests repeating the key within `IDEMPOTENCY_TTL_HOURS`, so that retried creations don't
+/// create duplicates.
+///
+/// Only successful responses are stored, a failed request can be retried with the same key.
+/// Reusing a key with another body is refused. Keys are scoped to the endpoint and to the
+/// authenticated user, apply the middleware inside `auth_guard` on protected routes.
+pub async fn idempotency(
+  State(state): State<AppState>,
+  req: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let Some(key) = req.headers().get(IDEMPOTENCY_KEY) else {
+    return Ok(next.run(req).await);
+  };
+  let key = key
+    .to_str()
+    .ok()
+    .map(str::trim)
+    .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+    .ok_or_else(|| ApiError::InvalidRequest("Invalid Idempotency-Key header".to_string()))?
+    .to_string();
+
+  let mut scope = format!("{} {}", req.method(), req.uri().path());
+  if let Some(user) = req.extensions().get::<UserDto>() {
+    scope = format!("{} {}", scope, user.id);
+  }
+
+  // Buffer the body to hash it, then hand it over to the handler
+  let (parts, body) = req.into_parts();
+  let body = Bytes::from_request(Request::new(body), &())
+    .await
+    .map_err(|rejection| {
+      if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        ApiError::PayloadTooLarge
+      } else {
+        ApiError::InvalidRequest("Failed to read the request body".to_string())
+      }
+    })?;
+  let request_hash = format!("{:x}", Sha256::digest(&body));
+
+  let expired_before =
+    chrono::Utc::now() - chrono::Duration::hours(state.cfg.idempotency_ttl_hours);
+  let stored = entity::Entity::find_by_id((scope.clone(), key.clone()))
+    .filter(entity::Column::CreatedAt.gt(expired_before))
+    .one(&state.db.conn)
+    .await?;
+  if let Some(stored) = stored {
+    if stored.request_hash != request_hash {
+      return Err(ApiError::InvalidRequest(
+        "Idempotency key reuse with different payload".to_string(),
+      ));
+    }
+    return Ok(replay(stored));
+  }
+
+  let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+  if !response.status().is_success() {
+    return Ok(response);
+  }
+
+  let (parts, body) = response.into_parts();
+  let body = axum::body::to_bytes(body, usize::MAX).await.map_err(|e| {
+    ApiError::InternalError(anyhow::anyhow!("Failed to buffer the response: {}", e))
+  })?;
+  let record = entity::ActiveModel {
+    scope: Set(scope),
+    key: Set(key),
+    request_hash: Set(request_hash),
+    status_code: Set(parts.status.as_u16() as i16),
+    response_body: Set(String::from_utf8_lossy(&body).into_owned()),
+    location: Set(
+      parts
+        .headers
+        .get(LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .map(str::to_string),
+    ),
+    created_at: Set(chrono::Utc::now()),
+  };
+  // Expired keys are replaced. Failures are only logged, the request has already succeeded
+  if let Err(e) = entity::Entity::insert(record)
+    .on_conflict(
+      OnConflict::columns([entity::Column::Scope, entity::Column::Key])
+        .update_columns([
+          entity::Column::RequestHash,
+          entity::Column::StatusCode,
+          entity::Column::ResponseBody,
+          entity::Column::Location,
+          entity::Column::CreatedAt,
+        ])
+        .to_owned(),
+    )
+    .exec_without_returning(&state.db.conn)
+    .await
+  {
+    error!("Failed to store the idempotency key: {}", e);
+  }
+
+  Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Rebuilds the stored response, flagged with `Idempotent-Replayed: true`.
+fn replay(stored: entity::Model) -> Response {
+  let status = StatusCode::from_u16(stored.status_code as u16).unwrap_or(StatusCode::OK);
+  let mut response = (
+    status,
+    [(CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+    stored.response_body,
+  )
+    .into_response();
+  let headers = response.headers_mut();
+  headers.insert(
+    HeaderName::from_static(IDEMPOTENT_REPLAYED),
+    HeaderValue::from_static("true"),
+  );
+  if let Some(location) = stored
+    .location
+    .and_then(|location| HeaderValue::from_str(&location).ok())
+  {
+    headers.insert(LOCATION, location);
+  }
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+
+  use axum::{routing::post, Router};
+  use sea_orm::{DbBackend, MockDatabase, MockExecResult};
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::common::background::BackgroundTasks;
+  use crate::common::config::Configuration;
+  use crate::common::events::UserEvents;
+  use crate::common::notifier::LogNotifier;
+  use crate::common::storage::LocalStorage;
+  use crate::common::streams::StreamLimit;
+  use crate::database::Db;
+
+  const BODY: &str = r#"{"email":"user@example.com"}"#;
+
+  fn state(conn: sea_orm::DatabaseConnection) -> AppState {
+    let cfg = Configuration::for_tests();
+    AppState {
+      db: Db {
+        conn,
+        tenants: None,
+      },
+      background: BackgroundTasks::new(cfg.background_concurrency),
+      streams: StreamLimit::new(cfg.max_stream_connections),
+      events: UserEvents::default(),
+      storage: Arc::new(LocalStorage::new(&cfg.storage_dir)),
+      cfg,
+      notifier: Arc::new(LogNotifier),
+      readiness: Default::default(),
+    }
+  }
+
+  fn stored(body: &str) -> entity::Model {
+    entity::Model {
+      scope: "POST /users".to_string(),
+      key: "key-1".to_string(),
+      request_hash: format!("{:x}", Sha256::digest(body.as_bytes())),
+      status_code: 201,
+      response_body: r#"{"id":"first"}"#.to_string(),
+      location: Some("http://localhost/users/first".to_string()),
+      created_at: chrono::Utc::now(),
+    }
+  }
+
+  /// Sends `body` with the key to a handler counting its calls.
+  async fn send(state: AppState, body: &'static str) -> (Response, usize) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let app: Router = Router::new()
+      .route(
+        "/users",
+        post(move || async move {
+          counter.fetch_add(1, Ordering::SeqCst);
+          (StatusCode::CREATED, r#"{"id":"new"}"#)
+        }),
+      )
+      .layer(axum::middleware::from_fn_with_state(state, idempotency));
+    let request = Request::builder()
+      .method("POST")
+      .uri("/users")
+      .header(IDEMPOTENCY_KEY, "key-1")
+      .body(Body::from(body))
+      .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    (response, calls.load(Ordering::SeqCst))
+  }
+
+  async fn body_of(response: Response) -> String {
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_first_request_is_run_and_stored() {
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([Vec::<entity::Model>::new()])
+      .append_exec_results([MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+      }])
+      .into_connection();
+    let state = state(conn);
+
+    let (response, calls) = send(state.clone(), BODY).await;
+    assert_eq!(calls, 1);
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(body_of(response).await, r#"{"id":"new"}"#);
+
+    let log = format!("{:?}", state.db.conn.into_transaction_log());
+    assert!(log.contains("INSERT INTO \\\"idempotency_keys\\\""));
+    assert!(log.contains("POST /users"));
+  }
+
+  #[tokio::test]
+  async fn test_repeated_key_replays_the_stored_response() {
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![stored(BODY)]])
+      .into_connection();
+
+    let (response, calls) = send(state(conn), BODY).await;
+    assert_eq!(calls, 0);
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.headers()[IDEMPOTENT_REPLAYED], "true");
+    assert_eq!(response.headers()[LOCATION], "http://localhost/users/first");
+    assert_eq!(body_of(response).await, r#"{"id":"first"}"#);
+  }
+
+  #[tokio::test]
+  async fn test_repeated_key_with_another_payload_is_refused() {
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![stored(r#"{"email":"other@example.com"}"#)]])
+      .into_connection();
+
+    let (response, calls) = send(state(conn), BODY).await;
+    assert_eq!(calls, 0);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(body_of(response)
+      .await
+      .contains("Idempotency key reuse with different payload"));
+  }
+}
@@ -0,0 +1,60 @@
+use axum::{
+  extract::{Request, State},
+  middleware::Next,
+  response::Response,
+};
+
+use crate::common::config::Config;
+use crate::common::errors::ApiError;
+
+/// Answers 503 Service Unavailable while `MAINTENANCE_MODE` is enabled.
+///
+/// Apply it to the routes that should be unavailable during maintenance, the health and admin
+/// routes are left out so that probes and admins keep working.
+pub async fn maintenance_guard(
+  State(cfg): State<Config>,
+  req: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  if cfg.features.maintenance_mode {
+    return Err(ApiError::ServiceUnavailable(
+      "The service is under maintenance".to_string(),
+    ));
+  }
+  Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use axum::{body::Body, routing::get, Router};
+  use hyper::StatusCode;
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::common::config::Configuration;
+
+  async fn status(maintenance_mode: bool) -> StatusCode {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().features.maintenance_mode = maintenance_mode;
+    let app = Router::new()
+      .route("/", get(|| async { "ok" }))
+      .layer(axum::middleware::from_fn_with_state(cfg, maintenance_guard));
+    app
+      .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+      .await
+      .unwrap()
+      .status()
+  }
+
+  #[tokio::test]
+  async fn test_requests_pass_outside_maintenance() {
+    assert_eq!(status(false).await, StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn test_requests_are_refused_in_maintenance() {
+    assert_eq!(status(true).await, StatusCode::SERVICE_UNAVAILABLE);
+  }
+}
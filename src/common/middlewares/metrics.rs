@@ -0,0 +1,74 @@
+use std::time::Instant;
+
+use axum::{
+  extract::{MatchedPath, Request},
+  middleware::Next,
+  response::Response,
+};
+use metrics::Label;
+
+/// Records the count, latency and in-flight number of requests.
+///
+/// Requests are labelled with the matched route (e.g. `/api/v1/users/{user_id}`) rather than
+/// the raw URI to keep the number of series bounded.
+pub async fn track_metrics(req: Request, next: Next) -> Response {
+  let start = Instant::now();
+  let method = req.method().to_string();
+  let path = req
+    .extensions()
+    .get::<MatchedPath>()
+    .map(|path| path.as_str().to_string())
+    .unwrap_or_else(|| "unmatched".to_string());
+
+  let in_flight = metrics::gauge!(
+    "http_requests_in_flight",
+    "method" => method.clone(),
+    "path" => path.clone()
+  );
+  in_flight.increment(1);
+  let response = next.run(req).await;
+  in_flight.decrement(1);
+
+  let labels = vec![
+    Label::new("method", method),
+    Label::new("path", path),
+    Label::new("status", response.status().as_u16().to_string()),
+  ];
+  metrics::counter!("http_requests_total", labels.clone()).increment(1);
+  metrics::histogram!("http_request_duration_seconds", labels)
+    .record(start.elapsed().as_secs_f64());
+
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, routing::get, Router};
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::common::metrics;
+
+  #[tokio::test]
+  async fn test_requests_are_labelled_with_route() {
+    let handle = metrics::handle();
+    let app: Router = Router::new()
+      .route("/metrics-test/{user_id}", get(|| async { "ok" }))
+      .layer(axum::middleware::from_fn(track_metrics));
+
+    app
+      .oneshot(
+        Request::builder()
+          .uri("/metrics-test/123")
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+
+    let rendered = handle.render();
+    assert!(rendered.contains(r#"path="/metrics-test/{user_id}""#));
+    assert!(!rendered.contains("/metrics-test/123"));
+    assert!(rendered.contains("http_request_duration_seconds_bucket"));
+  }
+}
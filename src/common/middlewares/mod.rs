@@ -1,10 +1,35 @@
+mod accept;
+mod access_log;
 pub mod basic_auth;
+mod body_limit;
+mod compression;
 mod cors;
+mod deprecation;
+pub mod idempotency;
+mod maintenance;
+mod metrics;
 mod normalize_path;
+mod rate_limit;
 mod request_id;
 mod timeout;
+mod trace_context;
+mod transaction;
+mod validation_log;
 
+pub use accept::accept_guard;
+pub use access_log::access_log;
+pub use body_limit::body_limit_layer;
+pub use compression::compression_layer;
 pub use cors::cors_layer;
+pub use deprecation::deprecation_layer;
+pub use idempotency::idempotency;
+pub use maintenance::maintenance_guard;
+pub use metrics::track_metrics;
 pub use normalize_path::normalize_path_layer;
-pub use request_id::{propagate_request_id_layer, request_id_layer};
-pub use timeout::timeout_layer;
+pub(crate) use rate_limit::client_ip;
+pub use rate_limit::{rate_limit_headers, rate_limit_layer};
+pub use request_id::{propagate_request_id_layer, request_id_layer, validate_request_id};
+pub use timeout::timeout;
+pub use trace_context::{trace_context, TraceContext};
+pub use transaction::transaction;
+pub use validation_log::log_validation_failures;
@@ -0,0 +1,367 @@
+use std::{
+  collections::HashMap,
+  future::Future,
+  net::{IpAddr, Ipv6Addr, SocketAddr},
+  pin::Pin,
+  sync::{Arc, Mutex},
+  task::{Context, Poll},
+  time::Instant,
+};
+
+use axum::{
+  extract::{ConnectInfo, Request},
+  http::{Extensions, HeaderMap, HeaderName, HeaderValue},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use tower::{Layer, Service};
+
+use crate::common::errors::ApiError;
+
+/// Number of tracked clients above which full buckets are evicted.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+const X_RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+const X_RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// Bucket of the client after its request, set on the response extensions by the rate limit
+/// layer and rendered as headers by `rate_limit_headers`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimitState {
+  /// Requests allowed per minute
+  pub limit: u32,
+  /// Requests the client can still make right away
+  pub remaining: u32,
+  /// Seconds until the bucket is full again, or until the next request is allowed when empty
+  pub reset: u64,
+}
+
+/// Layer that applies a token-bucket rate limit keyed on the client IP.
+///
+/// Each client gets a bucket of `per_minute` tokens refilled continuously over a minute.
+/// When the bucket is empty the request is rejected with `429 Too Many Requests` and a
+/// `Retry-After` header. A limit of `0` disables rate limiting.
+pub fn rate_limit_layer(per_minute: u32) -> RateLimitLayer {
+  RateLimitLayer {
+    limiter: (per_minute > 0).then(|| Arc::new(RateLimiter::new(per_minute))),
+  }
+}
+
+#[derive(Clone)]
+pub struct RateLimitLayer {
+  limiter: Option<Arc<RateLimiter>>,
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+  type Service = RateLimit<S>;
+
+  fn layer(&self, inner: S) -> Self::Service {
+    RateLimit {
+      inner,
+      limiter: self.limiter.clone(),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+  inner: S,
+  limiter: Option<Arc<RateLimiter>>,
+}
+
+impl<S> Service<Request> for RateLimit<S>
+where
+  S: Service<Request, Response = Response> + Send + 'static,
+  S::Future: Send + 'static,
+{
+  type Response = Response;
+  type Error = S::Error;
+  type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+  fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    self.inner.poll_ready(cx)
+  }
+
+  fn call(&mut self, req: Request) -> Self::Future {
+    let Some(limiter) = &self.limiter else {
+      return Box::pin(self.inner.call(req));
+    };
+
+    let ip = client_ip(req.headers(), req.extensions());
+    let now = Instant::now();
+    match limiter.check(ip, now) {
+      Ok(()) => {
+        let state = limiter.state(ip, now);
+        let response = self.inner.call(req);
+        Box::pin(async move {
+          let mut response = response.await?;
+          response.extensions_mut().insert(state);
+          Ok(response)
+        })
+      }
+      Err(retry_after) => {
+        let mut response = ApiError::TooManyRequests(retry_after).into_response();
+        response.extensions_mut().insert(RateLimitState {
+          remaining: 0,
+          reset: retry_after,
+          ..limiter.state(ip, now)
+        });
+        Box::pin(async move { Ok(response) })
+      }
+    }
+  }
+}
+
+/// Renders the `RateLimitState` of rate limited routes as the `X-RateLimit-Limit`,
+/// `X-RateLimit-Remaining` and `X-RateLimit-Reset` headers, on successful responses too so that
+/// clients can slow down before being refused. Other responses are left untouched.
+pub async fn rate_limit_headers(req: Request, next: Next) -> Response {
+  let mut response = next.run(req).await;
+  if let Some(state) = response.extensions().get::<RateLimitState>().copied() {
+    let headers = response.headers_mut();
+    headers.insert(X_RATELIMIT_LIMIT, HeaderValue::from(state.limit));
+    headers.insert(X_RATELIMIT_REMAINING, HeaderValue::from(state.remaining));
+    headers.insert(X_RATELIMIT_RESET, HeaderValue::from(state.reset));
+  }
+  response
+}
+
+/// Returns the client IP from the first `X-Forwarded-For` entry, falling back to the peer address.
+pub(crate) fn client_ip(headers: &HeaderMap, extensions: &Extensions) -> IpAddr {
+  headers
+    .get("x-forwarded-for")
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.split(',').next())
+    .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+    .or_else(|| {
+      extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+    })
+    .unwrap_or(IpAddr::V6(Ipv6Addr::UNSPECIFIED))
+}
+
+struct Bucket {
+  tokens: f64,
+  updated_at: Instant,
+}
+
+struct RateLimiter {
+  capacity: f64,
+  refill_per_sec: f64,
+  buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+  fn new(per_minute: u32) -> Self {
+    Self {
+      capacity: per_minute as f64,
+      refill_per_sec: per_minute as f64 / 60.0,
+      buckets: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Takes a token for `ip`, or returns the number of seconds until one is available.
+  fn check(&self, ip: IpAddr, now: Instant) -> Result<(), u64> {
+    let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+    if buckets.len() >= MAX_TRACKED_CLIENTS {
+      // Clients whose bucket has refilled completely carry no state worth keeping.
+      buckets.retain(|_, bucket| self.refilled(bucket, now) < self.capacity);
+    }
+
+    let bucket = buckets.entry(ip).or_insert(Bucket {
+      tokens: self.capacity,
+      updated_at: now,
+    });
+    bucket.tokens = self.refilled(bucket, now);
+    bucket.updated_at = now;
+
+    if bucket.tokens >= 1.0 {
+      bucket.tokens -= 1.0;
+      Ok(())
+    } else {
+      Err(((1.0 - bucket.tokens) / self.refill_per_sec).ceil() as u64)
+    }
+  }
+
+  /// Returns the bucket of `ip` as of `now`, full for clients that aren't tracked.
+  fn state(&self, ip: IpAddr, now: Instant) -> RateLimitState {
+    let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+    let tokens = buckets
+      .get(&ip)
+      .map_or(self.capacity, |bucket| self.refilled(bucket, now));
+    RateLimitState {
+      limit: self.capacity as u32,
+      remaining: tokens.floor() as u32,
+      reset: ((self.capacity - tokens) / self.refill_per_sec).ceil() as u64,
+    }
+  }
+
+  fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+    let elapsed = now
+      .saturating_duration_since(bucket.updated_at)
+      .as_secs_f64();
+    (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use axum::{body::Body, routing::get, Router};
+  use hyper::StatusCode;
+  use tower::ServiceExt;
+
+  use super::*;
+
+  fn ip(last: u8) -> IpAddr {
+    IpAddr::from([10, 0, 0, last])
+  }
+
+  #[test]
+  fn test_allows_up_to_capacity() {
+    let limiter = RateLimiter::new(3);
+    let now = Instant::now();
+    assert!(limiter.check(ip(1), now).is_ok());
+    assert!(limiter.check(ip(1), now).is_ok());
+    assert!(limiter.check(ip(1), now).is_ok());
+    assert_eq!(limiter.check(ip(1), now), Err(20));
+  }
+
+  #[test]
+  fn test_buckets_are_per_ip() {
+    let limiter = RateLimiter::new(1);
+    let now = Instant::now();
+    assert!(limiter.check(ip(1), now).is_ok());
+    assert!(limiter.check(ip(1), now).is_err());
+    assert!(limiter.check(ip(2), now).is_ok());
+  }
+
+  #[test]
+  fn test_refills_over_time() {
+    let limiter = RateLimiter::new(60);
+    let now = Instant::now();
+    for _ in 0..60 {
+      assert!(limiter.check(ip(1), now).is_ok());
+    }
+    assert_eq!(limiter.check(ip(1), now), Err(1));
+    assert!(limiter.check(ip(1), now + Duration::from_secs(1)).is_ok());
+  }
+
+  #[test]
+  fn test_state_after_requests() {
+    let limiter = RateLimiter::new(60);
+    let now = Instant::now();
+    assert_eq!(
+      limiter.state(ip(1), now),
+      RateLimitState {
+        limit: 60,
+        remaining: 60,
+        reset: 0
+      }
+    );
+
+    for _ in 0..3 {
+      limiter.check(ip(1), now).unwrap();
+    }
+    assert_eq!(
+      limiter.state(ip(1), now),
+      RateLimitState {
+        limit: 60,
+        remaining: 57,
+        reset: 3
+      }
+    );
+  }
+
+  #[test]
+  fn test_client_ip_from_forwarded_for() {
+    let req = Request::builder()
+      .header("x-forwarded-for", "203.0.113.7, 10.0.0.1")
+      .body(Body::empty())
+      .unwrap();
+    assert_eq!(
+      client_ip(req.headers(), req.extensions()),
+      IpAddr::from([203, 0, 113, 7])
+    );
+  }
+
+  #[test]
+  fn test_client_ip_from_connect_info() {
+    let mut req = Request::builder().body(Body::empty()).unwrap();
+    req
+      .extensions_mut()
+      .insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4000))));
+    assert_eq!(
+      client_ip(req.headers(), req.extensions()),
+      IpAddr::from([192, 0, 2, 1])
+    );
+  }
+
+  #[tokio::test]
+  async fn test_layer_returns_too_many_requests() {
+    let app = Router::new()
+      .route("/", get(|| async { "ok" }))
+      .layer(rate_limit_layer(1));
+
+    let response = app
+      .clone()
+      .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+      .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["retry-after"], "60");
+  }
+
+  #[tokio::test]
+  async fn test_headers_report_the_bucket() {
+    let app = Router::new()
+      .route("/", get(|| async { "ok" }))
+      .layer(rate_limit_layer(30))
+      .layer(axum::middleware::from_fn(rate_limit_headers));
+    let request = || Request::builder().uri("/").body(Body::empty()).unwrap();
+
+    // A token is refilled every 2 seconds
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-ratelimit-limit"], "30");
+    assert_eq!(response.headers()["x-ratelimit-remaining"], "29");
+    assert_eq!(response.headers()["x-ratelimit-reset"], "2");
+
+    for _ in 0..29 {
+      app.clone().oneshot(request()).await.unwrap();
+    }
+    let response = app.oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["x-ratelimit-limit"], "30");
+    assert_eq!(response.headers()["x-ratelimit-remaining"], "0");
+    assert_eq!(response.headers()["x-ratelimit-reset"], "2");
+  }
+
+  #[tokio::test]
+  async fn test_layer_disabled_with_zero_limit() {
+    let app = Router::new()
+      .route("/", get(|| async { "ok" }))
+      .layer(rate_limit_layer(0))
+      .layer(axum::middleware::from_fn(rate_limit_headers));
+
+    for _ in 0..5 {
+      let response = app
+        .clone()
+        .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+      assert_eq!(response.status(), StatusCode::OK);
+      assert!(!response.headers().contains_key("x-ratelimit-limit"));
+    }
+  }
+}
@@ -1,7 +1,18 @@
-use axum::{extract::Request, http::HeaderName};
+use axum::{
+  extract::{Request, State},
+  http::HeaderName,
+  middleware::Next,
+  response::Response,
+};
 use tower_http::request_id::{
   MakeRequestId, PropagateRequestIdLayer, RequestId, SetRequestIdLayer,
 };
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::common::config::Config;
+
+pub(super) const X_REQUEST_ID: &str = "x-request-id";
 
 #[derive(Clone, Default)]
 pub struct Id;
@@ -30,3 +41,76 @@ pub fn propagate_request_id_layer() -> PropagateRequestIdLayer {
   let x_request_id = HeaderName::from_static("x-request-id");
   PropagateRequestIdLayer::new(x_request_id)
 }
+
+/// Removes an incoming 'x-request-id' that isn't a well-formed UUID when `REQUEST_ID_VALIDATE`
+/// is enabled, so that `request_id_layer` generates a fresh one. Must run before it.
+pub async fn validate_request_id(
+  State(cfg): State<Config>,
+  mut req: Request,
+  next: Next,
+) -> Response {
+  if cfg.request_id_validate {
+    if let Some(value) = req.headers().get(X_REQUEST_ID) {
+      let valid = value
+        .to_str()
+        .is_ok_and(|id| Uuid::parse_str(id.trim()).is_ok());
+      if !valid {
+        warn!(request_id = ?value, "Replacing a malformed x-request-id");
+        req.headers_mut().remove(X_REQUEST_ID);
+      }
+    }
+  }
+  next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use axum::{body::Body, routing::get, Router};
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::common::config::Configuration;
+
+  async fn response_id(request_id: &str) -> String {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().request_id_validate = true;
+    let app = Router::new()
+      .route("/", get(|| async {}))
+      .layer(propagate_request_id_layer())
+      .layer(request_id_layer())
+      .layer(axum::middleware::from_fn_with_state(
+        cfg,
+        validate_request_id,
+      ));
+
+    let response = app
+      .oneshot(
+        Request::builder()
+          .uri("/")
+          .header(X_REQUEST_ID, request_id)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    response.headers()[X_REQUEST_ID]
+      .to_str()
+      .unwrap()
+      .to_string()
+  }
+
+  #[tokio::test]
+  async fn test_valid_request_id_is_preserved() {
+    let id = "0190f5a4-8c1e-7a3b-9d2f-5e6a7b8c9d0e";
+    assert_eq!(response_id(id).await, id);
+  }
+
+  #[tokio::test]
+  async fn test_malformed_request_id_is_replaced() {
+    let id = response_id("reused-id").await;
+    assert_ne!(id, "reused-id");
+    assert!(Uuid::parse_str(&id).is_ok());
+  }
+}
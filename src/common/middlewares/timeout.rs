@@ -1,10 +1,92 @@
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use hyper::StatusCode;
-use tower_http::timeout::TimeoutLayer;
+use axum::{
+  extract::{Request, State},
+  middleware::Next,
+  response::Response,
+};
+use tokio::time::Instant;
 
-/// Layer that applies the Timeout middleware which apply a timeout to requests.
-/// The default timeout value is set to 15 seconds.
-pub fn timeout_layer() -> TimeoutLayer {
-  TimeoutLayer::with_status_code(StatusCode::REQUEST_TIMEOUT, Duration::from_secs(15))
+use crate::common::errors::ApiError;
+
+/// Deadline of a request, which the timeouts of the routes it reaches replace.
+#[derive(Clone)]
+struct Deadline(Arc<Mutex<Instant>>);
+
+/// Middleware that fails requests not answered within `duration` with 408 Request Timeout.
+///
+/// Applied globally with `REQUEST_TIMEOUT_SECONDS`. Nested in another timeout, e.g. on a heavy
+/// route with `HEAVY_REQUEST_TIMEOUT_SECONDS`, it replaces the deadline of the outer one
+/// instead, which can then be longer.
+pub async fn timeout(
+  State(duration): State<Duration>,
+  mut req: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let deadline = Instant::now() + duration;
+  if let Some(Deadline(outer)) = req.extensions().get::<Deadline>() {
+    *outer.lock().unwrap() = deadline;
+    return Ok(next.run(req).await);
+  }
+
+  let shared = Arc::new(Mutex::new(deadline));
+  req.extensions_mut().insert(Deadline(shared.clone()));
+  let response = next.run(req);
+  tokio::pin!(response);
+  loop {
+    let deadline = *shared.lock().unwrap();
+    tokio::select! {
+      response = &mut response => return Ok(response),
+      _ = tokio::time::sleep_until(deadline) => {
+        if *shared.lock().unwrap() <= deadline {
+          return Err(ApiError::RequestTimeout);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, http::StatusCode, routing::get, Router};
+  use tower::ServiceExt;
+
+  use super::*;
+
+  async fn slow() -> &'static str {
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    "done"
+  }
+
+  fn app() -> Router {
+    Router::new()
+      .route("/slow", get(slow))
+      .route(
+        "/heavy",
+        get(slow).layer(axum::middleware::from_fn_with_state(
+          Duration::from_secs(5),
+          timeout,
+        )),
+      )
+      .layer(axum::middleware::from_fn_with_state(
+        Duration::from_millis(50),
+        timeout,
+      ))
+  }
+
+  async fn status(uri: &str) -> StatusCode {
+    let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    app().oneshot(request).await.unwrap().status()
+  }
+
+  #[tokio::test]
+  async fn test_slow_requests_time_out() {
+    assert_eq!(status("/slow").await, StatusCode::REQUEST_TIMEOUT);
+  }
+
+  #[tokio::test]
+  async fn test_route_timeout_replaces_the_global_one() {
+    assert_eq!(status("/heavy").await, StatusCode::OK);
+  }
 }
@@ -0,0 +1,218 @@
+use axum::{
+  extract::Request,
+  http::{HeaderMap, HeaderValue},
+  middleware::Next,
+  response::Response,
+};
+use uuid::Uuid;
+
+pub(super) const TRACEPARENT: &str = "traceparent";
+pub(super) const TRACESTATE: &str = "tracestate";
+
+/// W3C trace context of a request: the trace it belongs to and the span this service handles it
+/// in, see https://www.w3.org/TR/trace-context/.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceContext {
+  /// 32 hex digits shared by every service the trace goes through
+  pub trace_id: String,
+  /// 16 hex digits of the span of this service, the parent of the downstream ones
+  pub span_id: String,
+  /// Span of the caller, `None` for the root of a new trace
+  pub parent_id: Option<String>,
+  /// Trace flags, e.g. `01` when the caller samples the trace
+  pub flags: String,
+  /// Vendor-specific `tracestate`, passed along as is
+  pub state: Option<String>,
+}
+
+impl TraceContext {
+  /// Continues the trace of the incoming `traceparent` and `tracestate` headers, or starts a
+  /// new one when they are missing or malformed.
+  pub fn from_headers(headers: &HeaderMap) -> Self {
+    let parent = headers
+      .get(TRACEPARENT)
+      .and_then(|value| value.to_str().ok())
+      .and_then(parse_traceparent);
+    match parent {
+      Some((trace_id, parent_id, flags)) => Self {
+        trace_id,
+        span_id: new_span_id(),
+        parent_id: Some(parent_id),
+        flags,
+        // The state only makes sense along with the trace it was set on
+        state: headers
+          .get(TRACESTATE)
+          .and_then(|value| value.to_str().ok())
+          .map(str::trim)
+          .filter(|state| !state.is_empty())
+          .map(str::to_string),
+      },
+      None => Self {
+        trace_id: Uuid::new_v4().simple().to_string(),
+        span_id: new_span_id(),
+        parent_id: None,
+        flags: "01".to_string(),
+        state: None,
+      },
+    }
+  }
+
+  /// `traceparent` of the span of this service, for the response and the downstream calls.
+  pub fn traceparent(&self) -> String {
+    format!("00-{}-{}-{}", self.trace_id, self.span_id, self.flags)
+  }
+}
+
+/// Reads or starts the W3C trace context of the request, which `trace_layer` records on the
+/// request span so that logs correlate across services, and echoes `traceparent` and
+/// `tracestate` on the response. Must run before `trace_layer`.
+pub async fn trace_context(mut req: Request, next: Next) -> Response {
+  let context = TraceContext::from_headers(req.headers());
+  req.extensions_mut().insert(context.clone());
+
+  let mut response = next.run(req).await;
+  let headers = response.headers_mut();
+  if let Ok(traceparent) = HeaderValue::from_str(&context.traceparent()) {
+    headers.insert(TRACEPARENT, traceparent);
+  }
+  if let Some(state) = context
+    .state
+    .as_deref()
+    .and_then(|state| HeaderValue::from_str(state).ok())
+  {
+    headers.insert(TRACESTATE, state);
+  }
+  response
+}
+
+/// Parses a `traceparent` into its trace id, parent id and flags.
+///
+/// Only the fields of version `00` are read, later versions may append more. All-zero ids and
+/// the `ff` version are invalid.
+fn parse_traceparent(value: &str) -> Option<(String, String, String)> {
+  let mut parts = value.trim().split('-');
+  let version = parts.next()?;
+  let trace_id = parts.next()?;
+  let parent_id = parts.next()?;
+  let flags = parts.next()?;
+  if version == "00" && parts.next().is_some() {
+    return None;
+  }
+
+  let hex = |field: &str, len: usize| {
+    field.len() == len
+      && field
+        .bytes()
+        .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+  };
+  let zero = |field: &str| field.bytes().all(|b| b == b'0');
+  if !hex(version, 2) || version == "ff" || !hex(flags, 2) {
+    return None;
+  }
+  if !hex(trace_id, 32) || zero(trace_id) || !hex(parent_id, 16) || zero(parent_id) {
+    return None;
+  }
+  Some((
+    trace_id.to_string(),
+    parent_id.to_string(),
+    flags.to_string(),
+  ))
+}
+
+fn new_span_id() -> String {
+  Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use axum::{body::Body, routing::get, Extension, Router};
+  use tower::ServiceExt;
+
+  use super::*;
+
+  const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+  const PARENT_ID: &str = "00f067aa0ba902b7";
+
+  #[test]
+  fn test_parse_traceparent() {
+    let parsed = parse_traceparent(&format!("00-{TRACE_ID}-{PARENT_ID}-01")).unwrap();
+    assert_eq!(parsed.0, TRACE_ID);
+    assert_eq!(parsed.1, PARENT_ID);
+    assert_eq!(parsed.2, "01");
+
+    // Later versions may append fields
+    assert!(parse_traceparent(&format!("01-{TRACE_ID}-{PARENT_ID}-01-extra")).is_some());
+    assert!(parse_traceparent(&format!("00-{TRACE_ID}-{PARENT_ID}-01-extra")).is_none());
+    assert!(parse_traceparent(&format!("ff-{TRACE_ID}-{PARENT_ID}-01")).is_none());
+    assert!(parse_traceparent(&format!("00-{}-{PARENT_ID}-01", "0".repeat(32))).is_none());
+    assert!(parse_traceparent(&format!("00-{TRACE_ID}-{}-01", "0".repeat(16))).is_none());
+    assert!(parse_traceparent(&format!("00-{}-{PARENT_ID}-01", TRACE_ID.to_uppercase())).is_none());
+    assert!(parse_traceparent("00-abc-def-01").is_none());
+  }
+
+  /// Returns the response and the trace context the handler saw.
+  async fn respond(headers: &[(&str, &str)]) -> (Response, TraceContext) {
+    let seen = Arc::new(Mutex::new(None));
+    let handler_seen = seen.clone();
+    let app = Router::new()
+      .route(
+        "/",
+        get(move |Extension(context): Extension<TraceContext>| {
+          *handler_seen.lock().unwrap() = Some(context);
+          async {}
+        }),
+      )
+      .layer(axum::middleware::from_fn(trace_context));
+
+    let mut request = Request::builder().uri("/");
+    for (name, value) in headers {
+      request = request.header(*name, *value);
+    }
+    let response = app
+      .oneshot(request.body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    let context = seen.lock().unwrap().take().unwrap();
+    (response, context)
+  }
+
+  #[tokio::test]
+  async fn test_incoming_trace_is_continued() {
+    let traceparent = format!("00-{TRACE_ID}-{PARENT_ID}-01");
+    let (response, context) = respond(&[
+      (TRACEPARENT, &traceparent),
+      (TRACESTATE, "congo=t61rcWkgMzE"),
+    ])
+    .await;
+
+    assert_eq!(context.trace_id, TRACE_ID);
+    assert_eq!(context.parent_id.as_deref(), Some(PARENT_ID));
+    assert_ne!(context.span_id, PARENT_ID);
+    assert_eq!(
+      response.headers()[TRACEPARENT],
+      format!("00-{TRACE_ID}-{}-01", context.span_id).as_str()
+    );
+    assert_eq!(response.headers()[TRACESTATE], "congo=t61rcWkgMzE");
+  }
+
+  #[tokio::test]
+  async fn test_new_root_without_incoming_trace() {
+    // A state without a valid parent is dropped along with it
+    let (response, context) = respond(&[
+      (TRACEPARENT, "not-a-traceparent"),
+      (TRACESTATE, "congo=t61rcWkgMzE"),
+    ])
+    .await;
+
+    assert_eq!(context.parent_id, None);
+    assert_eq!(context.state, None);
+    let traceparent = response.headers()[TRACEPARENT].to_str().unwrap();
+    let (trace_id, parent_id, flags) = parse_traceparent(traceparent).unwrap();
+    assert_eq!(trace_id, context.trace_id);
+    assert_eq!(parent_id, context.span_id);
+    assert_eq!(flags, "01");
+    assert!(!response.headers().contains_key(TRACESTATE));
+  }
+}
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use axum::{
+  extract::{Request, State},
+  middleware::Next,
+  response::Response,
+};
+use sea_orm::{DatabaseConnection, TransactionTrait};
+
+use crate::common::errors::ApiError;
+use crate::common::extractors::Tx;
+
+/// Opens a transaction for the duration of the request (unit of work).
+///
+/// The transaction is exposed to handlers through the `Tx` extractor. It is committed when
+/// the response status is 2xx and rolled back otherwise. The middleware is opt-in, apply it
+/// to the routes that need it:
+///
+/// ```ignore
+/// Router::new()
+///   .route("/", post(handler))
+///   .route_layer(axum::middleware::from_fn_with_state(state.db.conn.clone(), transaction));
+/// ```
+pub async fn transaction(
+  State(conn): State<DatabaseConnection>,
+  mut req: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let txn = Arc::new(conn.begin().await?);
+  req.extensions_mut().insert(Tx::new(txn.clone()));
+
+  let response = next.run(req).await;
+
+  // The handler has returned, so the extractor's handle has been dropped with the request.
+  // If it was leaked (e.g. into a spawned task) the transaction rolls back once it's dropped.
+  let txn = Arc::try_unwrap(txn)
+    .map_err(|_| anyhow!("Transaction is still in use after the response was produced"))?;
+
+  if response.status().is_success() {
+    txn.commit().await?;
+  } else {
+    txn.rollback().await?;
+  }
+
+  Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, routing::post, Router};
+  use hyper::StatusCode;
+  use sea_orm::{ConnectionTrait, DbBackend, MockDatabase, MockExecResult};
+  use tower::ServiceExt;
+
+  use super::*;
+
+  const INSERT_SQL: &str = "INSERT INTO users (name) VALUES ('john')";
+
+  async fn insert_then_fail(tx: Tx) -> Result<(), ApiError> {
+    tx.execute_unprepared(INSERT_SQL).await?;
+    Err(ApiError::InvalidRequest("Something went wrong".to_string()))
+  }
+
+  async fn insert(tx: Tx) -> Result<(), ApiError> {
+    tx.execute_unprepared(INSERT_SQL).await?;
+    Ok(())
+  }
+
+  fn mock_db() -> DatabaseConnection {
+    MockDatabase::new(DbBackend::Postgres)
+      .append_exec_results([MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+      }])
+      .into_connection()
+  }
+
+  async fn call(db: DatabaseConnection, app: Router<DatabaseConnection>) -> StatusCode {
+    app
+      .route_layer(axum::middleware::from_fn_with_state(
+        db.clone(),
+        transaction,
+      ))
+      .with_state(db)
+      .oneshot(
+        Request::builder()
+          .method("POST")
+          .uri("/")
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap()
+      .status()
+  }
+
+  #[tokio::test]
+  async fn test_rolls_back_when_handler_fails() {
+    let db = mock_db();
+    let status = call(db.clone(), Router::new().route("/", post(insert_then_fail))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains(INSERT_SQL));
+    assert!(log.contains("ROLLBACK"));
+    assert!(!log.contains("COMMIT"));
+  }
+
+  #[tokio::test]
+  async fn test_commits_when_handler_succeeds() {
+    let db = mock_db();
+    let status = call(db.clone(), Router::new().route("/", post(insert))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains(INSERT_SQL));
+    assert!(log.contains("COMMIT"));
+    assert!(!log.contains("ROLLBACK"));
+  }
+
+  #[tokio::test]
+  async fn test_extractor_requires_middleware() {
+    let response = Router::new()
+      .route("/", post(insert))
+      .oneshot(
+        Request::builder()
+          .method("POST")
+          .uri("/")
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+  }
+}
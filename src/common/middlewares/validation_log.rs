@@ -0,0 +1,117 @@
+use axum::{
+  extract::{MatchedPath, Request, State},
+  middleware::Next,
+  response::Response,
+};
+use tracing::info;
+
+use super::request_id::X_REQUEST_ID;
+use crate::common::config::Config;
+use crate::common::errors::InvalidFields;
+
+/// Logs the route, the names of the invalid fields and the request id of payloads rejected by
+/// `ValidatedJson` when `LOG_VALIDATION_FAILURES` is enabled.
+///
+/// The submitted values are never logged as they may hold personal data.
+pub async fn log_validation_failures(
+  State(cfg): State<Config>,
+  req: Request,
+  next: Next,
+) -> Response {
+  if !cfg.log_validation_failures {
+    return next.run(req).await;
+  }
+
+  let route = req
+    .extensions()
+    .get::<MatchedPath>()
+    .map(|path| path.as_str().to_string())
+    .unwrap_or_else(|| req.uri().path().to_string());
+  let request_id = req
+    .headers()
+    .get(X_REQUEST_ID)
+    .and_then(|id| id.to_str().ok())
+    .map(str::to_string);
+
+  let response = next.run(req).await;
+
+  if let Some(InvalidFields(fields)) = response.extensions().get::<InvalidFields>() {
+    info!(
+      route = %route,
+      fields = %fields.join(","),
+      request_id = request_id.as_deref(),
+      "Request payload failed validation"
+    );
+  }
+
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use axum::{body::Body, http::header::CONTENT_TYPE, routing::post, Router};
+  use serde::Deserialize;
+  use tower::ServiceExt;
+  use validator::Validate;
+
+  use super::*;
+  use crate::common::config::{telemetry::capture_logs, Configuration};
+  use crate::common::extractors::ValidatedJson;
+
+  #[derive(Deserialize, Validate)]
+  struct Payload {
+    #[validate(email(message = "invalid email format"))]
+    email: String,
+    #[validate(length(min = 8, message = "too short"))]
+    password: String,
+  }
+
+  async fn submit(log_validation_failures: bool) -> String {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().log_validation_failures = log_validation_failures;
+    let (logs, _guard) = capture_logs();
+    let app: Router = Router::new()
+      .route(
+        "/signup",
+        post(|ValidatedJson(_): ValidatedJson<Payload>| async { "ok" }),
+      )
+      .layer(axum::middleware::from_fn_with_state(
+        cfg,
+        log_validation_failures,
+      ));
+
+    let request = Request::builder()
+      .method("POST")
+      .uri("/signup")
+      .header(CONTENT_TYPE, "application/json")
+      .header(X_REQUEST_ID, "request-1")
+      .body(Body::from(
+        r#"{"email":"jane.doe-at-example","password":"hunter2"}"#,
+      ))
+      .unwrap();
+    app.oneshot(request).await.unwrap();
+    logs.contents()
+  }
+
+  #[tokio::test]
+  async fn test_failure_is_logged_without_values() {
+    let logs = submit(true).await;
+    let line = logs
+      .lines()
+      .find(|line| line.contains("Request payload failed validation"))
+      .expect("validation failure logged");
+    assert!(line.contains(r#""route":"/signup""#));
+    assert!(line.contains(r#""fields":"email,password""#));
+    assert!(line.contains(r#""request_id":"request-1""#));
+    assert!(!logs.contains("jane.doe-at-example"));
+    assert!(!logs.contains("hunter2"));
+  }
+
+  #[tokio::test]
+  async fn test_failure_is_not_logged_when_disabled() {
+    let logs = submit(false).await;
+    assert!(!logs.contains("Request payload failed validation"));
+  }
+}
@@ -1,7 +1,17 @@
 pub mod api_doc;
+pub mod background;
 pub mod config;
+pub mod crypto;
 pub mod errors;
+pub mod etag;
+pub mod events;
 pub mod extractors;
 pub mod graphql;
+pub mod metrics;
 pub mod middlewares;
+pub mod notifier;
 pub mod pagination;
+pub mod redact;
+pub mod routing;
+pub mod storage;
+pub mod streams;
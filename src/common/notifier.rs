@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use tracing::info;
+
+pub type SharedNotifier = Arc<dyn Notifier>;
+
+/// Delivers out-of-band messages (e.g. emails) to users.
+///
+/// The default implementation only logs the message. Plug in a real mailer by
+/// implementing this trait and passing it to `app::router_with_notifier`.
+#[cfg_attr(test, mockall::automock)]
+pub trait Notifier: Send + Sync {
+  /// Sends the email verification token to a newly registered user.
+  fn send_email_verification(&self, email: &str, token: &str) -> anyhow::Result<()>;
+
+  /// Sends the password reset token to a user who requested it.
+  fn send_password_reset(&self, email: &str, token: &str) -> anyhow::Result<()>;
+}
+
+/// Notifier that writes messages to the application log instead of sending them.
+#[derive(Clone, Default)]
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+  fn send_email_verification(&self, email: &str, token: &str) -> anyhow::Result<()> {
+    info!(email, token, "Email verification token issued");
+    Ok(())
+  }
+
+  fn send_password_reset(&self, email: &str, token: &str) -> anyhow::Result<()> {
+    info!(email, token, "Password reset token issued");
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_log_notifier_succeeds() {
+    let notifier = LogNotifier;
+    assert!(notifier
+      .send_email_verification("user@example.com", "token")
+      .is_ok());
+    assert!(notifier
+      .send_password_reset("user@example.com", "token")
+      .is_ok());
+  }
+
+  #[test]
+  fn test_mock_notifier_captures_token() {
+    let mut notifier = MockNotifier::new();
+    notifier
+      .expect_send_email_verification()
+      .withf(|email, token| email == "user@example.com" && token == "abc")
+      .times(1)
+      .returning(|_, _| Ok(()));
+
+    let shared: SharedNotifier = Arc::new(notifier);
+    assert!(shared
+      .send_email_verification("user@example.com", "abc")
+      .is_ok());
+  }
+}
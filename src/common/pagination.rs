@@ -1,41 +1,136 @@
+use base64::{engine::general_purpose, Engine};
+use sea_orm::Order;
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
-const DEFAULT_PER_PAGE: u64 = 20;
-const MAX_PER_PAGE: u64 = 100;
+use crate::common::config::Config;
+
+/// Postgres `OFFSET` is a signed 64-bit integer.
+const MAX_OFFSET: u64 = i64::MAX as u64;
 
 /// Query parameters for pagination.
 ///
-/// Supports two modes:
+/// Supports three modes:
 /// - **Page mode** (default): `?page=1&per_page=20`
-/// - **Cursor mode**: `?cursor=<id>&per_page=20`
+/// - **Offset mode**: `?offset=40&limit=20`
+/// - **Cursor mode**: `?cursor=<id>&per_page=20`, or `?before=<id>&per_page=20` to page backward
 ///
-/// If `cursor` is provided, cursor mode is used. Otherwise page mode is used.
-#[derive(Debug, Deserialize, IntoParams)]
+/// Precedence is cursor > offset > page: if `cursor` or `before` is provided, cursor mode is used.
+/// Otherwise, if `offset` or `limit` is provided, offset mode is used. Otherwise page mode is used.
+#[derive(Debug, Default, Deserialize, IntoParams)]
 pub struct PaginationParams {
   /// Page number (1-indexed, page mode only)
   pub page: Option<u64>,
-  /// Items per page (default: 20, max: 100)
+  /// Items per page (default: 20, max: 100, see `PAGINATION_DEFAULT_PER_PAGE` and
+  /// `PAGINATION_MAX_PER_PAGE`)
   pub per_page: Option<u64>,
-  /// Cursor ID for cursor-based pagination (UUID of the last item)
+  /// Cursor to page forward from: a `next_cursor`, or the UUID of an item to start from
   pub cursor: Option<String>,
+  /// Cursor to page backward from: a `prev_cursor`, or the UUID of an item (cursor mode only)
+  pub before: Option<String>,
+  /// Number of items to skip (offset mode only)
+  pub offset: Option<u64>,
+  /// Maximum number of items to return (offset mode only, default and max like `per_page`)
+  pub limit: Option<u64>,
+  /// Column to sort by (allowed columns depend on the resource)
+  pub sort_by: Option<String>,
+  /// Sort direction: `asc` (default) or `desc`
+  pub sort_dir: Option<String>,
+  /// Case-insensitive search term (searched fields depend on the resource)
+  pub q: Option<String>,
+  /// `json` (default) or `ndjson` to stream one item per line, allowing up to `QUERY_MAX_ROWS`
+  /// items per page (page and offset modes only)
+  pub format: Option<String>,
+  /// Also count the items in cursor mode (default: false). The total is the whole filtered set,
+  /// regardless of the cursor position
+  #[serde(default)]
+  pub include_total: bool,
+  /// Comma-separated fields to return for each item, e.g. `id,email` (default: all fields).
+  /// Unknown fields are ignored, supported by the resources documenting it
+  pub fields: Option<String>,
 }
 
 impl PaginationParams {
-  pub fn per_page(&self) -> u64 {
-    self
-      .per_page
-      .unwrap_or(DEFAULT_PER_PAGE)
-      .min(MAX_PER_PAGE)
-      .max(1)
+  /// Items per page, `PAGINATION_DEFAULT_PER_PAGE` by default and at most
+  /// `PAGINATION_MAX_PER_PAGE`.
+  pub fn per_page_with(&self, cfg: &Config) -> u64 {
+    self.per_page_up_to(cfg.pagination_default_per_page, cfg.pagination_max_per_page)
+  }
+
+  /// Items per page with a custom default and maximum, e.g. for streamed responses.
+  pub fn per_page_up_to(&self, default: u64, max: u64) -> u64 {
+    self.per_page.unwrap_or(default).min(max).max(1)
   }
 
   pub fn page(&self) -> u64 {
     self.page.unwrap_or(1).max(1)
   }
 
+  pub fn offset(&self) -> u64 {
+    self.offset.unwrap_or(0).min(MAX_OFFSET)
+  }
+
+  /// Maximum number of items, with the same default and maximum as `per_page_with`.
+  pub fn limit_with(&self, cfg: &Config) -> u64 {
+    self.limit_up_to(cfg.pagination_default_per_page, cfg.pagination_max_per_page)
+  }
+
+  /// Maximum number of items with a custom default and maximum, e.g. for streamed responses.
+  pub fn limit_up_to(&self, default: u64, max: u64) -> u64 {
+    self.limit.unwrap_or(default).min(max).max(1)
+  }
+
+  /// Returns the sort direction, defaulting to ascending for missing or unknown values.
+  pub fn sort_order(&self) -> Order {
+    match self.sort_dir.as_deref() {
+      Some(dir) if dir.eq_ignore_ascii_case("desc") => Order::Desc,
+      _ => Order::Asc,
+    }
+  }
+
+  /// Returns the trimmed search term, or `None` if it is missing or blank.
+  pub fn search(&self) -> Option<&str> {
+    self.q.as_deref().map(str::trim).filter(|q| !q.is_empty())
+  }
+
   pub fn is_cursor_mode(&self) -> bool {
-    self.cursor.is_some()
+    self.cursor.is_some() || self.before.is_some()
+  }
+
+  pub fn is_offset_mode(&self) -> bool {
+    !self.is_cursor_mode() && (self.offset.is_some() || self.limit.is_some())
+  }
+
+  /// Whether items are streamed as newline-delimited JSON (`?format=ndjson`).
+  pub fn is_ndjson(&self) -> bool {
+    self
+      .format
+      .as_deref()
+      .is_some_and(|format| format.eq_ignore_ascii_case("ndjson"))
+  }
+
+  /// Returns the requested item fields, or `None` if `fields` is missing or blank.
+  pub fn fields(&self) -> Option<Vec<&str>> {
+    let fields: Vec<&str> = self
+      .fields
+      .as_deref()?
+      .split(',')
+      .map(str::trim)
+      .filter(|field| !field.is_empty())
+      .collect();
+    (!fields.is_empty()).then_some(fields)
+  }
+
+  /// Returns the `(offset, limit)` window of the page or offset mode, with up to `max` items.
+  pub fn window_up_to(&self, cfg: &Config, max: u64) -> (u64, u64) {
+    let default = cfg.pagination_default_per_page;
+    if self.is_offset_mode() {
+      (self.offset(), self.limit_up_to(default, max))
+    } else {
+      let per_page = self.per_page_up_to(default, max);
+      let offset = (self.page() - 1).saturating_mul(per_page).min(MAX_OFFSET);
+      (offset, per_page)
+    }
   }
 }
 
@@ -55,6 +150,32 @@ pub struct PageMeta {
   pub total_pages: u64,
 }
 
+/// Position encoded in the `next_cursor` and `prev_cursor` of cursor pages: every sort key of
+/// the item it points at, so that the next page starts right after it even among equal values.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CursorPosition {
+  /// Column the page is sorted by
+  pub sort: String,
+  /// Value of the sort column
+  pub key: serde_json::Value,
+  /// Id of the item, the tiebreaker of equal sort values
+  pub id: String,
+}
+
+impl CursorPosition {
+  /// Encodes the position as an opaque URL-safe string.
+  pub fn encode(&self) -> String {
+    let json = serde_json::to_vec(self).expect("Cursor position is serializable");
+    general_purpose::URL_SAFE_NO_PAD.encode(json)
+  }
+
+  /// Decodes a cursor made by `encode`, `None` if it isn't one.
+  pub fn decode(cursor: &str) -> Option<Self> {
+    let json = general_purpose::URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    serde_json::from_slice(&json).ok()
+  }
+}
+
 /// Paginated response wrapper for cursor-based pagination.
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CursorResponse<T: Serialize> {
@@ -63,24 +184,96 @@ pub struct CursorResponse<T: Serialize> {
 }
 
 /// Metadata for cursor-based pagination.
+///
+/// `next_cursor` is passed back as `cursor` and `prev_cursor` as `before`. `total` is only
+/// counted with `include_total=true`, and ignores the cursor position.
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CursorMeta {
   pub per_page: u64,
   pub next_cursor: Option<String>,
+  pub prev_cursor: Option<String>,
+  pub has_next: bool,
+  pub has_prev: bool,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub total: Option<u64>,
+}
+
+/// Paginated response wrapper for offset-based pagination.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OffsetResponse<T: Serialize> {
+  pub data: Vec<T>,
+  pub meta: OffsetMeta,
+}
+
+/// Metadata for offset-based pagination.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OffsetMeta {
+  pub total: u64,
+  pub offset: u64,
+  pub limit: u64,
 }
 
-/// Unified paginated response that supports both page and cursor modes.
+/// Unified paginated response that supports page, offset and cursor modes.
 /// Uses `#[serde(untagged)]` so the JSON output matches the inner variant directly.
 #[derive(Debug, Serialize, ToSchema)]
 #[serde(untagged)]
 pub enum PaginatedResponse<T: Serialize> {
   Page(PageResponse<T>),
+  Offset(OffsetResponse<T>),
   Cursor(CursorResponse<T>),
 }
 
+impl<T: Serialize> PaginatedResponse<T> {
+  /// Maps the items, keeping the pagination metadata.
+  pub fn map<U: Serialize>(self, f: impl FnMut(T) -> U) -> PaginatedResponse<U> {
+    match self {
+      Self::Page(page) => PaginatedResponse::Page(PageResponse {
+        data: page.data.into_iter().map(f).collect(),
+        meta: page.meta,
+      }),
+      Self::Offset(page) => PaginatedResponse::Offset(OffsetResponse {
+        data: page.data.into_iter().map(f).collect(),
+        meta: page.meta,
+      }),
+      Self::Cursor(page) => PaginatedResponse::Cursor(CursorResponse {
+        data: page.data.into_iter().map(f).collect(),
+        meta: page.meta,
+      }),
+    }
+  }
+
+  /// Keeps only the given fields of each item (sparse fieldsets), unknown fields are ignored.
+  pub fn select_fields(
+    self,
+    fields: &[&str],
+  ) -> PaginatedResponse<serde_json::Map<String, serde_json::Value>> {
+    self.map(|item| {
+      let serde_json::Value::Object(mut item) =
+        serde_json::to_value(item).expect("Paginated items are serializable")
+      else {
+        return serde_json::Map::new();
+      };
+      item.retain(|key, _| fields.contains(&key.as_str()));
+      item
+    })
+  }
+}
+
 #[cfg(test)]
 mod tests {
+  use std::sync::Arc;
+
   use super::*;
+  use crate::common::config::Configuration;
+
+  /// Configuration with pagination defaults other than the built-in ones.
+  fn config(default_per_page: u64, max_per_page: u64) -> Config {
+    let mut cfg = Configuration::for_tests();
+    let settings = Arc::get_mut(&mut cfg).unwrap();
+    settings.pagination_default_per_page = default_per_page;
+    settings.pagination_max_per_page = max_per_page;
+    cfg
+  }
 
   #[test]
   fn test_default_per_page() {
@@ -88,8 +281,12 @@ mod tests {
       page: None,
       per_page: None,
       cursor: None,
+      ..Default::default()
     };
-    assert_eq!(params.per_page(), DEFAULT_PER_PAGE);
+    for (default, max) in [(20, 100), (50, 500)] {
+      assert_eq!(params.per_page_with(&config(default, max)), default);
+      assert_eq!(params.limit_with(&config(default, max)), default);
+    }
   }
 
   #[test]
@@ -98,8 +295,11 @@ mod tests {
       page: None,
       per_page: Some(200),
       cursor: None,
+      ..Default::default()
     };
-    assert_eq!(params.per_page(), MAX_PER_PAGE);
+    assert_eq!(params.per_page_with(&config(20, 100)), 100);
+    assert_eq!(params.per_page_with(&config(20, 150)), 150);
+    assert_eq!(params.per_page_with(&config(20, 500)), 200);
   }
 
   #[test]
@@ -108,8 +308,9 @@ mod tests {
       page: None,
       per_page: Some(0),
       cursor: None,
+      ..Default::default()
     };
-    assert_eq!(params.per_page(), 1);
+    assert_eq!(params.per_page_with(&config(20, 100)), 1);
   }
 
   #[test]
@@ -118,6 +319,7 @@ mod tests {
       page: None,
       per_page: None,
       cursor: None,
+      ..Default::default()
     };
     assert_eq!(params.page(), 1);
   }
@@ -128,6 +330,7 @@ mod tests {
       page: Some(0),
       per_page: None,
       cursor: None,
+      ..Default::default()
     };
     assert_eq!(params.page(), 1);
   }
@@ -138,20 +341,155 @@ mod tests {
       page: None,
       per_page: None,
       cursor: Some("some-id".to_string()),
+      ..Default::default()
     };
     assert!(params.is_cursor_mode());
   }
 
+  #[test]
+  fn test_before_is_cursor_mode() {
+    let params = PaginationParams {
+      before: Some("some-id".to_string()),
+      offset: Some(40),
+      ..Default::default()
+    };
+    assert!(params.is_cursor_mode());
+    assert!(!params.is_offset_mode());
+  }
+
   #[test]
   fn test_is_not_cursor_mode() {
     let params = PaginationParams {
       page: Some(2),
       per_page: None,
       cursor: None,
+      ..Default::default()
+    };
+    assert!(!params.is_cursor_mode());
+  }
+
+  #[test]
+  fn test_is_offset_mode() {
+    let params = PaginationParams {
+      offset: Some(40),
+      ..Default::default()
     };
+    assert!(params.is_offset_mode());
     assert!(!params.is_cursor_mode());
   }
 
+  #[test]
+  fn test_is_offset_mode_with_limit_only() {
+    let params = PaginationParams {
+      limit: Some(10),
+      ..Default::default()
+    };
+    assert!(params.is_offset_mode());
+    assert_eq!(params.offset(), 0);
+  }
+
+  #[test]
+  fn test_cursor_takes_precedence_over_offset() {
+    let params = PaginationParams {
+      cursor: Some("some-id".to_string()),
+      offset: Some(40),
+      ..Default::default()
+    };
+    assert!(params.is_cursor_mode());
+    assert!(!params.is_offset_mode());
+  }
+
+  #[test]
+  fn test_offset_takes_precedence_over_page() {
+    let params = PaginationParams {
+      page: Some(3),
+      offset: Some(40),
+      ..Default::default()
+    };
+    assert!(params.is_offset_mode());
+  }
+
+  #[test]
+  fn test_offset_clamped_to_max() {
+    let params = PaginationParams {
+      offset: Some(u64::MAX),
+      ..Default::default()
+    };
+    assert_eq!(params.offset(), i64::MAX as u64);
+  }
+
+  #[test]
+  fn test_limit_clamped() {
+    let params = PaginationParams {
+      limit: Some(500),
+      ..Default::default()
+    };
+    assert_eq!(params.limit_with(&config(20, 100)), 100);
+    assert_eq!(params.limit_with(&config(20, 250)), 250);
+
+    let params = PaginationParams {
+      limit: Some(0),
+      ..Default::default()
+    };
+    assert_eq!(params.limit_with(&config(20, 100)), 1);
+  }
+
+  #[test]
+  fn test_sort_order_default_asc() {
+    let params = PaginationParams::default();
+    assert_eq!(params.sort_order(), Order::Asc);
+  }
+
+  #[test]
+  fn test_sort_order_desc() {
+    let params = PaginationParams {
+      sort_dir: Some("DESC".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(params.sort_order(), Order::Desc);
+  }
+
+  #[test]
+  fn test_sort_order_unknown_falls_back_to_asc() {
+    let params = PaginationParams {
+      sort_dir: Some("sideways".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(params.sort_order(), Order::Asc);
+  }
+
+  #[test]
+  fn test_search_trimmed() {
+    let params = PaginationParams {
+      q: Some("  john ".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(params.search(), Some("john"));
+  }
+
+  #[test]
+  fn test_search_blank_ignored() {
+    let params = PaginationParams {
+      q: Some("   ".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(params.search(), None);
+    assert_eq!(PaginationParams::default().search(), None);
+  }
+
+  #[test]
+  fn test_offset_meta_serialization() {
+    let meta = OffsetMeta {
+      total: 100,
+      offset: 40,
+      limit: 20,
+    };
+    let json = serde_json::to_string(&meta).unwrap();
+    assert!(json.contains("\"total\":100"));
+    assert!(json.contains("\"offset\":40"));
+    assert!(json.contains("\"limit\":20"));
+  }
+
   #[test]
   fn test_page_meta_serialization() {
     let meta = PageMeta {
@@ -172,10 +510,18 @@ mod tests {
     let meta = CursorMeta {
       per_page: 20,
       next_cursor: Some("abc-123".to_string()),
+      prev_cursor: Some("def-456".to_string()),
+      has_next: true,
+      has_prev: true,
+      total: Some(42),
     };
     let json = serde_json::to_string(&meta).unwrap();
     assert!(json.contains("\"per_page\":20"));
     assert!(json.contains("\"next_cursor\":\"abc-123\""));
+    assert!(json.contains("\"prev_cursor\":\"def-456\""));
+    assert!(json.contains("\"has_next\":true"));
+    assert!(json.contains("\"has_prev\":true"));
+    assert!(json.contains("\"total\":42"));
   }
 
   #[test]
@@ -183,8 +529,110 @@ mod tests {
     let meta = CursorMeta {
       per_page: 20,
       next_cursor: None,
+      prev_cursor: None,
+      has_next: false,
+      has_prev: false,
+      total: None,
     };
     let json = serde_json::to_string(&meta).unwrap();
     assert!(json.contains("\"next_cursor\":null"));
+    assert!(json.contains("\"has_next\":false"));
+    assert!(!json.contains("\"total\""));
+  }
+
+  #[test]
+  fn test_cursor_position_round_trip() {
+    let position = CursorPosition {
+      sort: "name".to_string(),
+      key: serde_json::json!("Jane"),
+      id: "00000000-0000-0000-0000-000000000001".to_string(),
+    };
+    let cursor = position.encode();
+    assert!(cursor
+      .bytes()
+      .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+    assert_eq!(CursorPosition::decode(&cursor), Some(position));
+  }
+
+  #[test]
+  fn test_cursor_position_rejects_other_strings() {
+    assert_eq!(
+      CursorPosition::decode("00000000-0000-0000-0000-000000000001"),
+      None
+    );
+    assert_eq!(CursorPosition::decode("not base64!"), None);
+  }
+
+  #[test]
+  fn test_window_allows_larger_pages() {
+    let params = PaginationParams {
+      page: Some(3),
+      per_page: Some(500),
+      ..Default::default()
+    };
+    let cfg = config(20, 100);
+    assert_eq!(params.window_up_to(&cfg, 1000), (1000, 500));
+    assert_eq!(params.window_up_to(&cfg, 200), (400, 200));
+
+    let params = PaginationParams {
+      offset: Some(10),
+      limit: Some(5000),
+      ..Default::default()
+    };
+    assert_eq!(params.window_up_to(&cfg, 1000), (10, 1000));
+
+    // Without per_page, pages have the configured default size
+    let params = PaginationParams {
+      page: Some(2),
+      ..Default::default()
+    };
+    assert_eq!(params.window_up_to(&config(50, 100), 1000), (50, 50));
+  }
+
+  #[test]
+  fn test_is_ndjson() {
+    let params = PaginationParams {
+      format: Some("NDJSON".to_string()),
+      ..Default::default()
+    };
+    assert!(params.is_ndjson());
+    assert!(!PaginationParams::default().is_ndjson());
+  }
+
+  #[test]
+  fn test_fields() {
+    let params = PaginationParams {
+      fields: Some(" id, email,,".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(params.fields(), Some(vec!["id", "email"]));
+
+    let blank = PaginationParams {
+      fields: Some(" , ".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(blank.fields(), None);
+    assert_eq!(PaginationParams::default().fields(), None);
+  }
+
+  #[test]
+  fn test_select_fields_keeps_meta() {
+    let response = PaginatedResponse::Offset(OffsetResponse {
+      data: vec![serde_json::json!({ "id": "1", "email": "a@example.com", "name": "A" })],
+      meta: OffsetMeta {
+        total: 1,
+        offset: 0,
+        limit: 20,
+      },
+    });
+
+    let json = serde_json::to_value(response.select_fields(&["email", "unknown"])).unwrap();
+    assert_eq!(
+      json,
+      serde_json::json!({
+        "data": [{ "email": "a@example.com" }],
+        "meta": { "total": 1, "offset": 0, "limit": 20 }
+      })
+    );
   }
 }
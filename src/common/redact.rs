@@ -0,0 +1,95 @@
+use serde_json::Value;
+
+/// Replacement of the redacted values.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Keys whose values are never logged, also matched as a suffix, e.g. `new_password` or
+/// `refresh_token`.
+const SENSITIVE_KEYS: [&str; 4] = ["password", "token", "secret", "api_key"];
+
+/// Whether the values of `key` must not be logged, regardless of its case.
+pub fn is_sensitive(key: &str) -> bool {
+  let key = key.to_ascii_lowercase();
+  SENSITIVE_KEYS.iter().any(|sensitive| {
+    key == *sensitive
+      || key
+        .strip_suffix(sensitive)
+        .is_some_and(|prefix| prefix.ends_with('_'))
+  })
+}
+
+/// Replaces the values of the sensitive keys with `[REDACTED]`, in nested objects and arrays too.
+pub fn redact(value: &mut Value) {
+  match value {
+    Value::Object(map) => {
+      for (key, value) in map.iter_mut() {
+        if is_sensitive(key) {
+          *value = Value::String(REDACTED.to_string());
+        } else {
+          redact(value);
+        }
+      }
+    }
+    Value::Array(items) => items.iter_mut().for_each(redact),
+    _ => {}
+  }
+}
+
+/// Returns a request body fit for the logs: JSON with its sensitive values redacted, or a
+/// placeholder when it isn't JSON as it can't be redacted.
+pub fn redact_body(body: &[u8]) -> String {
+  match serde_json::from_slice::<Value>(body) {
+    Ok(mut value) => {
+      redact(&mut value);
+      value.to_string()
+    }
+    Err(_) => format!("<{} bytes, not JSON>", body.len()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  #[test]
+  fn test_sensitive_keys() {
+    assert!(is_sensitive("password"));
+    assert!(is_sensitive("Password"));
+    assert!(is_sensitive("new_password"));
+    assert!(is_sensitive("refresh_token"));
+    assert!(is_sensitive("api_key"));
+    assert!(!is_sensitive("email"));
+    assert!(!is_sensitive("tokens_used"));
+    assert!(!is_sensitive("passwordless"));
+  }
+
+  #[test]
+  fn test_redact_nested_values() {
+    let mut value = json!({
+      "email": "user@example.com",
+      "password": "hunter22",
+      "keys": [{ "api_key": "abc", "name": "ci" }],
+      "auth": { "token": { "value": "xyz" } }
+    });
+    redact(&mut value);
+    assert_eq!(
+      value,
+      json!({
+        "email": "user@example.com",
+        "password": REDACTED,
+        "keys": [{ "api_key": REDACTED, "name": "ci" }],
+        "auth": { "token": REDACTED }
+      })
+    );
+  }
+
+  #[test]
+  fn test_redact_body() {
+    let body = redact_body(br#"{"email":"user@example.com","password":"hunter22"}"#);
+    assert!(body.contains(REDACTED));
+    assert!(!body.contains("hunter22"));
+    assert_eq!(redact_body(b"password=hunter22"), "<17 bytes, not JSON>");
+  }
+}
@@ -0,0 +1,46 @@
+use axum::{
+  http::{header::ALLOW, HeaderValue, StatusCode},
+  routing::{options, MethodRouter},
+};
+
+/// Answers `OPTIONS` with `204 No Content` and an `Allow` header listing `methods`, so that
+/// API explorers can discover what a route supports.
+///
+/// CORS preflight requests never reach it, they are answered by the CORS layer.
+pub fn allow<S>(methods: &[&str]) -> MethodRouter<S>
+where
+  S: Clone + Send + Sync + 'static,
+{
+  let allow = HeaderValue::from_str(&[methods, &["OPTIONS"]].concat().join(", "))
+    .expect("Invalid method list");
+  options(move || {
+    let allow = allow.clone();
+    async move { (StatusCode::NO_CONTENT, [(ALLOW, allow)]) }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, extract::Request, Router};
+  use tower::ServiceExt;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_allow_lists_methods() {
+    let app: Router = Router::new().route("/", allow(&["GET", "POST"]));
+
+    let response = app
+      .oneshot(
+        Request::builder()
+          .method("OPTIONS")
+          .uri("/")
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()[ALLOW], "GET, POST, OPTIONS");
+  }
+}
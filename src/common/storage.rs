@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use futures_util::future::BoxFuture;
+
+pub type SharedStorage = Arc<dyn Storage>;
+
+/// Stores uploaded files, e.g. avatars.
+///
+/// The default implementation writes to the local filesystem. Plug in another backend, e.g. an
+/// object store, by implementing this trait and setting it in the `AppState`.
+pub trait Storage: Send + Sync {
+  /// Stores `content` under `key`, replacing any previous file, and returns its location.
+  fn put<'a>(&'a self, key: &'a str, content: Bytes) -> BoxFuture<'a, anyhow::Result<String>>;
+
+  /// Removes the file at `location`, as returned by `put`. A missing file isn't an error.
+  fn delete<'a>(&'a self, location: &'a str) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+/// Storage writing files under a local directory (`STORAGE_DIR`).
+#[derive(Clone, Debug)]
+pub struct LocalStorage {
+  dir: PathBuf,
+}
+
+impl LocalStorage {
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    Self { dir: dir.into() }
+  }
+}
+
+impl Storage for LocalStorage {
+  fn put<'a>(&'a self, key: &'a str, content: Bytes) -> BoxFuture<'a, anyhow::Result<String>> {
+    Box::pin(async move {
+      let path = self.dir.join(key);
+      if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+      }
+      tokio::fs::write(&path, content).await?;
+      Ok(path.to_string_lossy().into_owned())
+    })
+  }
+
+  fn delete<'a>(&'a self, location: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+    Box::pin(async move {
+      // Locations are read back from the database, never remove files outside of the dir
+      let path = PathBuf::from(location);
+      if !path.starts_with(&self.dir) || path.components().any(|c| c.as_os_str() == "..") {
+        anyhow::bail!("Location '{}' is outside of the storage dir", location);
+      }
+      match tokio::fs::remove_file(&path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_local_storage_writes_under_its_dir() {
+    let dir = std::env::temp_dir().join(format!("storage-{}", uuid::Uuid::new_v4()));
+    let storage = LocalStorage::new(&dir);
+
+    let location = storage
+      .put("avatars/user.png", Bytes::from_static(b"first"))
+      .await
+      .unwrap();
+    assert_eq!(PathBuf::from(&location), dir.join("avatars/user.png"));
+
+    // A new upload replaces the previous file
+    storage
+      .put("avatars/user.png", Bytes::from_static(b"second"))
+      .await
+      .unwrap();
+    assert_eq!(tokio::fs::read(&location).await.unwrap(), b"second");
+
+    tokio::fs::remove_dir_all(dir).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_local_storage_deletes_only_under_its_dir() {
+    let dir = std::env::temp_dir().join(format!("storage-{}", uuid::Uuid::new_v4()));
+    let storage = LocalStorage::new(&dir);
+
+    let location = storage
+      .put("avatars/user.png", Bytes::from_static(b"avatar"))
+      .await
+      .unwrap();
+    storage.delete(&location).await.unwrap();
+    assert!(!PathBuf::from(&location).exists());
+    // Deleting it again is fine
+    storage.delete(&location).await.unwrap();
+
+    assert!(storage.delete("/etc/passwd").await.is_err());
+    let escaping = dir.join("../outside.png");
+    assert!(storage.delete(&escaping.to_string_lossy()).await.is_err());
+
+    tokio::fs::remove_dir_all(dir).await.unwrap();
+  }
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use futures_util::{Stream, StreamExt};
+use tokio::sync::Semaphore;
+
+use crate::common::errors::ApiError;
+
+/// Caps the number of long-lived streaming responses, e.g. NDJSON lists, open at once
+/// (`MAX_STREAM_CONNECTIONS`), so that they can't exhaust file descriptors.
+#[derive(Clone, Debug)]
+pub struct StreamLimit(Arc<Semaphore>);
+
+impl StreamLimit {
+  pub fn new(max: usize) -> Self {
+    Self(Arc::new(Semaphore::new(max)))
+  }
+
+  /// Holds a slot for as long as `stream` lives, i.e. until it ends or the client disconnects.
+  /// Rejected with 503 Service Unavailable when every slot is taken.
+  pub fn limit<S: Stream>(&self, stream: S) -> Result<impl Stream<Item = S::Item>, ApiError> {
+    let permit = self.0.clone().try_acquire_owned().map_err(|_| {
+      ApiError::ServiceUnavailable("Too many open streams, retry later".to_string())
+    })?;
+    Ok(stream.map(move |item| {
+      let _permit = &permit;
+      item
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use futures_util::stream;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_streams_over_the_limit_are_rejected() {
+    let limit = StreamLimit::new(2);
+    let first = limit.limit(stream::iter([1, 2])).unwrap();
+    let second = limit.limit(stream::iter([3])).unwrap();
+    assert!(matches!(
+      limit.limit(stream::iter([4])),
+      Err(ApiError::ServiceUnavailable(_))
+    ));
+
+    // The slot is released once the stream is done with, like a response body on disconnect
+    assert_eq!(second.collect::<Vec<_>>().await, vec![3]);
+    let third = limit.limit(stream::iter([5])).unwrap();
+    assert!(limit.limit(stream::iter([6])).is_err());
+
+    drop((first, third));
+    assert!(limit.limit(stream::iter([7])).is_ok());
+  }
+}
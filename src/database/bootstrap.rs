@@ -0,0 +1,120 @@
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, DatabaseConnection, EntityTrait};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::common::config::Config;
+use crate::common::crypto::hash_password;
+use crate::modules::users::entities;
+use crate::modules::users::enums::{UserRole, UserStatus};
+
+/// Creates the admin of `BOOTSTRAP_ADMIN_EMAIL` when the users table is empty, returning
+/// whether it was created.
+///
+/// Any existing user, soft-deleted ones included, makes it a no-op, so restarts never create a
+/// second admin or reset the password of the first one.
+pub async fn bootstrap_admin(
+  db: &DatabaseConnection,
+  cfg: &Config,
+) -> Result<bool, sea_orm::DbErr> {
+  let Some(admin) = &cfg.bootstrap_admin else {
+    return Ok(false);
+  };
+
+  if entities::Entity::find().one(db).await?.is_some() {
+    info!("Users already exist, skipping the admin bootstrap");
+    return Ok(false);
+  }
+
+  let password_hash = hash_password(&admin.password, cfg.password_hash_algo, cfg.bcrypt_cost)
+    .map_err(|e| sea_orm::DbErr::Custom(e.to_string()))?;
+
+  entities::ActiveModel {
+    id: Set(Uuid::new_v4()),
+    email: Set(admin.email.clone()),
+    password: Set(password_hash),
+    name: Set("Admin".to_string()),
+    status: Set(UserStatus::Active),
+    role: Set(UserRole::Admin),
+    ..Default::default()
+  }
+  .insert(db)
+  .await?;
+  info!("Bootstrap admin '{}' created", admin.email);
+
+  Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use sea_orm::{DbBackend, MockDatabase};
+
+  use super::*;
+  use crate::common::config::{BootstrapAdmin, Configuration};
+
+  fn admin(id: Uuid) -> entities::Model {
+    entities::Model {
+      id,
+      email: "admin@example.com".to_string(),
+      name: "Admin".to_string(),
+      password: "hash".to_string(),
+      status: UserStatus::Active,
+      role: UserRole::Admin,
+      created_at: None,
+      updated_at: None,
+      created_by: None,
+      updated_by: None,
+      deleted_at: None,
+      last_login_at: None,
+      avatar_url: None,
+      tenant_id: None,
+      token_version: 0,
+    }
+  }
+
+  fn config() -> Config {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().bootstrap_admin = Some(BootstrapAdmin {
+      email: "admin@example.com".to_string(),
+      password: "Admin@123".to_string(),
+    });
+    cfg
+  }
+
+  #[tokio::test]
+  async fn test_bootstrap_creates_admin_on_empty_database() {
+    let db = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([Vec::<entities::Model>::new()])
+      .append_query_results([vec![admin(Uuid::new_v4())]])
+      .into_connection();
+
+    assert!(bootstrap_admin(&db, &config()).await.unwrap());
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("INSERT INTO \\\"users\\\""));
+    assert!(log.contains("admin@example.com"));
+    assert!(!log.contains("Admin@123"));
+  }
+
+  #[tokio::test]
+  async fn test_bootstrap_is_noop_when_users_exist() {
+    let db = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![admin(Uuid::new_v4())]])
+      .into_connection();
+
+    assert!(!bootstrap_admin(&db, &config()).await.unwrap());
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(!log.contains("INSERT"));
+  }
+
+  #[tokio::test]
+  async fn test_bootstrap_is_noop_when_disabled() {
+    let db = MockDatabase::new(DbBackend::Postgres).into_connection();
+    assert!(!bootstrap_admin(&db, &Configuration::for_tests())
+      .await
+      .unwrap());
+    assert!(db.into_transaction_log().is_empty());
+  }
+}
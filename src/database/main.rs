@@ -1,5 +1,5 @@
 use server::common::config::telemetry;
-use server::common::config::Configuration;
+use server::common::config::{Configuration, Environment};
 use server::database::Db;
 use std::env;
 use std::process;
@@ -11,11 +11,38 @@ fn print_usage() {
   eprintln!("  migrate   Run all pending migrations");
   eprintln!("  seed      Run all database seeds");
   eprintln!("  setup     Run migrations then seeds");
+  eprintln!("  status    List applied and pending migrations");
+  eprintln!("  down [N]  Roll back the last N migrations (default: 1)");
+  eprintln!("  reset --force");
+  eprintln!("            Drop all tables, rerun every migration then seeds (not in production)");
   eprintln!();
   eprintln!("Examples:");
   eprintln!("  cargo run --bin db -- migrate");
   eprintln!("  cargo run --bin db -- seed");
   eprintln!("  cargo run --bin db -- setup");
+  eprintln!("  cargo run --bin db -- status");
+  eprintln!("  cargo run --bin db -- down 2");
+  eprintln!("  cargo run --bin db -- reset --force");
+}
+
+fn print_status(applied: &[String], pending: &[String]) {
+  let width = applied
+    .iter()
+    .chain(pending)
+    .map(String::len)
+    .max()
+    .unwrap_or(0)
+    .max("Migration".len());
+  println!("{:<width$}  Status", "Migration");
+  println!("{:-<width$}  -------", "");
+  for name in applied {
+    println!("{:<width$}  Applied", name);
+  }
+  for name in pending {
+    println!("{:<width$}  Pending", name);
+  }
+  println!();
+  println!("{} applied, {} pending", applied.len(), pending.len());
 }
 
 #[tokio::main]
@@ -29,16 +56,52 @@ async fn main() {
 
   let command = args[1].as_str();
 
-  if !matches!(command, "migrate" | "seed" | "setup") {
+  if !matches!(
+    command,
+    "migrate" | "seed" | "setup" | "status" | "down" | "reset"
+  ) {
     eprintln!("Error: unknown command '{}'\n", command);
     print_usage();
     process::exit(1);
   }
 
-  dotenvy::dotenv().ok();
-  telemetry::setup_tracing();
+  // Only `down` takes an argument, the number of migrations to roll back, and `reset` its
+  // confirmation
+  let force = command == "reset" && args.get(2).is_some_and(|arg| arg == "--force");
+  let steps = match args.get(2) {
+    None => 1,
+    Some(_) if force => 1,
+    Some(steps) if command == "down" => match steps.parse::<u32>() {
+      Ok(steps) if steps > 0 => steps,
+      _ => {
+        eprintln!("Error: invalid step count '{}'\n", steps);
+        print_usage();
+        process::exit(1);
+      }
+    },
+    Some(arg) => {
+      eprintln!("Error: unexpected argument '{}'\n", arg);
+      print_usage();
+      process::exit(1);
+    }
+  };
 
+  dotenvy::dotenv().ok();
   let cfg = Configuration::new();
+  let _guard = telemetry::setup_tracing(&cfg);
+
+  // Resetting destroys all data, it must be confirmed and never happens in production
+  if command == "reset" {
+    if matches!(cfg.env, Environment::Production) {
+      eprintln!("Error: refusing to reset the database when APP_ENV=production");
+      process::exit(1);
+    }
+    if !force {
+      eprintln!("Error: reset drops all tables, run it with --force to confirm\n");
+      print_usage();
+      process::exit(1);
+    }
+  }
 
   tracing::info!("Connecting to database...");
   let db = Db::new(&cfg).await.expect("Failed to connect to database");
@@ -63,6 +126,31 @@ async fn main() {
       db.run_seeds(&cfg).await.expect("Failed to run seeds");
       tracing::info!("Seeds completed successfully");
     }
+    "status" => {
+      let (applied, pending) = db
+        .migration_status()
+        .await
+        .expect("Failed to read the migration status");
+      print_status(&applied, &pending);
+    }
+    "down" => {
+      tracing::info!("Rolling back {} migration(s)...", steps);
+      db.rollback_migrations(steps)
+        .await
+        .expect("Failed to roll back migrations");
+      tracing::info!("Rollback completed successfully");
+    }
+    "reset" => {
+      tracing::info!("Dropping all tables and rerunning migrations...");
+      db.reset_migrations()
+        .await
+        .expect("Failed to reset the database");
+      tracing::info!("Migrations completed successfully");
+
+      tracing::info!("Running seeds...");
+      db.run_seeds(&cfg).await.expect("Failed to run seeds");
+      tracing::info!("Seeds completed successfully");
+    }
     _ => unreachable!(),
   }
 }
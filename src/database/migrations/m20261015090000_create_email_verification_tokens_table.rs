@@ -0,0 +1,100 @@
+use sea_orm::{ActiveEnum, DbBackend, Statement};
+use sea_orm_migration::prelude::*;
+
+use crate::modules::users::enums::UserStatus;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Add the Pending variant used for users that have not verified their email yet
+    let add_value = format!(
+      "ALTER TYPE {} ADD VALUE IF NOT EXISTS 'Pending'",
+      UserStatus::name()
+    );
+    manager
+      .get_connection()
+      .execute(Statement::from_string(DbBackend::Postgres, add_value))
+      .await?;
+
+    // Create the email verification tokens table
+    manager
+      .create_table(
+        Table::create()
+          .table(EmailVerificationTokens::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(EmailVerificationTokens::Id)
+              .uuid()
+              .not_null()
+              .primary_key(),
+          )
+          .col(
+            ColumnDef::new(EmailVerificationTokens::UserId)
+              .uuid()
+              .not_null(),
+          )
+          .col(
+            ColumnDef::new(EmailVerificationTokens::Token)
+              .string()
+              .not_null()
+              .unique_key(),
+          )
+          .col(
+            ColumnDef::new(EmailVerificationTokens::ExpiresAt)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .col(ColumnDef::new(EmailVerificationTokens::UsedAt).timestamp_with_time_zone())
+          .col(
+            ColumnDef::new(EmailVerificationTokens::CreatedAt)
+              .timestamp_with_time_zone()
+              .not_null()
+              .default(Expr::current_timestamp()),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .name("fk_email_verification_tokens_user_id")
+              .from(
+                EmailVerificationTokens::Table,
+                EmailVerificationTokens::UserId,
+              )
+              .to(Users::Table, Users::Id)
+              .on_delete(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Postgres cannot drop a single enum value, so the Pending variant is left in place.
+    manager
+      .drop_table(
+        Table::drop()
+          .table(EmailVerificationTokens::Table)
+          .if_exists()
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum EmailVerificationTokens {
+  Table,
+  Id,
+  UserId,
+  Token,
+  ExpiresAt,
+  UsedAt,
+  CreatedAt,
+}
+
+#[derive(Iden)]
+enum Users {
+  Table,
+  Id,
+}
@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Create the password reset tokens table
+    manager
+      .create_table(
+        Table::create()
+          .table(PasswordResetTokens::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(PasswordResetTokens::Id)
+              .uuid()
+              .not_null()
+              .primary_key(),
+          )
+          .col(
+            ColumnDef::new(PasswordResetTokens::UserId)
+              .uuid()
+              .not_null(),
+          )
+          .col(
+            ColumnDef::new(PasswordResetTokens::Token)
+              .string()
+              .not_null()
+              .unique_key(),
+          )
+          .col(
+            ColumnDef::new(PasswordResetTokens::ExpiresAt)
+              .timestamp_with_time_zone()
+              .not_null(),
+          )
+          .col(ColumnDef::new(PasswordResetTokens::UsedAt).timestamp_with_time_zone())
+          .col(
+            ColumnDef::new(PasswordResetTokens::CreatedAt)
+              .timestamp_with_time_zone()
+              .not_null()
+              .default(Expr::current_timestamp()),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .name("fk_password_reset_tokens_user_id")
+              .from(PasswordResetTokens::Table, PasswordResetTokens::UserId)
+              .to(Users::Table, Users::Id)
+              .on_delete(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(
+        Table::drop()
+          .table(PasswordResetTokens::Table)
+          .if_exists()
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum PasswordResetTokens {
+  Table,
+  Id,
+  UserId,
+  Token,
+  ExpiresAt,
+  UsedAt,
+  CreatedAt,
+}
+
+#[derive(Iden)]
+enum Users {
+  Table,
+  Id,
+}
@@ -0,0 +1,122 @@
+use sea_orm::{DbBackend, Statement};
+use sea_orm_migration::prelude::*;
+use tracing::error;
+
+/// Lists emails that would collide once trimmed and lowercased.
+const COLLISIONS_SQL: &str = "SELECT lower(trim(email)) AS email, count(*) AS count
+  FROM users
+  GROUP BY lower(trim(email))
+  HAVING count(*) > 1";
+
+/// Trims and lowercases emails that aren't normalized yet.
+const BACKFILL_SQL: &str =
+  "UPDATE users SET email = lower(trim(email)) WHERE email <> lower(trim(email))";
+
+/// Enforces case-insensitive uniqueness of emails.
+const CREATE_INDEX_SQL: &str =
+  "CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email_lower ON users (lower(email))";
+
+const DROP_INDEX_SQL: &str = "DROP INDEX IF EXISTS idx_users_email_lower";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    // Refuse to merge accounts silently, collisions have to be resolved by hand
+    let collisions: Vec<String> = db
+      .query_all(Statement::from_string(DbBackend::Postgres, COLLISIONS_SQL))
+      .await?
+      .iter()
+      .filter_map(|row| row.try_get::<String>("", "email").ok())
+      .collect();
+    if !collisions.is_empty() {
+      error!(
+        ?collisions,
+        "Emails collide once normalized, resolve them before migrating"
+      );
+      return Err(DbErr::Migration(format!(
+        "Emails collide once normalized: {}",
+        collisions.join(", ")
+      )));
+    }
+
+    // Both statements are no-ops when run again
+    db.execute_unprepared(BACKFILL_SQL).await?;
+    db.execute_unprepared(CREATE_INDEX_SQL).await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // The original casing of emails is lost, only the index can be dropped
+    manager
+      .get_connection()
+      .execute_unprepared(DROP_INDEX_SQL)
+      .await?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use sea_orm::{MockDatabase, MockExecResult, Transaction, Value};
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_backfills_mixed_case_emails_and_builds_index() {
+    let db = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([Vec::<BTreeMap<&str, Value>>::new()])
+      .append_exec_results([
+        MockExecResult {
+          last_insert_id: 0,
+          rows_affected: 2,
+        },
+        MockExecResult {
+          last_insert_id: 0,
+          rows_affected: 0,
+        },
+      ])
+      .into_connection();
+
+    let manager = SchemaManager::new(&db);
+    assert!(Migration.up(&manager).await.is_ok());
+
+    let log = db.into_transaction_log();
+    assert_eq!(log.len(), 3);
+    assert_eq!(
+      log[1],
+      Transaction::one(Statement::from_string(DbBackend::Postgres, BACKFILL_SQL))
+    );
+    assert_eq!(
+      log[2],
+      Transaction::one(Statement::from_string(
+        DbBackend::Postgres,
+        CREATE_INDEX_SQL
+      ))
+    );
+  }
+
+  #[tokio::test]
+  async fn test_fails_on_collisions() {
+    let db = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([[BTreeMap::from([
+        ("email", Value::from("john@example.com")),
+        ("count", Value::from(2i64)),
+      ])]])
+      .into_connection();
+
+    let manager = SchemaManager::new(&db);
+    let err = Migration.up(&manager).await.unwrap_err();
+    assert!(err.to_string().contains("john@example.com"));
+
+    // Nothing is updated when emails collide
+    assert_eq!(db.into_transaction_log().len(), 1);
+  }
+}
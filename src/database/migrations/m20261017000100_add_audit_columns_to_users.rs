@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Both columns reference the acting user, and are cleared if that user is removed
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .add_column_if_not_exists(ColumnDef::new(Users::CreatedBy).uuid())
+          .add_column_if_not_exists(ColumnDef::new(Users::UpdatedBy).uuid())
+          .add_foreign_key(
+            TableForeignKey::new()
+              .name("fk_users_created_by")
+              .from_tbl(Users::Table)
+              .from_col(Users::CreatedBy)
+              .to_tbl(Users::Table)
+              .to_col(Users::Id)
+              .on_delete(ForeignKeyAction::SetNull),
+          )
+          .add_foreign_key(
+            TableForeignKey::new()
+              .name("fk_users_updated_by")
+              .from_tbl(Users::Table)
+              .from_col(Users::UpdatedBy)
+              .to_tbl(Users::Table)
+              .to_col(Users::Id)
+              .on_delete(ForeignKeyAction::SetNull),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .drop_foreign_key(Alias::new("fk_users_created_by"))
+          .drop_foreign_key(Alias::new("fk_users_updated_by"))
+          .drop_column(Users::CreatedBy)
+          .drop_column(Users::UpdatedBy)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Users {
+  Table,
+  Id,
+  CreatedBy,
+  UpdatedBy,
+}
@@ -0,0 +1,86 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Create the auth events table, events outlive the users they relate to
+    manager
+      .create_table(
+        Table::create()
+          .table(AuthEvents::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(AuthEvents::Id)
+              .uuid()
+              .not_null()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(AuthEvents::UserId).uuid())
+          .col(ColumnDef::new(AuthEvents::Email).string())
+          .col(ColumnDef::new(AuthEvents::Kind).string().not_null())
+          .col(ColumnDef::new(AuthEvents::Outcome).string().not_null())
+          .col(ColumnDef::new(AuthEvents::Ip).string())
+          .col(ColumnDef::new(AuthEvents::UserAgent).string())
+          .col(
+            ColumnDef::new(AuthEvents::CreatedAt)
+              .timestamp_with_time_zone()
+              .not_null()
+              .default(Expr::current_timestamp()),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .name("fk_auth_events_user_id")
+              .from(AuthEvents::Table, AuthEvents::UserId)
+              .to(Users::Table, Users::Id)
+              .on_delete(ForeignKeyAction::SetNull),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    // Recent events are listed first, optionally filtered by outcome
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_auth_events_created_at")
+          .table(AuthEvents::Table)
+          .col(AuthEvents::CreatedAt)
+          .if_not_exists()
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(
+        Table::drop()
+          .table(AuthEvents::Table)
+          .if_exists()
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum AuthEvents {
+  Table,
+  Id,
+  UserId,
+  Email,
+  Kind,
+  Outcome,
+  Ip,
+  UserAgent,
+  CreatedAt,
+}
+
+#[derive(Iden)]
+enum Users {
+  Table,
+  Id,
+}
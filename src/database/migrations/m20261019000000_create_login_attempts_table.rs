@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Create the login attempts table, one row per user with failed logins
+    manager
+      .create_table(
+        Table::create()
+          .table(LoginAttempts::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(LoginAttempts::UserId)
+              .uuid()
+              .not_null()
+              .primary_key(),
+          )
+          .col(
+            ColumnDef::new(LoginAttempts::FailedAttempts)
+              .integer()
+              .not_null()
+              .default(0),
+          )
+          .col(ColumnDef::new(LoginAttempts::LockedUntil).timestamp_with_time_zone())
+          .col(
+            ColumnDef::new(LoginAttempts::UpdatedAt)
+              .timestamp_with_time_zone()
+              .not_null()
+              .default(Expr::current_timestamp()),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .name("fk_login_attempts_user_id")
+              .from(LoginAttempts::Table, LoginAttempts::UserId)
+              .to(Users::Table, Users::Id)
+              .on_delete(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(
+        Table::drop()
+          .table(LoginAttempts::Table)
+          .if_exists()
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum LoginAttempts {
+  Table,
+  UserId,
+  FailedAttempts,
+  LockedUntil,
+  UpdatedAt,
+}
+
+#[derive(Iden)]
+enum Users {
+  Table,
+  Id,
+}
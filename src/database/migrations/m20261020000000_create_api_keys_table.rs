@@ -0,0 +1,72 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Create the api keys table, only a hash of the keys is stored
+    manager
+      .create_table(
+        Table::create()
+          .table(ApiKeys::Table)
+          .if_not_exists()
+          .col(ColumnDef::new(ApiKeys::Id).uuid().not_null().primary_key())
+          .col(ColumnDef::new(ApiKeys::UserId).uuid().not_null())
+          .col(ColumnDef::new(ApiKeys::Label).string().not_null())
+          .col(
+            ColumnDef::new(ApiKeys::KeyHash)
+              .string()
+              .not_null()
+              .unique_key(),
+          )
+          .col(ColumnDef::new(ApiKeys::LastUsedAt).timestamp_with_time_zone())
+          .col(
+            ColumnDef::new(ApiKeys::Revoked)
+              .boolean()
+              .not_null()
+              .default(false),
+          )
+          .col(
+            ColumnDef::new(ApiKeys::CreatedAt)
+              .timestamp_with_time_zone()
+              .not_null()
+              .default(Expr::current_timestamp()),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .name("fk_api_keys_user_id")
+              .from(ApiKeys::Table, ApiKeys::UserId)
+              .to(Users::Table, Users::Id)
+              .on_delete(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(ApiKeys::Table).if_exists().to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum ApiKeys {
+  Table,
+  Id,
+  UserId,
+  Label,
+  KeyHash,
+  LastUsedAt,
+  Revoked,
+  CreatedAt,
+}
+
+#[derive(Iden)]
+enum Users {
+  Table,
+  Id,
+}
@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Time of the last successful login, empty for users who never logged in
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .add_column_if_not_exists(ColumnDef::new(Users::LastLoginAt).timestamp_with_time_zone())
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .drop_column(Users::LastLoginAt)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Users {
+  Table,
+  LastLoginAt,
+}
@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Create the idempotency keys table, one row per key and endpoint
+    manager
+      .create_table(
+        Table::create()
+          .table(IdempotencyKeys::Table)
+          .if_not_exists()
+          .col(ColumnDef::new(IdempotencyKeys::Scope).string().not_null())
+          .col(ColumnDef::new(IdempotencyKeys::Key).string().not_null())
+          .col(
+            ColumnDef::new(IdempotencyKeys::RequestHash)
+              .string()
+              .not_null(),
+          )
+          .col(
+            ColumnDef::new(IdempotencyKeys::StatusCode)
+              .small_integer()
+              .not_null(),
+          )
+          .col(
+            ColumnDef::new(IdempotencyKeys::ResponseBody)
+              .text()
+              .not_null(),
+          )
+          .col(ColumnDef::new(IdempotencyKeys::Location).string())
+          .col(
+            ColumnDef::new(IdempotencyKeys::CreatedAt)
+              .timestamp_with_time_zone()
+              .not_null()
+              .default(Expr::current_timestamp()),
+          )
+          .primary_key(
+            Index::create()
+              .col(IdempotencyKeys::Scope)
+              .col(IdempotencyKeys::Key),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(
+        Table::drop()
+          .table(IdempotencyKeys::Table)
+          .if_exists()
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum IdempotencyKeys {
+  Table,
+  Scope,
+  Key,
+  RequestHash,
+  StatusCode,
+  ResponseBody,
+  Location,
+  CreatedAt,
+}
@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Create the audit logs table, entries outlive the users who made them
+    manager
+      .create_table(
+        Table::create()
+          .table(AuditLogs::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(AuditLogs::Id)
+              .uuid()
+              .not_null()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(AuditLogs::ActorId).uuid())
+          .col(ColumnDef::new(AuditLogs::Action).string().not_null())
+          .col(ColumnDef::new(AuditLogs::TargetType).string().not_null())
+          .col(ColumnDef::new(AuditLogs::TargetId).string())
+          .col(
+            ColumnDef::new(AuditLogs::Timestamp)
+              .timestamp_with_time_zone()
+              .not_null()
+              .default(Expr::current_timestamp()),
+          )
+          .col(ColumnDef::new(AuditLogs::Metadata).json_binary())
+          .foreign_key(
+            ForeignKey::create()
+              .name("fk_audit_logs_actor_id")
+              .from(AuditLogs::Table, AuditLogs::ActorId)
+              .to(Users::Table, Users::Id)
+              .on_delete(ForeignKeyAction::SetNull),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    // Recent entries are listed first, optionally filtered by actor
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_audit_logs_timestamp")
+          .table(AuditLogs::Table)
+          .col(AuditLogs::Timestamp)
+          .if_not_exists()
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_audit_logs_actor_id")
+          .table(AuditLogs::Table)
+          .col(AuditLogs::ActorId)
+          .if_not_exists()
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(AuditLogs::Table).if_exists().to_owned())
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum AuditLogs {
+  Table,
+  Id,
+  ActorId,
+  Action,
+  TargetType,
+  TargetId,
+  Timestamp,
+  Metadata,
+}
+
+#[derive(Iden)]
+enum Users {
+  Table,
+  Id,
+}
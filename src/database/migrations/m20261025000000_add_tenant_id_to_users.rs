@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Tenant of the user, empty for the existing users which stay outside of any tenant
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .add_column_if_not_exists(ColumnDef::new(Users::TenantId).string())
+          .to_owned(),
+      )
+      .await?;
+
+    // Every user query is scoped to a tenant
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_users_tenant_id")
+          .table(Users::Table)
+          .col(Users::TenantId)
+          .if_not_exists()
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_index(
+        Index::drop()
+          .name("idx_users_tenant_id")
+          .table(Users::Table)
+          .if_exists()
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .drop_column(Users::TenantId)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Users {
+  Table,
+  TenantId,
+}
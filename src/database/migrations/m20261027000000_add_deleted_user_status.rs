@@ -0,0 +1,28 @@
+use sea_orm::{ActiveEnum, DbBackend, Statement};
+use sea_orm_migration::prelude::*;
+
+use crate::modules::users::enums::UserStatus;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Add the Deleted variant used for users anonymized on deletion
+    let add_value = format!(
+      "ALTER TYPE {} ADD VALUE IF NOT EXISTS 'Deleted'",
+      UserStatus::name()
+    );
+    manager
+      .get_connection()
+      .execute(Statement::from_string(DbBackend::Postgres, add_value))
+      .await?;
+    Ok(())
+  }
+
+  async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+    // Postgres can't drop a value of an enum, it's left in place
+    Ok(())
+  }
+}
@@ -1,12 +1,44 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20240126114845_create_users_table;
+mod m20261015090000_create_email_verification_tokens_table;
+mod m20261015090100_create_password_reset_tokens_table;
+mod m20261016000000_normalize_user_emails;
+mod m20261017000000_add_deleted_at_to_users;
+mod m20261017000100_add_audit_columns_to_users;
+mod m20261018000000_create_auth_events_table;
+mod m20261019000000_create_login_attempts_table;
+mod m20261020000000_create_api_keys_table;
+mod m20261021000000_add_last_login_at_to_users;
+mod m20261022000000_create_idempotency_keys_table;
+mod m20261023000000_add_avatar_url_to_users;
+mod m20261024000000_create_audit_logs_table;
+mod m20261025000000_add_tenant_id_to_users;
+mod m20261026000000_add_token_version_to_users;
+mod m20261027000000_add_deleted_user_status;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
   fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-    vec![Box::new(m20240126114845_create_users_table::Migration)]
+    vec![
+      Box::new(m20240126114845_create_users_table::Migration),
+      Box::new(m20261015090000_create_email_verification_tokens_table::Migration),
+      Box::new(m20261015090100_create_password_reset_tokens_table::Migration),
+      Box::new(m20261016000000_normalize_user_emails::Migration),
+      Box::new(m20261017000000_add_deleted_at_to_users::Migration),
+      Box::new(m20261017000100_add_audit_columns_to_users::Migration),
+      Box::new(m20261018000000_create_auth_events_table::Migration),
+      Box::new(m20261019000000_create_login_attempts_table::Migration),
+      Box::new(m20261020000000_create_api_keys_table::Migration),
+      Box::new(m20261021000000_add_last_login_at_to_users::Migration),
+      Box::new(m20261022000000_create_idempotency_keys_table::Migration),
+      Box::new(m20261023000000_add_avatar_url_to_users::Migration),
+      Box::new(m20261024000000_create_audit_logs_table::Migration),
+      Box::new(m20261025000000_add_tenant_id_to_users::Migration),
+      Box::new(m20261026000000_add_token_version_to_users::Migration),
+      Box::new(m20261027000000_add_deleted_user_status::Migration),
+    ]
   }
 }
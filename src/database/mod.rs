@@ -1,18 +1,39 @@
+pub mod bootstrap;
 pub mod migrations;
 pub mod seeds;
+pub mod tenants;
 
 use anyhow::Result;
-use sea_orm::{ConnectOptions, Database, DatabaseConnection};
+use sea_orm::{
+  sqlx, ActiveModelTrait, ConnectOptions, ConnectionTrait, Database, DatabaseConnection,
+  DatabaseTransaction, DbErr, EntityTrait, RuntimeErr, TransactionTrait,
+};
 use sea_orm_migration::MigratorTrait;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::common::config::Config;
+use crate::common::extractors::TenantContext;
 use crate::database::migrations::Migrator;
+use crate::database::tenants::TenantPools;
+
+/// Connections of the shared pool, see `Db::pool_stats`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolStats {
+  /// Open connections, idle or in use
+  pub size: u32,
+  /// Open connections waiting to be used
+  pub idle: usize,
+}
 
 #[derive(Clone)]
 pub struct Db {
   pub conn: DatabaseConnection,
+  /// Pools of the tenant databases, `None` in single-tenant mode
+  pub tenants: Option<Arc<TenantPools>>,
 }
 
 impl Db {
@@ -32,11 +53,44 @@ impl Db {
       .max_connections(cfg.db_pool_max_size)
       // Set min connections to 1
       .min_connections(1);
+    set_statement_timeout(&mut opt, cfg.db_statement_timeout);
 
     info!("Database connection options: {:?}", opt);
     info!("Connecting to database...");
-    let conn = Database::connect(opt).await?;
-    Ok(Self { conn })
+    let conn = connect_with_retry(
+      cfg.db_connect_retries,
+      Duration::from_millis(cfg.db_connect_retry_delay_ms),
+      || Database::connect(opt.clone()),
+    )
+    .await?;
+    let tenants = TenantPools::new(cfg).map(Arc::new);
+    Ok(Self { conn, tenants })
+  }
+
+  /// Returns the connection to the tenant's database, or the shared one in single-tenant mode
+  /// and for requests without a tenant.
+  pub async fn conn_for(&self, tenant: &TenantContext) -> Result<DatabaseConnection, DbErr> {
+    match (&self.tenants, tenant.id()) {
+      (Some(tenants), Some(tenant)) => tenants.get(tenant).await,
+      _ => Ok(self.conn.clone()),
+    }
+  }
+
+  /// Tenant of the database `conn_for` returns, `None` for the shared one.
+  pub fn tenant_of(&self, tenant: &TenantContext) -> Option<String> {
+    self.tenants.as_ref().and(tenant.0.clone())
+  }
+
+  /// Connections of the shared pool, `None` when it isn't a Postgres pool, e.g. in tests.
+  pub fn pool_stats(&self) -> Option<PoolStats> {
+    if !matches!(self.conn, DatabaseConnection::SqlxPostgresPoolConnection(_)) {
+      return None;
+    }
+    let pool = self.conn.get_postgres_connection_pool();
+    Some(PoolStats {
+      size: pool.size(),
+      idle: pool.num_idle(),
+    })
   }
 
   pub async fn run_migrations(&self) -> Result<(), sea_orm::DbErr> {
@@ -46,7 +100,412 @@ impl Db {
     Ok(())
   }
 
+  /// Names of the applied and pending migrations, in order.
+  pub async fn migration_status(&self) -> Result<(Vec<String>, Vec<String>), sea_orm::DbErr> {
+    let applied = Migrator::get_applied_migrations(&self.conn).await?;
+    let pending = Migrator::get_pending_migrations(&self.conn).await?;
+    Ok((
+      applied.iter().map(|m| m.name().to_string()).collect(),
+      pending.iter().map(|m| m.name().to_string()).collect(),
+    ))
+  }
+
+  /// Rolls back the last `steps` applied migrations.
+  pub async fn rollback_migrations(&self, steps: u32) -> Result<(), sea_orm::DbErr> {
+    Migrator::down(&self.conn, Some(steps)).await
+  }
+
+  /// Drops all tables then reruns every migration, destroying all data.
+  pub async fn reset_migrations(&self) -> Result<(), sea_orm::DbErr> {
+    Migrator::fresh(&self.conn).await
+  }
+
   pub async fn run_seeds(&self, cfg: &Config) -> Result<(), sea_orm::DbErr> {
     seeds::run(&self.conn, cfg).await
   }
+
+  pub async fn bootstrap_admin(&self, cfg: &Config) -> Result<bool, sea_orm::DbErr> {
+    bootstrap::bootstrap_admin(&self.conn, cfg).await
+  }
+
+  /// Runs `f` in a transaction, see `transaction`.
+  pub async fn transaction<F, T, E>(&self, f: F) -> Result<T, E>
+  where
+    F: for<'c> FnOnce(
+      &'c DatabaseTransaction,
+    ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>,
+    E: From<DbErr>,
+  {
+    transaction(&self.conn, f).await
+  }
+}
+
+/// Sets the Postgres `statement_timeout` of the pool's connections, the server cancelling the
+/// statements running longer. They fail with a database error, i.e. `ApiError::DatabaseError`.
+pub fn set_statement_timeout(opt: &mut ConnectOptions, timeout: Option<Duration>) {
+  if let Some(timeout) = timeout {
+    let millis = timeout.as_millis().to_string();
+    opt.map_sqlx_postgres_opts(move |pg| pg.options([("statement_timeout", millis.as_str())]));
+  }
+}
+
+/// Runs `f` in a transaction, committed when it returns `Ok` and rolled back otherwise, so that
+/// multi-step writes never persist partially.
+///
+/// ```ignore
+/// transaction(conn, |txn| {
+///   Box::pin(async move {
+///     let user = user.insert(txn).await?;
+///     Ok(UserDto::from(user))
+///   })
+/// })
+/// .await
+/// ```
+pub async fn transaction<F, T, E>(conn: &DatabaseConnection, f: F) -> Result<T, E>
+where
+  F: for<'c> FnOnce(
+    &'c DatabaseTransaction,
+  ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>,
+  E: From<DbErr>,
+{
+  let txn = conn.begin().await?;
+  match f(&txn).await {
+    Ok(value) => {
+      txn.commit().await?;
+      Ok(value)
+    }
+    Err(e) => {
+      // The error of `f` is the one worth reporting, the transaction is dropped either way
+      if let Err(rollback_err) = txn.rollback().await {
+        warn!("Failed to roll back transaction: {}", rollback_err);
+      }
+      Err(e)
+    }
+  }
+}
+
+/// Inserts `models` with one statement per `chunk_size` rows and returns the number of inserted
+/// rows (`BULK_INSERT_CHUNK`).
+///
+/// Chunks keep statements under the 65535 bind parameters of Postgres. Run it in a
+/// `transaction` for the insert to be all or nothing.
+pub async fn insert_chunked<A, C>(conn: &C, models: Vec<A>, chunk_size: usize) -> Result<u64, DbErr>
+where
+  A: ActiveModelTrait,
+  C: ConnectionTrait,
+{
+  let mut inserted = 0;
+  let mut models = models.into_iter().peekable();
+  while models.peek().is_some() {
+    let chunk: Vec<A> = models.by_ref().take(chunk_size.max(1)).collect();
+    inserted += A::Entity::insert_many(chunk)
+      .exec_without_returning(conn)
+      .await?;
+  }
+  Ok(inserted)
+}
+
+/// Runs the read `read`, running it once more when the connection was severed mid-query, e.g. by a
+/// database restart or failover, and `retry` is enabled (`DATABASE_RETRY_READS`).
+///
+/// The pool drops the broken connection so the second run gets a fresh one. Only pass
+/// idempotent reads: a write may have been applied before the connection dropped.
+pub async fn retry_read<T, F, Fut>(retry: bool, mut read: F) -> Result<T, DbErr>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<T, DbErr>>,
+{
+  match read().await {
+    Err(e) if retry && is_connection_reset(&e) => {
+      warn!(
+        "Database connection lost during a read ({}), retrying once",
+        e
+      );
+      read().await
+    }
+    result => result,
+  }
+}
+
+/// Returns whether the connection was severed while in use, as opposed to a failing query.
+fn is_connection_reset(err: &DbErr) -> bool {
+  use std::io::ErrorKind;
+
+  match err {
+    DbErr::Conn(RuntimeErr::SqlxError(e))
+    | DbErr::Exec(RuntimeErr::SqlxError(e))
+    | DbErr::Query(RuntimeErr::SqlxError(e)) => match e {
+      sqlx::Error::Io(e) => matches!(
+        e.kind(),
+        ErrorKind::ConnectionReset
+          | ErrorKind::ConnectionAborted
+          | ErrorKind::BrokenPipe
+          | ErrorKind::UnexpectedEof
+      ),
+      // admin_shutdown, e.g. the server was restarted
+      sqlx::Error::Database(e) => e.code().as_deref() == Some("57P01"),
+      _ => false,
+    },
+    _ => false,
+  }
+}
+
+/// Upper bound of the delay between two connection attempts.
+const MAX_CONNECT_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Calls `connect` until it succeeds, retrying transient errors up to `retries` times.
+///
+/// The delay starts at `delay` and doubles after each attempt. Other errors (e.g. a bad DSN)
+/// and the last transient error are returned as is.
+async fn connect_with_retry<T, F, Fut>(
+  retries: u32,
+  delay: Duration,
+  mut connect: F,
+) -> Result<T, DbErr>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<T, DbErr>>,
+{
+  let mut attempt = 0;
+  let mut delay = delay;
+  loop {
+    match connect().await {
+      Ok(conn) => return Ok(conn),
+      Err(e) if attempt < retries && is_transient(&e) => {
+        attempt += 1;
+        warn!(
+          "Database connection failed ({}), retrying in {:?} (attempt {}/{})",
+          e, delay, attempt, retries
+        );
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_CONNECT_RETRY_DELAY);
+      }
+      Err(e) => return Err(e),
+    }
+  }
+}
+
+/// Returns whether the connection error may go away by itself, e.g. while Postgres is starting.
+fn is_transient(err: &DbErr) -> bool {
+  match err {
+    DbErr::Conn(RuntimeErr::SqlxError(e)) => match e {
+      sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut => true,
+      // cannot_connect_now (starting up) and too_many_connections
+      sqlx::Error::Database(e) => matches!(e.code().as_deref(), Some("57P03" | "53300")),
+      _ => false,
+    },
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::ErrorKind;
+
+  use sea_orm::{ConnectionTrait, DbBackend, MockDatabase, MockExecResult};
+
+  use super::*;
+
+  const INSERT_SQL: &str = "INSERT INTO users (name) VALUES ('john')";
+
+  fn mock_db() -> Db {
+    Db {
+      conn: MockDatabase::new(DbBackend::Postgres)
+        .append_exec_results([MockExecResult {
+          last_insert_id: 0,
+          rows_affected: 1,
+        }])
+        .into_connection(),
+      tenants: None,
+    }
+  }
+
+  #[tokio::test]
+  async fn test_insert_chunked_inserts_every_chunk() {
+    use crate::modules::users::entities;
+    use sea_orm::Set;
+
+    let exec = |rows_affected| MockExecResult {
+      last_insert_id: 0,
+      rows_affected,
+    };
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_exec_results([exec(2), exec(2), exec(1)])
+      .into_connection();
+    let users = (0..5)
+      .map(|i| entities::ActiveModel {
+        id: Set(uuid::Uuid::new_v4()),
+        email: Set(format!("user{}@example.com", i)),
+        name: Set(format!("User {}", i)),
+        password: Set("hash".to_string()),
+        ..Default::default()
+      })
+      .collect();
+
+    let inserted = insert_chunked(&conn, users, 2).await.unwrap();
+    assert_eq!(inserted, 5);
+
+    let log = conn.into_transaction_log();
+    assert_eq!(log.len(), 3);
+    let log = format!("{:?}", log);
+    for i in 0..5 {
+      assert!(log.contains(&format!("user{}@example.com", i)));
+    }
+  }
+
+  #[tokio::test]
+  async fn test_transaction_rolls_back_on_failure() {
+    let db = mock_db();
+    let result: Result<(), DbErr> = db
+      .transaction(|txn| {
+        Box::pin(async move {
+          txn.execute_unprepared(INSERT_SQL).await?;
+          Err(DbErr::Custom("failed after the insert".to_string()))
+        })
+      })
+      .await;
+    assert!(result.is_err());
+
+    let log = format!("{:?}", db.conn.into_transaction_log());
+    assert!(log.contains(INSERT_SQL));
+    assert!(log.contains("ROLLBACK"));
+    assert!(!log.contains("COMMIT"));
+  }
+
+  #[tokio::test]
+  async fn test_transaction_commits_on_success() {
+    let db = mock_db();
+    let result: Result<(), DbErr> = db
+      .transaction(|txn| {
+        Box::pin(async move {
+          txn.execute_unprepared(INSERT_SQL).await?;
+          Ok(())
+        })
+      })
+      .await;
+    assert!(result.is_ok());
+
+    let log = format!("{:?}", db.conn.into_transaction_log());
+    assert!(log.contains("COMMIT"));
+    assert!(!log.contains("ROLLBACK"));
+  }
+
+  fn refused() -> DbErr {
+    DbErr::Conn(RuntimeErr::SqlxError(sqlx::Error::Io(
+      ErrorKind::ConnectionRefused.into(),
+    )))
+  }
+
+  fn bad_dsn() -> DbErr {
+    DbErr::Conn(RuntimeErr::SqlxError(sqlx::Error::Configuration(
+      "invalid DSN".into(),
+    )))
+  }
+
+  #[test]
+  fn test_is_transient() {
+    assert!(is_transient(&refused()));
+    assert!(is_transient(&DbErr::Conn(RuntimeErr::SqlxError(
+      sqlx::Error::PoolTimedOut
+    ))));
+    assert!(!is_transient(&bad_dsn()));
+    assert!(!is_transient(&DbErr::Conn(RuntimeErr::Internal(
+      "no supporting driver".to_string()
+    ))));
+  }
+
+  #[tokio::test]
+  async fn test_retries_until_connected() {
+    let mut attempts = 0;
+    let result = connect_with_retry(3, Duration::from_millis(1), || {
+      attempts += 1;
+      let result = if attempts < 3 { Err(refused()) } else { Ok(()) };
+      async move { result }
+    })
+    .await;
+    assert!(result.is_ok());
+    assert_eq!(attempts, 3);
+  }
+
+  #[tokio::test]
+  async fn test_returns_last_error_after_retries() {
+    let mut attempts = 0;
+    let result: Result<(), DbErr> = connect_with_retry(2, Duration::from_millis(1), || {
+      attempts += 1;
+      async { Err(refused()) }
+    })
+    .await;
+    assert!(matches!(result, Err(DbErr::Conn(_))));
+    assert_eq!(attempts, 3);
+  }
+
+  #[tokio::test]
+  async fn test_fails_fast_on_bad_dsn() {
+    let mut attempts = 0;
+    let result: Result<(), DbErr> = connect_with_retry(5, Duration::from_millis(1), || {
+      attempts += 1;
+      async { Err(bad_dsn()) }
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+  }
+
+  fn reset() -> DbErr {
+    DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Io(
+      ErrorKind::ConnectionReset.into(),
+    )))
+  }
+
+  #[tokio::test]
+  async fn test_read_is_retried_only_once_and_when_enabled() {
+    let mut attempts = 0;
+    let result: Result<(), DbErr> = retry_read(true, || {
+      attempts += 1;
+      async { Err(reset()) }
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(attempts, 2);
+
+    let mut attempts = 0;
+    let result: Result<(), DbErr> = retry_read(false, || {
+      attempts += 1;
+      async { Err(reset()) }
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+  }
+
+  #[tokio::test]
+  async fn test_read_succeeds_on_retry() {
+    let mut attempts = 0;
+    let result = retry_read(true, || {
+      attempts += 1;
+      let attempt = attempts;
+      async move {
+        if attempt == 1 {
+          Err(reset())
+        } else {
+          Ok(42)
+        }
+      }
+    })
+    .await;
+    assert_eq!(result.unwrap(), 42);
+    assert_eq!(attempts, 2);
+  }
+
+  #[tokio::test]
+  async fn test_failing_query_is_not_retried() {
+    let mut attempts = 0;
+    let result: Result<(), DbErr> = retry_read(true, || {
+      attempts += 1;
+      async { Err(DbErr::Custom("syntax error".to_string())) }
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+  }
 }
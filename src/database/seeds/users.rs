@@ -1,44 +1,116 @@
-use bcrypt::hash;
-use sea_orm::{ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
-use tracing::info;
+use std::collections::HashSet;
+
+use sea_orm::{
+  ActiveEnum, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+};
+use serde::Deserialize;
+use tracing::{info, warn};
 use uuid::Uuid;
+use validator::Validate;
 
 use crate::common::config::Config;
+use crate::common::crypto::hash_password;
+use crate::database::insert_chunked;
 use crate::modules::users::entities::{self, Column};
 use crate::modules::users::enums::{UserRole, UserStatus};
 
+#[derive(Debug, PartialEq)]
 struct SeedUser {
-  email: &'static str,
-  password: &'static str,
-  name: &'static str,
+  email: String,
+  password: String,
+  name: String,
   role: UserRole,
 }
 
-const SEED_USERS: &[SeedUser] = &[
-  SeedUser {
-    email: "admin@example.com",
-    password: "Admin@123",
-    name: "Admin",
-    role: UserRole::Admin,
-  },
-  SeedUser {
-    email: "user1@example.com",
-    password: "User@1234",
-    name: "User One",
-    role: UserRole::User,
-  },
-  SeedUser {
-    email: "user2@example.com",
-    password: "User@1234",
-    name: "User Two",
-    role: UserRole::User,
-  },
-];
-
-pub async fn seed(db: &DatabaseConnection, cfg: &Config) -> Result<(), sea_orm::DbErr> {
-  for seed_user in SEED_USERS {
+impl SeedUser {
+  fn new(email: &str, password: &str, name: &str, role: UserRole) -> Self {
+    Self {
+      email: email.to_string(),
+      password: password.to_string(),
+      name: name.to_string(),
+      role,
+    }
+  }
+}
+
+/// Users seeded when `SEED_USERS_FILE` is unset.
+fn built_in_users() -> Vec<SeedUser> {
+  vec![
+    SeedUser::new("admin@example.com", "Admin@123", "Admin", UserRole::Admin),
+    SeedUser::new("user1@example.com", "User@1234", "User One", UserRole::User),
+    SeedUser::new("user2@example.com", "User@1234", "User Two", UserRole::User),
+  ]
+}
+
+/// An entry of `SEED_USERS_FILE`, validated like `UserCreate`.
+#[derive(Debug, Deserialize, Validate)]
+struct SeedUserEntry {
+  #[validate(email(message = "invalid email format"))]
+  email: String,
+  #[validate(length(min = 8, max = 64, message = "must be between 8 and 64 characters"))]
+  password: String,
+  #[validate(length(min = 1, max = 100, message = "must be between 1 and 100 characters"))]
+  name: String,
+  /// `Admin` or `User`, `User` when missing
+  #[serde(default)]
+  role: Option<String>,
+}
+
+impl SeedUserEntry {
+  fn into_seed_user(self) -> Result<SeedUser, String> {
+    self.validate().map_err(|e| e.to_string())?;
+    let role = match &self.role {
+      None => UserRole::User,
+      Some(role) => UserRole::try_from_value(role)
+        .map_err(|_| "role: must be one of: Admin, User".to_string())?,
+    };
+    Ok(SeedUser {
+      email: self.email,
+      password: self.password,
+      name: self.name,
+      role,
+    })
+  }
+}
+
+/// Parses a JSON array of users, logging and skipping the invalid entries.
+fn parse_seed_users(json: &str) -> Result<Vec<SeedUser>, String> {
+  let entries: Vec<serde_json::Value> =
+    serde_json::from_str(json).map_err(|e| format!("expected a JSON array of users: {}", e))?;
+
+  let mut seen = HashSet::new();
+  let mut users = Vec::new();
+  for (index, entry) in entries.into_iter().enumerate() {
+    let user = serde_json::from_value::<SeedUserEntry>(entry)
+      .map_err(|e| e.to_string())
+      .and_then(SeedUserEntry::into_seed_user);
+    match user {
+      Err(e) => warn!("Skipping seed user #{}: {}", index + 1, e),
+      Ok(user) if !seen.insert(user.email.clone()) => {
+        warn!("Skipping seed user '{}', repeated in the file", user.email)
+      }
+      Ok(user) => users.push(user),
+    }
+  }
+  Ok(users)
+}
+
+/// Reads the users to seed from `SEED_USERS_FILE`, or else returns the built-in ones.
+fn seed_users(cfg: &Config) -> Result<Vec<SeedUser>, DbErr> {
+  let Some(path) = &cfg.seed_users_file else {
+    return Ok(built_in_users());
+  };
+  let json = std::fs::read_to_string(path)
+    .map_err(|e| DbErr::Custom(format!("Unable to read SEED_USERS_FILE {}: {}", path, e)))?;
+  parse_seed_users(&json)
+    .map_err(|e| DbErr::Custom(format!("Invalid SEED_USERS_FILE {}: {}", path, e)))
+}
+
+pub async fn seed(db: &DatabaseConnection, cfg: &Config) -> Result<(), DbErr> {
+  let mut users = Vec::new();
+  for seed_user in seed_users(cfg)? {
     let exists = entities::Entity::find()
-      .filter(Column::Email.eq(seed_user.email))
+      .filter(Column::Email.eq(&seed_user.email))
       .one(db)
       .await?;
 
@@ -47,22 +119,71 @@ pub async fn seed(db: &DatabaseConnection, cfg: &Config) -> Result<(), sea_orm::
       continue;
     }
 
-    let password_hash = hash(seed_user.password.as_bytes(), cfg.bcrypt_cost)
-      .map_err(|e| sea_orm::DbErr::Custom(format!("Failed to hash password: {}", e)))?;
+    let password_hash = hash_password(&seed_user.password, cfg.password_hash_algo, cfg.bcrypt_cost)
+      .map_err(|e| DbErr::Custom(e.to_string()))?;
 
-    let user = entities::ActiveModel {
+    users.push(entities::ActiveModel {
       id: Set(Uuid::new_v4()),
-      email: Set(seed_user.email.to_string()),
+      email: Set(seed_user.email),
       password: Set(password_hash),
-      name: Set(seed_user.name.to_string()),
+      name: Set(seed_user.name),
       status: Set(UserStatus::Active),
-      role: Set(seed_user.role.clone()),
+      role: Set(seed_user.role),
       ..Default::default()
-    };
-
-    entities::Entity::insert(user).exec(db).await?;
-    info!("Seed user '{}' created successfully", seed_user.email);
+    });
   }
 
+  let created = insert_chunked(db, users, cfg.bulk_insert_chunk).await?;
+  info!("{} seed users created successfully", created);
+
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::common::config::Configuration;
+
+  #[test]
+  fn test_invalid_entries_are_skipped() {
+    let users = parse_seed_users(
+      r#"[
+        {"email": "admin@test.com", "password": "Admin@123", "name": "Admin", "role": "Admin"},
+        {"email": "not-an-email", "password": "User@1234", "name": "Bad"},
+        {"email": "short@test.com", "password": "short", "name": "Short"},
+        {"email": "role@test.com", "password": "User@1234", "name": "Role", "role": "Root"},
+        {"email": "missing@test.com"},
+        {"email": "admin@test.com", "password": "Admin@123", "name": "Again"},
+        {"email": "user@test.com", "password": "User@1234", "name": "User"}
+      ]"#,
+    )
+    .unwrap();
+    assert_eq!(
+      users,
+      vec![
+        SeedUser::new("admin@test.com", "Admin@123", "Admin", UserRole::Admin),
+        SeedUser::new("user@test.com", "User@1234", "User", UserRole::User),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_file_must_be_an_array() {
+    assert!(parse_seed_users(r#"{"email": "user@test.com"}"#).is_err());
+  }
+
+  #[test]
+  fn test_built_in_users_without_file() {
+    let users = seed_users(&Configuration::for_tests()).unwrap();
+    assert_eq!(users, built_in_users());
+  }
+
+  #[test]
+  fn test_missing_file_is_an_error() {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().seed_users_file = Some("does/not/exist.json".to_string());
+    assert!(seed_users(&cfg).is_err());
+  }
+}
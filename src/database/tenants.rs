@@ -0,0 +1,157 @@
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use sea_orm::{ConnectOptions, Database, DatabaseConnection, DbErr};
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::common::config::Config;
+use crate::database::set_statement_timeout;
+
+/// Placeholder replaced by the tenant id in `TENANT_DATABASE_URL`.
+pub const TENANT_PLACEHOLDER: &str = "{tenant}";
+
+pub(crate) type Connect = Arc<
+  dyn Fn(String) -> Pin<Box<dyn Future<Output = Result<DatabaseConnection, DbErr>> + Send>>
+    + Send
+    + Sync,
+>;
+
+/// Connection pools of the tenants, one database per tenant.
+///
+/// At most `max_tenants` pools are kept open, the least recently used one is closed to make
+/// room for a new tenant.
+pub struct TenantPools {
+  dsn_template: String,
+  max_tenants: usize,
+  connect: Connect,
+  /// Pools by tenant id, the most recently used last
+  pools: Mutex<Vec<(String, DatabaseConnection)>>,
+}
+
+impl TenantPools {
+  /// Creates the tenant pools from `TENANT_DATABASE_URL`, `None` in single-tenant mode.
+  pub fn new(cfg: &Config) -> Option<Self> {
+    let dsn_template = cfg.tenant_database_url.clone()?;
+    let timeout = Duration::from_secs(cfg.db_timeout);
+    let max_connections = cfg.db_pool_max_size;
+    let statement_timeout = cfg.db_statement_timeout;
+    let connect: Connect = Arc::new(move |dsn| {
+      let mut opt = ConnectOptions::new(dsn);
+      opt
+        .connect_timeout(timeout)
+        .idle_timeout(Duration::from_secs(600))
+        .max_connections(max_connections)
+        .min_connections(0);
+      set_statement_timeout(&mut opt, statement_timeout);
+      Box::pin(Database::connect(opt))
+    });
+    Some(Self::with_connect(
+      dsn_template,
+      cfg.tenant_pool_max_tenants,
+      connect,
+    ))
+  }
+
+  pub(crate) fn with_connect(dsn_template: String, max_tenants: usize, connect: Connect) -> Self {
+    Self {
+      dsn_template,
+      max_tenants: max_tenants.max(1),
+      connect,
+      pools: Mutex::new(Vec::new()),
+    }
+  }
+
+  /// Returns the pool of `tenant`, connecting to its database on first use.
+  pub async fn get(&self, tenant: &str) -> Result<DatabaseConnection, DbErr> {
+    if let Some(conn) = self.touch(&mut *self.pools.lock().await, tenant) {
+      return Ok(conn);
+    }
+
+    // Connect without holding the lock, other tenants keep being served meanwhile
+    let dsn = self.dsn_template.replace(TENANT_PLACEHOLDER, tenant);
+    let conn = (self.connect)(dsn).await?;
+
+    let mut pools = self.pools.lock().await;
+    if let Some(existing) = self.touch(&mut pools, tenant) {
+      // Another request connected first, the new pool is dropped
+      return Ok(existing);
+    }
+    if pools.len() >= self.max_tenants {
+      let (evicted, _) = pools.remove(0);
+      info!("Closing the connection pool of tenant '{}'", evicted);
+    }
+    pools.push((tenant.to_string(), conn.clone()));
+    Ok(conn)
+  }
+
+  /// Marks the pool of `tenant` as the most recently used and returns it.
+  fn touch(
+    &self,
+    pools: &mut Vec<(String, DatabaseConnection)>,
+    tenant: &str,
+  ) -> Option<DatabaseConnection> {
+    let index = pools.iter().position(|(id, _)| id == tenant)?;
+    let entry = pools.remove(index);
+    let conn = entry.1.clone();
+    pools.push(entry);
+    Some(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex as StdMutex;
+
+  use sea_orm::{DbBackend, MockDatabase};
+
+  use super::*;
+
+  fn pools(max_tenants: usize) -> (TenantPools, Arc<StdMutex<Vec<String>>>) {
+    let connected = Arc::new(StdMutex::new(Vec::new()));
+    let calls = connected.clone();
+    let connect: Connect = Arc::new(move |dsn| {
+      calls.lock().unwrap().push(dsn);
+      Box::pin(async { Ok(MockDatabase::new(DbBackend::Postgres).into_connection()) })
+    });
+    let pools = TenantPools::with_connect(
+      "postgres://localhost/app_{tenant}".to_string(),
+      max_tenants,
+      connect,
+    );
+    (pools, connected)
+  }
+
+  #[tokio::test]
+  async fn test_tenants_route_to_distinct_databases() {
+    let (pools, connected) = pools(4);
+    pools.get("acme").await.unwrap();
+    pools.get("globex").await.unwrap();
+    pools.get("acme").await.unwrap();
+
+    assert_eq!(
+      *connected.lock().unwrap(),
+      vec![
+        "postgres://localhost/app_acme".to_string(),
+        "postgres://localhost/app_globex".to_string(),
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn test_least_recently_used_pool_is_evicted() {
+    let (pools, connected) = pools(2);
+    pools.get("acme").await.unwrap();
+    pools.get("globex").await.unwrap();
+    pools.get("acme").await.unwrap();
+    // globex is the least recently used one
+    pools.get("initech").await.unwrap();
+    pools.get("acme").await.unwrap();
+    pools.get("globex").await.unwrap();
+
+    assert_eq!(connected.lock().unwrap().len(), 4);
+    assert_eq!(
+      connected.lock().unwrap().last().unwrap(),
+      "postgres://localhost/app_globex"
+    );
+  }
+}
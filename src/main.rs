@@ -1,7 +1,19 @@
-use server::common::config::shutdown::shutdown_signal;
+use server::app::AppState;
+use server::common::background::BackgroundTasks;
+use server::common::config::shutdown::{shutdown_signal_with_delay, Readiness};
 use server::common::config::telemetry;
 use server::common::config::Configuration;
+use server::common::crypto;
+use server::common::events::UserEvents;
+use server::common::graphql;
+use server::common::notifier::LogNotifier;
+use server::common::storage::LocalStorage;
+use server::common::streams::StreamLimit;
 use server::database::Db;
+use server::modules::auth;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 
 #[tokio::main]
@@ -10,14 +22,18 @@ async fn main() {
   // .env used only for development, so we discard error in all other cases.
   dotenvy::dotenv().ok();
 
-  // Tries to load tracing config from environment (RUST_LOG) or uses "debug".
-  telemetry::setup_tracing();
-
   // Parse configuration from the environment.
   // This will exit with a help message if something is wrong.
-  tracing::debug!("Initializing configuration");
   let cfg = Configuration::new();
 
+  // Passwords saved through the GraphQL mutations are hashed by the users entity, as configured.
+  crypto::configure_password_hashing(cfg.password_hash_algo, cfg.bcrypt_cost);
+  auth::service::init_dummy_hash(cfg.password_hash_algo, cfg.bcrypt_cost)
+    .expect("Failed to hash the dummy password of unknown logins");
+
+  // Tries to load tracing config from environment (RUST_LOG) or uses "debug".
+  let _guard = telemetry::setup_tracing(&cfg);
+
   // Initialize db connection.
   tracing::debug!("Initializing db connection");
   let db = Db::new(&cfg).await.expect("Failed to initialize db");
@@ -30,6 +46,14 @@ async fn main() {
     tracing::debug!("Skipping migrations as DATABASE_RUN_MIGRATIONS is disabled");
   }
 
+  // Create the first admin on an empty database if enabled
+  if cfg.bootstrap_admin.is_some() {
+    tracing::debug!("Bootstrapping the admin");
+    db.bootstrap_admin(&cfg)
+      .await
+      .expect("Failed to bootstrap the admin");
+  }
+
   // Run seeds if enabled
   if cfg.db_run_seeds {
     tracing::debug!("Running seeds");
@@ -44,7 +68,24 @@ async fn main() {
     .await
     .expect("Failed to bind address");
 
-  let router = server::app::router(cfg.clone(), db);
+  // The readiness probe fails once a shutdown signal is received.
+  let readiness = Readiness::default();
+  let app_state = AppState {
+    db,
+    cfg: cfg.clone(),
+    notifier: Arc::new(LogNotifier),
+    readiness: readiness.clone(),
+    background: BackgroundTasks::new(cfg.background_concurrency),
+    streams: StreamLimit::new(cfg.max_stream_connections),
+    events: UserEvents::default(),
+    storage: Arc::new(LocalStorage::new(&cfg.storage_dir)),
+  };
+
+  // Fail fast on an invalid GraphQL schema, e.g. from GRAPHQL_ENTITIES, with the reason
+  let schemas = graphql::build_schema(&app_state)
+    .inspect_err(|e| tracing::error!("Failed to build the GraphQL schema: {}", e))
+    .expect("Failed to build the GraphQL schema");
+  let router = server::app::router_with_state(app_state, schemas);
 
   tracing::info!("Swagger at http://{}{}", cfg.listen_address, "/docs");
   tracing::info!(
@@ -53,8 +94,15 @@ async fn main() {
     cfg.graphql_endpoint
   );
 
-  axum::serve(listener, router)
-    .with_graceful_shutdown(shutdown_signal())
-    .await
-    .expect("Failed to start server")
+  // Connect info exposes the peer address to middlewares such as the rate limiter.
+  axum::serve(
+    listener,
+    router.into_make_service_with_connect_info::<SocketAddr>(),
+  )
+  .with_graceful_shutdown(shutdown_signal_with_delay(
+    readiness,
+    Duration::from_secs(cfg.pre_shutdown_delay_secs),
+  ))
+  .await
+  .expect("Failed to start server")
 }
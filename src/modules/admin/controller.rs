@@ -0,0 +1,90 @@
+use axum::{extract::State, Json};
+
+use crate::app::AppState;
+use crate::common::config::features::Features;
+
+#[utoipa::path(
+  get,
+  tag = "Admin",
+  path = "/api/v1/admin/features",
+  operation_id = "adminFeatures",
+  responses(
+    (status = 200, description = "Current feature flags", body = Features),
+    (status = 401, description = "Unauthorized"),
+    (status = 403, description = "Admin access required")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn features(State(state): State<AppState>) -> Json<Features> {
+  Json(state.cfg.features.clone())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use sea_orm::{DbBackend, MockDatabase};
+
+  use super::*;
+  use crate::common::background::BackgroundTasks;
+  use crate::common::config::Configuration;
+  use crate::common::errors::ApiError;
+  use crate::common::events::UserEvents;
+  use crate::common::extractors::{TenantContext, ValidatedJson};
+  use crate::common::notifier::LogNotifier;
+  use crate::common::storage::LocalStorage;
+  use crate::common::streams::StreamLimit;
+  use crate::database::Db;
+  use crate::modules::auth::{self, dto::RegisterRequest};
+
+  fn state_without_registration() -> AppState {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg)
+      .unwrap()
+      .features
+      .registration_enabled = false;
+    AppState {
+      db: Db {
+        conn: MockDatabase::new(DbBackend::Postgres).into_connection(),
+        tenants: None,
+      },
+      background: BackgroundTasks::new(cfg.background_concurrency),
+      streams: StreamLimit::new(cfg.max_stream_connections),
+      events: UserEvents::default(),
+      storage: Arc::new(LocalStorage::new(&cfg.storage_dir)),
+      cfg,
+      notifier: Arc::new(LogNotifier),
+      readiness: Default::default(),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_disabled_feature_is_reported() {
+    let Json(features) = features(State(state_without_registration())).await;
+    assert!(!features.registration_enabled);
+    assert!(features.swagger_enabled);
+  }
+
+  #[tokio::test]
+  async fn test_disabled_registration_is_enforced() {
+    let req = RegisterRequest {
+      email: "user@example.com".to_string(),
+      name: "user".to_string(),
+      password: "password123".to_string(),
+    };
+    let err = auth::controller::register(
+      State(state_without_registration()),
+      TenantContext::default(),
+      ValidatedJson(req),
+    )
+    .await
+    .err()
+    .unwrap();
+    match err {
+      ApiError::Forbidden(message) => assert_eq!(message, "Registration is disabled"),
+      err => panic!("unexpected error: {}", err),
+    }
+  }
+}
@@ -0,0 +1,18 @@
+pub mod controller;
+
+use axum::{extract::State, Router};
+
+use crate::app::AppState;
+use crate::common::routing::allow;
+use crate::modules::auth::guards::{admin_guard, auth_guard};
+
+pub fn router(State(state): State<AppState>) -> Router<AppState> {
+  Router::new()
+    .route(
+      "/v1/admin/features",
+      axum::routing::get(controller::features),
+    )
+    .layer(axum::middleware::from_fn(admin_guard))
+    .layer(axum::middleware::from_fn_with_state(state, auth_guard))
+    .merge(Router::new().route("/v1/admin/features", allow(&["GET"])))
+}
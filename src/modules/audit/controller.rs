@@ -0,0 +1,34 @@
+use axum::{extract::State, Json};
+
+use crate::app::AppState;
+use crate::common::errors::ApiError;
+use crate::common::extractors::{ListQuery, TenantContext};
+use crate::common::pagination::{PaginatedResponse, PaginationParams};
+use crate::modules::audit::dto::{AuditLogDto, AuditParams};
+use crate::modules::audit::service;
+
+#[utoipa::path(
+  get,
+  tag = "Audit",
+  path = "/api/v1/audit",
+  operation_id = "auditIndex",
+  params(PaginationParams, AuditParams),
+  responses(
+    (status = 200, description = "List the audit logs of user and auth mutations, newest first"),
+    (status = 400, description = "Invalid actor_id or action"),
+    (status = 403, description = "Admin role required")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn index(
+  State(state): State<AppState>,
+  tenant: TenantContext,
+  ListQuery { params, filter }: ListQuery<AuditParams>,
+) -> Result<Json<PaginatedResponse<AuditLogDto>>, ApiError> {
+  // Audit logs are written next to the users they describe, in the tenant's database
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::index(&conn, &state.cfg, &params, &filter).await?;
+  Ok(Json(result))
+}
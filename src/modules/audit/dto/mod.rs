@@ -0,0 +1,43 @@
+use chrono::SecondsFormat;
+use sea_orm::ActiveEnum;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::modules::audit::entities as audit_log;
+
+/// Query parameters for listing audit logs.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct AuditParams {
+  /// Only return entries made by this user
+  pub actor_id: Option<String>,
+  /// Only return entries with this action: `create`, `update`, `delete` or `login`
+  pub action: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditLogDto {
+  pub id: String,
+  pub actor_id: Option<String>,
+  /// `create`, `update`, `delete` or `login`
+  pub action: String,
+  pub target_type: String,
+  pub target_id: Option<String>,
+  #[schema(format = "date-time")]
+  pub timestamp: String,
+  #[schema(value_type = Option<Object>)]
+  pub metadata: Option<serde_json::Value>,
+}
+
+impl From<audit_log::Model> for AuditLogDto {
+  fn from(model: audit_log::Model) -> Self {
+    Self {
+      id: model.id.to_string(),
+      actor_id: model.actor_id.map(|id| id.to_string()),
+      action: model.action.into_value(),
+      target_type: model.target_type,
+      target_id: model.target_id,
+      timestamp: model.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+      metadata: model.metadata,
+    }
+  }
+}
@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Append-only record of a mutation, kept for compliance.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "audit_logs")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub id: Uuid,
+  /// User who made the change, `None` once that user is deleted
+  pub actor_id: Option<Uuid>,
+  pub action: AuditAction,
+  /// Kind of the changed resource, e.g. `user`
+  pub target_type: String,
+  pub target_id: Option<String>,
+  #[sea_orm(column_type = "TimestampWithTimeZone")]
+  pub timestamp: DateTime<Utc>,
+  /// Details of the change, e.g. the new role
+  #[sea_orm(column_type = "JsonBinary", nullable)]
+  pub metadata: Option<Json>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelatedEntity)]
+pub enum RelatedEntity {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+  #[sea_orm(string_value = "create")]
+  Create,
+  #[sea_orm(string_value = "update")]
+  Update,
+  #[sea_orm(string_value = "delete")]
+  Delete,
+  #[sea_orm(string_value = "login")]
+  Login,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_action_values() {
+    assert_eq!(AuditAction::Create.to_value(), "create");
+    assert_eq!(
+      AuditAction::try_from_value(&"login".to_string()).unwrap(),
+      AuditAction::Login
+    );
+    assert!(AuditAction::try_from_value(&"unknown".to_string()).is_err());
+  }
+}
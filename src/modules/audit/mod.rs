@@ -0,0 +1,18 @@
+pub mod controller;
+pub mod dto;
+pub mod entities;
+pub mod service;
+
+use axum::{extract::State, Router};
+
+use crate::app::AppState;
+use crate::common::routing::allow;
+use crate::modules::auth::guards::{admin_guard, auth_guard};
+
+pub fn router(State(state): State<AppState>) -> Router<AppState> {
+  Router::new()
+    .route("/v1/audit", axum::routing::get(controller::index))
+    .layer(axum::middleware::from_fn(admin_guard))
+    .layer(axum::middleware::from_fn_with_state(state, auth_guard))
+    .merge(Router::new().route("/v1/audit", allow(&["GET"])))
+}
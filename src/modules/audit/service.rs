@@ -0,0 +1,146 @@
+use sea_orm::{
+  ActiveEnum, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, PaginatorTrait,
+  QueryFilter, QueryOrder, Set,
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::common::config::Config;
+use crate::common::errors::ApiError;
+use crate::common::pagination::{PageMeta, PageResponse, PaginatedResponse, PaginationParams};
+use crate::modules::audit::dto::{AuditLogDto, AuditParams};
+use crate::modules::audit::entities::{self as AuditLogEntities, AuditAction};
+
+/// Appends an entry to the audit log.
+///
+/// Failures are logged rather than returned, the change being audited is already made.
+pub async fn log<C: ConnectionTrait>(
+  conn: &C,
+  actor_id: Option<Uuid>,
+  action: AuditAction,
+  target_type: &str,
+  target_id: Option<String>,
+  metadata: Option<serde_json::Value>,
+) {
+  let entry = AuditLogEntities::ActiveModel {
+    id: Set(Uuid::new_v4()),
+    actor_id: Set(actor_id),
+    action: Set(action),
+    target_type: Set(target_type.to_string()),
+    target_id: Set(target_id),
+    timestamp: Set(chrono::Utc::now()),
+    metadata: Set(metadata),
+  };
+  if let Err(e) = AuditLogEntities::Entity::insert(entry)
+    .exec_without_returning(conn)
+    .await
+  {
+    error!(
+      "Failed to record {:?} audit log of {}: {}",
+      action, target_type, e
+    );
+  }
+}
+
+/// Lists the audit logs, newest first.
+pub async fn index(
+  conn: &DatabaseConnection,
+  cfg: &Config,
+  params: &PaginationParams,
+  filter: &AuditParams,
+) -> Result<PaginatedResponse<AuditLogDto>, ApiError> {
+  let per_page = params.per_page_with(cfg);
+  let page = params.page();
+
+  let mut query = AuditLogEntities::Entity::find()
+    .order_by_desc(AuditLogEntities::Column::Timestamp)
+    .order_by_desc(AuditLogEntities::Column::Id);
+  if let Some(actor_id) = filter.actor_id.as_ref() {
+    let actor_id = Uuid::parse_str(actor_id)
+      .map_err(|_| ApiError::InvalidRequest("actor_id must be a valid UUID".to_string()))?;
+    query = query.filter(AuditLogEntities::Column::ActorId.eq(actor_id));
+  }
+  if let Some(action) = filter.action.as_ref() {
+    let action = AuditAction::try_from_value(action).map_err(|_| {
+      ApiError::InvalidRequest("action must be one of: create, update, delete, login".to_string())
+    })?;
+    query = query.filter(AuditLogEntities::Column::Action.eq(action));
+  }
+
+  let paginator = query.paginate(conn, per_page);
+  let total = paginator.num_items().await?;
+  let total_pages = (total + per_page - 1) / per_page;
+  let entries = paginator.fetch_page(page - 1).await?;
+
+  Ok(PaginatedResponse::Page(PageResponse {
+    data: entries.into_iter().map(AuditLogDto::from).collect(),
+    meta: PageMeta {
+      total,
+      page,
+      per_page,
+      total_pages,
+    },
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_log_inserts_entry() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_exec_results([sea_orm::MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+      }])
+      .into_connection();
+
+    log(
+      &db,
+      Some(Uuid::nil()),
+      AuditAction::Update,
+      "user",
+      Some(Uuid::nil().to_string()),
+      Some(serde_json::json!({ "role": "Admin" })),
+    )
+    .await;
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("INSERT INTO \\\"audit_logs\\\""));
+  }
+
+  #[tokio::test]
+  async fn test_log_swallows_failures() {
+    // No exec result is set up, so the insert fails
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres).into_connection();
+
+    log(&db, None, AuditAction::Login, "user", None, None).await;
+  }
+
+  #[tokio::test]
+  async fn test_index_rejects_invalid_filters() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres).into_connection();
+
+    for filter in [
+      AuditParams {
+        actor_id: Some("not-a-uuid".to_string()),
+        ..Default::default()
+      },
+      AuditParams {
+        action: Some("unknown".to_string()),
+        ..Default::default()
+      },
+    ] {
+      let result = index(
+        &db,
+        &crate::common::config::Configuration::for_tests(),
+        &PaginationParams::default(),
+        &filter,
+      )
+      .await;
+      assert!(matches!(result, Err(ApiError::InvalidRequest(_))));
+    }
+    assert!(db.into_transaction_log().is_empty());
+  }
+}
@@ -1,29 +1,97 @@
-use axum::{extract::State, Json};
+use axum::{
+  extract::{Query, State},
+  http::{header::SET_COOKIE, HeaderMap, HeaderValue},
+  Json,
+};
 
 use crate::app::AppState;
+use crate::common::config::{Config, Environment};
 use crate::common::errors::ApiError;
-use crate::common::extractors::ValidatedJson;
-use crate::modules::auth::dto::{AuthResponse, LoginRequest, RegisterRequest};
+use crate::common::extractors::{ClientInfo, ListQuery, ValidatedJson};
+use crate::common::extractors::{CurrentUser, TenantContext};
+use crate::common::pagination::{PaginatedResponse, PaginationParams};
+use crate::modules::auth::dto::{
+  ApiKeyCreated, AuthEventDto, AuthEventParams, AuthResponse, CreateApiKeyRequest,
+  ForgotPasswordRequest, LoginRequest, RegisterRequest, ResetPasswordRequest, VerifyEmailParams,
+  VerifyEmailRequest,
+};
+use crate::modules::auth::guards::auth_guard::AUTH_COOKIE;
 use crate::modules::auth::service;
+use crate::modules::users::dto::UserDto;
 
 #[utoipa::path(
   post,
   tag = "Auth",
   path = "/api/v1/auth/register",
   operation_id = "authRegister",
+  params(
+    ("Idempotency-Key" = Option<String>, Header, description = "Replays the first response for repeated requests with this key (IDEMPOTENCY_TTL_HOURS)")
+  ),
   request_body = RegisterRequest,
   responses(
-    (status = 200, description = "Register successful", body = AuthResponse),
-    (status = 400, description = "Validation error"),
+    (status = 200, description = "Register successful, verification email sent. Not 201: the account is only usable once verified, and users can't read it at `/api/v1/users/{id}` until they log in", body = UserDto),
+    (status = 400, description = "Validation error, or idempotency key reuse with different payload"),
     (status = 409, description = "Email already exists"),
     (status = 500, description = "Internal server error")
   )
 )]
 pub async fn register(
   State(state): State<AppState>,
+  tenant: TenantContext,
   ValidatedJson(req): ValidatedJson<RegisterRequest>,
-) -> Result<Json<AuthResponse>, ApiError> {
-  let result = service::register(&state.db.conn, &state.cfg, req).await?;
+) -> Result<Json<UserDto>, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  // Users registering in a tenant database join its tenant, so that their tokens route back to it
+  let tenant_id = state.db.tenant_of(&tenant);
+  let result =
+    service::register(&conn, &state.cfg, state.notifier.as_ref(), tenant_id, req).await?;
+  Ok(Json(result))
+}
+
+#[utoipa::path(
+  get,
+  tag = "Auth",
+  path = "/api/v1/auth/verify",
+  operation_id = "authVerify",
+  params(VerifyEmailParams),
+  responses(
+    (status = 200, description = "Email verified", body = UserDto),
+    (status = 400, description = "Invalid or expired token"),
+    (status = 500, description = "Internal server error")
+  )
+)]
+#[deprecated(
+  note = "use `POST /api/v1/auth/verify`, link prefetchers can consume tokens sent via GET"
+)]
+pub async fn verify(
+  State(state): State<AppState>,
+  tenant: TenantContext,
+  Query(params): Query<VerifyEmailParams>,
+) -> Result<Json<UserDto>, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::verify_email(&conn, &params.token).await?;
+  Ok(Json(result))
+}
+
+#[utoipa::path(
+  post,
+  tag = "Auth",
+  path = "/api/v1/auth/verify",
+  operation_id = "authVerifyEmail",
+  request_body = VerifyEmailRequest,
+  responses(
+    (status = 200, description = "Email verified", body = UserDto),
+    (status = 400, description = "Invalid or expired token"),
+    (status = 500, description = "Internal server error")
+  )
+)]
+pub async fn verify_email(
+  State(state): State<AppState>,
+  tenant: TenantContext,
+  ValidatedJson(req): ValidatedJson<VerifyEmailRequest>,
+) -> Result<Json<UserDto>, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::verify_email(&conn, &req.token).await?;
   Ok(Json(result))
 }
 
@@ -34,16 +102,272 @@ pub async fn register(
   operation_id = "authLogin",
   request_body = LoginRequest,
   responses(
-    (status = 200, description = "Login successful", body = AuthResponse),
+    (status = 200, description = "Login successful, the token is set in the `access_token` cookie instead of the body when AUTH_COOKIE is enabled", body = AuthResponse),
     (status = 400, description = "Validation error"),
     (status = 401, description = "Invalid credentials"),
+    (status = 403, description = "Email not verified"),
     (status = 500, description = "Internal server error")
   )
 )]
 pub async fn login(
   State(state): State<AppState>,
+  tenant: TenantContext,
+  client: ClientInfo,
   ValidatedJson(req): ValidatedJson<LoginRequest>,
-) -> Result<Json<AuthResponse>, ApiError> {
-  let result = service::login(&state.db.conn, &state.cfg, req).await?;
+) -> Result<(HeaderMap, Json<AuthResponse>), ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::login(&conn, &state.cfg, req, &client).await?;
+  let (headers, result) = token_transport(&state.cfg, result)?;
+  Ok((headers, Json(result)))
+}
+
+/// Moves the token from the body to an httpOnly cookie when `AUTH_COOKIE` is enabled, as a
+/// token readable by scripts would defeat the cookie's protection.
+fn token_transport(
+  cfg: &Config,
+  mut result: AuthResponse,
+) -> Result<(HeaderMap, AuthResponse), ApiError> {
+  let mut headers = HeaderMap::new();
+  if cfg.auth_cookie {
+    if let Some(token) = result.token.take() {
+      let secure = if matches!(cfg.env, Environment::Production) {
+        "; Secure"
+      } else {
+        ""
+      };
+      let cookie = format!(
+        "{}={}; HttpOnly; SameSite=Lax; Path=/; Max-Age={}{}",
+        AUTH_COOKIE,
+        token,
+        cfg.jwt_expiration.as_secs(),
+        secure
+      );
+      let cookie = HeaderValue::from_str(&cookie).map_err(|e| ApiError::InternalError(e.into()))?;
+      headers.insert(SET_COOKIE, cookie);
+    }
+  }
+  Ok((headers, result))
+}
+
+#[utoipa::path(
+  post,
+  tag = "Auth",
+  path = "/api/v1/auth/forgot-password",
+  operation_id = "authForgotPassword",
+  request_body = ForgotPasswordRequest,
+  responses(
+    (status = 200, description = "Password reset email sent if the account exists"),
+    (status = 400, description = "Validation error"),
+    (status = 500, description = "Internal server error")
+  )
+)]
+pub async fn forgot_password(
+  State(state): State<AppState>,
+  tenant: TenantContext,
+  ValidatedJson(req): ValidatedJson<ForgotPasswordRequest>,
+) -> Result<(), ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  service::forgot_password(&conn, &state.cfg, state.notifier.as_ref(), req).await
+}
+
+#[utoipa::path(
+  post,
+  tag = "Auth",
+  path = "/api/v1/auth/reset-password",
+  operation_id = "authResetPassword",
+  request_body = ResetPasswordRequest,
+  responses(
+    (status = 200, description = "Password reset successful"),
+    (status = 400, description = "Validation error or invalid token"),
+    (status = 500, description = "Internal server error")
+  )
+)]
+pub async fn reset_password(
+  State(state): State<AppState>,
+  tenant: TenantContext,
+  client: ClientInfo,
+  ValidatedJson(req): ValidatedJson<ResetPasswordRequest>,
+) -> Result<(), ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  service::reset_password(&conn, &state.cfg, req, &client).await
+}
+
+#[utoipa::path(
+  get,
+  tag = "Auth",
+  path = "/api/v1/auth/events",
+  operation_id = "authEvents",
+  params(PaginationParams, AuthEventParams),
+  responses(
+    (status = 200, description = "List recent login and password reset attempts, newest first"),
+    (status = 400, description = "Invalid outcome"),
+    (status = 403, description = "Admin role required")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn events(
+  State(state): State<AppState>,
+  tenant: TenantContext,
+  ListQuery { params, filter }: ListQuery<AuthEventParams>,
+) -> Result<Json<PaginatedResponse<AuthEventDto>>, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::events(&conn, &state.cfg, &params, &filter).await?;
+  Ok(Json(result))
+}
+
+#[utoipa::path(
+  post,
+  tag = "Auth",
+  path = "/api/v1/auth/api-keys",
+  operation_id = "authCreateApiKey",
+  request_body = CreateApiKeyRequest,
+  responses(
+    (status = 200, description = "API key minted, the key is only returned this once", body = ApiKeyCreated),
+    (status = 400, description = "Validation error"),
+    (status = 401, description = "Unauthorized")
+  ),
+  security(
+    ("bearerAuth" = []),
+    ("api_key" = [])
+  )
+)]
+pub async fn create_api_key(
+  State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  ValidatedJson(req): ValidatedJson<CreateApiKeyRequest>,
+) -> Result<Json<ApiKeyCreated>, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::create_api_key(&conn, current_user.id()?, req).await?;
   Ok(Json(result))
 }
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use sea_orm::{DbBackend, MockDatabase, MockExecResult};
+  use uuid::Uuid;
+
+  use super::*;
+  use crate::common::background::BackgroundTasks;
+  use crate::common::config::Configuration;
+  use crate::common::crypto::{self, PasswordHashAlgo};
+  use crate::common::events::UserEvents;
+  use crate::common::notifier::LogNotifier;
+  use crate::common::storage::LocalStorage;
+  use crate::common::streams::StreamLimit;
+  use crate::database::tenants::{Connect, TenantPools};
+  use crate::database::Db;
+  use crate::modules::auth::entities::login_attempt;
+  use crate::modules::auth::guards::auth_guard;
+  use crate::modules::users::entities;
+  use crate::modules::users::enums::{UserRole, UserStatus};
+
+  fn auth_response() -> AuthResponse {
+    AuthResponse {
+      token: Some("abc.def.ghi".to_string()),
+      user: UserDto::default(),
+    }
+  }
+
+  #[test]
+  fn test_cookie_mode_omits_the_body_token() {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().auth_cookie = true;
+
+    let (headers, result) = token_transport(&cfg, auth_response()).unwrap();
+    assert_eq!(result.token, None);
+    assert!(!serde_json::to_string(&result).unwrap().contains("token"));
+    let cookie = headers[SET_COOKIE].to_str().unwrap();
+    assert!(cookie.starts_with("access_token=abc.def.ghi;"));
+    assert!(cookie.contains("HttpOnly"));
+  }
+
+  #[test]
+  fn test_header_mode_returns_the_body_token() {
+    let (headers, result) = token_transport(&Configuration::for_tests(), auth_response()).unwrap();
+    assert_eq!(result.token.as_deref(), Some("abc.def.ghi"));
+    assert!(headers.get(SET_COOKIE).is_none());
+  }
+
+  #[tokio::test]
+  async fn test_tenant_user_logs_in_and_authenticates_on_its_database() {
+    let user = entities::Model {
+      id: Uuid::new_v4(),
+      email: "user@acme.com".to_string(),
+      name: "User".to_string(),
+      password: crypto::hash_password("password123", PasswordHashAlgo::Bcrypt, 4).unwrap(),
+      status: UserStatus::Active,
+      role: UserRole::User,
+      created_at: None,
+      updated_at: None,
+      created_by: None,
+      updated_by: None,
+      deleted_at: None,
+      last_login_at: None,
+      avatar_url: None,
+      tenant_id: Some("acme".to_string()),
+      token_version: 0,
+    };
+    let exec = || MockExecResult {
+      last_insert_id: 0,
+      rows_affected: 1,
+    };
+    // The login reads the user and its attempts, then the session check reads the user again
+    let tenant_db = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_query_results([Vec::<login_attempt::Model>::new()])
+      .append_query_results([vec![user.clone()]])
+      .append_exec_results([exec(), exec(), exec()])
+      .into_connection();
+    let connect: Connect = Arc::new(move |_| {
+      let conn = tenant_db.clone();
+      Box::pin(async move { Ok(conn) })
+    });
+    let cfg = Configuration::for_tests();
+    let state = AppState {
+      db: Db {
+        // The shared database has no rows, any query on it fails
+        conn: MockDatabase::new(DbBackend::Postgres).into_connection(),
+        tenants: Some(Arc::new(TenantPools::with_connect(
+          "postgres://localhost/app_{tenant}".to_string(),
+          1,
+          connect,
+        ))),
+      },
+      background: BackgroundTasks::new(cfg.background_concurrency),
+      streams: StreamLimit::new(cfg.max_stream_connections),
+      events: UserEvents::default(),
+      storage: Arc::new(LocalStorage::new(&cfg.storage_dir)),
+      cfg,
+      notifier: Arc::new(LogNotifier),
+      readiness: Default::default(),
+    };
+    let req = LoginRequest {
+      email: user.email.clone(),
+      password: "password123".to_string(),
+    };
+
+    let (_, Json(response)) = login(
+      State(state.clone()),
+      TenantContext(Some("acme".to_string())),
+      ClientInfo {
+        ip: None,
+        user_agent: None,
+      },
+      ValidatedJson(req),
+    )
+    .await
+    .unwrap();
+    let token = response.token.unwrap();
+
+    let authenticated = auth_guard::authenticate_token(&state, &token)
+      .await
+      .unwrap();
+    assert_eq!(authenticated.id, user.id.to_string());
+    assert_eq!(authenticated.tenant_id.as_deref(), Some("acme"));
+  }
+}
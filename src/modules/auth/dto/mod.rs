@@ -1,7 +1,10 @@
+use chrono::SecondsFormat;
+use sea_orm::ActiveEnum;
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
+use crate::modules::auth::entities::{api_key, auth_event};
 use crate::modules::users::dto::UserDto;
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
@@ -24,10 +27,79 @@ pub struct RegisterRequest {
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct AuthResponse {
-  pub token: String,
+  /// Omitted when `AUTH_COOKIE` is enabled, the token is then set in the `access_token` cookie
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub token: Option<String>,
   pub user: UserDto,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ForgotPasswordRequest {
+  #[validate(email(message = "invalid email format"))]
+  pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ResetPasswordRequest {
+  #[validate(length(min = 1, message = "must not be empty"))]
+  pub token: String,
+  #[validate(length(min = 8, max = 64, message = "must be between 8 and 64 characters"))]
+  pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct VerifyEmailRequest {
+  /// Verification token sent to the user's email
+  #[validate(length(min = 1, message = "must not be empty"))]
+  pub token: String,
+}
+
+/// Query parameters for email verification.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct VerifyEmailParams {
+  /// Verification token sent to the user's email
+  pub token: String,
+}
+
+/// Query parameters for listing auth events.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct AuthEventParams {
+  /// Only return events with this outcome: `success` or `failure`
+  pub outcome: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuthEventDto {
+  pub id: String,
+  pub user_id: Option<String>,
+  pub email: Option<String>,
+  /// `login`, `password_reset` or `password_change`
+  pub kind: String,
+  /// `success` or `failure`
+  pub outcome: String,
+  pub ip: Option<String>,
+  pub user_agent: Option<String>,
+  #[schema(format = "date-time")]
+  pub created_at: String,
+}
+
+impl From<auth_event::Model> for AuthEventDto {
+  fn from(model: auth_event::Model) -> Self {
+    Self {
+      id: model.id.to_string(),
+      user_id: model.user_id.map(|id| id.to_string()),
+      email: model.email,
+      kind: model.kind.into_value(),
+      outcome: model.outcome.into_value(),
+      ip: model.ip,
+      user_agent: model.user_agent,
+      created_at: model
+        .created_at
+        .to_rfc3339_opts(SecondsFormat::Millis, true),
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -164,6 +236,56 @@ mod tests {
     assert!(err.field_errors().contains_key("name"));
   }
 
+  // --- ForgotPasswordRequest validation tests ---
+
+  #[test]
+  fn test_forgot_password_valid() {
+    let req = ForgotPasswordRequest {
+      email: "user@example.com".to_string(),
+    };
+    assert!(req.validate().is_ok());
+  }
+
+  #[test]
+  fn test_forgot_password_invalid_email() {
+    let req = ForgotPasswordRequest {
+      email: "not-an-email".to_string(),
+    };
+    let err = req.validate().unwrap_err();
+    assert!(err.field_errors().contains_key("email"));
+  }
+
+  // --- ResetPasswordRequest validation tests ---
+
+  #[test]
+  fn test_reset_password_valid() {
+    let req = ResetPasswordRequest {
+      token: "abc123".to_string(),
+      new_password: "password123".to_string(),
+    };
+    assert!(req.validate().is_ok());
+  }
+
+  #[test]
+  fn test_reset_password_empty_token() {
+    let req = ResetPasswordRequest {
+      token: "".to_string(),
+      new_password: "password123".to_string(),
+    };
+    let err = req.validate().unwrap_err();
+    assert!(err.field_errors().contains_key("token"));
+  }
+
+  #[test]
+  fn test_reset_password_too_short() {
+    let req = ResetPasswordRequest {
+      token: "abc123".to_string(),
+      new_password: "short".to_string(),
+    };
+    let err = req.validate().unwrap_err();
+    assert!(err.field_errors().contains_key("new_password"));
+  }
+
   // --- Serialization tests ---
 
   #[test]
@@ -208,4 +330,51 @@ mod tests {
     assert_eq!(register_req.password, "pass123");
     assert_eq!(register_req.name, "Jane Smith");
   }
+
+  #[test]
+  fn test_verify_email_request_empty_token() {
+    let req = VerifyEmailRequest {
+      token: "".to_string(),
+    };
+    let err = req.validate().unwrap_err();
+    assert!(err.field_errors().contains_key("token"));
+  }
+
+  #[test]
+  fn test_verify_email_params_deserialization() {
+    let json = r#"{"token":"abc123"}"#;
+    let params: VerifyEmailParams = serde_json::from_str(json).unwrap();
+    assert_eq!(params.token, "abc123");
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CreateApiKeyRequest {
+  /// What the key is used for, e.g. "CI deployments"
+  #[validate(length(min = 1, max = 100, message = "must be between 1 and 100 characters"))]
+  pub label: String,
+}
+
+/// A freshly minted API key, the only time the key is returned in plain text.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyCreated {
+  pub id: String,
+  pub label: String,
+  /// Send it in the `api_key` header, it can't be retrieved again
+  pub key: String,
+  #[schema(format = "date-time")]
+  pub created_at: String,
+}
+
+impl ApiKeyCreated {
+  pub fn new(model: api_key::Model, key: String) -> Self {
+    Self {
+      id: model.id.to_string(),
+      label: model.label,
+      key,
+      created_at: model
+        .created_at
+        .to_rfc3339_opts(SecondsFormat::Millis, true),
+    }
+  }
 }
@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// API key of a user, sent in the `api_key` header as an alternative to a JWT.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "api_keys")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub id: Uuid,
+  pub user_id: Uuid,
+  pub label: String,
+  /// SHA-256 of the key, the key itself is only shown once when minted
+  #[sea_orm(unique)]
+  pub key_hash: String,
+  #[sea_orm(column_type = "TimestampWithTimeZone", nullable)]
+  pub last_used_at: Option<DateTime<Utc>>,
+  pub revoked: bool,
+  #[sea_orm(column_type = "TimestampWithTimeZone")]
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Authentication event recorded for security monitoring.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "auth_events")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub id: Uuid,
+  /// User the event relates to, `None` when the email is unknown
+  pub user_id: Option<Uuid>,
+  /// Email submitted with the request
+  pub email: Option<String>,
+  pub kind: AuthEventKind,
+  pub outcome: AuthEventOutcome,
+  pub ip: Option<String>,
+  pub user_agent: Option<String>,
+  #[sea_orm(column_type = "TimestampWithTimeZone")]
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelatedEntity)]
+pub enum RelatedEntity {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum AuthEventKind {
+  #[sea_orm(string_value = "login")]
+  Login,
+  #[sea_orm(string_value = "password_reset")]
+  PasswordReset,
+  /// The password was changed, e.g. by a successful password reset
+  #[sea_orm(string_value = "password_change")]
+  PasswordChange,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum AuthEventOutcome {
+  #[sea_orm(string_value = "success")]
+  Success,
+  #[sea_orm(string_value = "failure")]
+  Failure,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_outcome_values() {
+    assert_eq!(AuthEventOutcome::Success.to_value(), "success");
+    assert_eq!(
+      AuthEventOutcome::try_from_value(&"failure".to_string()).unwrap(),
+      AuthEventOutcome::Failure
+    );
+    assert!(AuthEventOutcome::try_from_value(&"unknown".to_string()).is_err());
+  }
+}
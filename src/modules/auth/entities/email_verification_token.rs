@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "email_verification_tokens")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub id: Uuid,
+  pub user_id: Uuid,
+  #[sea_orm(unique)]
+  pub token: String,
+  #[sea_orm(column_type = "TimestampWithTimeZone")]
+  pub expires_at: DateTime<Utc>,
+  #[sea_orm(column_type = "TimestampWithTimeZone", nullable)]
+  pub used_at: Option<DateTime<Utc>>,
+  #[sea_orm(column_type = "TimestampWithTimeZone", nullable)]
+  pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelatedEntity)]
+pub enum RelatedEntity {}
+
+impl Model {
+  /// Returns true if the token has not been used yet and has not expired at `now`.
+  pub fn is_usable(&self, now: DateTime<Utc>) -> bool {
+    self.used_at.is_none() && self.expires_at > now
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn token(expires_at: DateTime<Utc>, used_at: Option<DateTime<Utc>>) -> Model {
+    Model {
+      id: Uuid::nil(),
+      user_id: Uuid::nil(),
+      token: "token".to_string(),
+      expires_at,
+      used_at,
+      created_at: None,
+    }
+  }
+
+  #[test]
+  fn test_token_usable_before_expiry() {
+    let now = Utc::now();
+    let model = token(now + chrono::Duration::hours(1), None);
+    assert!(model.is_usable(now));
+  }
+
+  #[test]
+  fn test_token_not_usable_after_expiry() {
+    let now = Utc::now();
+    let model = token(now - chrono::Duration::seconds(1), None);
+    assert!(!model.is_usable(now));
+  }
+
+  #[test]
+  fn test_token_not_usable_once_used() {
+    let now = Utc::now();
+    let model = token(now + chrono::Duration::hours(1), Some(now));
+    assert!(!model.is_usable(now));
+  }
+}
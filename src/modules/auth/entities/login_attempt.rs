@@ -0,0 +1,75 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Consecutive failed logins of a user, used to lock the account after `MAX_LOGIN_ATTEMPTS`.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "login_attempts")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub user_id: Uuid,
+  pub failed_attempts: i32,
+  #[sea_orm(column_type = "TimestampWithTimeZone", nullable)]
+  pub locked_until: Option<DateTime<Utc>>,
+  #[sea_orm(column_type = "TimestampWithTimeZone")]
+  pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+  /// Returns true if the account is locked at `now`.
+  pub fn is_locked(&self, now: DateTime<Utc>) -> bool {
+    self
+      .locked_until
+      .is_some_and(|locked_until| locked_until > now)
+  }
+
+  /// Returns the failures counting towards the next lock, they start over once a lock expired.
+  pub fn current_failures(&self, now: DateTime<Utc>) -> i32 {
+    match self.locked_until {
+      Some(locked_until) if locked_until <= now => 0,
+      _ => self.failed_attempts,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn attempts(failed_attempts: i32, locked_until: Option<DateTime<Utc>>) -> Model {
+    Model {
+      user_id: Uuid::nil(),
+      failed_attempts,
+      locked_until,
+      updated_at: Utc::now(),
+    }
+  }
+
+  #[test]
+  fn test_locked_until_cooldown_ends() {
+    let now = Utc::now();
+    let model = attempts(5, Some(now + chrono::Duration::minutes(15)));
+    assert!(model.is_locked(now));
+    assert_eq!(model.current_failures(now), 5);
+  }
+
+  #[test]
+  fn test_failures_start_over_after_cooldown() {
+    let now = Utc::now();
+    let model = attempts(5, Some(now - chrono::Duration::seconds(1)));
+    assert!(!model.is_locked(now));
+    assert_eq!(model.current_failures(now), 0);
+  }
+
+  #[test]
+  fn test_not_locked_below_max_attempts() {
+    let model = attempts(2, None);
+    assert!(!model.is_locked(Utc::now()));
+    assert_eq!(model.current_failures(Utc::now()), 2);
+  }
+}
@@ -0,0 +1,5 @@
+pub mod api_key;
+pub mod auth_event;
+pub mod email_verification_token;
+pub mod login_attempt;
+pub mod password_reset_token;
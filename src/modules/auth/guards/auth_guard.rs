@@ -1,68 +1,499 @@
-use axum::extract::State;
-use axum::{extract::Request, middleware::Next, response::Response};
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use axum::extract::{FromRequestParts, State};
+use axum::{
+  extract::Request,
+  http::{header::COOKIE, HeaderMap},
+  middleware::Next,
+  response::Response,
+};
+use jsonwebtoken::{decode, errors::ErrorKind, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 
 use crate::app::AppState;
+use crate::common::config::Config;
 use crate::common::errors::ApiError;
+use crate::common::extractors::TenantContext;
+use crate::modules::auth::service;
 use crate::modules::users::dto::UserDto;
 
+/// Header carrying an API key, an alternative to the `Bearer` JWT (see `api_key` in the
+/// OpenAPI security schemes).
+pub const API_KEY_HEADER: &str = "api_key";
+
+/// Cookie carrying the JWT when `AUTH_COOKIE` is enabled.
+pub const AUTH_COOKIE: &str = "access_token";
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Claims {
   pub sub: String,
   pub exp: usize,
   pub iat: usize,
+  /// Issuer, from `JWT_ISSUER`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub iss: Option<String>,
+  /// Audience, from `JWT_AUDIENCE`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub aud: Option<String>,
+  /// Tenant the token is bound to, which scopes every request made with it
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub tenant_id: Option<String>,
+  /// Version of the sessions of the user at issuance, the token is revoked once it changes
+  #[serde(default)]
+  pub token_version: i32,
   pub user: UserDto,
 }
 
 pub async fn auth_guard(
-  State(_): State<AppState>,
+  State(state): State<AppState>,
   req: Request,
   next: Next,
 ) -> Result<Response, ApiError> {
-  // Get the authorization header
-  let auth_header = req
-    .headers()
-    .get("authorization")
-    .ok_or_else(|| ApiError::Unauthorized("Missing authorization header".to_string()))?
-    .to_str()
-    .map_err(|_| ApiError::Unauthorized("Invalid authorization header".to_string()))?;
-
-  // Check if it's a Bearer token
-  let token = auth_header
-    .strip_prefix("Bearer ")
-    .ok_or_else(|| ApiError::Unauthorized("Invalid authorization format".to_string()))?;
+  // An API key authenticates its owner, like the JWT path below. Keys live in the database of
+  // their owner, which `X-Tenant-Id` selects
+  if let Some(api_key) = req.headers().get(API_KEY_HEADER) {
+    let api_key = api_key
+      .to_str()
+      .map_err(|_| ApiError::Unauthorized("Invalid API key".to_string()))?
+      .trim()
+      .to_string();
+    let (mut parts, body) = req.into_parts();
+    let tenant = TenantContext::from_request_parts(&mut parts, &state).await?;
+    let conn = state.db.conn_for(&tenant).await?;
+    let user = service::authenticate_api_key(&conn, &api_key).await?;
+    return Ok(run_as(user, Request::from_parts(parts, body), next).await);
+  }
 
-  // Get JWT secret from environment
-  let secret = std::env::var("JWT_SECRET")
-    .unwrap_or_else(|_| "a-string-secret-at-least-256-bits-long".to_string());
+  // Get the Bearer token of the authorization header, or else the cookie set on login
+  let token = match req.headers().get("authorization") {
+    Some(auth_header) => {
+      let auth_header = auth_header
+        .to_str()
+        .map_err(|_| ApiError::Unauthorized("Invalid authorization header".to_string()))?;
+      bearer_token(auth_header)?
+    }
+    None if state.cfg.auth_cookie => cookie_token(req.headers())
+      .ok_or_else(|| ApiError::Unauthorized("Missing authorization header".to_string()))?,
+    None => {
+      return Err(ApiError::Unauthorized(
+        "Missing authorization header".to_string(),
+      ))
+    }
+  };
 
-  // Decode and validate the token
-  let token_data = decode::<Claims>(
-    token,
-    &DecodingKey::from_secret(secret.as_bytes()),
-    &Validation::default(),
-  )
-  .map_err(|_| ApiError::Unauthorized("Invalid token".to_string()))?;
+  let user = authenticate_token(&state, token).await?;
+  Ok(run_as(user, req, next).await)
+}
+
+/// Returns the user of a JWT, checked like the guard does for requests. Also used by the
+/// GraphQL WebSocket, which authenticates on connection init rather than per request.
+pub async fn authenticate_token(state: &AppState, token: &str) -> Result<UserDto, ApiError> {
+  // Decode and validate the token with the key of JWT_ALGORITHM
+  let key = service::decoding_key(&state.cfg)?;
+  let claims = decode_claims(token, &key, &state.cfg)?;
 
   // Check if token is expired
   let now = chrono::Utc::now().timestamp() as usize;
-  if token_data.claims.exp < now {
+  if claims.exp < now {
     return Err(ApiError::Unauthorized("Token has expired".to_string()));
   }
 
-  // Add user role to request extensions for GraphQL context
-  let mut req = req;
-  req.extensions_mut().insert(UserDto {
-    ..token_data.claims.user
-  });
+  // The token alone can't tell that its sessions have been revoked, or that the user has been
+  // deactivated, since it was issued. The user is read from the database of its tenant
+  let conn = state
+    .db
+    .conn_for(&TenantContext(claims.tenant_id.clone()))
+    .await?;
+  service::ensure_session(
+    &conn,
+    &claims.user.id,
+    claims.token_version,
+    state.cfg.auth_recheck_status,
+  )
+  .await?;
+
+  // The signed tenant claim is the one that scopes the requests
+  let mut user = claims.user;
+  user.tenant_id = claims.tenant_id;
+  Ok(user)
+}
+
+/// Runs the request as `user`, which handlers and the GraphQL context read from the request
+/// extensions, and `access_log` from the response ones.
+///
+/// The request is also bound to the tenant of the user, which `TenantContext` then returns
+/// whatever `X-Tenant-Id` says.
+async fn run_as(user: UserDto, mut req: Request, next: Next) -> Response {
+  req
+    .extensions_mut()
+    .insert(TenantContext(user.tenant_id.clone()));
+  req.extensions_mut().insert(user.clone());
+  let mut response = next.run(req).await;
+  response.extensions_mut().insert(user);
+  response
+}
+
+/// Claims as signed, with the user left unparsed so that tokens issued before a change of
+/// `UserDto` can be told apart from forged or expired ones.
+#[derive(Deserialize)]
+struct RawClaims {
+  sub: String,
+  exp: usize,
+  iat: usize,
+  #[serde(default)]
+  iss: Option<String>,
+  #[serde(default)]
+  aud: Option<String>,
+  #[serde(default)]
+  tenant_id: Option<String>,
+  #[serde(default)]
+  token_version: i32,
+  user: serde_json::Value,
+}
+
+/// Builds the token validation, accepting only `JWT_ALGORITHM` and checking the issuer and the
+/// audience when `JWT_ISSUER` and `JWT_AUDIENCE` are set so that tokens of other systems are
+/// refused.
+fn validation(cfg: &Config) -> Validation {
+  let mut validation = Validation::new(cfg.jwt_algorithm.into());
+  if let Some(issuer) = &cfg.jwt_issuer {
+    validation.set_issuer(&[issuer]);
+    validation.required_spec_claims.insert("iss".to_string());
+  }
+  if let Some(audience) = &cfg.jwt_audience {
+    validation.set_audience(&[audience]);
+    validation.required_spec_claims.insert("aud".to_string());
+  }
+  validation
+}
+
+/// Verifies the token and decodes its claims.
+///
+/// A `user` claim that doesn't match `UserDto` is rebuilt from its core fields when
+/// `JWT_LEGACY_CLAIMS` is enabled, and otherwise refused with a prompt to log in again.
+fn decode_claims(token: &str, key: &DecodingKey, cfg: &Config) -> Result<Claims, ApiError> {
+  let raw = decode::<RawClaims>(token, key, &validation(cfg))
+    .map_err(|e| match e.kind() {
+      ErrorKind::ExpiredSignature => ApiError::Unauthorized("Token has expired".to_string()),
+      _ => ApiError::Unauthorized("Invalid token".to_string()),
+    })?
+    .claims;
+
+  let outdated = || ApiError::Unauthorized("Token is outdated, please log in again".to_string());
+  let user = match serde_json::from_value::<UserDto>(raw.user.clone()) {
+    Ok(user) => user,
+    Err(_) if cfg.jwt_legacy_claims => legacy_user(&raw).ok_or_else(outdated)?,
+    Err(_) => return Err(outdated()),
+  };
+  Ok(Claims {
+    sub: raw.sub,
+    exp: raw.exp,
+    iat: raw.iat,
+    iss: raw.iss,
+    aud: raw.aud,
+    tenant_id: raw.tenant_id,
+    token_version: raw.token_version,
+    user,
+  })
+}
+
+/// Rebuilds the user of an older token from the fields authorization relies on: the id, or
+/// else the subject, the email and the role.
+fn legacy_user(raw: &RawClaims) -> Option<UserDto> {
+  let field = |name: &str| raw.user.get(name)?.as_str().map(str::to_string);
+  Some(UserDto {
+    id: field("id").unwrap_or_else(|| raw.sub.clone()),
+    email: field("email")?,
+    name: field("name").unwrap_or_default(),
+    status: field("status").unwrap_or_default(),
+    role: field("role")?,
+    ..Default::default()
+  })
+}
+
+/// Extracts the token from a `Bearer <token>` header value.
+///
+/// Surrounding whitespace, e.g. a double space or a trailing newline, is ignored. Tokens with
+/// other characters than the base64url alphabet and the `.` separators are rejected.
+pub(crate) fn bearer_token(auth_header: &str) -> Result<&str, ApiError> {
+  let token = auth_header
+    .strip_prefix("Bearer ")
+    .ok_or_else(|| ApiError::Unauthorized("Invalid authorization format".to_string()))?
+    .trim();
+  if token.is_empty() {
+    return Err(ApiError::Unauthorized("Missing bearer token".to_string()));
+  }
+  if !token
+    .bytes()
+    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+  {
+    return Err(ApiError::Unauthorized(
+      "Bearer token contains invalid characters".to_string(),
+    ));
+  }
+  Ok(token)
+}
 
-  Ok(next.run(req).await)
+/// Extracts the token from the `access_token` cookie.
+fn cookie_token(headers: &HeaderMap) -> Option<&str> {
+  headers
+    .get_all(COOKIE)
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(';'))
+    .filter_map(|cookie| cookie.trim().strip_prefix(AUTH_COOKIE)?.strip_prefix('='))
+    .find(|token| !token.is_empty())
 }
 
 #[cfg(test)]
 mod tests {
+  use std::sync::Arc;
+
   use super::*;
+  use crate::common::config::{Configuration, JwtRsaKeys};
+  use crate::common::crypto::JwtAlgorithm;
+
+  fn unauthorized_message(result: Result<&str, ApiError>) -> String {
+    match result {
+      Err(ApiError::Unauthorized(message)) => message,
+      result => panic!(
+        "expected unauthorized, got {:?}",
+        result.map(str::to_string)
+      ),
+    }
+  }
+
+  #[test]
+  fn test_bearer_token() {
+    assert_eq!(
+      bearer_token("Bearer abc.def-_.ghi").unwrap(),
+      "abc.def-_.ghi"
+    );
+  }
+
+  #[test]
+  fn test_bearer_token_extra_whitespace() {
+    assert_eq!(bearer_token("Bearer  abc.def.ghi").unwrap(), "abc.def.ghi");
+    assert_eq!(bearer_token("Bearer abc.def.ghi  ").unwrap(), "abc.def.ghi");
+  }
+
+  #[test]
+  fn test_bearer_token_trailing_newline() {
+    assert_eq!(bearer_token("Bearer abc.def.ghi\n").unwrap(), "abc.def.ghi");
+  }
+
+  #[test]
+  fn test_bearer_token_invalid_characters() {
+    assert_eq!(
+      unauthorized_message(bearer_token("Bearer abc.d\x07ef.ghi")),
+      "Bearer token contains invalid characters"
+    );
+    assert_eq!(
+      unauthorized_message(bearer_token("Bearer abc def")),
+      "Bearer token contains invalid characters"
+    );
+  }
+
+  #[test]
+  fn test_bearer_token_missing() {
+    assert_eq!(
+      unauthorized_message(bearer_token("Bearer   ")),
+      "Missing bearer token"
+    );
+    assert_eq!(
+      unauthorized_message(bearer_token("Basic abc")),
+      "Invalid authorization format"
+    );
+  }
+
+  #[test]
+  fn test_cookie_token() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(cookie_token(&headers), None);
+
+    headers.insert(
+      COOKIE,
+      "theme=dark; access_token=abc.def.ghi; lang=en"
+        .parse()
+        .unwrap(),
+    );
+    assert_eq!(cookie_token(&headers), Some("abc.def.ghi"));
+
+    headers.insert(COOKIE, "access_token_old=x; access_token=".parse().unwrap());
+    assert_eq!(cookie_token(&headers), None);
+  }
+
+  const SECRET: &str = "test-secret";
+
+  fn key() -> DecodingKey {
+    DecodingKey::from_secret(SECRET.as_bytes())
+  }
+
+  fn token(user: serde_json::Value) -> String {
+    let exp = chrono::Utc::now().timestamp() + 3600;
+    signed(serde_json::json!({"sub": "user-123", "exp": exp, "iat": 0, "user": user}))
+  }
+
+  fn config(legacy_claims: bool) -> Config {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().jwt_legacy_claims = legacy_claims;
+    cfg
+  }
+
+  fn decode_error(token: &str, cfg: &Config) -> String {
+    match decode_claims(token, &key(), cfg) {
+      Err(ApiError::Unauthorized(message)) => message,
+      result => panic!("expected unauthorized, got {:?}", result.map(|c| c.sub)),
+    }
+  }
+
+  #[test]
+  fn test_decode_current_claims() {
+    let user = serde_json::to_value(UserDto {
+      id: "user-123".to_string(),
+      role: "Admin".to_string(),
+      ..Default::default()
+    })
+    .unwrap();
+    let claims = decode_claims(&token(user), &key(), &config(false)).unwrap();
+    assert_eq!(claims.sub, "user-123");
+    assert_eq!(claims.user.role, "Admin");
+  }
+
+  #[test]
+  fn test_drifted_claims_keep_core_fields() {
+    // An older shape: a numeric status, no id and an unknown field
+    let user = serde_json::json!({
+      "email": "user@example.com",
+      "role": "Admin",
+      "status": 1,
+      "avatar": "a.png"
+    });
+    let claims = decode_claims(&token(user), &key(), &config(true)).unwrap();
+    assert_eq!(claims.user.id, "user-123");
+    assert_eq!(claims.user.email, "user@example.com");
+    assert_eq!(claims.user.role, "Admin");
+    assert_eq!(claims.user.status, "");
+  }
+
+  #[test]
+  fn test_drifted_claims_prompt_to_log_in_again() {
+    let drifted = token(serde_json::json!({"email": "user@example.com", "role": "User"}));
+    assert_eq!(
+      decode_error(&drifted, &config(false)),
+      "Token is outdated, please log in again"
+    );
+
+    // Without a role, the token can't be migrated
+    let roleless = token(serde_json::json!({"email": "user@example.com"}));
+    assert_eq!(
+      decode_error(&roleless, &config(true)),
+      "Token is outdated, please log in again"
+    );
+  }
+
+  #[test]
+  fn test_bad_signature_is_invalid() {
+    let mut forged = token(serde_json::json!({}));
+    forged.push('x');
+    assert_eq!(decode_error(&forged, &config(true)), "Invalid token");
+  }
+
+  fn signed(claims: serde_json::Value) -> String {
+    jsonwebtoken::encode(
+      &jsonwebtoken::Header::default(),
+      &claims,
+      &jsonwebtoken::EncodingKey::from_secret(SECRET.as_bytes()),
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn test_issuer_and_audience_are_checked_when_set() {
+    let mut cfg = Configuration::for_tests();
+    let c = Arc::get_mut(&mut cfg).unwrap();
+    c.jwt_issuer = Some("boilerplate".to_string());
+    c.jwt_audience = Some("boilerplate-api".to_string());
+
+    let exp = chrono::Utc::now().timestamp() + 3600;
+    let user = serde_json::to_value(UserDto::default()).unwrap();
+    let claims = |iss: &str, aud: &str| serde_json::json!({"sub": "user-123", "exp": exp, "iat": 0, "iss": iss, "aud": aud, "user": user});
+
+    let valid = signed(claims("boilerplate", "boilerplate-api"));
+    let decoded = decode_claims(&valid, &key(), &cfg).unwrap();
+    assert_eq!(decoded.iss.as_deref(), Some("boilerplate"));
+    assert_eq!(decoded.aud.as_deref(), Some("boilerplate-api"));
+
+    let other_issuer = signed(claims("other-system", "boilerplate-api"));
+    assert_eq!(decode_error(&other_issuer, &cfg), "Invalid token");
+    let other_audience = signed(claims("boilerplate", "other-api"));
+    assert_eq!(decode_error(&other_audience, &cfg), "Invalid token");
+    // Tokens issued before the issuer was configured are refused too
+    assert_eq!(decode_error(&token(user.clone()), &cfg), "Invalid token");
+  }
+
+  #[test]
+  fn test_issuer_is_not_checked_when_unset() {
+    let exp = chrono::Utc::now().timestamp() + 3600;
+    let user = serde_json::to_value(UserDto::default()).unwrap();
+    let foreign = signed(
+      serde_json::json!({"sub": "user-123", "exp": exp, "iat": 0, "iss": "other-system", "user": user}),
+    );
+    assert!(decode_claims(&foreign, &key(), &config(false)).is_ok());
+  }
+
+  #[test]
+  fn test_rs256_tokens_are_verified_with_the_public_key() {
+    let mut cfg = Configuration::for_tests();
+    let c = Arc::get_mut(&mut cfg).unwrap();
+    c.jwt_algorithm = JwtAlgorithm::RS256;
+    c.jwt_rsa_keys = Some(JwtRsaKeys {
+      private_pem: include_str!("testdata/jwt_private.pem").to_string(),
+      public_pem: include_str!("testdata/jwt_public.pem").to_string(),
+    });
+
+    let exp = chrono::Utc::now().timestamp() + 3600;
+    let user = serde_json::to_value(UserDto::default()).unwrap();
+    let claims = serde_json::json!({"sub": "user-123", "exp": exp, "iat": 0, "user": user});
+    let rs256 = jsonwebtoken::encode(
+      &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256),
+      &claims,
+      &jsonwebtoken::EncodingKey::from_rsa_pem(include_bytes!("testdata/jwt_private.pem")).unwrap(),
+    )
+    .unwrap();
+
+    let key = service::decoding_key(&cfg).unwrap();
+    assert_eq!(decode_claims(&rs256, &key, &cfg).unwrap().sub, "user-123");
+    // HMAC tokens are refused once RS256 is selected
+    assert!(decode_claims(&token(user), &key, &cfg).is_err());
+  }
+
+  #[test]
+  fn test_decode_tenant_claim() {
+    let exp = chrono::Utc::now().timestamp() + 3600;
+    let user = serde_json::to_value(UserDto::default()).unwrap();
+    let tenant = signed(serde_json::json!({
+      "sub": "user-123", "exp": exp, "iat": 0, "tenant_id": "acme", "user": user
+    }));
+
+    let claims = decode_claims(&tenant, &key(), &config(false)).unwrap();
+    assert_eq!(claims.tenant_id.as_deref(), Some("acme"));
+    let claims = decode_claims(&token(user), &key(), &config(false)).unwrap();
+    assert_eq!(claims.tenant_id, None);
+  }
+
+  #[test]
+  fn test_decode_token_version_claim() {
+    let exp = chrono::Utc::now().timestamp() + 3600;
+    let user = serde_json::to_value(UserDto::default()).unwrap();
+    let versioned = signed(serde_json::json!({
+      "sub": "user-123", "exp": exp, "iat": 0, "token_version": 3, "user": user
+    }));
+
+    let claims = decode_claims(&versioned, &key(), &config(false)).unwrap();
+    assert_eq!(claims.token_version, 3);
+    // Tokens issued before the claim existed match the initial version
+    let claims = decode_claims(&token(user), &key(), &config(false)).unwrap();
+    assert_eq!(claims.token_version, 0);
+  }
 
   #[test]
   fn test_claims_default() {
@@ -79,12 +510,14 @@ mod tests {
       exp: 1234567890,
       iat: 1234567800,
       user: UserDto::default(),
+      ..Default::default()
     };
 
     let json = serde_json::to_string(&claims).unwrap();
     assert!(json.contains("\"sub\":\"user-123\""));
     assert!(json.contains("\"exp\":1234567890"));
     assert!(json.contains("\"iat\":1234567800"));
+    assert!(!json.contains("\"iss\""));
   }
 
   #[test]
@@ -1,7 +1,9 @@
 use async_graphql::dynamic::ResolverContext;
-use seaography::GuardsConfig;
+use sea_orm::Condition;
+use seaography::{GuardsConfig, LifecycleHooksInterface, OperationType};
 
-use crate::modules::users::enums::UserRole;
+use crate::common::extractors::TenantContext;
+use crate::modules::users::{entities::Entity as UserEntity, enums::UserRole};
 
 pub fn admin_guard(ctx: &ResolverContext) -> seaography::GuardAction {
   // Get the user role from the context
@@ -14,24 +16,103 @@ pub fn admin_guard(ctx: &ResolverContext) -> seaography::GuardAction {
   seaography::GuardAction::Block(Some("Admin role required".to_string()))
 }
 
+/// Blocks the users of a tenant, as the entity queries and mutations can't be scoped to it.
+pub fn tenant_guard(ctx: &ResolverContext) -> seaography::GuardAction {
+  match ctx.data_opt::<TenantContext>() {
+    Some(TenantContext(Some(_))) => {
+      seaography::GuardAction::Block(Some("Not available to the users of a tenant".to_string()))
+    }
+    _ => seaography::GuardAction::Allow,
+  }
+}
+
+/// Maximum number of rows a GraphQL entity query may return, stored in the schema data.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryMaxRows(pub u64);
+
+/// Returns a guard blocking the `query_field` entity query when it isn't paginated, or asks for
+/// more than `QueryMaxRows` rows. Other fields of the entity, like its mutations, are allowed.
+pub fn bounded_query_guard(
+  query_field: &'static str,
+) -> impl Fn(&ResolverContext) -> seaography::GuardAction + Send + Sync {
+  move |ctx| {
+    let Some(QueryMaxRows(max_rows)) = ctx.data_opt::<QueryMaxRows>().copied() else {
+      return seaography::GuardAction::Allow;
+    };
+    if ctx.field().name() != query_field {
+      return seaography::GuardAction::Allow;
+    }
+    match pagination_limit(ctx) {
+      Some(limit) if limit <= max_rows => seaography::GuardAction::Allow,
+      Some(_) => seaography::GuardAction::Block(Some(format!(
+        "Pagination limit must not exceed {} rows",
+        max_rows
+      ))),
+      None => seaography::GuardAction::Block(Some(format!(
+        "Pagination with a limit of at most {} rows is required",
+        max_rows
+      ))),
+    }
+  }
+}
+
+/// Returns the `limit` of whichever pagination mode (page, offset or cursor) is used.
+fn pagination_limit(ctx: &ResolverContext) -> Option<u64> {
+  let pagination = ctx.args.get("pagination")?.object().ok()?;
+  pagination
+    .iter()
+    .find_map(|(_, mode)| mode.object().ok()?.get("limit")?.u64().ok())
+}
+
+/// Blocks reading a write-only field, it can still be set through the mutations.
+pub fn write_only_guard(_ctx: &ResolverContext) -> seaography::GuardAction {
+  seaography::GuardAction::Block(Some("Field is write-only".to_string()))
+}
+
+/// Hides the soft-deleted and anonymized users from the entity queries, like
+/// `users::entities::Entity::find_not_deleted` does for the REST routes.
+pub struct NotDeletedHooks;
+
+impl LifecycleHooksInterface for NotDeletedHooks {
+  fn entity_filter(
+    &self,
+    _ctx: &ResolverContext,
+    entity: &str,
+    _action: OperationType,
+  ) -> Option<Condition> {
+    (entity == "Users").then(UserEntity::not_deleted)
+  }
+}
+
 pub fn setup_guards() -> GuardsConfig {
   tracing::info!("Setting up GraphQL guards");
   let mut config = GuardsConfig::default();
 
-  // Add entity guards
-  config
-    .entity_guards
-    .insert("users".to_string(), Box::new(admin_guard));
-  tracing::info!("Added entity guard for 'users'");
+  // Add entity guards, keyed by GraphQL type name. Entity queries must be paginated
+  let users_query_guard = bounded_query_guard("users");
+  config.entity_guards.insert(
+    "Users".to_string(),
+    Box::new(move |ctx| match (admin_guard(ctx), tenant_guard(ctx)) {
+      (seaography::GuardAction::Allow, seaography::GuardAction::Allow) => users_query_guard(ctx),
+      (seaography::GuardAction::Allow, blocked) | (blocked, _) => blocked,
+    }),
+  );
+  tracing::info!("Added entity guard for 'Users'");
 
   // Add field guards for specific fields that require admin access
   config
     .field_guards
-    .insert("users.role".to_string(), Box::new(admin_guard));
+    .insert("Users.role".to_string(), Box::new(admin_guard));
+  config
+    .field_guards
+    .insert("Users.status".to_string(), Box::new(admin_guard));
+  tracing::info!("Added field guards for 'Users.role' and 'Users.status'");
+
+  // Password hashes are never returned, by queries nor by the mutations
   config
     .field_guards
-    .insert("users.status".to_string(), Box::new(admin_guard));
-  tracing::info!("Added field guards for 'users.role' and 'users.status'");
+    .insert("Users.password".to_string(), Box::new(write_only_guard));
+  tracing::info!("Added write-only field guard for 'Users.password'");
 
   config
 }
@@ -1,15 +1,27 @@
-use axum::{extract::Request, middleware::Next, response::Response};
+use axum::{
+  extract::{RawPathParams, Request},
+  middleware::Next,
+  response::Response,
+};
 use sea_orm::ActiveEnum;
 
 use crate::common::errors::ApiError;
 use crate::modules::users::dto::UserDto;
 use crate::modules::users::enums::UserRole;
 
+/// Path segment standing for the authenticated user's ID, e.g. `/users/me`.
+pub const ME: &str = "me";
+
 /// Middleware that allows access if the user is an admin OR is accessing their own resource.
 ///
-/// Extracts `user_id` from the path (e.g. `/users/{user_id}`) and compares it
-/// to the authenticated user's ID. Admins bypass the check entirely.
-pub async fn admin_or_owner_guard(req: Request, next: Next) -> Result<Response, ApiError> {
+/// Compares the `user_id` parameter of the matched route (e.g. `/users/{user_id}/avatar`) to the
+/// authenticated user's ID, `me` always referring to the authenticated user, as do the routes
+/// without a `user_id` such as `/users/me`. Admins bypass the check entirely.
+pub async fn admin_or_owner_guard(
+  params: RawPathParams,
+  req: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
   let user = req
     .extensions()
     .get::<UserDto>()
@@ -21,15 +33,14 @@ pub async fn admin_or_owner_guard(req: Request, next: Next) -> Result<Response,
     return Ok(next.run(req).await);
   }
 
-  // Extract user_id from the path
-  let path = req.uri().path().to_string();
-  let path_user_id = path
-    .rsplit('/')
-    .next()
-    .ok_or_else(|| ApiError::Forbidden("Access denied".to_string()))?;
+  // Read the user_id of the matched route, the path itself being stripped of the nesting prefixes
+  let path_user_id = params
+    .iter()
+    .find(|(key, _)| *key == "user_id")
+    .map_or(ME, |(_, value)| value);
 
   // Check if the authenticated user is the resource owner
-  if user.id == path_user_id {
+  if path_user_id == ME || user.id == path_user_id {
     return Ok(next.run(req).await);
   }
 
@@ -37,3 +48,74 @@ pub async fn admin_or_owner_guard(req: Request, next: Next) -> Result<Response,
     "You can only access your own resource".to_string(),
   ))
 }
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, routing::get, Extension, Router};
+  use hyper::StatusCode;
+  use tower::ServiceExt;
+
+  use super::*;
+
+  const USER_ID: &str = "123e4567-e89b-12d3-a456-426614174000";
+
+  async fn status(role: UserRole, uri: &str) -> StatusCode {
+    let user = UserDto {
+      id: USER_ID.to_string(),
+      role: role.to_value(),
+      ..Default::default()
+    };
+    // Nested like the users routes, which strips the prefix from the path the guard sees
+    let users = Router::new()
+      .route("/me", get(|| async { "ok" }))
+      .route("/{user_id}", get(|| async { "ok" }))
+      .route("/{user_id}/avatar", get(|| async { "ok" }))
+      .layer(axum::middleware::from_fn(admin_or_owner_guard));
+    let app = Router::new()
+      .nest("/api", Router::new().nest("/v1/users", users))
+      .layer(Extension(user));
+
+    app
+      .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+      .await
+      .unwrap()
+      .status()
+  }
+
+  #[tokio::test]
+  async fn test_owner_can_access_own_resource() {
+    let uri = format!("/api/v1/users/{}", USER_ID);
+    assert_eq!(status(UserRole::User, &uri).await, StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn test_me_resolves_to_authenticated_user() {
+    assert_eq!(
+      status(UserRole::User, "/api/v1/users/me").await,
+      StatusCode::OK
+    );
+  }
+
+  #[tokio::test]
+  async fn test_user_cannot_access_other_resource() {
+    let uri = "/api/v1/users/00000000-0000-0000-0000-000000000000";
+    assert_eq!(status(UserRole::User, uri).await, StatusCode::FORBIDDEN);
+    assert_eq!(status(UserRole::Admin, uri).await, StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn test_owner_can_access_own_avatar() {
+    let uri = format!("/api/v1/users/{}/avatar", USER_ID);
+    assert_eq!(status(UserRole::User, &uri).await, StatusCode::OK);
+    assert_eq!(
+      status(UserRole::User, "/api/v1/users/me/avatar").await,
+      StatusCode::OK
+    );
+  }
+
+  #[tokio::test]
+  async fn test_user_cannot_access_other_avatar() {
+    let uri = "/api/v1/users/00000000-0000-0000-0000-000000000000/avatar";
+    assert_eq!(status(UserRole::User, uri).await, StatusCode::FORBIDDEN);
+  }
+}
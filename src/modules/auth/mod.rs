@@ -1,17 +1,65 @@
 pub mod controller;
 pub mod dto;
+pub mod entities;
 pub mod guards;
 pub mod service;
 
-use axum::Router;
+use axum::{extract::State, Router};
 
 use crate::app::AppState;
+use crate::common::middlewares::{deprecation_layer, idempotency};
+use crate::common::routing::allow;
+use crate::modules::auth::guards::{admin_guard, auth_guard};
 
-pub fn router() -> Router<AppState> {
+/// Date after which `GET /v1/auth/verify` may be removed.
+const VERIFY_GET_SUNSET: &str = "Sat, 01 May 2027 00:00:00 GMT";
+
+#[allow(deprecated)]
+pub fn router(State(state): State<AppState>) -> Router<AppState> {
   Router::new()
     .route(
       "/v1/auth/register",
-      axum::routing::post(controller::register),
+      axum::routing::post(controller::register)
+        .layer(axum::middleware::from_fn_with_state(state, idempotency))
+        .merge(allow(&["POST"])),
+    )
+    .route(
+      "/v1/auth/login",
+      axum::routing::post(controller::login).merge(allow(&["POST"])),
+    )
+    .route(
+      "/v1/auth/verify",
+      axum::routing::get(controller::verify)
+        .layer(deprecation_layer(Some(VERIFY_GET_SUNSET)))
+        .post(controller::verify_email)
+        .merge(allow(&["GET", "POST"])),
+    )
+    .route(
+      "/v1/auth/forgot-password",
+      axum::routing::post(controller::forgot_password).merge(allow(&["POST"])),
+    )
+    .route(
+      "/v1/auth/reset-password",
+      axum::routing::post(controller::reset_password).merge(allow(&["POST"])),
     )
-    .route("/v1/auth/login", axum::routing::post(controller::login))
+}
+
+/// Routes of the auth module for authenticated users.
+pub fn user_router(State(state): State<AppState>) -> Router<AppState> {
+  Router::new()
+    .route(
+      "/v1/auth/api-keys",
+      axum::routing::post(controller::create_api_key),
+    )
+    .layer(axum::middleware::from_fn_with_state(state, auth_guard))
+    .merge(Router::new().route("/v1/auth/api-keys", allow(&["POST"])))
+}
+
+/// Admin routes of the auth module, kept out of the rate limited public routes.
+pub fn admin_router(State(state): State<AppState>) -> Router<AppState> {
+  Router::new()
+    .route("/v1/auth/events", axum::routing::get(controller::events))
+    .layer(axum::middleware::from_fn(admin_guard))
+    .layer(axum::middleware::from_fn_with_state(state, auth_guard))
+    .merge(Router::new().route("/v1/auth/events", allow(&["GET"])))
 }
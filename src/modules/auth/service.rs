@@ -1,35 +1,69 @@
+use std::sync::OnceLock;
+
 use anyhow::anyhow;
-use bcrypt::{hash, verify};
-use jsonwebtoken::{encode, EncodingKey, Header};
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use jsonwebtoken::{encode, DecodingKey, EncodingKey, Header};
+use sea_orm::{
+  sea_query::{Expr, OnConflict},
+  ActiveEnum, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+  PaginatorTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+use sha2::{Digest, Sha256};
+use tracing::error;
 use uuid::Uuid;
 
 use crate::common::config::Config;
+use crate::common::crypto::{self, JwtAlgorithm, PasswordHashAlgo};
 use crate::common::errors::ApiError;
-use crate::modules::auth::dto::{AuthResponse, LoginRequest, RegisterRequest};
+use crate::common::extractors::ClientInfo;
+use crate::common::notifier::Notifier;
+use crate::common::pagination::{PageMeta, PageResponse, PaginatedResponse, PaginationParams};
+use crate::modules::audit::entities::AuditAction;
+use crate::modules::audit::service as audit;
+use crate::modules::auth::dto::{
+  ApiKeyCreated, AuthEventDto, AuthEventParams, AuthResponse, CreateApiKeyRequest,
+  ForgotPasswordRequest, LoginRequest, RegisterRequest, ResetPasswordRequest,
+};
+use crate::modules::auth::entities::api_key::{self as ApiKeyEntities};
+use crate::modules::auth::entities::auth_event::{
+  self as AuthEventEntities, AuthEventKind, AuthEventOutcome,
+};
+use crate::modules::auth::entities::email_verification_token::{self as VerificationTokenEntities};
+use crate::modules::auth::entities::login_attempt::{self as LoginAttemptEntities};
+use crate::modules::auth::entities::password_reset_token::{self as ResetTokenEntities};
 use crate::modules::auth::guards::auth_guard::Claims;
-use crate::modules::users::dto::UserDto;
+use crate::modules::users::dto::{normalize_email, UserDto};
 use crate::modules::users::entities::{self as UserEntities};
+use crate::modules::users::enums::UserStatus;
 
 pub async fn register(
   conn: &DatabaseConnection,
   cfg: &Config,
+  notifier: &dyn Notifier,
+  tenant_id: Option<String>,
   req: RegisterRequest,
-) -> Result<AuthResponse, ApiError> {
+) -> Result<UserDto, ApiError> {
+  if !cfg.features.registration_enabled {
+    return Err(ApiError::Forbidden("Registration is disabled".to_string()));
+  }
+
   // Hash password
-  let password_hash = hash(req.password.as_bytes(), cfg.bcrypt_cost)
-    .map_err(|e| ApiError::InternalError(anyhow!("Failed to hash password: {}", e)))?;
+  let password_hash =
+    crypto::hash_password(&req.password, cfg.password_hash_algo, cfg.bcrypt_cost)?;
+
+  let txn = conn.begin().await?;
 
-  // Create user
+  // Create user, pending until the email is verified
   let user = UserEntities::ActiveModel {
     id: sea_orm::ActiveValue::Set(Uuid::new_v4()),
-    email: sea_orm::ActiveValue::Set(req.email),
+    email: sea_orm::ActiveValue::Set(normalize_email(&req.email)),
     password: sea_orm::ActiveValue::Set(password_hash),
     name: sea_orm::ActiveValue::Set(req.name),
+    status: sea_orm::ActiveValue::Set(UserStatus::Pending),
+    tenant_id: sea_orm::ActiveValue::Set(tenant_id),
     ..Default::default()
   };
 
-  let user = user.insert(conn).await.map_err(|e| {
+  let user = user.insert(&txn).await.map_err(|e| {
     if e.to_string().contains("duplicate key") {
       ApiError::InvalidRequest("Email already exists".to_string())
     } else {
@@ -37,62 +71,1193 @@ pub async fn register(
     }
   })?;
 
-  // Generate JWT token
-  let token = generate_token(&user, cfg)?;
+  // Issue the verification token, and send it before committing: an account whose email
+  // can't be verified couldn't register again with the same email
+  let token = create_verification_token(&txn, cfg, user.id).await?;
+  notifier
+    .send_email_verification(&user.email, &token)
+    .map_err(ApiError::InternalError)?;
 
-  Ok(AuthResponse {
-    token,
-    user: UserDto::from(user),
-  })
+  txn.commit().await?;
+
+  // Users register themselves, so they are the actor
+  audit::log(
+    conn,
+    Some(user.id),
+    AuditAction::Create,
+    "user",
+    Some(user.id.to_string()),
+    Some(serde_json::json!({ "source": "register" })),
+  )
+  .await;
+
+  Ok(UserDto::from(user))
+}
+
+pub async fn verify_email(conn: &DatabaseConnection, token: &str) -> Result<UserDto, ApiError> {
+  let now = chrono::Utc::now();
+  let invalid_token = || ApiError::InvalidRequest("Invalid or expired token".to_string());
+
+  let txn = conn.begin().await?;
+
+  let record = VerificationTokenEntities::Entity::find()
+    .filter(VerificationTokenEntities::Column::Token.eq(token))
+    .one(&txn)
+    .await?
+    .filter(|record| record.is_usable(now))
+    .ok_or_else(invalid_token)?;
+
+  // Mark the token as used. The `used_at IS NULL` condition makes this single-use
+  // even when the same token is submitted concurrently.
+  let result = VerificationTokenEntities::Entity::update_many()
+    .col_expr(
+      VerificationTokenEntities::Column::UsedAt,
+      Expr::value(Some(now)),
+    )
+    .filter(VerificationTokenEntities::Column::Id.eq(record.id))
+    .filter(VerificationTokenEntities::Column::UsedAt.is_null())
+    .exec(&txn)
+    .await?;
+  if result.rows_affected != 1 {
+    return Err(invalid_token());
+  }
+
+  let user = UserEntities::Entity::find_by_id(record.user_id)
+    .one(&txn)
+    .await?
+    .ok_or_else(invalid_token)?;
+
+  let user = if user.status == UserStatus::Pending {
+    let mut user: UserEntities::ActiveModel = user.into();
+    user.status = Set(UserStatus::Active);
+    user.update(&txn).await?
+  } else {
+    user
+  };
+
+  txn.commit().await?;
+
+  Ok(UserDto::from(user))
 }
 
 pub async fn login(
   conn: &DatabaseConnection,
   cfg: &Config,
   req: LoginRequest,
+  client: &ClientInfo,
 ) -> Result<AuthResponse, ApiError> {
-  // Find user by email
-  let user = UserEntities::Entity::find()
-    .filter(UserEntities::Column::Email.eq(req.email))
+  // Find user by email, whatever its case
+  let email = normalize_email(&req.email);
+  let user = UserEntities::Entity::find_not_deleted()
+    .filter(UserEntities::Column::Email.eq(&email))
     .one(conn)
-    .await?
-    .ok_or_else(|| ApiError::InvalidRequest("Invalid credentials".to_string()))?;
+    .await?;
+  let user_id = user.as_ref().map(|user| user.id);
 
-  // Verify password
-  if !verify(req.password, &user.password)
-    .map_err(|e| ApiError::InternalError(anyhow!("Failed to verify password: {}", e)))?
+  // Look up the lockout state, with a nil id for unknown emails so that the same queries run
+  let attempts = if cfg.max_login_attempts > 0 {
+    LoginAttemptEntities::Entity::find_by_id(user_id.unwrap_or(Uuid::nil()))
+      .one(conn)
+      .await?
+  } else {
+    None
+  };
+
+  // Verify password, hashing runs even when the user doesn't exist
+  let password_hash = user.as_ref().map(|user| user.password.as_str());
+  let valid = verify_password(
+    &req.password,
+    password_hash,
+    cfg.password_hash_algo,
+    cfg.bcrypt_cost,
+  )?;
+
+  // Locked accounts are refused whether the password is valid or not
+  let now = chrono::Utc::now();
+  if attempts
+    .as_ref()
+    .is_some_and(|attempts| attempts.is_locked(now))
   {
+    record_event(
+      conn,
+      cfg,
+      AuthEventKind::Login,
+      AuthEventOutcome::Failure,
+      user_id,
+      Some(&email),
+      client,
+    )
+    .await;
+    return Err(ApiError::Forbidden(
+      "Account temporarily locked".to_string(),
+    ));
+  }
+
+  let Some(user) = user.filter(|_| valid) else {
+    // Only existing accounts are tracked, unknown emails must not be lockable
+    if let Some(user_id) = user_id {
+      record_failed_login(conn, cfg, user_id, attempts.as_ref(), now).await?;
+    }
+    record_event(
+      conn,
+      cfg,
+      AuthEventKind::Login,
+      AuthEventOutcome::Failure,
+      user_id,
+      Some(&email),
+      client,
+    )
+    .await;
     return Err(ApiError::InvalidRequest("Invalid credentials".to_string()));
+  };
+
+  // A valid password resets the failed attempts
+  if attempts.is_some() {
+    LoginAttemptEntities::Entity::delete_by_id(user.id)
+      .exec(conn)
+      .await?;
   }
 
+  // Migrate hashes made with another algorithm, now that the password is known
+  let user = if crypto::needs_rehash(&user.password, cfg.password_hash_algo) {
+    rehash_password(conn, cfg, user, &req.password).await
+  } else {
+    user
+  };
+
+  // Reject users who have not verified their email yet
+  if user.status == UserStatus::Pending {
+    record_event(
+      conn,
+      cfg,
+      AuthEventKind::Login,
+      AuthEventOutcome::Failure,
+      user_id,
+      Some(&email),
+      client,
+    )
+    .await;
+    return Err(ApiError::Forbidden("Email not verified".to_string()));
+  }
+
+  // Reject users deactivated by an admin
+  if user.status == UserStatus::Inactive {
+    record_event(
+      conn,
+      cfg,
+      AuthEventKind::Login,
+      AuthEventOutcome::Failure,
+      user_id,
+      Some(&email),
+      client,
+    )
+    .await;
+    return Err(ApiError::Forbidden("Account is inactive".to_string()));
+  }
+
+  let user = record_last_login(conn, user).await;
+
   // Generate JWT token
   let token = generate_token(&user, cfg)?;
 
+  record_event(
+    conn,
+    cfg,
+    AuthEventKind::Login,
+    AuthEventOutcome::Success,
+    user_id,
+    Some(&email),
+    client,
+  )
+  .await;
+  audit::log(
+    conn,
+    Some(user.id),
+    AuditAction::Login,
+    "user",
+    Some(user.id.to_string()),
+    Some(serde_json::json!({ "ip": client.ip })),
+  )
+  .await;
+
   Ok(AuthResponse {
-    token,
+    token: Some(token),
     user: UserDto::from(user),
   })
 }
 
-fn generate_token(user: &UserEntities::Model, cfg: &Config) -> Result<String, ApiError> {
-  let secret = std::env::var("JWT_SECRET")
-    .unwrap_or_else(|_| "a-string-secret-at-least-256-bits-long".to_string());
+/// Sets `last_login_at` of a user who just logged in.
+///
+/// Failures are only logged and the login goes on: the timestamp feeds activity reports, which
+/// isn't worth refusing valid credentials over, so it isn't written in a transaction with the
+/// rest of the login either.
+async fn record_last_login(
+  conn: &DatabaseConnection,
+  mut user: UserEntities::Model,
+) -> UserEntities::Model {
+  let now = chrono::Utc::now();
+  match UserEntities::Entity::update_many()
+    .col_expr(UserEntities::Column::LastLoginAt, Expr::value(Some(now)))
+    .filter(UserEntities::Column::Id.eq(user.id))
+    .exec(conn)
+    .await
+  {
+    Ok(_) => user.last_login_at = Some(now),
+    Err(e) => error!("Failed to record the last login of user {}: {}", user.id, e),
+  }
+  user
+}
+
+/// Counts a failed login of an existing user, locking the account for `LOGIN_LOCKOUT_DURATION`
+/// once `MAX_LOGIN_ATTEMPTS` consecutive failures are reached.
+async fn record_failed_login(
+  conn: &DatabaseConnection,
+  cfg: &Config,
+  user_id: Uuid,
+  attempts: Option<&LoginAttemptEntities::Model>,
+  now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), ApiError> {
+  if cfg.max_login_attempts == 0 {
+    return Ok(());
+  }
+
+  let failed_attempts = attempts.map_or(0, |attempts| attempts.current_failures(now)) + 1;
+  let locked_until = (failed_attempts as u32 >= cfg.max_login_attempts).then(|| {
+    now + chrono::Duration::from_std(cfg.login_lockout_duration).expect("valid lockout duration")
+  });
+
+  LoginAttemptEntities::Entity::insert(LoginAttemptEntities::ActiveModel {
+    user_id: Set(user_id),
+    failed_attempts: Set(failed_attempts),
+    locked_until: Set(locked_until),
+    updated_at: Set(now),
+  })
+  .on_conflict(
+    OnConflict::column(LoginAttemptEntities::Column::UserId)
+      .update_columns([
+        LoginAttemptEntities::Column::FailedAttempts,
+        LoginAttemptEntities::Column::LockedUntil,
+        LoginAttemptEntities::Column::UpdatedAt,
+      ])
+      .to_owned(),
+  )
+  .exec_without_returning(conn)
+  .await?;
+  Ok(())
+}
+
+/// Verifies `password` against the user's hash.
+///
+/// When the user doesn't exist, a dummy hash made with the configured algorithm is verified
+/// instead and `false` is returned, so the response time doesn't reveal whether the email is
+/// registered.
+fn verify_password(
+  password: &str,
+  password_hash: Option<&str>,
+  algo: PasswordHashAlgo,
+  bcrypt_cost: u32,
+) -> Result<bool, ApiError> {
+  match password_hash {
+    Some(password_hash) => Ok(crypto::verify_password(password, password_hash)?),
+    None => {
+      let _ = crypto::verify_password(password, dummy_hash(algo, bcrypt_cost)?);
+      Ok(false)
+    }
+  }
+}
+
+/// Hash verified against for unknown emails, computed once with the configured algorithm.
+static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+
+/// Computes the dummy hash of `verify_password`, on startup so that a failure is reported
+/// right away rather than on the first login of an unknown email.
+pub fn init_dummy_hash(algo: PasswordHashAlgo, bcrypt_cost: u32) -> anyhow::Result<()> {
+  dummy_hash(algo, bcrypt_cost).map(|_| ())
+}
+
+/// Only a valid hash is cached, verifying against anything else would be faster and reveal
+/// that the email is unknown.
+fn dummy_hash(algo: PasswordHashAlgo, bcrypt_cost: u32) -> anyhow::Result<&'static str> {
+  if let Some(hash) = DUMMY_HASH.get() {
+    return Ok(hash);
+  }
+  let hash = crypto::hash_password("dummy-password", algo, bcrypt_cost)?;
+  Ok(DUMMY_HASH.get_or_init(|| hash))
+}
+
+/// Replaces the user's password hash with one made with the configured algorithm.
+///
+/// Failures are only logged, the user is logged in with the old hash left in place.
+async fn rehash_password(
+  conn: &DatabaseConnection,
+  cfg: &Config,
+  user: UserEntities::Model,
+  password: &str,
+) -> UserEntities::Model {
+  let password_hash = match crypto::hash_password(password, cfg.password_hash_algo, cfg.bcrypt_cost)
+  {
+    Ok(password_hash) => password_hash,
+    Err(e) => {
+      error!("Failed to rehash password of user {}: {}", user.id, e);
+      return user;
+    }
+  };
+
+  let mut active: UserEntities::ActiveModel = user.clone().into();
+  active.password = Set(password_hash);
+  match active.update(conn).await {
+    Ok(user) => user,
+    Err(e) => {
+      error!("Failed to rehash password of user {}: {}", user.id, e);
+      user
+    }
+  }
+}
+
+pub async fn forgot_password(
+  conn: &DatabaseConnection,
+  cfg: &Config,
+  notifier: &dyn Notifier,
+  req: ForgotPasswordRequest,
+) -> Result<(), ApiError> {
+  // Always succeed, whether the email exists or not, to avoid user enumeration
+  let Some(user) = UserEntities::Entity::find_not_deleted()
+    .filter(UserEntities::Column::Email.eq(normalize_email(&req.email)))
+    .one(conn)
+    .await?
+  else {
+    return Ok(());
+  };
+
+  let token = random_token();
+  let expires_at = chrono::Utc::now()
+    .checked_add_signed(chrono::Duration::minutes(
+      cfg.password_reset_expiration_minutes,
+    ))
+    .expect("valid timestamp");
+
+  ResetTokenEntities::ActiveModel {
+    id: Set(Uuid::new_v4()),
+    user_id: Set(user.id),
+    token: Set(token.clone()),
+    expires_at: Set(expires_at),
+    ..Default::default()
+  }
+  .insert(conn)
+  .await?;
+
+  // Delivery failures are only logged, a different response would reveal that the email exists
+  if let Err(e) = notifier.send_password_reset(&user.email, &token) {
+    error!("Failed to send password reset email: {}", e);
+  }
+
+  Ok(())
+}
+
+pub async fn reset_password(
+  conn: &DatabaseConnection,
+  cfg: &Config,
+  req: ResetPasswordRequest,
+  client: &ClientInfo,
+) -> Result<(), ApiError> {
+  let kind = AuthEventKind::PasswordReset;
+  let result = apply_password_reset(conn, cfg, req).await;
+  match &result {
+    Ok(user) => {
+      let outcome = AuthEventOutcome::Success;
+      for kind in [kind, AuthEventKind::PasswordChange] {
+        record_event(
+          conn,
+          cfg,
+          kind,
+          outcome,
+          Some(user.id),
+          Some(&user.email),
+          client,
+        )
+        .await
+      }
+    }
+    // Invalid tokens are attempts worth monitoring, server errors aren't
+    Err(ApiError::InvalidRequest(_)) => {
+      record_event(
+        conn,
+        cfg,
+        kind,
+        AuthEventOutcome::Failure,
+        None,
+        None,
+        client,
+      )
+      .await
+    }
+    Err(_) => {}
+  }
+  result.map(|_| ())
+}
+
+/// Consumes the reset token, sets the new password and revokes the sessions, returning the
+/// updated user.
+async fn apply_password_reset(
+  conn: &DatabaseConnection,
+  cfg: &Config,
+  req: ResetPasswordRequest,
+) -> Result<UserEntities::Model, ApiError> {
+  let now = chrono::Utc::now();
+  let invalid_token = || ApiError::InvalidRequest("Invalid or expired token".to_string());
+
+  let txn = conn.begin().await?;
+
+  let record = ResetTokenEntities::Entity::find()
+    .filter(ResetTokenEntities::Column::Token.eq(req.token))
+    .one(&txn)
+    .await?
+    .filter(|record| record.is_usable(now))
+    .ok_or_else(invalid_token)?;
+
+  // Mark the token as used. The `used_at IS NULL` condition makes this single-use
+  // even when the same token is submitted concurrently.
+  let result = ResetTokenEntities::Entity::update_many()
+    .col_expr(ResetTokenEntities::Column::UsedAt, Expr::value(Some(now)))
+    .filter(ResetTokenEntities::Column::Id.eq(record.id))
+    .filter(ResetTokenEntities::Column::UsedAt.is_null())
+    .exec(&txn)
+    .await?;
+  if result.rows_affected != 1 {
+    return Err(invalid_token());
+  }
+
+  // Any other outstanding reset token of this user is no longer needed
+  ResetTokenEntities::Entity::update_many()
+    .col_expr(ResetTokenEntities::Column::UsedAt, Expr::value(Some(now)))
+    .filter(ResetTokenEntities::Column::UserId.eq(record.user_id))
+    .filter(ResetTokenEntities::Column::UsedAt.is_null())
+    .exec(&txn)
+    .await?;
+
+  let user = UserEntities::Entity::find_not_deleted()
+    .filter(UserEntities::Column::Id.eq(record.user_id))
+    .one(&txn)
+    .await?
+    .ok_or_else(invalid_token)?;
+
+  let password_hash =
+    crypto::hash_password(&req.new_password, cfg.password_hash_algo, cfg.bcrypt_cost)?;
+
+  // Sessions opened with the old password are revoked, see `ensure_session`
+  let token_version = user.token_version + 1;
+  let mut user: UserEntities::ActiveModel = user.into();
+  user.password = Set(password_hash);
+  user.token_version = Set(token_version);
+  let user = user.update(&txn).await?;
+
+  txn.commit().await?;
+
+  Ok(user)
+}
+
+/// Lists the most recent auth events first, optionally filtered by outcome.
+pub async fn events(
+  conn: &DatabaseConnection,
+  cfg: &Config,
+  params: &PaginationParams,
+  filter: &AuthEventParams,
+) -> Result<PaginatedResponse<AuthEventDto>, ApiError> {
+  let per_page = params.per_page_with(cfg);
+  let page = params.page();
+
+  let mut query = AuthEventEntities::Entity::find()
+    .order_by_desc(AuthEventEntities::Column::CreatedAt)
+    .order_by_desc(AuthEventEntities::Column::Id);
+  if let Some(outcome) = filter.outcome.as_ref() {
+    let outcome = AuthEventOutcome::try_from_value(outcome).map_err(|_| {
+      ApiError::InvalidRequest("outcome must be one of: success, failure".to_string())
+    })?;
+    query = query.filter(AuthEventEntities::Column::Outcome.eq(outcome));
+  }
+
+  let paginator = query.paginate(conn, per_page);
+  let total = paginator.num_items().await?;
+  let total_pages = (total + per_page - 1) / per_page;
+  let events = paginator.fetch_page(page - 1).await?;
+
+  Ok(PaginatedResponse::Page(PageResponse {
+    data: events.into_iter().map(AuthEventDto::from).collect(),
+    meta: PageMeta {
+      total,
+      page,
+      per_page,
+      total_pages,
+    },
+  }))
+}
+
+/// Mints an API key for the user, returned once in plain text.
+pub async fn create_api_key(
+  conn: &DatabaseConnection,
+  user_id: Uuid,
+  req: CreateApiKeyRequest,
+) -> Result<ApiKeyCreated, ApiError> {
+  // 244 random bits, prefixed so that leaked keys are easy to spot
+  let key = format!("ak_{}{}", random_token(), random_token());
+
+  let api_key = ApiKeyEntities::ActiveModel {
+    id: Set(Uuid::new_v4()),
+    user_id: Set(user_id),
+    label: Set(req.label),
+    key_hash: Set(hash_api_key(&key)),
+    last_used_at: Set(None),
+    revoked: Set(false),
+    created_at: Set(chrono::Utc::now()),
+  }
+  .insert(conn)
+  .await?;
+
+  Ok(ApiKeyCreated::new(api_key, key))
+}
+
+/// Returns the user owning the API key, unless the key is unknown or revoked.
+pub async fn authenticate_api_key(
+  conn: &DatabaseConnection,
+  key: &str,
+) -> Result<UserDto, ApiError> {
+  let api_key = ApiKeyEntities::Entity::find()
+    .filter(ApiKeyEntities::Column::KeyHash.eq(hash_api_key(key)))
+    .one(conn)
+    .await?
+    .ok_or_else(|| ApiError::Unauthorized("Invalid API key".to_string()))?;
+  if api_key.revoked {
+    return Err(ApiError::Unauthorized(
+      "API key has been revoked".to_string(),
+    ));
+  }
+
+  let user = UserEntities::Entity::find_not_deleted()
+    .filter(UserEntities::Column::Id.eq(api_key.user_id))
+    .one(conn)
+    .await?
+    .ok_or_else(|| ApiError::Unauthorized("Invalid API key".to_string()))?;
+  if user.status == UserStatus::Inactive {
+    return Err(ApiError::Forbidden("Account is inactive".to_string()));
+  }
+
+  // Failures are only logged, the usage date is informative
+  if let Err(e) = ApiKeyEntities::Entity::update_many()
+    .col_expr(
+      ApiKeyEntities::Column::LastUsedAt,
+      Expr::value(chrono::Utc::now()),
+    )
+    .filter(ApiKeyEntities::Column::Id.eq(api_key.id))
+    .exec(conn)
+    .await
+  {
+    error!(
+      "Failed to update the last use of API key {}: {}",
+      api_key.id, e
+    );
+  }
+
+  Ok(UserDto::from(user))
+}
+
+/// Rejects tokens whose sessions have been revoked since they were issued, see
+/// `users::service::revoke_sessions`, and with `check_status` the tokens of users deleted or
+/// deactivated since, see `AUTH_RECHECK_STATUS`.
+pub async fn ensure_session(
+  conn: &DatabaseConnection,
+  user_id: &str,
+  token_version: i32,
+  check_status: bool,
+) -> Result<(), ApiError> {
+  let user_id = Uuid::parse_str(user_id)
+    .map_err(|_| ApiError::Unauthorized("Invalid user in token".to_string()))?;
+  let user = UserEntities::Entity::find_by_id(user_id)
+    .one(conn)
+    .await?
+    .ok_or_else(|| ApiError::Unauthorized("User not found".to_string()))?;
+  if user.token_version != token_version {
+    return Err(ApiError::Unauthorized("Session revoked".to_string()));
+  }
+  if check_status {
+    if user.deleted_at.is_some() {
+      return Err(ApiError::Unauthorized("User not found".to_string()));
+    }
+    if user.status == UserStatus::Inactive {
+      return Err(ApiError::Forbidden("Account is inactive".to_string()));
+    }
+  }
+  Ok(())
+}
+
+/// Hashes API keys for storage and lookup, they are random enough for a fast hash.
+fn hash_api_key(key: &str) -> String {
+  format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// Records an authentication attempt when auth events are enabled.
+///
+/// Failures are only logged, monitoring must not prevent users from logging in.
+async fn record_event(
+  conn: &DatabaseConnection,
+  cfg: &Config,
+  kind: AuthEventKind,
+  outcome: AuthEventOutcome,
+  user_id: Option<Uuid>,
+  email: Option<&str>,
+  client: &ClientInfo,
+) {
+  if !cfg.auth_events_enabled {
+    return;
+  }
+
+  let event = AuthEventEntities::ActiveModel {
+    id: Set(Uuid::new_v4()),
+    user_id: Set(user_id),
+    email: Set(email.map(|email| email.to_string())),
+    kind: Set(kind),
+    outcome: Set(outcome),
+    ip: Set(client.ip.clone()),
+    user_agent: Set(client.user_agent.clone()),
+    created_at: Set(chrono::Utc::now()),
+  };
+  if let Err(e) = AuthEventEntities::Entity::insert(event)
+    .exec_without_returning(conn)
+    .await
+  {
+    error!("Failed to record {:?} auth event: {}", kind, e);
+  }
+}
+
+async fn create_verification_token<C: ConnectionTrait>(
+  conn: &C,
+  cfg: &Config,
+  user_id: Uuid,
+) -> Result<String, ApiError> {
+  let token = random_token();
+  let expires_at = chrono::Utc::now()
+    .checked_add_signed(chrono::Duration::hours(
+      cfg.email_verification_expiration_hours,
+    ))
+    .expect("valid timestamp");
+
+  VerificationTokenEntities::ActiveModel {
+    id: Set(Uuid::new_v4()),
+    user_id: Set(user_id),
+    token: Set(token.clone()),
+    expires_at: Set(expires_at),
+    ..Default::default()
+  }
+  .insert(conn)
+  .await?;
+
+  Ok(token)
+}
+
+/// Generates an unguessable single-use token (122 random bits).
+fn random_token() -> String {
+  Uuid::new_v4().simple().to_string()
+}
+
+/// Key signing the tokens, `JWT_SECRET` with `HS256` and the private key with `RS256`.
+fn encoding_key(cfg: &Config) -> Result<EncodingKey, ApiError> {
+  match (cfg.jwt_algorithm, &cfg.jwt_rsa_keys) {
+    (JwtAlgorithm::HS256, _) => Ok(EncodingKey::from_secret(cfg.jwt_secret.expose().as_bytes())),
+    (JwtAlgorithm::RS256, Some(keys)) => EncodingKey::from_rsa_pem(keys.private_pem.as_bytes())
+      .map_err(|e| ApiError::InternalError(anyhow!("Invalid JWT private key: {}", e))),
+    (JwtAlgorithm::RS256, None) => Err(ApiError::InternalError(anyhow!(
+      "RS256 requires JWT_PRIVATE_KEY_PATH"
+    ))),
+  }
+}
+
+/// Key verifying the tokens, `JWT_SECRET` with `HS256` and the public key with `RS256`.
+pub fn decoding_key(cfg: &Config) -> Result<DecodingKey, ApiError> {
+  match (cfg.jwt_algorithm, &cfg.jwt_rsa_keys) {
+    (JwtAlgorithm::HS256, _) => Ok(DecodingKey::from_secret(cfg.jwt_secret.expose().as_bytes())),
+    (JwtAlgorithm::RS256, Some(keys)) => DecodingKey::from_rsa_pem(keys.public_pem.as_bytes())
+      .map_err(|e| ApiError::InternalError(anyhow!("Invalid JWT public key: {}", e))),
+    (JwtAlgorithm::RS256, None) => Err(ApiError::InternalError(anyhow!(
+      "RS256 requires JWT_PUBLIC_KEY_PATH"
+    ))),
+  }
+}
+
+/// Issues the JWT of `user`, as returned on login.
+pub(crate) fn generate_token(user: &UserEntities::Model, cfg: &Config) -> Result<String, ApiError> {
   let expiration = chrono::Utc::now()
-    .checked_add_signed(chrono::Duration::days(cfg.jwt_expiration_days))
+    .checked_add_signed(chrono::Duration::seconds(
+      cfg.jwt_expiration.as_secs() as i64
+    ))
     .expect("valid timestamp")
     .timestamp();
 
   let claims = Claims {
     sub: user.id.to_string(),
     exp: expiration as usize,
+    iss: cfg.jwt_issuer.clone(),
+    aud: cfg.jwt_audience.clone(),
+    tenant_id: user.tenant_id.clone(),
+    token_version: user.token_version,
     user: user.clone().into(),
     ..Default::default()
   };
 
   encode(
-    &Header::default(),
+    &Header::new(cfg.jwt_algorithm.into()),
     &claims,
-    &EncodingKey::from_secret(secret.as_bytes()),
+    &encoding_key(cfg)?,
   )
   .map_err(|e| ApiError::InternalError(anyhow!("Failed to generate token: {}", e)))
 }
+
+#[cfg(test)]
+mod tests {
+  use sea_orm::{DbBackend, MockDatabase, MockExecResult};
+
+  use super::*;
+  use crate::common::config::Configuration;
+
+  const BCRYPT: PasswordHashAlgo = PasswordHashAlgo::Bcrypt;
+
+  #[test]
+  fn test_verify_password_known_user() {
+    let password_hash = crypto::hash_password("password123", BCRYPT, 4).unwrap();
+    let password_hash = Some(password_hash.as_str());
+    assert!(verify_password("password123", password_hash, BCRYPT, 4).unwrap());
+    assert!(!verify_password("wrong-password", password_hash, BCRYPT, 4).unwrap());
+  }
+
+  #[test]
+  fn test_verify_password_unknown_user_runs_dummy_verify() {
+    assert!(!verify_password("password123", None, BCRYPT, 4).unwrap());
+
+    // The dummy hash is only built by the unknown-user path
+    let dummy = DUMMY_HASH
+      .get()
+      .expect("dummy hash should have been computed");
+    assert!(dummy.starts_with("$2b$04$"));
+  }
+
+  #[test]
+  fn test_dummy_hash_is_never_empty() {
+    init_dummy_hash(BCRYPT, 4).unwrap();
+    let dummy = dummy_hash(BCRYPT, 4).unwrap();
+    assert!(crypto::verify_password("dummy-password", dummy).unwrap());
+  }
+
+  #[tokio::test]
+  async fn test_register_is_rolled_back_when_the_email_fails() {
+    let mut pending = user("Password@123");
+    pending.status = UserStatus::Pending;
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![pending.clone()]])
+      .append_query_results([vec![VerificationTokenEntities::Model {
+        id: Uuid::new_v4(),
+        user_id: pending.id,
+        token: "token".to_string(),
+        expires_at: chrono::Utc::now(),
+        used_at: None,
+        created_at: None,
+      }]])
+      .into_connection();
+    let mut notifier = crate::common::notifier::MockNotifier::new();
+    notifier
+      .expect_send_email_verification()
+      .times(1)
+      .returning(|_, _| Err(anyhow!("SMTP server unavailable")));
+
+    let request = RegisterRequest {
+      email: pending.email.clone(),
+      password: "Password@123".to_string(),
+      name: pending.name.clone(),
+    };
+    let result = register(&conn, &Configuration::for_tests(), &notifier, None, request).await;
+    assert!(matches!(result, Err(ApiError::InternalError(_))));
+
+    // The user can register again once the email goes through
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains("ROLLBACK"));
+    assert!(!log.contains("COMMIT"));
+    assert!(!log.contains("INSERT INTO \\\"audit_logs\\\""));
+  }
+
+  #[tokio::test]
+  async fn test_reset_password_records_password_change_event() {
+    let user = user("old-password");
+    let token = ResetTokenEntities::Model {
+      id: Uuid::new_v4(),
+      user_id: user.id,
+      token: "token".to_string(),
+      expires_at: chrono::Utc::now() + chrono::Duration::minutes(10),
+      used_at: None,
+      created_at: None,
+    };
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![token]])
+      .append_query_results([vec![user.clone()], vec![user.clone()]])
+      .append_exec_results([exec_result(), exec_result(), exec_result(), exec_result()])
+      .into_connection();
+    let req = ResetPasswordRequest {
+      token: "token".to_string(),
+      new_password: "new-password".to_string(),
+    };
+
+    reset_password(&conn, &Configuration::for_tests(), req, &client())
+      .await
+      .unwrap();
+
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains("\"password_reset\""));
+    assert!(log.contains("\"password_change\""));
+    // The sessions opened with the old password are revoked
+    assert!(log.contains("\\\"token_version\\\" = $"));
+  }
+
+  #[tokio::test]
+  async fn test_failed_login_records_failure_event() {
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([Vec::<UserEntities::Model>::new()])
+      .append_query_results([Vec::<LoginAttemptEntities::Model>::new()])
+      .append_exec_results([exec_result()])
+      .into_connection();
+    let req = login_request("unknown@example.com", "password123");
+    let client = ClientInfo {
+      ip: Some("203.0.113.7".to_string()),
+      user_agent: Some("curl/8.0".to_string()),
+    };
+
+    let result = login(&conn, &Configuration::for_tests(), req, &client).await;
+    assert!(matches!(result, Err(ApiError::InvalidRequest(_))));
+
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains("INSERT INTO \\\"auth_events\\\""));
+    assert!(log.contains("\"failure\""));
+    assert!(log.contains("\"login\""));
+    assert!(log.contains("203.0.113.7"));
+    assert!(!log.contains("INSERT INTO \\\"login_attempts\\\""));
+  }
+
+  #[tokio::test]
+  async fn test_unknown_email_and_wrong_password_fail_alike() {
+    let unknown = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([Vec::<UserEntities::Model>::new()])
+      .append_query_results([Vec::<LoginAttemptEntities::Model>::new()])
+      .append_exec_results([exec_result()])
+      .into_connection();
+    let req = login_request("unknown@example.com", "password123");
+    let unknown = login(&unknown, &Configuration::for_tests(), req, &client()).await;
+
+    let user = user("password123");
+    let wrong_password = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_query_results([Vec::<LoginAttemptEntities::Model>::new()])
+      .append_exec_results([exec_result(), exec_result()])
+      .into_connection();
+    let req = login_request(&user.email, "wrong-password");
+    let wrong_password = login(&wrong_password, &Configuration::for_tests(), req, &client()).await;
+
+    for result in [unknown, wrong_password] {
+      match result {
+        Err(ApiError::InvalidRequest(message)) => assert_eq!(message, "Invalid credentials"),
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+      }
+    }
+  }
+
+  #[tokio::test]
+  async fn test_failed_login_locks_account_after_max_attempts() {
+    let user = user("password123");
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_query_results([vec![attempts(user.id, 4, None)]])
+      .append_exec_results([exec_result(), exec_result()])
+      .into_connection();
+    let req = login_request(&user.email, "wrong-password");
+
+    let result = login(&conn, &Configuration::for_tests(), req, &client()).await;
+    assert!(matches!(result, Err(ApiError::InvalidRequest(_))));
+
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains("INSERT INTO \\\"login_attempts\\\""));
+    assert!(log.contains("Int(Some(5))"));
+    assert!(log.contains("ON CONFLICT"));
+  }
+
+  #[tokio::test]
+  async fn test_locked_account_is_refused_with_valid_password() {
+    let user = user("password123");
+    let locked_until = chrono::Utc::now() + chrono::Duration::minutes(15);
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_query_results([vec![attempts(user.id, 5, Some(locked_until))]])
+      .append_exec_results([exec_result()])
+      .into_connection();
+    let req = login_request(&user.email, "password123");
+
+    match login(&conn, &Configuration::for_tests(), req, &client()).await {
+      Err(ApiError::Forbidden(message)) => assert_eq!(message, "Account temporarily locked"),
+      result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_inactive_account_is_refused_with_valid_password() {
+    let user = UserEntities::Model {
+      status: UserStatus::Inactive,
+      ..user("password123")
+    };
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_query_results([Vec::<LoginAttemptEntities::Model>::new()])
+      .append_exec_results([exec_result()])
+      .into_connection();
+    let req = login_request(&user.email, "password123");
+
+    match login(&conn, &Configuration::for_tests(), req, &client()).await {
+      Err(ApiError::Forbidden(message)) => assert_eq!(message, "Account is inactive"),
+      result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(!log.contains("last_login_at"));
+  }
+
+  #[tokio::test]
+  async fn test_successful_login_resets_failed_attempts() {
+    let user = user("password123");
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_query_results([vec![attempts(user.id, 3, None)]])
+      .append_exec_results([exec_result(), exec_result(), exec_result()])
+      .into_connection();
+    let req = login_request(&user.email, "password123");
+
+    let response = login(&conn, &Configuration::for_tests(), req, &client())
+      .await
+      .unwrap();
+    assert_eq!(response.user.id, user.id.to_string());
+
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains("DELETE FROM \\\"login_attempts\\\""));
+  }
+
+  #[tokio::test]
+  async fn test_successful_login_records_last_login() {
+    let user = user("password123");
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_query_results([Vec::<LoginAttemptEntities::Model>::new()])
+      .append_exec_results([exec_result(), exec_result()])
+      .into_connection();
+    let req = login_request(&user.email, "password123");
+
+    let response = login(&conn, &Configuration::for_tests(), req, &client())
+      .await
+      .unwrap();
+    assert!(response.user.last_login_at.is_some());
+
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains("UPDATE \\\"users\\\" SET \\\"last_login_at\\\""));
+  }
+
+  #[tokio::test]
+  async fn test_login_email_is_case_insensitive() {
+    let user = user("password123");
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_query_results([Vec::<LoginAttemptEntities::Model>::new()])
+      .append_exec_results([exec_result(), exec_result()])
+      .into_connection();
+    let req = login_request(" User@Example.COM", "password123");
+
+    assert!(login(&conn, &Configuration::for_tests(), req, &client())
+      .await
+      .is_ok());
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains("\"user@example.com\""));
+    assert!(!log.contains("User@Example.COM"));
+  }
+
+  #[tokio::test]
+  async fn test_last_login_failure_does_not_block_login() {
+    let user = user("password123");
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_query_results([Vec::<LoginAttemptEntities::Model>::new()])
+      .append_exec_errors([sea_orm::DbErr::Custom("connection reset".to_string())])
+      .into_connection();
+    let req = login_request(&user.email, "password123");
+
+    let response = login(&conn, &Configuration::for_tests(), req, &client())
+      .await
+      .unwrap();
+    assert!(response.user.last_login_at.is_none());
+    assert!(response.token.is_some_and(|token| !token.is_empty()));
+  }
+
+  #[tokio::test]
+  async fn test_api_key_is_stored_hashed() {
+    let now = chrono::Utc::now();
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![ApiKeyEntities::Model {
+        id: Uuid::new_v4(),
+        user_id: Uuid::new_v4(),
+        label: "CI".to_string(),
+        key_hash: "hash".to_string(),
+        last_used_at: None,
+        revoked: false,
+        created_at: now,
+      }]])
+      .into_connection();
+    let req = CreateApiKeyRequest {
+      label: "CI".to_string(),
+    };
+
+    let created = create_api_key(&conn, Uuid::new_v4(), req).await.unwrap();
+    assert!(created.key.starts_with("ak_"));
+
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains(&hash_api_key(&created.key)));
+    assert!(!log.contains(&created.key));
+  }
+
+  #[tokio::test]
+  async fn test_api_key_authenticates_its_user() {
+    let user = user("password123");
+    let api_key = api_key(user.id, false);
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![api_key]])
+      .append_query_results([vec![user.clone()]])
+      .append_exec_results([exec_result()])
+      .into_connection();
+
+    let authenticated = authenticate_api_key(&conn, "ak_key").await.unwrap();
+    assert_eq!(authenticated.id, user.id.to_string());
+
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains("UPDATE \\\"api_keys\\\" SET \\\"last_used_at\\\""));
+  }
+
+  #[tokio::test]
+  async fn test_revoked_api_key_is_unauthorized() {
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![api_key(Uuid::new_v4(), true)]])
+      .into_connection();
+
+    match authenticate_api_key(&conn, "ak_key").await {
+      Err(ApiError::Unauthorized(message)) => assert_eq!(message, "API key has been revoked"),
+      result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_unknown_api_key_is_unauthorized() {
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([Vec::<ApiKeyEntities::Model>::new()])
+      .into_connection();
+
+    match authenticate_api_key(&conn, "ak_unknown").await {
+      Err(ApiError::Unauthorized(message)) => assert_eq!(message, "Invalid API key"),
+      result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_revoked_session_is_unauthorized() {
+    let mut revoked = user("Password@123");
+    revoked.token_version = 1;
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![revoked.clone()], vec![revoked.clone()]])
+      .into_connection();
+
+    let id = revoked.id.to_string();
+    match ensure_session(&conn, &id, 0, false).await {
+      Err(ApiError::Unauthorized(message)) => assert_eq!(message, "Session revoked"),
+      result => panic!("unexpected result: {:?}", result),
+    }
+    // Tokens issued after the revocation carry the new version
+    assert!(ensure_session(&conn, &id, 1, false).await.is_ok());
+  }
+
+  #[tokio::test]
+  async fn test_session_status_is_only_checked_when_enabled() {
+    let mut inactive = user("Password@123");
+    inactive.status = UserStatus::Inactive;
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![inactive.clone()], vec![inactive.clone()]])
+      .into_connection();
+
+    let id = inactive.id.to_string();
+    assert!(ensure_session(&conn, &id, 0, false).await.is_ok());
+    match ensure_session(&conn, &id, 0, true).await {
+      Err(ApiError::Forbidden(message)) => assert_eq!(message, "Account is inactive"),
+      result => panic!("unexpected result: {:?}", result),
+    }
+  }
+
+  fn api_key(user_id: Uuid, revoked: bool) -> ApiKeyEntities::Model {
+    ApiKeyEntities::Model {
+      id: Uuid::new_v4(),
+      user_id,
+      label: "CI".to_string(),
+      key_hash: hash_api_key("ak_key"),
+      last_used_at: None,
+      revoked,
+      created_at: chrono::Utc::now(),
+    }
+  }
+
+  fn user(password: &str) -> UserEntities::Model {
+    let now = chrono::Utc::now();
+    UserEntities::Model {
+      id: Uuid::new_v4(),
+      email: "user@example.com".to_string(),
+      name: "user".to_string(),
+      password: crypto::hash_password(password, BCRYPT, 4).unwrap(),
+      status: UserStatus::Active,
+      role: crate::modules::users::enums::UserRole::User,
+      created_at: Some(now),
+      updated_at: Some(now),
+      created_by: None,
+      updated_by: None,
+      deleted_at: None,
+      last_login_at: None,
+      avatar_url: None,
+      tenant_id: None,
+      token_version: 0,
+    }
+  }
+
+  fn attempts(
+    user_id: Uuid,
+    failed_attempts: i32,
+    locked_until: Option<chrono::DateTime<chrono::Utc>>,
+  ) -> LoginAttemptEntities::Model {
+    LoginAttemptEntities::Model {
+      user_id,
+      failed_attempts,
+      locked_until,
+      updated_at: chrono::Utc::now(),
+    }
+  }
+
+  fn login_request(email: &str, password: &str) -> LoginRequest {
+    LoginRequest {
+      email: email.to_string(),
+      password: password.to_string(),
+    }
+  }
+
+  fn client() -> ClientInfo {
+    ClientInfo {
+      ip: None,
+      user_agent: None,
+    }
+  }
+
+  fn exec_result() -> MockExecResult {
+    MockExecResult {
+      last_insert_id: 0,
+      rows_affected: 1,
+    }
+  }
+}
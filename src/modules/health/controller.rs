@@ -1,6 +1,14 @@
-use axum::Json;
+use axum::{
+  extract::{Query, State},
+  Json,
+};
+use hyper::StatusCode;
 
-use crate::modules::health::{dto::Healthy, service};
+use crate::app::AppState;
+use crate::modules::health::{
+  dto::{Healthy, ReadyParams},
+  service,
+};
 
 #[utoipa::path(
   get,
@@ -15,3 +23,29 @@ pub async fn index() -> Json<Healthy> {
   let result = service::index().await;
   Json(result)
 }
+
+#[utoipa::path(
+  get,
+  tag = "Health",
+  path = "/api/v1/health/ready",
+  operation_id = "healthReady",
+  params(ReadyParams),
+  responses(
+      (status = 200, description = "Ready to serve traffic", body = Healthy),
+      (status = 503, description = "Database is unreachable or the instance is shutting down", body = Healthy)
+  )
+)]
+pub async fn ready(
+  State(state): State<AppState>,
+  Query(params): Query<ReadyParams>,
+) -> (StatusCode, Json<Healthy>) {
+  let (status, mut result) = match service::ready(&state.db.conn, &state.readiness).await {
+    Ok(result) => (StatusCode::OK, result),
+    Err(result) => (StatusCode::SERVICE_UNAVAILABLE, result),
+  };
+  // Pool stats are opt-in so that the default probe stays minimal
+  if params.verbose {
+    result.database = service::database_stats(&state.db, &state.cfg);
+  }
+  (status, Json(result))
+}
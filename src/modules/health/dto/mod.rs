@@ -1,7 +1,38 @@
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct Healthy {
   pub status: String,
+  /// Connection pool usage, only with `verbose=true`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub database: Option<DatabaseStats>,
+}
+
+impl Healthy {
+  pub fn new(status: &str) -> Self {
+    Self {
+      status: status.to_string(),
+      database: None,
+    }
+  }
+}
+
+/// Usage of the database connection pool.
+#[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DatabaseStats {
+  /// Open connections, idle or in use
+  pub size: u32,
+  /// Open connections waiting to be used
+  pub idle: usize,
+  /// Maximum number of connections (`DATABASE_POOL_MAX_SIZE`)
+  pub max: u32,
+}
+
+/// Query parameters for the readiness probe.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct ReadyParams {
+  /// Also report the connection pool usage (default: false)
+  #[serde(default)]
+  pub verbose: bool,
 }
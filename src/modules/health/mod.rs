@@ -2,13 +2,23 @@ pub mod controller;
 pub mod dto;
 pub mod service;
 
-use axum::Router;
+use axum::{routing::get, Router};
 use axum_extra::routing::Resource;
 
 use crate::app::AppState;
+use crate::common::routing::allow;
 
 pub fn router() -> axum::Router<AppState> {
   let resources_v1 = Resource::named("health").index(controller::index);
 
-  Router::new().nest("/v1", Router::new().merge(resources_v1))
+  Router::new().nest(
+    "/v1",
+    Router::new()
+      .merge(resources_v1)
+      .route("/health", allow(&["GET"]))
+      .route(
+        "/health/ready",
+        get(controller::ready).merge(allow(&["GET"])),
+      ),
+  )
 }
@@ -1,13 +1,44 @@
-use crate::modules::health::dto::Healthy;
+use sea_orm::DatabaseConnection;
+use tracing::error;
 
+use crate::common::config::shutdown::Readiness;
+use crate::common::config::Config;
+use crate::database::Db;
+use crate::modules::health::dto::{DatabaseStats, Healthy};
+
+/// Liveness: the process is up and serving requests.
 pub async fn index() -> Healthy {
-  Healthy {
-    status: "ok".to_string(),
+  Healthy::new("ok")
+}
+
+/// Readiness: the database can be reached and the instance isn't shutting down.
+pub async fn ready(conn: &DatabaseConnection, readiness: &Readiness) -> Result<Healthy, Healthy> {
+  if readiness.is_shutting_down() {
+    return Err(Healthy::new("shutting_down"));
   }
+
+  match conn.ping().await {
+    Ok(()) => Ok(Healthy::new("ok")),
+    Err(e) => {
+      error!("Readiness check failed: {}", e);
+      Err(Healthy::new("unavailable"))
+    }
+  }
+}
+
+/// Usage of the connection pool, `None` when it can't be inspected.
+pub fn database_stats(db: &Db, cfg: &Config) -> Option<DatabaseStats> {
+  db.pool_stats().map(|stats| DatabaseStats {
+    size: stats.size,
+    idle: stats.idle,
+    max: cfg.db_pool_max_size,
+  })
 }
 
 #[cfg(test)]
 mod tests {
+  use sea_orm::{DbBackend, MockDatabase};
+
   use super::*;
 
   #[tokio::test]
@@ -21,4 +52,58 @@ mod tests {
     let result = index().await;
     assert!(!result.status.is_empty());
   }
+
+  #[tokio::test]
+  async fn test_health_ready_when_database_reachable() {
+    let db = MockDatabase::new(DbBackend::Postgres).into_connection();
+    let result = ready(&db, &Readiness::default()).await.ok().unwrap();
+    assert_eq!(result.status, "ok");
+  }
+
+  #[tokio::test]
+  async fn test_health_ready_fails_when_shutting_down() {
+    let db = MockDatabase::new(DbBackend::Postgres).into_connection();
+    let readiness = Readiness::default();
+    readiness.shut_down();
+    let result = ready(&db, &readiness).await.err().unwrap();
+    assert_eq!(result.status, "shutting_down");
+  }
+
+  #[test]
+  fn test_database_stats_are_omitted_by_default() {
+    let json = serde_json::to_string(&Healthy::new("ok")).unwrap();
+    assert_eq!(json, r#"{"status":"ok"}"#);
+
+    let healthy = Healthy {
+      database: Some(DatabaseStats {
+        size: 3,
+        idle: 1,
+        max: 10,
+      }),
+      ..Healthy::new("ok")
+    };
+    let json = serde_json::to_string(&healthy).unwrap();
+    assert!(json.contains(r#""database":{"size":3,"idle":1,"max":10}"#));
+  }
+
+  #[test]
+  fn test_database_stats_need_a_postgres_pool() {
+    let db = Db {
+      conn: MockDatabase::new(DbBackend::Postgres).into_connection(),
+      tenants: None,
+    };
+    assert_eq!(
+      database_stats(&db, &crate::common::config::Configuration::for_tests()),
+      None
+    );
+  }
+
+  #[tokio::test]
+  async fn test_health_ready_unavailable_when_database_unreachable() {
+    let result = ready(&DatabaseConnection::Disconnected, &Readiness::default())
+      .await
+      .err()
+      .unwrap();
+    assert_eq!(result.status, "unavailable");
+  }
 }
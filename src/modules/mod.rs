@@ -1,3 +1,5 @@
+pub mod admin;
+pub mod audit;
 pub mod auth;
 pub mod health;
 pub mod users;
@@ -5,16 +7,38 @@ pub mod users;
 use axum::{extract::State, Router};
 
 use crate::app::AppState;
+use crate::common::middlewares;
 
 pub fn router(State(state): State<AppState>) -> Router<AppState> {
-  let router_auth: Router<AppState> = auth::router();
+  // Auth endpoints are rate limited per client IP to slow down brute-force attempts.
+  let router_auth: Router<AppState> = auth::router(State(state.clone())).layer(
+    middlewares::rate_limit_layer(state.cfg.auth_rate_limit_per_minute),
+  );
+  let router_auth_user: Router<AppState> = auth::user_router(State(state.clone()));
+  let router_auth_admin: Router<AppState> = auth::admin_router(State(state.clone()));
+  let router_admin: Router<AppState> = admin::router(State(state.clone()));
+  let router_audit: Router<AppState> = audit::router(State(state.clone()));
   let router_health: Router<AppState> = health::router();
-  let router_users: Router<AppState> = users::router(axum::extract::State(state));
+  let router_users: Router<AppState> = users::router(axum::extract::State(state.clone()));
+
+  // The health and admin endpoints stay available in maintenance mode.
+  let maintenance_guard =
+    axum::middleware::from_fn_with_state(state.cfg.clone(), middlewares::maintenance_guard);
 
   let routers: Router<AppState> = Router::new()
-    .merge(router_auth)
+    .merge(router_auth.layer(maintenance_guard.clone()))
+    .merge(router_auth_user.layer(maintenance_guard.clone()))
+    .merge(router_auth_admin)
+    .merge(router_admin)
+    .merge(router_audit)
     .merge(router_health)
-    .merge(router_users);
+    .merge(router_users.layer(maintenance_guard));
 
-  Router::new().nest("/api", routers)
+  // Every API route produces JSON, strict clients get 406 when they don't accept it.
+  Router::new()
+    .nest("/api", routers)
+    .layer(axum::middleware::from_fn_with_state(
+      state.cfg.clone(),
+      middlewares::accept_guard,
+    ))
 }
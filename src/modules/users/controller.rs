@@ -1,23 +1,52 @@
 use axum::{
-  extract::{Query, State},
+  body::Body,
+  extract::{
+    multipart::MultipartError, rejection::JsonRejection, FromRequest, Multipart, Query, Request,
+    State,
+  },
+  http::{
+    header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, ETAG, LOCATION},
+    HeaderMap, HeaderName, HeaderValue, StatusCode,
+  },
+  response::{
+    sse::{Event, KeepAlive, Sse},
+    IntoResponse, Response,
+  },
   Json,
 };
+use futures_util::StreamExt;
+use tracing::error;
 use uuid::Uuid;
 
 use crate::common::errors::ApiError;
-use crate::common::extractors::{ValidatedJson, ValidatedPath};
-use crate::common::pagination::{PaginatedResponse, PaginationParams};
-use crate::modules::users::dto::{UserCreate, UserDto, UserUpdate};
+use crate::common::etag;
+use crate::common::extractors::{
+  BaseUrl, CurrentUser, ListQuery, TenantContext, ValidatedJson, ValidatedPath,
+};
+use crate::common::pagination::PaginationParams;
+use crate::modules::auth::dto::ForgotPasswordRequest;
+use crate::modules::auth::service as auth_service;
+use crate::modules::users::dto::{
+  BulkDeleteRequest, BulkDeleteResponse, BulkDeleteStatus, RoleUpdate, UserCreate, UserDeleteMode,
+  UserDeleteParams, UserDto, UserFilter, UserImportParams, UserImportResponse, UserImportStatus,
+  UserPatch, UserUpdate,
+};
+use crate::modules::users::enums::UserStatus;
 use crate::{app::AppState, modules::users::service};
 
+/// Media type of the streamed lists, one JSON document per line.
+const NDJSON: &str = "application/x-ndjson";
+
 #[utoipa::path(
   get,
   tag = "Users",
   path = "/api/v1/users",
   operation_id = "usersIndex",
-  params(PaginationParams),
+  params(PaginationParams, UserFilter),
   responses(
-      (status = 200, description = "List users (page mode or cursor mode)")
+      (status = 200, description = "List users (page, offset or cursor mode), optionally filtered by `q` on name or email, `status` and `role`, with only the user fields listed in `fields` (e.g. `id,email`). With `format=ndjson`, one full user per line is streamed instead"),
+      (status = 400, description = "Malformed query params, or cursor mode requested with `format=ndjson`"),
+      (status = 503, description = "Too many `format=ndjson` streams open (MAX_STREAM_CONNECTIONS)")
   ),
   security(
     ("bearerAuth" = [])
@@ -25,10 +54,22 @@ use crate::{app::AppState, modules::users::service};
 )]
 pub async fn index(
   State(state): State<AppState>,
-  Query(params): Query<PaginationParams>,
-) -> Result<Json<PaginatedResponse<UserDto>>, ApiError> {
-  let result = service::index(&state.db.conn, &params).await?;
-  Ok(Json(result))
+  tenant: TenantContext,
+  ListQuery { params, filter }: ListQuery<UserFilter>,
+) -> Result<Response, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  if params.is_ndjson() {
+    let stream = state.streams.limit(service::index_ndjson(
+      &conn, &state.cfg, &tenant, &params, &filter,
+    )?)?;
+    return Ok(([(CONTENT_TYPE, NDJSON)], Body::from_stream(stream)).into_response());
+  }
+
+  let result = service::index(&conn, &state.cfg, &tenant, &params, &filter).await?;
+  match params.fields() {
+    Some(fields) => Ok(Json(result.select_fields(&fields)).into_response()),
+    None => Ok(Json(result).into_response()),
+  }
 }
 
 #[utoipa::path(
@@ -36,9 +77,13 @@ pub async fn index(
   tag = "Users",
   path = "/api/v1/users",
   operation_id = "usersCreate",
+  params(
+    ("Idempotency-Key" = Option<String>, Header, description = "Replays the first response for repeated requests with this key (IDEMPOTENCY_TTL_HOURS)")
+  ),
   request_body = UserCreate,
   responses(
-      (status = 200, description = "Create a user", body = UserDto)
+      (status = 201, description = "Create a user, its URL is in the `Location` header", body = UserDto),
+      (status = 400, description = "Idempotency key reuse with different payload")
   ),
   security(
     ("bearerAuth" = [])
@@ -46,17 +91,90 @@ pub async fn index(
 )]
 pub async fn create(
   State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  base_url: BaseUrl,
   ValidatedJson(user): ValidatedJson<UserCreate>,
-) -> Result<Json<UserDto>, ApiError> {
+) -> Result<(StatusCode, [(HeaderName, String); 1], Json<UserDto>), ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
   let result = service::create(
-    &state.db.conn,
+    &conn,
     &state.cfg,
+    &state.events,
+    &tenant,
     user.email,
     user.password,
     user.name,
+    Some(current_user.id()?),
   )
   .await?;
-  Ok(Json(result))
+  let location = base_url.join(&format!("/api/v1/users/{}", result.id));
+  Ok((StatusCode::CREATED, [(LOCATION, location)], Json(result)))
+}
+
+#[utoipa::path(
+  get,
+  tag = "Users",
+  path = "/api/v1/users/export.csv",
+  operation_id = "usersExport",
+  responses(
+      (status = 200, description = "Download every user of the tenant as CSV (`id,email,name,role,status,created_at`), streamed as it is read", content_type = "text/csv", body = String),
+      (status = 503, description = "Too many streams open (MAX_STREAM_CONNECTIONS)")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn export(
+  State(state): State<AppState>,
+  tenant: TenantContext,
+) -> Result<Response, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let stream = state
+    .streams
+    .limit(service::export_csv(&conn, &state.cfg, &tenant))?;
+  Ok(
+    (
+      [
+        (CONTENT_TYPE, "text/csv"),
+        (CONTENT_DISPOSITION, r#"attachment; filename="users.csv""#),
+      ],
+      Body::from_stream(stream),
+    )
+      .into_response(),
+  )
+}
+
+#[utoipa::path(
+  get,
+  tag = "Users",
+  path = "/api/v1/users/events",
+  operation_id = "usersEvents",
+  responses(
+      (status = 200, description = "Stream of `user_created` server-sent events, each with a user created in the tenant as JSON data, and a keep-alive comment every 15 seconds", content_type = "text/event-stream", body = UserDto),
+      (status = 503, description = "Too many streams open (MAX_STREAM_CONNECTIONS)")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn events(
+  State(state): State<AppState>,
+  tenant: TenantContext,
+) -> Result<Response, ApiError> {
+  // The broadcast receiver lives in the stream, dropped with it when the client disconnects.
+  // Only the users of the subscriber's tenant are sent.
+  let events = state
+    .events
+    .created_users()
+    .filter(move |user| std::future::ready(user.tenant_id == tenant.0))
+    .map(|user| Event::default().event("user_created").json_data(user));
+  let stream = state.streams.limit(events)?;
+  Ok(
+    Sse::new(stream)
+      .keep_alive(KeepAlive::default())
+      .into_response(),
+  )
 }
 
 #[utoipa::path(
@@ -68,7 +186,8 @@ pub async fn create(
     ("user_id" = String, Path, description = "User ID (UUID format)")
   ),
   responses(
-    (status = 200, description = "Get user details", body = UserDto),
+    (status = 200, description = "Get user details, with an `ETag` changing whenever the user does", body = UserDto),
+    (status = 304, description = "The user is unchanged since the `If-None-Match` ETag"),
     (status = 404, description = "User not found")
   ),
   security(
@@ -77,10 +196,34 @@ pub async fn create(
 )]
 pub async fn show(
   State(state): State<AppState>,
+  tenant: TenantContext,
   ValidatedPath(user_id): ValidatedPath<Uuid>,
-) -> Result<Json<UserDto>, ApiError> {
-  let result = service::show(&state.db.conn, user_id).await?;
-  Ok(Json(result))
+  headers: HeaderMap,
+) -> Result<Response, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::show(&conn, &state.cfg, &tenant, user_id).await?;
+  Ok(cacheable_json(&headers, &result))
+}
+
+/// Serializes `user` with an `ETag` of its content, or answers `304 Not Modified` when the
+/// client's copy still matches it. Caches must revalidate, users may change at any time.
+fn cacheable_json(headers: &HeaderMap, user: &UserDto) -> Response {
+  let json = serde_json::to_vec(user).expect("UserDto is serializable");
+  let etag = etag::etag(&json);
+  let cache_headers = [
+    (ETAG, etag.clone()),
+    (CACHE_CONTROL, HeaderValue::from_static("private, no-cache")),
+  ];
+  if etag::is_fresh(headers, &etag) {
+    return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+  }
+
+  (
+    cache_headers,
+    [(CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+    json,
+  )
+    .into_response()
 }
 
 #[utoipa::path(
@@ -93,7 +236,8 @@ pub async fn show(
   ),
   request_body = UserUpdate,
   responses(
-    (status = 200, description = "Update user", body = UserDto),
+    (status = 200, description = "Replace the profile of a user, `name` is required. Use `PATCH` to change some fields only", body = UserDto),
+    (status = 403, description = "Field cannot be changed"),
     (status = 404, description = "User not found")
   ),
   security(
@@ -101,24 +245,506 @@ pub async fn show(
   )
 )]
 pub async fn update(
+  state: State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  user_id: ValidatedPath<Uuid>,
+  ValidatedJson(user): ValidatedJson<UserUpdate>,
+) -> Result<Json<UserDto>, ApiError> {
+  patch(
+    state,
+    current_user,
+    tenant,
+    user_id,
+    ValidatedJson(user.into()),
+  )
+  .await
+}
+
+#[utoipa::path(
+  patch,
+  tag = "Users",
+  path = "/api/v1/users/{user_id}",
+  operation_id = "usersPatch",
+  params(
+    ("user_id" = String, Path, description = "User ID (UUID format)")
+  ),
+  request_body = UserPatch,
+  responses(
+    (status = 200, description = "Update the given fields of a user, absent or `null` fields are kept", body = UserDto),
+    (status = 403, description = "Field cannot be changed"),
+    (status = 404, description = "User not found")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn patch(
   State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
   ValidatedPath(user_id): ValidatedPath<Uuid>,
-  ValidatedJson(user): ValidatedJson<UserUpdate>,
+  ValidatedJson(user): ValidatedJson<UserPatch>,
 ) -> Result<Json<UserDto>, ApiError> {
-  let result = service::update(&state.db.conn, user_id, user.name).await?;
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::update(
+    &conn,
+    &state.cfg,
+    &tenant,
+    user_id,
+    user,
+    current_user.is_admin(),
+    current_user.id()?,
+  )
+  .await?;
   Ok(Json(result))
 }
 
+#[utoipa::path(
+  get,
+  tag = "Users",
+  path = "/api/v1/users/me",
+  operation_id = "usersShowMe",
+  responses(
+    (status = 200, description = "Get the authenticated user, with an `ETag` like `usersShow`", body = UserDto),
+    (status = 304, description = "The user is unchanged since the `If-None-Match` ETag"),
+    (status = 404, description = "User deleted since the token was issued")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn show_me(
+  state: State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  headers: HeaderMap,
+) -> Result<Response, ApiError> {
+  // Only the id is taken from the token, the rest of its claims may be stale
+  let user_id = current_user.id()?;
+  show(state, tenant, ValidatedPath(user_id), headers).await
+}
+
+#[utoipa::path(
+  put,
+  tag = "Users",
+  path = "/api/v1/users/me",
+  operation_id = "usersUpdateMe",
+  request_body = UserUpdate,
+  responses(
+    (status = 200, description = "Replace the profile of the authenticated user, `name` is required", body = UserDto),
+    (status = 403, description = "Field cannot be changed"),
+    (status = 404, description = "User not found")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn update_me(
+  state: State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  user: ValidatedJson<UserUpdate>,
+) -> Result<Json<UserDto>, ApiError> {
+  let user_id = current_user.id()?;
+  update(state, current_user, tenant, ValidatedPath(user_id), user).await
+}
+
+#[utoipa::path(
+  patch,
+  tag = "Users",
+  path = "/api/v1/users/me",
+  operation_id = "usersPatchMe",
+  request_body = UserPatch,
+  responses(
+    (status = 200, description = "Update the given fields of the authenticated user", body = UserDto),
+    (status = 403, description = "Field cannot be changed"),
+    (status = 404, description = "User not found")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn patch_me(
+  state: State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  user: ValidatedJson<UserPatch>,
+) -> Result<Json<UserDto>, ApiError> {
+  let user_id = current_user.id()?;
+  patch(state, current_user, tenant, ValidatedPath(user_id), user).await
+}
+
+#[utoipa::path(
+  put,
+  tag = "Users",
+  path = "/api/v1/users/{user_id}/role",
+  operation_id = "usersUpdateRole",
+  params(
+    ("user_id" = String, Path, description = "User ID (UUID format)")
+  ),
+  request_body = RoleUpdate,
+  responses(
+    (status = 200, description = "Update user role", body = UserDto),
+    (status = 400, description = "Invalid role"),
+    (status = 403, description = "Cannot demote the last remaining admin"),
+    (status = 404, description = "User not found")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn update_role(
+  State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  ValidatedPath(user_id): ValidatedPath<Uuid>,
+  payload: Result<Json<RoleUpdate>, JsonRejection>,
+) -> Result<Json<UserDto>, ApiError> {
+  // Unknown role names fail deserialization, report them as an invalid request
+  let Json(req) = payload.map_err(|rejection| match rejection {
+    JsonRejection::JsonDataError(_) => {
+      ApiError::InvalidRequest("role must be one of: Admin, User".to_string())
+    }
+    rejection => ApiError::InvalidJsonBody(rejection),
+  })?;
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::update_role(&conn, &tenant, user_id, req.role, current_user.id()?).await?;
+  Ok(Json(result))
+}
+
+#[utoipa::path(
+  post,
+  tag = "Users",
+  path = "/api/v1/users/{user_id}/deactivate",
+  operation_id = "usersDeactivate",
+  params(
+    ("user_id" = String, Path, description = "User ID (UUID format)")
+  ),
+  responses(
+    (status = 200, description = "Deactivate a user, who can no longer log in", body = UserDto),
+    (status = 403, description = "Cannot deactivate the last active admin"),
+    (status = 404, description = "User not found")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn deactivate(
+  State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  ValidatedPath(user_id): ValidatedPath<Uuid>,
+) -> Result<Json<UserDto>, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::update_status(
+    &conn,
+    &tenant,
+    user_id,
+    UserStatus::Inactive,
+    current_user.id()?,
+  )
+  .await?;
+  Ok(Json(result))
+}
+
+#[utoipa::path(
+  post,
+  tag = "Users",
+  path = "/api/v1/users/{user_id}/avatar",
+  operation_id = "usersUploadAvatar",
+  params(
+    ("user_id" = String, Path, description = "User ID (UUID format)")
+  ),
+  request_body(content = String, description = "Multipart form whose first file is a PNG or JPEG image", content_type = "multipart/form-data"),
+  responses(
+    (status = 200, description = "Upload the avatar of a user, its location is in `avatar_url`", body = UserDto),
+    (status = 400, description = "Not a PNG or JPEG image"),
+    (status = 404, description = "User not found"),
+    (status = 413, description = "Avatar larger than AVATAR_MAX_BYTES")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn upload_avatar(
+  State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  ValidatedPath(user_id): ValidatedPath<Uuid>,
+  request: Request,
+) -> Result<Json<UserDto>, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let mut multipart = Multipart::from_request(request, &state)
+    .await
+    .map_err(|rejection| ApiError::InvalidRequest(rejection.body_text()))?;
+  let mut field = multipart
+    .next_field()
+    .await
+    .map_err(multipart_error)?
+    .ok_or_else(|| ApiError::InvalidRequest("Multipart form has no file".to_string()))?;
+  let content_type = field.content_type().unwrap_or_default().to_string();
+
+  // Read chunk by chunk, so that oversized files are rejected without being buffered
+  let mut content = Vec::new();
+  while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+    if content.len() + chunk.len() > state.cfg.avatar_max_bytes {
+      return Err(ApiError::PayloadTooLarge);
+    }
+    content.extend_from_slice(&chunk);
+  }
+
+  let result = service::set_avatar(
+    &conn,
+    state.storage.as_ref(),
+    &tenant,
+    user_id,
+    &content_type,
+    content.into(),
+    current_user.id()?,
+  )
+  .await?;
+  Ok(Json(result))
+}
+
+#[utoipa::path(
+  post,
+  tag = "Users",
+  path = "/api/v1/users/me/avatar",
+  operation_id = "usersUploadMyAvatar",
+  request_body(content = String, description = "Multipart form whose first file is a PNG or JPEG image", content_type = "multipart/form-data"),
+  responses(
+    (status = 200, description = "Upload the avatar of the authenticated user like `usersUploadAvatar`", body = UserDto),
+    (status = 400, description = "Not a PNG or JPEG image"),
+    (status = 404, description = "User not found"),
+    (status = 413, description = "Avatar larger than AVATAR_MAX_BYTES")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn upload_my_avatar(
+  state: State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  request: Request,
+) -> Result<Json<UserDto>, ApiError> {
+  let user_id = current_user.id()?;
+  upload_avatar(state, current_user, tenant, ValidatedPath(user_id), request).await
+}
+
+/// Maps a multipart error, a body over `MAX_BODY_BYTES` being 413 Payload Too Large.
+fn multipart_error(error: MultipartError) -> ApiError {
+  if error.status() == StatusCode::PAYLOAD_TOO_LARGE {
+    ApiError::PayloadTooLarge
+  } else {
+    ApiError::InvalidRequest(error.body_text())
+  }
+}
+
+#[utoipa::path(
+  post,
+  tag = "Users",
+  path = "/api/v1/users/{user_id}/activate",
+  operation_id = "usersActivate",
+  params(
+    ("user_id" = String, Path, description = "User ID (UUID format)")
+  ),
+  responses(
+    (status = 200, description = "Activate a user", body = UserDto),
+    (status = 404, description = "User not found")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn activate(
+  State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  ValidatedPath(user_id): ValidatedPath<Uuid>,
+) -> Result<Json<UserDto>, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let result = service::update_status(
+    &conn,
+    &tenant,
+    user_id,
+    UserStatus::Active,
+    current_user.id()?,
+  )
+  .await?;
+  Ok(Json(result))
+}
+
+#[utoipa::path(
+  post,
+  tag = "Users",
+  path = "/api/v1/users/{user_id}/revoke-sessions",
+  operation_id = "usersRevokeSessions",
+  params(
+    ("user_id" = String, Path, description = "User ID (UUID format)")
+  ),
+  responses(
+    (status = 204, description = "Revoke every token issued to the user so far, and their API keys"),
+    (status = 404, description = "User not found")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn revoke_sessions(
+  State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  ValidatedPath(user_id): ValidatedPath<Uuid>,
+) -> Result<StatusCode, ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  service::revoke_sessions(&conn, &tenant, user_id, current_user.id()?).await?;
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+  post,
+  tag = "Users",
+  path = "/api/v1/users/bulk-delete",
+  operation_id = "usersBulkDelete",
+  request_body = BulkDeleteRequest,
+  responses(
+    (status = 200, description = "Soft-delete users, with the outcome for each id", body = BulkDeleteResponse),
+    (status = 207, description = "Only some users were deleted, when BULK_MULTISTATUS is enabled", body = BulkDeleteResponse),
+    (status = 400, description = "Missing confirmation or too many ids")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn bulk_delete(
+  State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  ValidatedJson(req): ValidatedJson<BulkDeleteRequest>,
+) -> Result<(StatusCode, Json<BulkDeleteResponse>), ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let results = service::bulk_delete(&conn, &tenant, current_user.id()?, req.ids).await?;
+  let status = bulk_status(
+    state.cfg.bulk_multistatus,
+    results
+      .iter()
+      .map(|result| result.status == BulkDeleteStatus::Deleted),
+  );
+  Ok((status, Json(BulkDeleteResponse { results })))
+}
+
+#[utoipa::path(
+  post,
+  tag = "Users",
+  path = "/api/v1/users/import",
+  operation_id = "usersImport",
+  params(UserImportParams),
+  request_body(
+    content = String,
+    content_type = "text/csv",
+    description = "CSV with `email`, `name` and optional `role` columns, as the raw body or the first field of a multipart form"
+  ),
+  responses(
+    (status = 200, description = "Import users, with the outcome for each row", body = UserImportResponse),
+    (status = 207, description = "Only some rows were imported, when BULK_MULTISTATUS is enabled", body = UserImportResponse),
+    (status = 400, description = "Malformed CSV or missing columns"),
+    (status = 413, description = "Upload larger than MAX_BODY_BYTES")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn import(
+  State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  Query(params): Query<UserImportParams>,
+  request: Request,
+) -> Result<(StatusCode, Json<UserImportResponse>), ApiError> {
+  let conn = state.db.conn_for(&tenant).await?;
+  let created_by = current_user.id()?;
+  let is_multipart = request
+    .headers()
+    .get(CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .is_some_and(|value| value.starts_with("multipart/form-data"));
+
+  let results = if is_multipart {
+    let mut multipart = Multipart::from_request(request, &state)
+      .await
+      .map_err(|rejection| ApiError::InvalidRequest(rejection.body_text()))?;
+    let field = multipart
+      .next_field()
+      .await
+      .map_err(|e| ApiError::InvalidRequest(e.body_text()))?
+      .ok_or_else(|| ApiError::InvalidRequest("Multipart form has no file".to_string()))?;
+    service::import(&conn, &state.cfg, &tenant, field, created_by).await?
+  } else {
+    let body = request.into_body().into_data_stream();
+    service::import(&conn, &state.cfg, &tenant, body, created_by).await?
+  };
+
+  if params.send_reset_email {
+    let imported = results
+      .iter()
+      .filter(|result| result.status == UserImportStatus::Success);
+    for result in imported {
+      let req = ForgotPasswordRequest {
+        email: result.email.clone(),
+      };
+      let (conn, cfg, notifier) = (conn.clone(), state.cfg.clone(), state.notifier.clone());
+      // Sent in the background, the users are already imported and a failed email must not turn
+      // the import into an error
+      state.background.spawn(async move {
+        let email = req.email.clone();
+        if let Err(e) = auth_service::forgot_password(&conn, &cfg, notifier.as_ref(), req).await {
+          error!("Failed to send password reset email to {}: {}", email, e);
+        }
+      });
+    }
+  }
+
+  let status = bulk_status(
+    state.cfg.bulk_multistatus,
+    results
+      .iter()
+      .map(|result| result.status == UserImportStatus::Success),
+  );
+  Ok((status, Json(UserImportResponse { results })))
+}
+
+/// Status of a bulk response given whether each item succeeded: 207 Multi-Status when
+/// `multistatus` is enabled and the outcomes are mixed, 200 otherwise.
+fn bulk_status(multistatus: bool, outcomes: impl IntoIterator<Item = bool>) -> StatusCode {
+  let (mut succeeded, mut failed) = (false, false);
+  for outcome in outcomes {
+    if outcome {
+      succeeded = true;
+    } else {
+      failed = true;
+    }
+  }
+  if multistatus && succeeded && failed {
+    StatusCode::MULTI_STATUS
+  } else {
+    StatusCode::OK
+  }
+}
+
 #[utoipa::path(
   delete,
   tag = "Users",
   path = "/api/v1/users/{user_id}",
   operation_id = "usersDestroy",
   params(
-    ("user_id" = String, Path, description = "User ID (UUID format)")
+    ("user_id" = String, Path, description = "User ID (UUID format)"),
+    UserDeleteParams
   ),
   responses(
-    (status = 204, description = "User deleted successfully"),
+    (status = 204, description = "User anonymized, or removed with mode=hard"),
+    (status = 403, description = "Only admins can hard delete users"),
     (status = 404, description = "User not found")
   ),
   security(
@@ -127,7 +753,674 @@ pub async fn update(
 )]
 pub async fn destroy(
   State(state): State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
   ValidatedPath(user_id): ValidatedPath<Uuid>,
+  Query(params): Query<UserDeleteParams>,
 ) -> Result<(), ApiError> {
-  service::destroy(&state.db.conn, user_id).await
+  if params.mode == UserDeleteMode::Hard && !current_user.is_admin() {
+    return Err(ApiError::Forbidden(
+      "Only admins can hard delete users".to_string(),
+    ));
+  }
+  let conn = state.db.conn_for(&tenant).await?;
+  service::destroy(
+    &conn,
+    state.storage.as_ref(),
+    &tenant,
+    user_id,
+    params.mode,
+    current_user.id()?,
+  )
+  .await
+}
+
+#[utoipa::path(
+  delete,
+  tag = "Users",
+  path = "/api/v1/users/me",
+  operation_id = "usersDestroyMe",
+  params(UserDeleteParams),
+  responses(
+    (status = 204, description = "Authenticated user anonymized, or removed with mode=hard"),
+    (status = 403, description = "Only admins can hard delete users"),
+    (status = 404, description = "User not found")
+  ),
+  security(
+    ("bearerAuth" = [])
+  )
+)]
+pub async fn destroy_me(
+  state: State<AppState>,
+  current_user: CurrentUser,
+  tenant: TenantContext,
+  params: Query<UserDeleteParams>,
+) -> Result<(), ApiError> {
+  let user_id = current_user.id()?;
+  destroy(state, current_user, tenant, ValidatedPath(user_id), params).await
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use sea_orm::{DatabaseConnection, DbBackend, MockDatabase};
+
+  use super::*;
+  use crate::common::background::BackgroundTasks;
+  use crate::common::config::{Config, Configuration};
+  use crate::common::events::UserEvents;
+  use crate::common::notifier::LogNotifier;
+  use crate::common::storage::LocalStorage;
+  use crate::common::streams::StreamLimit;
+  use crate::database::tenants::TenantPools;
+  use crate::database::Db;
+  use crate::modules::users::entities;
+  use crate::modules::users::enums::{UserRole, UserStatus};
+
+  fn user(id: u128) -> entities::Model {
+    entities::Model {
+      id: Uuid::from_u128(id),
+      email: format!("user{}@example.com", id),
+      name: "User".to_string(),
+      password: "hash".to_string(),
+      status: UserStatus::Active,
+      role: UserRole::User,
+      created_at: Some(chrono::Utc::now()),
+      updated_at: None,
+      created_by: None,
+      updated_by: None,
+      deleted_at: None,
+      last_login_at: None,
+      avatar_url: None,
+      tenant_id: None,
+      token_version: 0,
+    }
+  }
+
+  fn state(conn: DatabaseConnection, cfg: Config) -> AppState {
+    AppState {
+      db: Db {
+        conn,
+        tenants: None,
+      },
+      background: BackgroundTasks::new(cfg.background_concurrency),
+      streams: StreamLimit::new(cfg.max_stream_connections),
+      events: UserEvents::default(),
+      storage: Arc::new(LocalStorage::new(&cfg.storage_dir)),
+      cfg,
+      notifier: Arc::new(LogNotifier),
+      readiness: Default::default(),
+    }
+  }
+
+  fn current_user(user: &entities::Model) -> CurrentUser {
+    CurrentUser(UserDto::from(user.clone()))
+  }
+
+  #[tokio::test]
+  async fn test_show_me_returns_the_fresh_row() {
+    let mut stale = user(1);
+    stale.name = "Old name".to_string();
+    let fresh = user(1);
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![fresh.clone()]])
+      .into_connection();
+
+    let response = show_me(
+      State(state(conn, Configuration::for_tests())),
+      current_user(&stale),
+      TenantContext::default(),
+      HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    let me: UserDto = serde_json::from_slice(&body).unwrap();
+    assert_eq!(me.id, fresh.id.to_string());
+    assert_eq!(me.name, fresh.name);
+  }
+
+  #[tokio::test]
+  async fn test_hard_delete_is_admin_only() {
+    let owner = user(1);
+    let conn = MockDatabase::new(DbBackend::Postgres).into_connection();
+
+    let result = destroy(
+      State(state(conn, Configuration::for_tests())),
+      current_user(&owner),
+      TenantContext::default(),
+      ValidatedPath(owner.id),
+      Query(UserDeleteParams {
+        mode: UserDeleteMode::Hard,
+      }),
+    )
+    .await;
+    match result {
+      Err(ApiError::Forbidden(message)) => assert_eq!(message, "Only admins can hard delete users"),
+      result => panic!("unexpected result: {:?}", result),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_delete_anonymizes_by_default() {
+    let owner = user(1);
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![owner.clone()]])
+      .append_query_results([vec![entities::Model {
+        email: format!("deleted-{}@example.invalid", owner.id),
+        status: UserStatus::Deleted,
+        ..owner.clone()
+      }]])
+      .append_exec_results([sea_orm::MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+      }])
+      .into_connection();
+    let state = state(conn, Configuration::for_tests());
+
+    destroy(
+      State(state.clone()),
+      current_user(&owner),
+      TenantContext::default(),
+      ValidatedPath(owner.id),
+      Query(UserDeleteParams::default()),
+    )
+    .await
+    .unwrap();
+    let AppState { db, .. } = state;
+    let log = format!("{:?}", db.conn.into_transaction_log());
+    assert!(log.contains("UPDATE \\\"users\\\""));
+    assert!(log.contains(&format!("deleted-{}@example.invalid", owner.id)));
+    assert!(log.contains("\"Deleted\""));
+    assert!(!log.contains("DELETE FROM"));
+  }
+
+  #[tokio::test]
+  async fn test_delete_removes_the_avatar() {
+    let dir = std::env::temp_dir().join(format!("storage-{}", Uuid::new_v4()));
+    let storage = LocalStorage::new(&dir);
+    let location = storage
+      .put(
+        "avatars/user.png",
+        axum::body::Bytes::from_static(b"avatar"),
+      )
+      .await
+      .unwrap();
+    let owner = entities::Model {
+      avatar_url: Some(location.clone()),
+      ..user(1)
+    };
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![owner.clone()]])
+      .append_query_results([vec![entities::Model {
+        avatar_url: None,
+        status: UserStatus::Deleted,
+        ..owner.clone()
+      }]])
+      .append_exec_results([sea_orm::MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+      }])
+      .into_connection();
+    let mut state = state(conn, Configuration::for_tests());
+    state.storage = Arc::new(storage);
+
+    destroy(
+      State(state.clone()),
+      current_user(&owner),
+      TenantContext::default(),
+      ValidatedPath(owner.id),
+      Query(UserDeleteParams::default()),
+    )
+    .await
+    .unwrap();
+    assert!(!std::path::Path::new(&location).exists());
+    let AppState { db, .. } = state;
+    let log = format!("{:?}", db.conn.into_transaction_log());
+    assert!(log.contains("\\\"avatar_url\\\" = $"));
+
+    tokio::fs::remove_dir_all(dir).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_admin_cannot_delete_own_account() {
+    let admin = entities::Model {
+      role: UserRole::Admin,
+      ..user(1)
+    };
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![admin.clone()]])
+      .into_connection();
+
+    let result = destroy(
+      State(state(conn, Configuration::for_tests())),
+      current_user(&admin),
+      TenantContext::default(),
+      ValidatedPath(admin.id),
+      Query(UserDeleteParams::default()),
+    )
+    .await;
+    match result {
+      Err(ApiError::Forbidden(message)) => assert_eq!(message, "Cannot delete your own account"),
+      result => panic!("unexpected result: {:?}", result),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_delete_keeps_the_last_admin() {
+    let admin = entities::Model {
+      role: UserRole::Admin,
+      ..user(1)
+    };
+    let target = entities::Model {
+      role: UserRole::Admin,
+      ..user(2)
+    };
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![target.clone()]])
+      .append_query_results([[std::collections::BTreeMap::from([(
+        "num_items",
+        sea_orm::Value::from(1i64),
+      )])]])
+      .into_connection();
+
+    let result = destroy(
+      State(state(conn, Configuration::for_tests())),
+      current_user(&admin),
+      TenantContext::default(),
+      ValidatedPath(target.id),
+      Query(UserDeleteParams {
+        mode: UserDeleteMode::Hard,
+      }),
+    )
+    .await;
+    match result {
+      Err(ApiError::Forbidden(message)) => {
+        assert_eq!(message, "Cannot delete the last remaining admin")
+      }
+      result => panic!("unexpected result: {:?}", result),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_show_me_of_deleted_user_is_not_found() {
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([Vec::<entities::Model>::new()])
+      .into_connection();
+
+    let result = show_me(
+      State(state(conn, Configuration::for_tests())),
+      current_user(&user(1)),
+      TenantContext::default(),
+      HeaderMap::new(),
+    )
+    .await;
+    assert!(matches!(result, Err(ApiError::NotFound(_))));
+  }
+
+  #[tokio::test]
+  async fn test_tenant_requests_run_on_the_tenant_database() {
+    let mut stored = user(1);
+    stored.tenant_id = Some("acme".to_string());
+    let tenant_conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![stored.clone()]])
+      .into_connection();
+    let pool = tenant_conn.clone();
+    let tenants = TenantPools::with_connect(
+      "postgres://localhost/app_{tenant}".to_string(),
+      4,
+      Arc::new(move |_| {
+        let conn = pool.clone();
+        Box::pin(async move { Ok(conn) })
+      }),
+    );
+    // The shared database has no rows, a query reaching it would fail
+    let mut state = state(
+      MockDatabase::new(DbBackend::Postgres).into_connection(),
+      Configuration::for_tests(),
+    );
+    state.db.tenants = Some(Arc::new(tenants));
+
+    show(
+      State(state),
+      TenantContext(Some("acme".to_string())),
+      ValidatedPath(stored.id),
+      HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let log = format!("{:?}", tenant_conn.into_transaction_log());
+    assert!(log.contains("SELECT"));
+  }
+
+  #[tokio::test]
+  async fn test_show_answers_not_modified_until_the_user_changes() {
+    let user = user(1);
+    let mut renamed = user.clone();
+    renamed.name = "Renamed".to_string();
+    renamed.updated_at = Some(chrono::Utc::now());
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user.clone()], vec![user.clone()], vec![renamed]])
+      .into_connection();
+    let state = state(conn, Configuration::for_tests());
+    let request = |headers| {
+      show(
+        State(state.clone()),
+        TenantContext::default(),
+        ValidatedPath(user.id),
+        headers,
+      )
+    };
+
+    let response = request(HeaderMap::new()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[ETAG].clone();
+    let cached = HeaderMap::from_iter([(axum::http::header::IF_NONE_MATCH, etag.clone())]);
+
+    let response = request(cached.clone()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[ETAG], etag);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert!(body.is_empty());
+
+    // The update changes the ETag, so the stale copy is sent again
+    let response = request(cached).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()[ETAG], etag);
+  }
+
+  #[tokio::test]
+  async fn test_create_responds_201_with_location() {
+    let created = user(2);
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![created.clone()]])
+      .into_connection();
+    let req = UserCreate {
+      email: created.email.clone(),
+      password: "password123".to_string(),
+      name: created.name.clone(),
+    };
+
+    let (status, [(header, location)], Json(body)) = create(
+      State(state(conn, Configuration::for_tests())),
+      current_user(&user(1)),
+      TenantContext::default(),
+      BaseUrl("https://api.example.com".to_string()),
+      ValidatedJson(req),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(header, LOCATION);
+    assert_eq!(
+      location,
+      format!("https://api.example.com/api/v1/users/{}", created.id)
+    );
+    assert_eq!(body.id, created.id.to_string());
+  }
+
+  #[tokio::test]
+  async fn test_index_streams_ndjson() {
+    // Batches of 2 rows, the short second batch ends the stream
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().ndjson_batch_size = 2;
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user(1), user(2)], vec![user(3)]])
+      .into_connection();
+    let state = state(conn, cfg);
+    let params = PaginationParams {
+      per_page: Some(500),
+      format: Some("ndjson".to_string()),
+      ..Default::default()
+    };
+
+    let query = ListQuery {
+      params,
+      filter: UserFilter::default(),
+    };
+    let response = index(State(state), TenantContext::default(), query)
+      .await
+      .unwrap();
+    assert_eq!(response.headers()[CONTENT_TYPE], NDJSON);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    let users: Vec<UserDto> = std::str::from_utf8(&body)
+      .unwrap()
+      .lines()
+      .map(|line| serde_json::from_str(line).unwrap())
+      .collect();
+    assert_eq!(users.len(), 3);
+    assert_eq!(users[2].email, "user3@example.com");
+  }
+
+  #[tokio::test]
+  async fn test_index_returns_selected_fields() {
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([[std::collections::BTreeMap::from([(
+        "num_items",
+        sea_orm::Value::from(1i64),
+      )])]])
+      .append_query_results([vec![user(1)]])
+      .into_connection();
+    let query = ListQuery {
+      params: PaginationParams {
+        limit: Some(10),
+        fields: Some("id,email,password".to_string()),
+        ..Default::default()
+      },
+      filter: UserFilter::default(),
+    };
+
+    let response = index(
+      State(state(conn, Configuration::for_tests())),
+      TenantContext::default(),
+      query,
+    )
+    .await
+    .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+      body,
+      serde_json::json!({
+        "data": [{ "id": user(1).id.to_string(), "email": "user1@example.com" }],
+        "meta": { "total": 1, "offset": 0, "limit": 10 }
+      })
+    );
+  }
+
+  #[tokio::test]
+  async fn test_export_streams_csv_without_passwords() {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().ndjson_batch_size = 2;
+    let mut quoted = user(3);
+    quoted.name = "Doe, Jane".to_string();
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([vec![user(1), user(2)], vec![quoted]])
+      .into_connection();
+
+    let response = export(State(state(conn, cfg)), TenantContext::default())
+      .await
+      .unwrap();
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/csv");
+    assert_eq!(
+      response.headers()[CONTENT_DISPOSITION],
+      r#"attachment; filename="users.csv""#
+    );
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    let body = std::str::from_utf8(&body).unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "id,email,name,role,status,created_at");
+    assert!(lines[1].starts_with(&format!(
+      "{},user1@example.com,User,User,Active,",
+      Uuid::from_u128(1)
+    )));
+    assert!(lines[3].contains(r#","Doe, Jane","#));
+    assert!(!body.contains("hash"));
+  }
+
+  fn avatar_request(content_type: &str, content: &[u8]) -> Request {
+    let mut body = Vec::new();
+    body.extend_from_slice(
+      b"--boundary\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"avatar\"\r\n",
+    );
+    body.extend_from_slice(format!("Content-Type: {}\r\n\r\n", content_type).as_bytes());
+    body.extend_from_slice(content);
+    body.extend_from_slice(b"\r\n--boundary--\r\n");
+    Request::builder()
+      .header(CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+      .body(Body::from(body))
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_upload_avatar_rejects_oversized_files() {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().avatar_max_bytes = 16;
+    let conn = MockDatabase::new(DbBackend::Postgres).into_connection();
+
+    let result = upload_avatar(
+      State(state(conn, cfg)),
+      current_user(&user(1)),
+      TenantContext::default(),
+      ValidatedPath(Uuid::from_u128(1)),
+      avatar_request("image/png", &[0; 17]),
+    )
+    .await;
+    assert!(matches!(result, Err(ApiError::PayloadTooLarge)));
+  }
+
+  #[tokio::test]
+  async fn test_upload_avatar_rejects_unsupported_types() {
+    let conn = MockDatabase::new(DbBackend::Postgres).into_connection();
+
+    let result = upload_avatar(
+      State(state(conn, Configuration::for_tests())),
+      current_user(&user(1)),
+      TenantContext::default(),
+      ValidatedPath(Uuid::from_u128(1)),
+      avatar_request("image/gif", b"GIF89a"),
+    )
+    .await;
+    match result {
+      Err(ApiError::InvalidRequest(message)) => {
+        assert_eq!(message, "Avatar must be a PNG or JPEG image")
+      }
+      result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_events_streams_created_users() {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().max_stream_connections = 1;
+    let state = state(
+      MockDatabase::new(DbBackend::Postgres).into_connection(),
+      cfg,
+    );
+
+    let response = events(State(state.clone()), TenantContext::default())
+      .await
+      .unwrap();
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+    state.events.user_created(UserDto::from(user(1)));
+
+    let mut body = response.into_body().into_data_stream();
+    let frame = body.next().await.unwrap().unwrap();
+    let frame = std::str::from_utf8(&frame).unwrap();
+    assert!(frame.starts_with("event: user_created\ndata: {"));
+    assert!(frame.contains("user1@example.com"));
+
+    // A disconnected client releases its stream
+    assert!(events(State(state.clone()), TenantContext::default())
+      .await
+      .is_err());
+    drop(body);
+    assert!(events(State(state), TenantContext::default()).await.is_ok());
+  }
+
+  async fn bulk_delete_status(multistatus: bool, existing: Vec<entities::Model>) -> StatusCode {
+    let mut cfg = Configuration::for_tests();
+    Arc::get_mut(&mut cfg).unwrap().bulk_multistatus = multistatus;
+    let conn = MockDatabase::new(DbBackend::Postgres)
+      .append_query_results([existing])
+      .append_query_results([[std::collections::BTreeMap::from([(
+        "num_items",
+        sea_orm::Value::from(1i64),
+      )])]])
+      .append_exec_results([sea_orm::MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+      }])
+      .into_connection();
+    let req = BulkDeleteRequest {
+      ids: vec![Uuid::from_u128(2), Uuid::from_u128(3)],
+      confirm: true,
+    };
+
+    let (status, _) = bulk_delete(
+      State(state(conn, cfg)),
+      current_user(&user(1)),
+      TenantContext::default(),
+      ValidatedJson(req),
+    )
+    .await
+    .unwrap();
+    status
+  }
+
+  #[tokio::test]
+  async fn test_bulk_delete_partial_success_is_multi_status() {
+    let status = bulk_delete_status(true, vec![user(2)]).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+  }
+
+  #[tokio::test]
+  async fn test_bulk_delete_full_success_is_ok() {
+    let status = bulk_delete_status(true, vec![user(2), user(3)]).await;
+    assert_eq!(status, StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn test_bulk_delete_partial_success_is_ok_by_default() {
+    let status = bulk_delete_status(false, vec![user(2)]).await;
+    assert_eq!(status, StatusCode::OK);
+  }
+
+  #[test]
+  fn test_bulk_status_without_success_is_ok() {
+    assert_eq!(bulk_status(true, [false, false]), StatusCode::OK);
+    assert_eq!(bulk_status(true, []), StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn test_ndjson_rejects_cursor_mode() {
+    let params = PaginationParams {
+      cursor: Some(Uuid::nil().to_string()),
+      format: Some("ndjson".to_string()),
+      ..Default::default()
+    };
+    let conn = MockDatabase::new(DbBackend::Postgres).into_connection();
+    let result = service::index_ndjson(
+      &conn,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      &params,
+      &UserFilter::default(),
+    );
+    assert!(matches!(result, Err(ApiError::InvalidRequest(_))));
+  }
 }
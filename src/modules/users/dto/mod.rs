@@ -1,10 +1,12 @@
 use chrono::SecondsFormat;
 use sea_orm::ActiveEnum;
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
-use validator::Validate;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
 
 use crate::modules::users::entities::Model;
+use crate::modules::users::enums::{UserRole, UserStatus};
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Validate)]
 pub struct UserCreate {
@@ -16,10 +18,165 @@ pub struct UserCreate {
   pub name: String,
 }
 
+/// Replaces the profile of a user (`PUT`): `name` is required, `email` is kept when absent.
+///
+/// The role is only changed by admins, through `PUT /api/v1/users/{user_id}/role`.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Validate)]
 pub struct UserUpdate {
   #[validate(length(min = 1, max = 100, message = "must be between 1 and 100 characters"))]
   pub name: String,
+  #[validate(email(message = "invalid email format"))]
+  pub email: Option<String>,
+}
+
+/// Partially updates a user (`PATCH`): only the given fields are changed, absent or `null`
+/// fields are kept.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, Validate)]
+pub struct UserPatch {
+  #[validate(length(min = 1, max = 100, message = "must be between 1 and 100 characters"))]
+  pub name: Option<String>,
+  #[validate(email(message = "invalid email format"))]
+  pub email: Option<String>,
+}
+
+impl From<UserUpdate> for UserPatch {
+  fn from(update: UserUpdate) -> Self {
+    Self {
+      name: Some(update.name),
+      email: update.email,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoleUpdate {
+  #[schema(value_type = String, example = "Admin")]
+  pub role: UserRole,
+}
+
+/// Maximum number of users deleted by a single bulk-delete request.
+pub const BULK_DELETE_MAX_IDS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Validate)]
+pub struct BulkDeleteRequest {
+  #[validate(length(min = 1, max = 100, message = "must contain between 1 and 100 ids"))]
+  pub ids: Vec<Uuid>,
+  /// Must be `true`, guards against accidental deletions
+  #[validate(custom(function = "validate_confirm"))]
+  pub confirm: bool,
+}
+
+fn validate_confirm(confirm: &bool) -> Result<(), ValidationError> {
+  if !*confirm {
+    let mut err = ValidationError::new("confirm");
+    err.message = Some("must be true to confirm the deletion".into());
+    return Err(err);
+  }
+  Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkDeleteStatus {
+  Deleted,
+  NotFound,
+  Forbidden,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BulkDeleteResult {
+  pub id: String,
+  pub status: BulkDeleteStatus,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BulkDeleteResponse {
+  pub results: Vec<BulkDeleteResult>,
+}
+
+/// A data row of the CSV import, validated like `UserCreate` minus the generated password.
+#[derive(Debug, Clone, Validate)]
+pub struct UserImportRow {
+  #[validate(email(message = "invalid email format"))]
+  pub email: String,
+  #[validate(length(min = 1, max = 100, message = "must be between 1 and 100 characters"))]
+  pub name: String,
+  /// `Admin` or `User`, empty for `User`
+  pub role: String,
+}
+
+/// Filters of the user listing, on top of the pagination params.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct UserFilter {
+  /// Only return users with this status: `Active`, `Inactive`, `Banned` or `Pending`, deleted
+  /// users being never listed
+  #[param(value_type = Option<String>)]
+  pub status: Option<UserStatus>,
+  /// Only return users with this role: `Admin` or `User`
+  #[param(value_type = Option<String>)]
+  pub role: Option<UserRole>,
+}
+
+/// Query parameters for the user import.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct UserImportParams {
+  /// Sends a password reset email to each imported user, their generated password being unknown
+  #[serde(default)]
+  pub send_reset_email: bool,
+}
+
+/// How a user is deleted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UserDeleteMode {
+  /// Erases the personal data but keeps the row, which the audit logs may reference
+  #[default]
+  Anonymize,
+  /// Removes the row, admins only
+  Hard,
+}
+
+/// Query parameters for the user deletion.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct UserDeleteParams {
+  /// `anonymize` (default) to erase the personal data and keep the row, or `hard` to remove it
+  /// (admins only)
+  #[param(value_type = Option<String>)]
+  #[serde(default)]
+  pub mode: UserDeleteMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UserImportStatus {
+  Success,
+  ValidationError,
+  Conflict,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UserImportResult {
+  /// Position of the data row in the CSV, starting at 1 after the header
+  pub row: u64,
+  pub email: String,
+  pub status: UserImportStatus,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub id: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UserImportResponse {
+  pub results: Vec<UserImportResult>,
+}
+
+/// Normalizes an email for storage and lookups, emails being unique regardless of case (see the
+/// `idx_users_email_lower` index).
+pub fn normalize_email(email: &str) -> String {
+  email.trim().to_lowercase()
 }
 
 // Custom type for OpenAPI documentation
@@ -34,6 +191,14 @@ pub struct UserDto {
   pub created_at: Option<String>,
   #[schema(format = "date-time")]
   pub updated_at: Option<String>,
+  /// Time of the last successful login, absent for users who never logged in
+  #[schema(format = "date-time")]
+  pub last_login_at: Option<String>,
+  /// Location of the uploaded avatar, absent until one is uploaded
+  pub avatar_url: Option<String>,
+  /// Tenant of the user, absent for users outside of any tenant
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub tenant_id: Option<String>,
 }
 
 impl From<Model> for UserDto {
@@ -50,6 +215,11 @@ impl From<Model> for UserDto {
       updated_at: model
         .updated_at
         .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Millis, true)),
+      last_login_at: model
+        .last_login_at
+        .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Millis, true)),
+      avatar_url: model.avatar_url,
+      tenant_id: model.tenant_id,
     }
   }
 }
@@ -59,6 +229,11 @@ mod tests {
   use super::*;
   use validator::Validate;
 
+  #[test]
+  fn test_normalize_email() {
+    assert_eq!(normalize_email(" Foo@Example.COM\n"), "foo@example.com");
+  }
+
   // --- UserCreate validation tests ---
 
   #[test]
@@ -170,6 +345,109 @@ mod tests {
     assert!(user.validate().is_ok());
   }
 
+  // --- UserUpdate validation tests ---
+
+  #[test]
+  fn test_user_update_name_only() {
+    let json = r#"{"name":"New Name"}"#;
+    let user: UserUpdate = serde_json::from_str(json).unwrap();
+    assert!(user.validate().is_ok());
+    assert!(user.email.is_none());
+  }
+
+  #[test]
+  fn test_user_update_invalid_email() {
+    let user = UserUpdate {
+      name: "New Name".to_string(),
+      email: Some("not-an-email".to_string()),
+    };
+    let err = user.validate().unwrap_err();
+    assert!(err.field_errors().contains_key("email"));
+  }
+
+  // --- UserPatch validation tests ---
+
+  #[test]
+  fn test_user_patch_fields_are_optional() {
+    let user: UserPatch =
+      serde_json::from_str(r#"{"email":"new@example.com","name":null}"#).unwrap();
+    assert!(user.validate().is_ok());
+    assert!(user.name.is_none());
+    assert_eq!(user.email.as_deref(), Some("new@example.com"));
+
+    let user: UserPatch = serde_json::from_str("{}").unwrap();
+    assert!(user.validate().is_ok());
+  }
+
+  #[test]
+  fn test_user_patch_validates_present_fields() {
+    let user = UserPatch {
+      name: Some(String::new()),
+      email: Some("not-an-email".to_string()),
+    };
+    let err = user.validate().unwrap_err();
+    assert!(err.field_errors().contains_key("name"));
+    assert!(err.field_errors().contains_key("email"));
+  }
+
+  // --- RoleUpdate tests ---
+
+  #[test]
+  fn test_role_update_deserialization() {
+    let json = r#"{"role":"Admin"}"#;
+    let req: RoleUpdate = serde_json::from_str(json).unwrap();
+    assert_eq!(req.role, UserRole::Admin);
+  }
+
+  #[test]
+  fn test_role_update_invalid_role() {
+    let json = r#"{"role":"SuperAdmin"}"#;
+    assert!(serde_json::from_str::<RoleUpdate>(json).is_err());
+  }
+
+  // --- BulkDeleteRequest tests ---
+
+  #[test]
+  fn test_bulk_delete_requires_confirm() {
+    let req = BulkDeleteRequest {
+      ids: vec![Uuid::nil()],
+      confirm: false,
+    };
+    let err = req.validate().unwrap_err();
+    assert!(err.field_errors().contains_key("confirm"));
+  }
+
+  #[test]
+  fn test_bulk_delete_batch_size_capped() {
+    let req = BulkDeleteRequest {
+      ids: vec![Uuid::nil(); BULK_DELETE_MAX_IDS + 1],
+      confirm: true,
+    };
+    let err = req.validate().unwrap_err();
+    assert!(err.field_errors().contains_key("ids"));
+  }
+
+  #[test]
+  fn test_bulk_delete_empty_ids_rejected() {
+    let req = BulkDeleteRequest {
+      ids: vec![],
+      confirm: true,
+    };
+    assert!(req.validate().is_err());
+  }
+
+  #[test]
+  fn test_bulk_delete_result_serialization() {
+    let result = BulkDeleteResult {
+      id: Uuid::nil().to_string(),
+      status: BulkDeleteStatus::NotFound,
+      message: None,
+    };
+    let json = serde_json::to_string(&result).unwrap();
+    assert!(json.contains("\"status\":\"not_found\""));
+    assert!(!json.contains("message"));
+  }
+
   // --- Serialization tests ---
 
   #[test]
@@ -217,6 +495,9 @@ mod tests {
       role: "User".to_string(),
       created_at: Some("2024-01-01T00:00:00.000Z".to_string()),
       updated_at: Some("2024-01-02T00:00:00.000Z".to_string()),
+      last_login_at: Some("2024-01-03T00:00:00.000Z".to_string()),
+      avatar_url: None,
+      tenant_id: None,
     };
 
     let json = serde_json::to_string(&dto).unwrap();
@@ -225,6 +506,7 @@ mod tests {
     assert!(json.contains("\"name\":\"Test User\""));
     assert!(json.contains("\"status\":\"Active\""));
     assert!(json.contains("\"role\":\"User\""));
+    assert!(json.contains("\"last_login_at\":\"2024-01-03T00:00:00.000Z\""));
   }
 
   #[test]
@@ -246,5 +528,6 @@ mod tests {
     assert_eq!(dto.role, "Admin");
     assert!(dto.created_at.is_some());
     assert!(dto.updated_at.is_some());
+    assert!(dto.last_login_at.is_none());
   }
 }
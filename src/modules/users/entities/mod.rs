@@ -1,7 +1,13 @@
 use chrono::{DateTime, Utc};
-use sea_orm::{entity::prelude::*, ActiveValue::Set};
+use sea_orm::{
+  entity::prelude::*,
+  ActiveValue::{self, Set},
+  Condition,
+};
 use serde::{Deserialize, Serialize};
 
+use crate::common::crypto;
+use crate::modules::users::dto::normalize_email;
 use crate::modules::users::enums::{UserRole, UserStatus};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
@@ -18,11 +24,52 @@ pub struct Model {
   pub created_at: Option<DateTime<Utc>>,
   #[sea_orm(column_type = "TimestampWithTimeZone", nullable)]
   pub updated_at: Option<DateTime<Utc>>,
+  /// User who created this user, `None` for self-registration
+  pub created_by: Option<Uuid>,
+  /// User who last updated this user
+  pub updated_by: Option<Uuid>,
+  /// Set when the user is soft-deleted
+  #[sea_orm(column_type = "TimestampWithTimeZone", nullable)]
+  pub deleted_at: Option<DateTime<Utc>>,
+  /// Time of the last successful login
+  #[sea_orm(column_type = "TimestampWithTimeZone", nullable)]
+  pub last_login_at: Option<DateTime<Utc>>,
+  /// Location of the uploaded avatar, see `Storage`
+  pub avatar_url: Option<String>,
+  /// Tenant the user belongs to, which scopes what the user can read and change
+  pub tenant_id: Option<String>,
+  /// Version of the sessions of the user, bumped to revoke every token issued before
+  pub token_version: i32,
+}
+
+impl Entity {
+  /// Selects the users that haven't been soft-deleted or anonymized.
+  pub fn find_not_deleted() -> Select<Entity> {
+    Self::find().filter(Self::not_deleted())
+  }
+
+  /// Condition matching the users that haven't been soft-deleted or anonymized.
+  pub fn not_deleted() -> Condition {
+    Condition::all()
+      .add(Column::DeletedAt.is_null())
+      .add(Column::Status.ne(UserStatus::Deleted))
+  }
+
+  /// Selects the users of `tenant` that haven't been soft-deleted, `None` being the users
+  /// outside of any tenant.
+  pub fn find_in_tenant(tenant: Option<&str>) -> Select<Entity> {
+    let query = Self::find_not_deleted();
+    match tenant {
+      Some(tenant) => query.filter(Column::TenantId.eq(tenant)),
+      None => query.filter(Column::TenantId.is_null()),
+    }
+  }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {}
 
+#[async_trait::async_trait]
 impl ActiveModelBehavior for ActiveModel {
   fn new() -> Self {
     Self {
@@ -32,6 +79,29 @@ impl ActiveModelBehavior for ActiveModel {
       ..ActiveModelTrait::default()
     }
   }
+
+  /// Hashes plain text passwords and normalizes emails, e.g. the ones set through the GraphQL
+  /// mutations.
+  ///
+  /// The service layer already stores hashes, which are left untouched, as are the empty
+  /// passwords of anonymized users, which match no password. Others are hashed as configured
+  /// on startup, see `crypto::configure_password_hashing`.
+  async fn before_save<C>(mut self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+  where
+    C: ConnectionTrait,
+  {
+    if let ActiveValue::Set(email) = &self.email {
+      self.email = Set(normalize_email(email));
+    }
+    if let ActiveValue::Set(password) = &self.password {
+      if !password.is_empty() && !crypto::is_password_hash(password) {
+        let hash = crypto::hash_password_as_configured(password)
+          .map_err(|e| DbErr::Custom(e.to_string()))?;
+        self.password = Set(hash);
+      }
+    }
+    Ok(self)
+  }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelatedEntity)]
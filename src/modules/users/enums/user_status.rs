@@ -10,6 +10,11 @@ pub enum UserStatus {
   Inactive,
   #[sea_orm(string_value = "Banned")]
   Banned,
+  #[sea_orm(string_value = "Pending")]
+  Pending,
+  /// Anonymized on deletion, the row is kept for the audit logs referencing it
+  #[sea_orm(string_value = "Deleted")]
+  Deleted,
 }
 
 impl Default for UserStatus {
@@ -34,6 +39,7 @@ mod tests {
     assert_eq!(UserStatus::Banned, UserStatus::Banned);
     assert_ne!(UserStatus::Active, UserStatus::Inactive);
     assert_ne!(UserStatus::Active, UserStatus::Banned);
+    assert_ne!(UserStatus::Active, UserStatus::Pending);
   }
 
   #[test]
@@ -41,14 +47,21 @@ mod tests {
     let active = UserStatus::Active;
     let inactive = UserStatus::Inactive;
     let banned = UserStatus::Banned;
+    let pending = UserStatus::Pending;
 
     let active_json = serde_json::to_string(&active).unwrap();
     let inactive_json = serde_json::to_string(&inactive).unwrap();
     let banned_json = serde_json::to_string(&banned).unwrap();
+    let pending_json = serde_json::to_string(&pending).unwrap();
 
     assert_eq!(active_json, "\"Active\"");
     assert_eq!(inactive_json, "\"Inactive\"");
     assert_eq!(banned_json, "\"Banned\"");
+    assert_eq!(pending_json, "\"Pending\"");
+    assert_eq!(
+      serde_json::to_string(&UserStatus::Deleted).unwrap(),
+      "\"Deleted\""
+    );
   }
 
   #[test]
@@ -0,0 +1,157 @@
+//! Incremental CSV parsing for the user import.
+//!
+//! Records are parsed as the upload streams in, so only the current record and the rows waiting
+//! to be inserted are held in memory, not the whole file.
+
+use crate::common::errors::ApiError;
+
+/// Longest record accepted, a row of the import is only an email, a name and a role.
+pub const MAX_RECORD_BYTES: usize = 4096;
+
+/// Splits a CSV stream into records, fed with chunks of any size.
+///
+/// Fields follow RFC 4180: separated by commas, optionally quoted, with `""` escaping a quote.
+/// Quoted fields may contain commas and line breaks.
+#[derive(Debug, Default)]
+pub struct CsvReader {
+  buf: Vec<u8>,
+  /// Bytes of `buf` already scanned for the end of the current record
+  scanned: usize,
+  in_quotes: bool,
+}
+
+impl CsvReader {
+  /// Appends a chunk of the upload.
+  pub fn push(&mut self, chunk: &[u8]) {
+    self.buf.extend_from_slice(chunk);
+  }
+
+  /// Returns the next complete record, `None` when more input is needed.
+  pub fn next_record(&mut self) -> Result<Option<Vec<String>>, ApiError> {
+    while self.scanned < self.buf.len() {
+      let byte = self.buf[self.scanned];
+      self.scanned += 1;
+      match byte {
+        b'"' => self.in_quotes = !self.in_quotes,
+        b'\n' if !self.in_quotes => {
+          let line: Vec<u8> = self.buf.drain(..self.scanned).collect();
+          self.scanned = 0;
+          return parse_record(&line).map(Some);
+        }
+        _ => {}
+      }
+    }
+    if self.buf.len() > MAX_RECORD_BYTES {
+      return Err(record_too_long());
+    }
+    Ok(None)
+  }
+
+  /// Returns the last record once the upload is complete, if it isn't terminated by a newline.
+  pub fn finish(&mut self) -> Result<Option<Vec<String>>, ApiError> {
+    if self.in_quotes {
+      return Err(ApiError::InvalidRequest(
+        "CSV ends inside a quoted field".to_string(),
+      ));
+    }
+    if self.buf.iter().all(u8::is_ascii_whitespace) {
+      return Ok(None);
+    }
+    let line = std::mem::take(&mut self.buf);
+    self.scanned = 0;
+    parse_record(&line).map(Some)
+  }
+}
+
+fn record_too_long() -> ApiError {
+  ApiError::InvalidRequest(format!(
+    "CSV records must not exceed {} bytes",
+    MAX_RECORD_BYTES
+  ))
+}
+
+/// Parses a single record, its trailing line break included.
+fn parse_record(line: &[u8]) -> Result<Vec<String>, ApiError> {
+  if line.len() > MAX_RECORD_BYTES {
+    return Err(record_too_long());
+  }
+  let line = std::str::from_utf8(line)
+    .map_err(|_| ApiError::InvalidRequest("CSV must be UTF-8 encoded".to_string()))?;
+  let line = line.strip_suffix('\n').unwrap_or(line);
+  let line = line.strip_suffix('\r').unwrap_or(line);
+
+  let mut fields = Vec::new();
+  let mut field = String::new();
+  let mut in_quotes = false;
+  let mut chars = line.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '"' if in_quotes && chars.peek() == Some(&'"') => {
+        field.push('"');
+        chars.next();
+      }
+      '"' => in_quotes = !in_quotes,
+      ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+      c => field.push(c),
+    }
+  }
+  fields.push(field);
+  Ok(fields.into_iter().map(|f| f.trim().to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn records(chunks: &[&str]) -> Vec<Vec<String>> {
+    let mut reader = CsvReader::default();
+    let mut records = Vec::new();
+    for chunk in chunks {
+      reader.push(chunk.as_bytes());
+      while let Some(record) = reader.next_record().unwrap() {
+        records.push(record);
+      }
+    }
+    records.extend(reader.finish().unwrap());
+    records
+  }
+
+  #[test]
+  fn test_records_split_across_chunks() {
+    let records = records(&["email,na", "me\r\nuser@example.com,", "User\n"]);
+    assert_eq!(
+      records,
+      vec![vec!["email", "name"], vec!["user@example.com", "User"]]
+    );
+  }
+
+  #[test]
+  fn test_last_record_without_newline() {
+    let records = records(&["email\nuser@example.com"]);
+    assert_eq!(records, vec![vec!["email"], vec!["user@example.com"]]);
+  }
+
+  #[test]
+  fn test_quoted_fields() {
+    let records = records(&["\"Doe, \"\"Jane\"\"\",\"multi\nline\"\n"]);
+    assert_eq!(records, vec![vec!["Doe, \"Jane\"", "multi\nline"]]);
+  }
+
+  #[test]
+  fn test_unterminated_quote_is_rejected() {
+    let mut reader = CsvReader::default();
+    reader.push(b"\"email\n");
+    assert!(reader.next_record().unwrap().is_none());
+    assert!(matches!(reader.finish(), Err(ApiError::InvalidRequest(_))));
+  }
+
+  #[test]
+  fn test_record_too_long_is_rejected() {
+    let mut reader = CsvReader::default();
+    reader.push(&vec![b'a'; MAX_RECORD_BYTES + 1]);
+    assert!(matches!(
+      reader.next_record(),
+      Err(ApiError::InvalidRequest(_))
+    ));
+  }
+}
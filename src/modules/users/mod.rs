@@ -2,36 +2,271 @@ pub mod controller;
 pub mod dto;
 pub mod entities;
 pub mod enums;
+pub mod import;
 pub mod service;
 
 use axum::{
   extract::State,
-  routing::{delete, get, post, put},
+  routing::{delete, get, patch, post, put},
   Router,
 };
 
 use crate::app::AppState;
+use crate::common::middlewares::{idempotency, timeout};
+use crate::common::routing::allow;
 use crate::modules::auth::guards::{admin_guard, admin_or_owner_guard, auth_guard};
 
 pub fn router(State(state): State<AppState>) -> axum::Router<AppState> {
-  // Admin-only routes: list all users, create user
+  // Heavy routes get their own timeout, replacing the global one
+  let heavy = axum::middleware::from_fn_with_state(state.cfg.heavy_request_timeout, timeout);
+
+  // Admin-only routes: list all users, create user, export, user events, update role and status,
+  // revoke sessions, bulk delete, import
   let admin_routes = Router::new()
     .route("/", get(controller::index))
-    .route("/", post(controller::create))
+    .route(
+      "/",
+      post(controller::create).layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        idempotency,
+      )),
+    )
+    .route("/export.csv", get(controller::export).layer(heavy.clone()))
+    .route("/events", get(controller::events))
+    .route(
+      "/bulk-delete",
+      post(controller::bulk_delete).layer(heavy.clone()),
+    )
+    .route("/import", post(controller::import).layer(heavy))
+    .route("/{user_id}/role", put(controller::update_role))
+    .route("/{user_id}/deactivate", post(controller::deactivate))
+    .route("/{user_id}/activate", post(controller::activate))
+    .route(
+      "/{user_id}/revoke-sessions",
+      post(controller::revoke_sessions),
+    )
     .layer(axum::middleware::from_fn(admin_guard));
 
-  // Admin or owner routes: show, update, delete own profile
+  // Admin or owner routes: show, update, delete own profile, upload avatar, `me` being the
+  // authenticated user
   let owner_routes = Router::new()
+    .route(
+      "/me",
+      get(controller::show_me)
+        .put(controller::update_me)
+        .patch(controller::patch_me)
+        .delete(controller::destroy_me),
+    )
+    .route("/me/avatar", post(controller::upload_my_avatar))
+    .route("/{user_id}/avatar", post(controller::upload_avatar))
     .route("/{user_id}", get(controller::show))
     .route("/{user_id}", put(controller::update))
+    .route("/{user_id}", patch(controller::patch))
     .route("/{user_id}", delete(controller::destroy))
     .layer(axum::middleware::from_fn(admin_or_owner_guard));
 
-  // All routes require authentication
+  // All routes require authentication, except OPTIONS which only describes them
   Router::new()
     .nest(
       "/v1/users",
       Router::new().merge(admin_routes).merge(owner_routes),
     )
     .layer(axum::middleware::from_fn_with_state(state, auth_guard))
+    .merge(options_routes())
+}
+
+fn options_routes<S>() -> Router<S>
+where
+  S: Clone + Send + Sync + 'static,
+{
+  Router::new().nest(
+    "/v1/users",
+    Router::new()
+      .route("/", allow(&["GET", "POST"]))
+      .route("/export.csv", allow(&["GET"]))
+      .route("/events", allow(&["GET"]))
+      .route("/bulk-delete", allow(&["POST"]))
+      .route("/import", allow(&["POST"]))
+      .route("/me", allow(&["GET", "PUT", "PATCH", "DELETE"]))
+      .route("/me/avatar", allow(&["POST"]))
+      .route("/{user_id}", allow(&["GET", "PUT", "PATCH", "DELETE"]))
+      .route("/{user_id}/role", allow(&["PUT"]))
+      .route("/{user_id}/avatar", allow(&["POST"]))
+      .route("/{user_id}/deactivate", allow(&["POST"]))
+      .route("/{user_id}/activate", allow(&["POST"]))
+      .route("/{user_id}/revoke-sessions", allow(&["POST"])),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use axum::{
+    body::Body,
+    extract::Request,
+    http::header::{ALLOW, AUTHORIZATION, CONTENT_TYPE},
+  };
+  use hyper::StatusCode;
+  use sea_orm::{DbBackend, MockDatabase};
+  use tower::ServiceExt;
+  use uuid::Uuid;
+
+  use super::*;
+  use crate::common::background::BackgroundTasks;
+  use crate::common::config::Configuration;
+  use crate::common::events::UserEvents;
+  use crate::common::notifier::LogNotifier;
+  use crate::common::storage::LocalStorage;
+  use crate::common::streams::StreamLimit;
+  use crate::database::Db;
+  use crate::modules::auth::service::generate_token;
+  use crate::modules::users::entities;
+  use crate::modules::users::enums::{UserRole, UserStatus};
+
+  fn user(id: u128) -> entities::Model {
+    entities::Model {
+      id: Uuid::from_u128(id),
+      email: format!("user{}@example.com", id),
+      name: "User".to_string(),
+      password: "hash".to_string(),
+      status: UserStatus::Active,
+      role: UserRole::User,
+      created_at: Some(chrono::Utc::now()),
+      updated_at: None,
+      created_by: None,
+      updated_by: None,
+      deleted_at: None,
+      last_login_at: None,
+      avatar_url: None,
+      tenant_id: None,
+      token_version: 0,
+    }
+  }
+
+  /// Sends a request as `owner`, a non-admin, through the users routes nested like
+  /// `modules::router` nests them. `rows` are the users read by the session check and the
+  /// handler, in order.
+  async fn status_as_owner(method: &str, uri: &str, rows: Vec<entities::Model>) -> StatusCode {
+    let owner = user(1);
+    let cfg = Configuration::for_tests();
+    let token = generate_token(&owner, &cfg).unwrap();
+    let mut db = MockDatabase::new(DbBackend::Postgres);
+    for row in rows {
+      db = db.append_query_results([vec![row]]);
+    }
+    let state = AppState {
+      db: Db {
+        conn: db.into_connection(),
+        tenants: None,
+      },
+      background: BackgroundTasks::new(cfg.background_concurrency),
+      streams: StreamLimit::new(cfg.max_stream_connections),
+      events: UserEvents::default(),
+      storage: Arc::new(LocalStorage::new(&cfg.storage_dir)),
+      cfg,
+      notifier: Arc::new(LogNotifier),
+      readiness: Default::default(),
+    };
+    let app: Router = Router::new()
+      .nest("/api", router(State(state.clone())))
+      .with_state(state);
+
+    // A GIF avatar is rejected after the guard, without touching the storage
+    let body =
+      "--boundary\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"avatar\"\r\n\
+      Content-Type: image/gif\r\n\r\nGIF89a\r\n--boundary--\r\n";
+    let request = Request::builder()
+      .method(method)
+      .uri(uri)
+      .header(AUTHORIZATION, format!("Bearer {}", token))
+      .header(CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+      .body(Body::from(body))
+      .unwrap();
+    app.oneshot(request).await.unwrap().status()
+  }
+
+  #[tokio::test]
+  async fn test_owner_can_access_own_profile() {
+    let owner = user(1);
+    let uri = format!("/api/v1/users/{}", owner.id);
+    assert_eq!(
+      status_as_owner("GET", &uri, vec![owner.clone(), owner.clone()]).await,
+      StatusCode::OK
+    );
+    assert_eq!(
+      status_as_owner("GET", "/api/v1/users/me", vec![owner.clone(), owner]).await,
+      StatusCode::OK
+    );
+  }
+
+  #[tokio::test]
+  async fn test_owner_cannot_access_other_profile() {
+    let uri = format!("/api/v1/users/{}", Uuid::from_u128(2));
+    assert_eq!(
+      status_as_owner("GET", &uri, vec![user(1)]).await,
+      StatusCode::FORBIDDEN
+    );
+  }
+
+  #[tokio::test]
+  async fn test_owner_can_upload_own_avatar() {
+    let uri = format!("/api/v1/users/{}/avatar", Uuid::from_u128(1));
+    assert_eq!(
+      status_as_owner("POST", &uri, vec![user(1)]).await,
+      StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+      status_as_owner("POST", "/api/v1/users/me/avatar", vec![user(1)]).await,
+      StatusCode::BAD_REQUEST
+    );
+
+    let uri = format!("/api/v1/users/{}/avatar", Uuid::from_u128(2));
+    assert_eq!(
+      status_as_owner("POST", &uri, vec![user(1)]).await,
+      StatusCode::FORBIDDEN
+    );
+  }
+
+  #[tokio::test]
+  async fn test_options_user_lists_allowed_methods() {
+    let app: Router = options_routes();
+
+    let response = app
+      .oneshot(
+        Request::builder()
+          .method("OPTIONS")
+          .uri("/v1/users/123e4567-e89b-12d3-a456-426614174000")
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+      response.headers()[ALLOW],
+      "GET, PUT, PATCH, DELETE, OPTIONS"
+    );
+  }
+
+  #[tokio::test]
+  async fn test_options_me_lists_allowed_methods() {
+    let app: Router = options_routes();
+
+    let response = app
+      .oneshot(
+        Request::builder()
+          .method("OPTIONS")
+          .uri("/v1/users/me")
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+      response.headers()[ALLOW],
+      "GET, PUT, PATCH, DELETE, OPTIONS"
+    );
+  }
 }
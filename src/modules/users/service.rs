@@ -1,66 +1,126 @@
-use bcrypt::hash;
+use std::collections::HashSet;
+
+use axum::body::Bytes;
+use futures_util::{Stream, StreamExt, TryStreamExt};
 use sea_orm::{
-  ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
-  QueryOrder, QuerySelect, Set,
+  sea_query::{extension::postgres::PgExpr, Expr},
+  ActiveEnum, ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait,
+  IdenStatic, Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Select, Set,
+  TransactionTrait,
 };
 use uuid::Uuid;
+use validator::Validate;
 
 use crate::common::config::Config;
+use crate::common::crypto::hash_password;
 use crate::common::errors::ApiError;
+use crate::common::events::UserEvents;
+use crate::common::extractors::TenantContext;
 use crate::common::pagination::{
-  CursorMeta, CursorResponse, PageMeta, PageResponse, PaginatedResponse, PaginationParams,
+  CursorMeta, CursorPosition, CursorResponse, OffsetMeta, OffsetResponse, PageMeta, PageResponse,
+  PaginatedResponse, PaginationParams,
+};
+use crate::common::storage::Storage;
+use crate::database::{insert_chunked, retry_read, transaction};
+use crate::modules::audit::entities::AuditAction;
+use crate::modules::audit::service as audit;
+use crate::modules::auth::entities::api_key::{self as ApiKeyEntities};
+use crate::modules::users::dto::{
+  normalize_email, BulkDeleteResult, BulkDeleteStatus, UserDeleteMode, UserDto, UserFilter,
+  UserImportResult, UserImportRow, UserImportStatus, UserPatch,
 };
-use crate::modules::users::dto::UserDto;
 use crate::modules::users::entities::{self, Entity as UserEntity};
-use crate::modules::users::enums::UserStatus;
+use crate::modules::users::enums::{UserRole, UserStatus};
+use crate::modules::users::import::CsvReader;
 
+/// Lists the users of the tenant, with its reads retried on a lost connection
+/// (`DATABASE_RETRY_READS`).
 pub async fn index(
   db: &DatabaseConnection,
+  cfg: &Config,
+  tenant: &TenantContext,
   params: &PaginationParams,
+  filter: &UserFilter,
 ) -> Result<PaginatedResponse<UserDto>, ApiError> {
-  let per_page = params.per_page();
+  let retry = cfg.db_retry_reads;
+  let per_page = params.per_page_with(cfg);
+  let sort_column = sort_column(params.sort_by.as_deref());
+  let sort_order = params.sort_order();
+  let query = index_query(tenant, params, filter, sort_order.clone());
 
   if params.is_cursor_mode() {
-    // Cursor-based pagination
-    let cursor = params.cursor.as_deref().unwrap_or_default();
-    let cursor_id = Uuid::parse_str(cursor)
-      .map_err(|_| ApiError::InvalidRequest("Invalid cursor".to_string()))?;
-
-    // Find cursor item to get its created_at
-    let cursor_item = UserEntity::find()
-      .filter(entities::Column::Id.eq(cursor_id))
-      .one(db)
-      .await?
-      .ok_or_else(|| ApiError::InvalidRequest("Cursor not found".to_string()))?;
+    // Cursor-based pagination, forward from `cursor` or backward from `before`
+    let backward = params.before.is_some();
+    if backward && params.cursor.is_some() {
+      return Err(ApiError::InvalidRequest(
+        "Only one of cursor and before can be given".to_string(),
+      ));
+    }
+    let cursor = params
+      .cursor
+      .as_deref()
+      .or(params.before.as_deref())
+      .unwrap_or_default();
+    let (cursor_key, cursor_id) = cursor_position(db, retry, tenant, sort_column, cursor).await?;
+
+    // Paging backward walks the reversed sort order from the cursor, and the page is flipped
+    // back once fetched
+    let fetch_order = match (backward, sort_order) {
+      (false, order) => order,
+      (true, Order::Desc) => Order::Asc,
+      (true, _) => Order::Desc,
+    };
 
-    // Fetch items after cursor: (created_at, id) > (cursor_created_at, cursor_id)
-    // Order by created_at ASC, id ASC for stable ordering
-    let users = UserEntity::find()
+    // Fetch items after cursor in the fetch direction, with the id as the tiebreaker of equal
+    // sort values so that no row is skipped or repeated:
+    // (sort_key, id) > (cursor_key, cursor_id) when ascending, < when descending
+    let (key_after, id_after) = match fetch_order {
+      Order::Desc => (
+        sort_column.lt(cursor_key.clone()),
+        entities::Column::Id.lt(cursor_id),
+      ),
+      _ => (
+        sort_column.gt(cursor_key.clone()),
+        entities::Column::Id.gt(cursor_id),
+      ),
+    };
+    let page_query = index_query(tenant, params, filter, fetch_order)
       .filter(
-        sea_orm::Condition::any()
-          .add(entities::Column::CreatedAt.gt(cursor_item.created_at))
-          .add(
-            sea_orm::Condition::all()
-              .add(entities::Column::CreatedAt.eq(cursor_item.created_at))
-              .add(entities::Column::Id.gt(cursor_id)),
-          ),
+        sea_orm::Condition::any().add(key_after).add(
+          sea_orm::Condition::all()
+            .add(sort_column.eq(cursor_key))
+            .add(id_after),
+        ),
       )
-      .order_by_asc(entities::Column::CreatedAt)
-      .order_by_asc(entities::Column::Id)
-      .limit(per_page + 1)
-      .all(db)
-      .await?;
+      .limit(per_page + 1);
+    let users = retry_read(retry, || page_query.clone().all(db)).await?;
 
-    // Take per_page + 1 to determine if there's a next page
-    let has_next = users.len() as u64 > per_page;
-    let items: Vec<UserDto> = users
-      .into_iter()
-      .take(per_page as usize)
-      .map(UserDto::from)
-      .collect();
+    // Take per_page + 1 to determine if there's another page in the fetch direction
+    let has_more = users.len() as u64 > per_page;
+    let mut users: Vec<entities::Model> = users.into_iter().take(per_page as usize).collect();
+    if backward {
+      users.reverse();
+    }
 
-    let next_cursor = if has_next {
-      items.last().map(|u| u.id.clone())
+    // The cursor item itself lies on the other side of the page
+    let (has_next, has_prev) = if backward {
+      (true, has_more)
+    } else {
+      (has_more, true)
+    };
+    let next_cursor = users
+      .last()
+      .filter(|_| has_next)
+      .map(|u| encode_cursor(sort_column, u));
+    let prev_cursor = users
+      .first()
+      .filter(|_| has_prev)
+      .map(|u| encode_cursor(sort_column, u));
+    let items: Vec<UserDto> = users.into_iter().map(UserDto::from).collect();
+
+    // Counting is opt-in as it scans the whole filtered set
+    let total = if params.include_total {
+      Some(retry_read(retry, || query.clone().count(db)).await?)
     } else {
       None
     };
@@ -70,20 +130,45 @@ pub async fn index(
       meta: CursorMeta {
         per_page,
         next_cursor,
+        prev_cursor,
+        has_next,
+        has_prev,
+        total,
+      },
+    }))
+  } else if params.is_offset_mode() {
+    // Offset-based pagination
+    let offset = params.offset();
+    let limit = params.limit_with(cfg);
+
+    let total = retry_read(retry, || query.clone().count(db)).await?;
+    let window = query.offset(offset).limit(limit);
+    let users = retry_read(retry, || window.clone().all(db)).await?;
+
+    let items: Vec<UserDto> = users.into_iter().map(UserDto::from).collect();
+
+    Ok(PaginatedResponse::Offset(OffsetResponse {
+      data: items,
+      meta: OffsetMeta {
+        total,
+        offset,
+        limit,
       },
     }))
   } else {
     // Page-based pagination
     let page = params.page();
 
-    let query = UserEntity::find()
-      .order_by_asc(entities::Column::CreatedAt)
-      .order_by_asc(entities::Column::Id);
-
     let paginator = query.paginate(db, per_page);
-    let total = paginator.num_items().await?;
+    let total = retry_read(retry, || paginator.num_items()).await?;
     let total_pages = (total + per_page - 1) / per_page;
-    let users = paginator.fetch_page(page - 1).await?;
+
+    // Pages past the end are empty, skip the query as a huge offset is slow to execute
+    let users = if page > total_pages {
+      Vec::new()
+    } else {
+      retry_read(retry, || paginator.fetch_page(page - 1)).await?
+    };
 
     let items: Vec<UserDto> = users.into_iter().map(UserDto::from).collect();
 
@@ -99,27 +184,338 @@ pub async fn index(
   }
 }
 
+/// Streams the users of the requested page or offset window as newline-delimited JSON, one
+/// `UserDto` per line.
+///
+/// Rows are fetched `NDJSON_BATCH_SIZE` at a time and written as they arrive, so pages of up to
+/// `QUERY_MAX_ROWS` users are never held in memory at once. Cursor mode isn't supported.
+pub fn index_ndjson(
+  db: &DatabaseConnection,
+  cfg: &Config,
+  tenant: &TenantContext,
+  params: &PaginationParams,
+  filter: &UserFilter,
+) -> Result<impl Stream<Item = Result<Bytes, DbErr>> + Send + 'static, ApiError> {
+  if params.is_cursor_mode() {
+    return Err(ApiError::InvalidRequest(
+      "The ndjson format doesn't support cursor pagination".to_string(),
+    ));
+  }
+
+  let query = index_query(tenant, params, filter, params.sort_order());
+  let (offset, limit) = params.window_up_to(cfg, cfg.query_max_rows);
+
+  Ok(
+    batches(db, query, offset, limit, cfg.ndjson_batch_size).map_ok(|users| {
+      let mut lines = Vec::new();
+      for user in users {
+        serde_json::to_writer(&mut lines, &UserDto::from(user)).expect("UserDto is serializable");
+        lines.push(b'\n');
+      }
+      Bytes::from(lines)
+    }),
+  )
+}
+
+/// Columns of the CSV export, the password is never exported.
+const EXPORT_COLUMNS: [&str; 6] = ["id", "email", "name", "role", "status", "created_at"];
+
+/// Streams every user of the tenant as CSV, starting with the `EXPORT_COLUMNS` header.
+///
+/// Like `index_ndjson`, rows are fetched `NDJSON_BATCH_SIZE` at a time and written as they
+/// arrive, so the export is never held in memory at once.
+pub fn export_csv(
+  db: &DatabaseConnection,
+  cfg: &Config,
+  tenant: &TenantContext,
+) -> impl Stream<Item = Result<Bytes, DbErr>> + Send + 'static {
+  let query = index_query(
+    tenant,
+    &PaginationParams::default(),
+    &UserFilter::default(),
+    Order::Asc,
+  );
+  let header = futures_util::stream::once(async { Ok(csv_rows(&[EXPORT_COLUMNS])) });
+  let rows = batches(db, query, 0, u64::MAX, cfg.ndjson_batch_size).map_ok(|users| {
+    let rows: Vec<[String; 6]> = users
+      .into_iter()
+      .map(UserDto::from)
+      .map(|user| {
+        [
+          user.id,
+          user.email,
+          user.name,
+          user.role,
+          user.status,
+          user.created_at.unwrap_or_default(),
+        ]
+      })
+      .collect();
+    csv_rows(&rows)
+  });
+  header.chain(rows)
+}
+
+/// Writes records as CSV lines, quoted where needed.
+fn csv_rows<R: AsRef<[F]>, F: AsRef<[u8]>>(records: &[R]) -> Bytes {
+  let mut writer = csv::Writer::from_writer(Vec::new());
+  for record in records {
+    writer
+      .write_record(record.as_ref())
+      .expect("writing to memory can't fail");
+  }
+  Bytes::from(writer.into_inner().expect("writing to memory can't fail"))
+}
+
+/// Fetches the `limit` users of `query` from `offset`, `batch_size` at a time.
+fn batches(
+  db: &DatabaseConnection,
+  query: Select<UserEntity>,
+  offset: u64,
+  limit: u64,
+  batch_size: u64,
+) -> impl Stream<Item = Result<Vec<entities::Model>, DbErr>> + Send + 'static {
+  futures_util::stream::try_unfold(
+    (db.clone(), query, offset, limit),
+    move |(db, query, offset, remaining)| async move {
+      if remaining == 0 {
+        return Ok(None);
+      }
+      let batch = remaining.min(batch_size);
+      let users = query.clone().offset(offset).limit(batch).all(&db).await?;
+      let fetched = users.len() as u64;
+      if fetched == 0 {
+        return Ok(None);
+      }
+
+      // A short batch means there are no more rows
+      let remaining = if fetched < batch {
+        0
+      } else {
+        remaining - fetched
+      };
+      Ok(Some((users, (db, query, offset + fetched, remaining))))
+    },
+  )
+}
+
+/// Selects the users of the tenant to list, filtered by `params` and sorted in `sort_order`.
+fn index_query(
+  tenant: &TenantContext,
+  params: &PaginationParams,
+  filter: &UserFilter,
+  sort_order: Order,
+) -> Select<UserEntity> {
+  // Id is used as a tiebreaker so that the ordering is always stable
+  let mut query = UserEntity::find_in_tenant(tenant.id())
+    .order_by(sort_column(params.sort_by.as_deref()), sort_order.clone())
+    .order_by(entities::Column::Id, sort_order);
+
+  // Search applies to every pagination mode, and to the total count in page mode
+  if let Some(q) = params.search() {
+    query = query.filter(search_condition(q));
+  }
+  if let Some(status) = filter.status.clone() {
+    query = query.filter(entities::Column::Status.eq(status));
+  }
+  if let Some(role) = filter.role.clone() {
+    query = query.filter(entities::Column::Role.eq(role));
+  }
+  query
+}
+
+/// Matches users whose name or email contains `q`, case-insensitively.
+fn search_condition(q: &str) -> Condition {
+  let pattern = format!("%{}%", escape_like(q));
+  Condition::any()
+    .add(Expr::col((UserEntity, entities::Column::Name)).ilike(pattern.clone()))
+    .add(Expr::col((UserEntity, entities::Column::Email)).ilike(pattern))
+}
+
+/// Escapes the LIKE wildcards so that user input is matched literally.
+fn escape_like(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('%', "\\%")
+    .replace('_', "\\_")
+}
+
+/// Value of the sort column of a user, as encoded in cursors.
+fn sort_key(column: entities::Column, user: &entities::Model) -> serde_json::Value {
+  match column {
+    entities::Column::Name => serde_json::json!(user.name),
+    entities::Column::Email => serde_json::json!(user.email),
+    entities::Column::UpdatedAt => serde_json::json!(user.updated_at),
+    _ => serde_json::json!(user.created_at),
+  }
+}
+
+/// Converts a sort key decoded from a cursor back to a value of the sort column.
+fn sort_value(column: entities::Column, key: serde_json::Value) -> Option<sea_orm::Value> {
+  match column {
+    entities::Column::Name | entities::Column::Email => {
+      serde_json::from_value::<String>(key).ok().map(Into::into)
+    }
+    _ => serde_json::from_value::<Option<chrono::DateTime<chrono::Utc>>>(key)
+      .ok()
+      .map(Into::into),
+  }
+}
+
+fn encode_cursor(column: entities::Column, user: &entities::Model) -> String {
+  CursorPosition {
+    sort: column.as_str().to_string(),
+    key: sort_key(column, user),
+    id: user.id.to_string(),
+  }
+  .encode()
+}
+
+/// Returns the sort value and id a cursor points at.
+///
+/// Cursors are those of the previous pages, which hold every sort key, or the UUID of a user
+/// to start from, whose sort value is then looked up.
+async fn cursor_position(
+  db: &DatabaseConnection,
+  retry: bool,
+  tenant: &TenantContext,
+  column: entities::Column,
+  cursor: &str,
+) -> Result<(sea_orm::Value, Uuid), ApiError> {
+  let invalid = || ApiError::InvalidRequest("Invalid cursor".to_string());
+
+  if let Ok(id) = Uuid::parse_str(cursor) {
+    let query = UserEntity::find_in_tenant(tenant.id()).filter(entities::Column::Id.eq(id));
+    let user = retry_read(retry, || query.clone().one(db))
+      .await?
+      .ok_or_else(|| ApiError::InvalidRequest("Cursor not found".to_string()))?;
+    let value = sort_value(column, sort_key(column, &user)).ok_or_else(invalid)?;
+    return Ok((value, id));
+  }
+
+  let position = CursorPosition::decode(cursor).ok_or_else(invalid)?;
+  if position.sort != column.as_str() {
+    return Err(ApiError::InvalidRequest(
+      "Cursor was made for another sort_by".to_string(),
+    ));
+  }
+  let id = Uuid::parse_str(&position.id).map_err(|_| invalid())?;
+  let value = sort_value(column, position.key).ok_or_else(invalid)?;
+  Ok((value, id))
+}
+
+/// Maps the `sort_by` query parameter to a column, defaulting to `created_at` for unknown values.
+fn sort_column(sort_by: Option<&str>) -> entities::Column {
+  match sort_by {
+    Some("name") => entities::Column::Name,
+    Some("email") => entities::Column::Email,
+    Some("updated_at") => entities::Column::UpdatedAt,
+    _ => entities::Column::CreatedAt,
+  }
+}
+
+/// Creates a user in the tenant, published to the `events` subscribers once committed.
 pub async fn create(
   db: &DatabaseConnection,
   cfg: &Config,
+  events: &UserEvents,
+  tenant: &TenantContext,
   email: String,
   password: String,
   name: String,
+  created_by: Option<Uuid>,
 ) -> Result<UserDto, ApiError> {
   // Hash password
-  let password_hash = hash(password.as_bytes(), cfg.bcrypt_cost)
-    .map_err(|e| ApiError::InternalError(anyhow::anyhow!("Failed to hash password: {}", e)))?;
+  let password_hash = hash_password(&password, cfg.password_hash_algo, cfg.bcrypt_cost)?;
 
   let user = entities::ActiveModel {
     id: Set(Uuid::new_v4()),
-    email: Set(email),
+    email: Set(normalize_email(&email)),
     password: Set(password_hash),
     name: Set(name),
     status: Set(UserStatus::Active),
+    created_by: Set(created_by),
+    tenant_id: Set(tenant.0.clone()),
     ..Default::default()
   };
 
-  let user = user.insert(db).await.map_err(|e| {
+  // Later steps (e.g. default settings) join the transaction and roll back with the user
+  let user = transaction(db, move |txn| {
+    Box::pin(async move {
+      let user = user.insert(txn).await.map_err(|e| {
+        if e.to_string().contains("duplicate key") {
+          ApiError::InvalidRequest("Email already exists".to_string())
+        } else {
+          ApiError::InternalError(anyhow::anyhow!(e))
+        }
+      })?;
+
+      Ok(UserDto::from(user))
+    })
+  })
+  .await?;
+
+  audit::log(
+    db,
+    created_by,
+    AuditAction::Create,
+    "user",
+    Some(user.id.clone()),
+    None,
+  )
+  .await;
+  events.user_created(user.clone());
+  Ok(user)
+}
+
+/// Gets a user of the tenant, with the read retried on a lost connection
+/// (`DATABASE_RETRY_READS`).
+///
+/// Users of other tenants are not found, so that their existence isn't revealed.
+pub async fn show(
+  db: &DatabaseConnection,
+  cfg: &Config,
+  tenant: &TenantContext,
+  id: Uuid,
+) -> Result<UserDto, ApiError> {
+  let query = UserEntity::find_in_tenant(tenant.id()).filter(entities::Column::Id.eq(id));
+  let user = retry_read(cfg.db_retry_reads, || query.clone().one(db))
+    .await?
+    .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+  Ok(UserDto::from(user))
+}
+
+pub async fn update(
+  db: &DatabaseConnection,
+  cfg: &Config,
+  tenant: &TenantContext,
+  id: Uuid,
+  update: UserPatch,
+  is_admin: bool,
+  updated_by: Uuid,
+) -> Result<UserDto, ApiError> {
+  let user = UserEntity::find_in_tenant(tenant.id())
+    .filter(entities::Column::Id.eq(id))
+    .one(db)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+  ensure_mutable_fields(&user, &update, &cfg.user_immutable_fields, is_admin)?;
+
+  let mut fields = vec![];
+  let mut user: entities::ActiveModel = user.into();
+  if let Some(name) = update.name {
+    user.name = Set(name);
+    fields.push("name");
+  }
+  if let Some(email) = update.email {
+    user.email = Set(normalize_email(&email));
+    fields.push("email");
+  }
+  user.updated_by = Set(Some(updated_by));
+
+  let user = user.update(db).await.map_err(|e| {
     if e.to_string().contains("duplicate key") {
       ApiError::InvalidRequest("Email already exists".to_string())
     } else {
@@ -127,41 +523,1766 @@ pub async fn create(
     }
   })?;
 
+  audit::log(
+    db,
+    Some(updated_by),
+    AuditAction::Update,
+    "user",
+    Some(id.to_string()),
+    Some(serde_json::json!({ "fields": fields })),
+  )
+  .await;
   Ok(UserDto::from(user))
 }
 
-pub async fn show(db: &DatabaseConnection, id: Uuid) -> Result<UserDto, ApiError> {
-  let user = UserEntity::find()
+pub async fn update_role(
+  db: &DatabaseConnection,
+  tenant: &TenantContext,
+  id: Uuid,
+  role: UserRole,
+  updated_by: Uuid,
+) -> Result<UserDto, ApiError> {
+  let txn = db.begin().await?;
+
+  let user = UserEntity::find_in_tenant(tenant.id())
     .filter(entities::Column::Id.eq(id))
-    .one(db)
+    .one(&txn)
     .await?
     .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
 
+  // Count admins before demoting one, so that at least one admin always remains. Their rows
+  // are locked until the commit, concurrent demotions would otherwise both see another admin
+  if user.role == UserRole::Admin && role != UserRole::Admin {
+    let admin_count = UserEntity::find_in_tenant(tenant.id())
+      .filter(entities::Column::Role.eq(UserRole::Admin))
+      .lock_exclusive()
+      .count(&txn)
+      .await?;
+    ensure_not_last_admin(admin_count)?;
+  }
+
+  let mut user: entities::ActiveModel = user.into();
+  user.role = Set(role.clone());
+  user.updated_by = Set(Some(updated_by));
+  let user = user.update(&txn).await?;
+
+  txn.commit().await?;
+
+  audit::log(
+    db,
+    Some(updated_by),
+    AuditAction::Update,
+    "user",
+    Some(id.to_string()),
+    Some(serde_json::json!({ "role": role.to_value() })),
+  )
+  .await;
   Ok(UserDto::from(user))
 }
 
-pub async fn update(db: &DatabaseConnection, id: Uuid, name: String) -> Result<UserDto, ApiError> {
-  let user = UserEntity::find()
+/// Activates or deactivates a user, deactivated users being refused at login.
+///
+/// The last active admin can't be deactivated, so that an admin always remains.
+pub async fn update_status(
+  db: &DatabaseConnection,
+  tenant: &TenantContext,
+  id: Uuid,
+  status: UserStatus,
+  updated_by: Uuid,
+) -> Result<UserDto, ApiError> {
+  let txn = db.begin().await?;
+
+  let user = UserEntity::find_in_tenant(tenant.id())
     .filter(entities::Column::Id.eq(id))
-    .one(db)
+    .one(&txn)
     .await?
     .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
 
+  // Count the active admins before deactivating one, so that at least one remains. Their rows
+  // are locked until the commit, like in `update_role`
+  if user.role == UserRole::Admin
+    && user.status == UserStatus::Active
+    && status != UserStatus::Active
+  {
+    let active_admin_count = UserEntity::find_in_tenant(tenant.id())
+      .filter(entities::Column::Role.eq(UserRole::Admin))
+      .filter(entities::Column::Status.eq(UserStatus::Active))
+      .lock_exclusive()
+      .count(&txn)
+      .await?;
+    if active_admin_count <= 1 {
+      return Err(ApiError::Forbidden(
+        "Cannot deactivate the last active admin".to_string(),
+      ));
+    }
+  }
+
   let mut user: entities::ActiveModel = user.into();
-  user.name = Set(name);
+  user.status = Set(status.clone());
+  user.updated_by = Set(Some(updated_by));
+  let user = user.update(&txn).await?;
 
-  let user = user.update(db).await?;
+  txn.commit().await?;
+
+  audit::log(
+    db,
+    Some(updated_by),
+    AuditAction::Update,
+    "user",
+    Some(id.to_string()),
+    Some(serde_json::json!({ "status": status.to_value() })),
+  )
+  .await;
   Ok(UserDto::from(user))
 }
 
-pub async fn destroy(db: &DatabaseConnection, id: Uuid) -> Result<(), ApiError> {
-  let user = UserEntity::find()
+/// Revokes every token issued to the user so far by bumping its token version, which the
+/// auth guard compares to the one of each token, along with the user's API keys.
+pub async fn revoke_sessions(
+  db: &DatabaseConnection,
+  tenant: &TenantContext,
+  id: Uuid,
+  revoked_by: Uuid,
+) -> Result<(), ApiError> {
+  let user = UserEntity::find_in_tenant(tenant.id())
+    .filter(entities::Column::Id.eq(id))
+    .one(db)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+  let txn = db.begin().await?;
+
+  // Incremented in place, so that concurrent revocations can't both write the same version
+  UserEntity::update_many()
+    .col_expr(
+      entities::Column::TokenVersion,
+      Expr::col(entities::Column::TokenVersion).add(1),
+    )
+    .col_expr(
+      entities::Column::UpdatedAt,
+      Expr::value(Some(chrono::Utc::now())),
+    )
+    .col_expr(entities::Column::UpdatedBy, Expr::value(Some(revoked_by)))
+    .filter(entities::Column::Id.eq(user.id))
+    .exec(&txn)
+    .await?;
+
+  // API keys don't carry a token version, they are revoked outright
+  ApiKeyEntities::Entity::update_many()
+    .col_expr(ApiKeyEntities::Column::Revoked, Expr::value(true))
+    .filter(ApiKeyEntities::Column::UserId.eq(user.id))
+    .filter(ApiKeyEntities::Column::Revoked.eq(false))
+    .exec(&txn)
+    .await?;
+
+  txn.commit().await?;
+
+  audit::log(
+    db,
+    Some(revoked_by),
+    AuditAction::Update,
+    "user",
+    Some(id.to_string()),
+    Some(serde_json::json!({ "fields": ["token_version"] })),
+  )
+  .await;
+  Ok(())
+}
+
+/// Avatar formats by content type, with their file extension and signature.
+const AVATAR_FORMATS: [(&str, &str, &[u8]); 2] = [
+  ("image/png", "png", b"\x89PNG\r\n\x1a\n"),
+  ("image/jpeg", "jpg", b"\xff\xd8\xff"),
+];
+
+/// Stores the avatar of a user, keyed by user id, and saves its location.
+///
+/// Only PNG and JPEG images are accepted, and the content must match its declared type.
+pub async fn set_avatar(
+  db: &DatabaseConnection,
+  storage: &dyn Storage,
+  tenant: &TenantContext,
+  id: Uuid,
+  content_type: &str,
+  content: Bytes,
+  updated_by: Uuid,
+) -> Result<UserDto, ApiError> {
+  let (_, extension, signature) = AVATAR_FORMATS
+    .iter()
+    .find(|(media_type, _, _)| *media_type == content_type)
+    .ok_or_else(|| ApiError::InvalidRequest("Avatar must be a PNG or JPEG image".to_string()))?;
+  if !content.starts_with(signature) {
+    return Err(ApiError::InvalidRequest(format!(
+      "Avatar content is not a valid {} image",
+      extension.to_uppercase()
+    )));
+  }
+
+  let user = UserEntity::find_in_tenant(tenant.id())
     .filter(entities::Column::Id.eq(id))
     .one(db)
     .await?
     .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
 
-  let user: entities::ActiveModel = user.into();
-  user.delete(db).await?;
+  let location = storage
+    .put(&format!("avatars/{}.{}", id, extension), content)
+    .await?;
+
+  let mut user: entities::ActiveModel = user.into();
+  user.avatar_url = Set(Some(location));
+  user.updated_by = Set(Some(updated_by));
+  let user = user.update(db).await?;
+
+  audit::log(
+    db,
+    Some(updated_by),
+    AuditAction::Update,
+    "user",
+    Some(id.to_string()),
+    Some(serde_json::json!({ "fields": ["avatar_url"] })),
+  )
+  .await;
+  Ok(UserDto::from(user))
+}
+
+/// Soft-deletes the given users in one transaction, revoking their sessions, and reports the
+/// outcome for each id.
+///
+/// The requesting admin and the last remaining admin are never deleted.
+pub async fn bulk_delete(
+  db: &DatabaseConnection,
+  tenant: &TenantContext,
+  current_user_id: Uuid,
+  ids: Vec<Uuid>,
+) -> Result<Vec<BulkDeleteResult>, ApiError> {
+  let txn = db.begin().await?;
+
+  let users = UserEntity::find_in_tenant(tenant.id())
+    .filter(entities::Column::Id.is_in(ids.clone()))
+    .all(&txn)
+    .await?;
+  let admin_count = UserEntity::find_in_tenant(tenant.id())
+    .filter(entities::Column::Role.eq(UserRole::Admin))
+    .lock_exclusive()
+    .count(&txn)
+    .await?;
+
+  let results = plan_bulk_delete(current_user_id, &ids, &users, admin_count);
+  let deleted_ids: Vec<Uuid> = results
+    .iter()
+    .filter(|result| result.status == BulkDeleteStatus::Deleted)
+    .filter_map(|result| Uuid::parse_str(&result.id).ok())
+    .collect();
+
+  if !deleted_ids.is_empty() {
+    let now = chrono::Utc::now();
+    UserEntity::update_many()
+      .col_expr(entities::Column::DeletedAt, Expr::value(Some(now)))
+      .col_expr(entities::Column::UpdatedAt, Expr::value(Some(now)))
+      // Revoke their sessions, like anonymizing a user does
+      .col_expr(
+        entities::Column::TokenVersion,
+        Expr::col(entities::Column::TokenVersion).add(1),
+      )
+      .col_expr(
+        entities::Column::UpdatedBy,
+        Expr::value(Some(current_user_id)),
+      )
+      .filter(entities::Column::Id.is_in(deleted_ids.clone()))
+      .exec(&txn)
+      .await?;
+  }
+
+  txn.commit().await?;
+
+  for id in deleted_ids {
+    audit::log(
+      db,
+      Some(current_user_id),
+      AuditAction::Delete,
+      "user",
+      Some(id.to_string()),
+      None,
+    )
+    .await;
+  }
+  Ok(results)
+}
+
+/// Decides, for each requested id, whether the user can be deleted.
+fn plan_bulk_delete(
+  current_user_id: Uuid,
+  ids: &[Uuid],
+  users: &[entities::Model],
+  mut admin_count: u64,
+) -> Vec<BulkDeleteResult> {
+  let mut seen = Vec::with_capacity(ids.len());
+  ids
+    .iter()
+    .filter(|id| {
+      let first = !seen.contains(*id);
+      seen.push(**id);
+      first
+    })
+    .map(|id| {
+      let result = |status, message: Option<&str>| BulkDeleteResult {
+        id: id.to_string(),
+        status,
+        message: message.map(str::to_string),
+      };
+
+      if *id == current_user_id {
+        return result(
+          BulkDeleteStatus::Forbidden,
+          Some("Cannot delete your own account"),
+        );
+      }
+      let Some(user) = users.iter().find(|user| user.id == *id) else {
+        return result(BulkDeleteStatus::NotFound, Some("User not found"));
+      };
+      if user.role == UserRole::Admin {
+        if admin_count <= 1 {
+          return result(
+            BulkDeleteStatus::Forbidden,
+            Some("Cannot delete the last remaining admin"),
+          );
+        }
+        admin_count -= 1;
+      }
+      result(BulkDeleteStatus::Deleted, None)
+    })
+    .collect()
+}
+
+/// Imports users into the tenant from a CSV with `email`, `name` and optionally `role` columns,
+/// reporting the outcome of each data row.
+///
+/// The upload is parsed as it streams in. Valid rows are inserted `BULK_INSERT_CHUNK` at a time,
+/// each chunk in its own transaction, with a random password. Emails already taken, or repeated
+/// in the file, are reported as conflicts.
+pub async fn import<S, E>(
+  db: &DatabaseConnection,
+  cfg: &Config,
+  tenant: &TenantContext,
+  body: S,
+  created_by: Uuid,
+) -> Result<Vec<UserImportResult>, ApiError>
+where
+  S: Stream<Item = Result<Bytes, E>>,
+  E: std::fmt::Display,
+{
+  let mut body = std::pin::pin!(body);
+  let mut reader = CsvReader::default();
+  let mut columns: Option<ImportColumns> = None;
+  let mut seen = HashSet::new();
+  let mut pending = Vec::new();
+  let mut results = Vec::new();
+  let mut row = 0;
+  let mut done = false;
+
+  while !done {
+    match body.next().await {
+      Some(chunk) => {
+        let chunk = chunk
+          .map_err(|e| ApiError::InvalidRequest(format!("Failed to read the CSV upload: {}", e)))?;
+        reader.push(&chunk);
+      }
+      None => done = true,
+    }
+
+    while let Some(record) = if done {
+      reader.finish()?
+    } else {
+      reader.next_record()?
+    } {
+      let Some(cols) = &columns else {
+        columns = Some(ImportColumns::from_header(&record)?);
+        continue;
+      };
+      // Blank lines are not rows
+      if record.iter().all(String::is_empty) {
+        continue;
+      }
+
+      row += 1;
+      let user = cols.row(&record);
+      match validate_import_row(&user) {
+        Err(message) => results.push(import_result(
+          row,
+          user.email,
+          UserImportStatus::ValidationError,
+          Some(message),
+        )),
+        Ok(_) if !seen.insert(user.email.clone()) => results.push(import_result(
+          row,
+          user.email,
+          UserImportStatus::Conflict,
+          Some("Email is repeated in the file".to_string()),
+        )),
+        Ok(role) => pending.push((row, user, role)),
+      }
+
+      if pending.len() >= cfg.bulk_insert_chunk.max(1) {
+        let chunk = std::mem::take(&mut pending);
+        results.extend(import_chunk(db, cfg, tenant, chunk, created_by).await?);
+      }
+    }
+  }
+
+  if columns.is_none() {
+    return Err(ApiError::InvalidRequest("CSV is empty".to_string()));
+  }
+  if !pending.is_empty() {
+    results.extend(import_chunk(db, cfg, tenant, pending, created_by).await?);
+  }
+
+  // Invalid rows are reported as they're read, inserted ones once their chunk is committed
+  results.sort_by_key(|result| result.row);
+  Ok(results)
+}
+
+/// Positions of the imported columns, from the CSV header.
+struct ImportColumns {
+  email: usize,
+  name: usize,
+  role: Option<usize>,
+}
+
+impl ImportColumns {
+  fn from_header(header: &[String]) -> Result<Self, ApiError> {
+    let position = |name: &str| {
+      header.iter().position(|column| {
+        column
+          .trim_start_matches('\u{feff}')
+          .eq_ignore_ascii_case(name)
+      })
+    };
+    match (position("email"), position("name")) {
+      (Some(email), Some(name)) => Ok(Self {
+        email,
+        name,
+        role: position("role"),
+      }),
+      _ => Err(ApiError::InvalidRequest(
+        "CSV header must contain the email and name columns".to_string(),
+      )),
+    }
+  }
+
+  fn row(&self, record: &[String]) -> UserImportRow {
+    let field = |index: usize| record.get(index).cloned().unwrap_or_default();
+    UserImportRow {
+      email: normalize_email(&field(self.email)),
+      name: field(self.name),
+      role: self.role.map(field).unwrap_or_default(),
+    }
+  }
+}
+
+/// Validates an imported row, returning its role or the errors as a single message.
+fn validate_import_row(row: &UserImportRow) -> Result<UserRole, String> {
+  let mut messages: Vec<String> = match row.validate() {
+    Ok(()) => vec![],
+    Err(e) => e
+      .field_errors()
+      .into_iter()
+      .flat_map(|(field, errors)| {
+        errors.iter().map(move |err| {
+          let message = err
+            .message
+            .as_ref()
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| "validation failed".to_string());
+          format!("{}: {}", field, message)
+        })
+      })
+      .collect(),
+  };
+  messages.sort();
+
+  let role = if row.role.is_empty() {
+    Some(UserRole::User)
+  } else {
+    UserRole::try_from_value(&row.role).ok()
+  };
+  if role.is_none() {
+    messages.push("role: must be one of: Admin, User".to_string());
+  }
+
+  match role {
+    Some(role) if messages.is_empty() => Ok(role),
+    _ => Err(messages.join("; ")),
+  }
+}
+
+fn import_result(
+  row: u64,
+  email: String,
+  status: UserImportStatus,
+  message: Option<String>,
+) -> UserImportResult {
+  UserImportResult {
+    row,
+    email,
+    status,
+    id: None,
+    message,
+  }
+}
+
+/// Inserts a chunk of valid rows in one transaction, the emails already taken being reported as
+/// conflicts instead.
+async fn import_chunk(
+  db: &DatabaseConnection,
+  cfg: &Config,
+  tenant: &TenantContext,
+  rows: Vec<(u64, UserImportRow, UserRole)>,
+  created_by: Uuid,
+) -> Result<Vec<UserImportResult>, ApiError> {
+  let cfg = cfg.clone();
+  let tenant_id = tenant.0.clone();
+  let results = transaction(db, move |txn| {
+    Box::pin(async move {
+      // Soft-deleted users keep their email, which is unique across the whole table
+      let emails: Vec<String> = rows.iter().map(|(_, user, _)| user.email.clone()).collect();
+      let taken: HashSet<String> = UserEntity::find()
+        .filter(entities::Column::Email.is_in(emails))
+        .all(txn)
+        .await?
+        .into_iter()
+        .map(|user| user.email)
+        .collect();
+
+      let mut results = Vec::with_capacity(rows.len());
+      let mut models = Vec::with_capacity(rows.len());
+      for (row, user, role) in rows {
+        if taken.contains(&user.email) {
+          results.push(import_result(
+            row,
+            user.email,
+            UserImportStatus::Conflict,
+            Some("Email already exists".to_string()),
+          ));
+          continue;
+        }
+
+        // Nobody knows this password, imported users set their own through a password reset
+        let password = Uuid::new_v4().simple().to_string();
+        let password_hash = hash_password(&password, cfg.password_hash_algo, cfg.bcrypt_cost)?;
+        let id = Uuid::new_v4();
+        models.push(entities::ActiveModel {
+          id: Set(id),
+          email: Set(user.email.clone()),
+          password: Set(password_hash),
+          name: Set(user.name),
+          role: Set(role),
+          status: Set(UserStatus::Active),
+          created_by: Set(Some(created_by)),
+          tenant_id: Set(tenant_id.clone()),
+          ..Default::default()
+        });
+        results.push(UserImportResult {
+          id: Some(id.to_string()),
+          ..import_result(row, user.email, UserImportStatus::Success, None)
+        });
+      }
+
+      insert_chunked(txn, models, cfg.bulk_insert_chunk).await?;
+      Ok(results)
+    })
+  })
+  .await?;
+
+  for result in results.iter().filter(|result| result.id.is_some()) {
+    audit::log(
+      db,
+      Some(created_by),
+      AuditAction::Create,
+      "user",
+      result.id.clone(),
+      Some(serde_json::json!({ "source": "import" })),
+    )
+    .await;
+  }
+  Ok(results)
+}
+
+fn ensure_not_last_admin(admin_count: u64) -> Result<(), ApiError> {
+  if admin_count <= 1 {
+    return Err(ApiError::Forbidden(
+      "Cannot demote the last remaining admin".to_string(),
+    ));
+  }
+  Ok(())
+}
+
+/// Rejects updates that change a field listed in `immutable_fields`, unless done by an admin.
+/// Fields sent with their current value are not considered changed.
+fn ensure_mutable_fields(
+  user: &entities::Model,
+  update: &UserPatch,
+  immutable_fields: &[String],
+  is_admin: bool,
+) -> Result<(), ApiError> {
+  if is_admin {
+    return Ok(());
+  }
+
+  let mut changed_fields = vec![];
+  if update.name.as_ref().is_some_and(|name| *name != user.name) {
+    changed_fields.push("name");
+  }
+  if update
+    .email
+    .as_ref()
+    .is_some_and(|email| normalize_email(email) != user.email)
+  {
+    changed_fields.push("email");
+  }
+
+  match changed_fields
+    .into_iter()
+    .find(|field| immutable_fields.iter().any(|f| f == field))
+  {
+    Some(field) => Err(ApiError::Forbidden(format!(
+      "Field '{}' cannot be changed",
+      field
+    ))),
+    None => Ok(()),
+  }
+}
+
+/// Deletes a user, by default anonymizing it: the email is replaced with a placeholder, the
+/// name, the password and the avatar are erased and the sessions revoked, while the row is kept
+/// for the audit logs referencing it. Anonymized users are no longer listed nor able to log in.
+pub async fn destroy(
+  db: &DatabaseConnection,
+  storage: &dyn Storage,
+  tenant: &TenantContext,
+  id: Uuid,
+  mode: UserDeleteMode,
+  deleted_by: Uuid,
+) -> Result<(), ApiError> {
+  let txn = db.begin().await?;
+
+  let user = UserEntity::find_in_tenant(tenant.id())
+    .filter(entities::Column::Id.eq(id))
+    .one(&txn)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+  // Same protections as `bulk_delete`: admins can't delete their own account, and at least
+  // one admin always remains
+  if user.role == UserRole::Admin {
+    if id == deleted_by {
+      return Err(ApiError::Forbidden(
+        "Cannot delete your own account".to_string(),
+      ));
+    }
+    let admin_count = UserEntity::find_in_tenant(tenant.id())
+      .filter(entities::Column::Role.eq(UserRole::Admin))
+      .lock_exclusive()
+      .count(&txn)
+      .await?;
+    if admin_count <= 1 {
+      return Err(ApiError::Forbidden(
+        "Cannot delete the last remaining admin".to_string(),
+      ));
+    }
+  }
+
+  let avatar_url = user.avatar_url.clone();
+  match mode {
+    UserDeleteMode::Anonymize => {
+      let token_version = user.token_version + 1;
+      let mut user: entities::ActiveModel = user.into();
+      user.email = Set(anonymized_email(id));
+      user.name = Set(String::new());
+      user.password = Set(String::new());
+      user.avatar_url = Set(None);
+      user.status = Set(UserStatus::Deleted);
+      user.token_version = Set(token_version);
+      user.updated_by = Set(Some(deleted_by));
+      user.update(&txn).await?;
+    }
+    UserDeleteMode::Hard => {
+      let user: entities::ActiveModel = user.into();
+      user.delete(&txn).await?;
+    }
+  }
+
+  txn.commit().await?;
+
+  // The user is gone either way, a leftover file is only worth a warning
+  if let Some(location) = avatar_url {
+    if let Err(e) = storage.delete(&location).await {
+      tracing::warn!(user_id = %id, "Failed to delete the avatar: {}", e);
+    }
+  }
+
+  audit::log(
+    db,
+    Some(deleted_by),
+    AuditAction::Delete,
+    "user",
+    Some(id.to_string()),
+    Some(serde_json::json!({ "mode": mode })),
+  )
+  .await;
   Ok(())
 }
+
+/// Email of an anonymized user, unique and on a domain reserved to never be delivered to.
+fn anonymized_email(id: Uuid) -> String {
+  format!("deleted-{}@example.invalid", id)
+}
+
+#[cfg(test)]
+mod tests {
+  use base64::{engine::general_purpose, Engine};
+
+  use super::*;
+  use crate::common::config::Configuration;
+  use crate::modules::users::dto::UserUpdate;
+
+  fn user() -> entities::Model {
+    entities::Model {
+      id: Uuid::nil(),
+      email: "user@example.com".to_string(),
+      name: "User".to_string(),
+      password: "hash".to_string(),
+      status: UserStatus::Active,
+      role: UserRole::User,
+      created_at: None,
+      updated_at: None,
+      created_by: None,
+      updated_by: None,
+      deleted_at: None,
+      last_login_at: None,
+      avatar_url: None,
+      tenant_id: None,
+      token_version: 0,
+    }
+  }
+
+  fn user_with(id: u128, role: UserRole) -> entities::Model {
+    entities::Model {
+      id: Uuid::from_u128(id),
+      role,
+      ..user()
+    }
+  }
+
+  fn immutable_fields() -> Vec<String> {
+    vec!["email".to_string()]
+  }
+
+  fn email_update(email: &str) -> UserPatch {
+    UserUpdate {
+      name: "User".to_string(),
+      email: Some(email.to_string()),
+    }
+    .into()
+  }
+
+  #[test]
+  fn test_admin_email_change_allowed() {
+    let result = ensure_mutable_fields(
+      &user(),
+      &email_update("other@example.com"),
+      &immutable_fields(),
+      true,
+    );
+    assert!(result.is_ok());
+  }
+
+  #[test]
+  fn test_self_service_unchanged_immutable_field_allowed() {
+    let result = ensure_mutable_fields(
+      &user(),
+      &email_update("User@Example.com"),
+      &immutable_fields(),
+      false,
+    );
+    assert!(result.is_ok());
+  }
+
+  #[test]
+  fn test_self_service_email_change_rejected() {
+    let update = email_update("other@example.com");
+    let result = ensure_mutable_fields(&user(), &update, &immutable_fields(), false);
+    assert!(matches!(result, Err(ApiError::Forbidden(_))));
+  }
+
+  #[test]
+  fn test_self_service_mutable_field_allowed() {
+    let update = UserUpdate {
+      name: "New Name".to_string(),
+      email: None,
+    };
+    let result = ensure_mutable_fields(&user(), &update.into(), &immutable_fields(), false);
+    assert!(result.is_ok());
+  }
+
+  #[test]
+  fn test_escape_like() {
+    assert_eq!(escape_like("john"), "john");
+    assert_eq!(escape_like("100%_\\"), "100\\%\\_\\\\");
+  }
+
+  #[test]
+  fn test_search_condition_matches_name_or_email() {
+    use sea_orm::{DbBackend, QueryTrait};
+
+    let sql = UserEntity::find()
+      .filter(search_condition("john"))
+      .build(DbBackend::Postgres)
+      .to_string();
+    assert!(sql.contains(r#""users"."name" ILIKE '%john%' OR "users"."email" ILIKE '%john%'"#));
+  }
+
+  #[test]
+  fn test_sort_column_mapping() {
+    assert!(matches!(sort_column(Some("name")), entities::Column::Name));
+    assert!(matches!(
+      sort_column(Some("email")),
+      entities::Column::Email
+    ));
+    assert!(matches!(
+      sort_column(Some("updated_at")),
+      entities::Column::UpdatedAt
+    ));
+    assert!(matches!(
+      sort_column(Some("created_at")),
+      entities::Column::CreatedAt
+    ));
+  }
+
+  #[test]
+  fn test_sort_column_unknown_defaults_to_created_at() {
+    assert!(matches!(
+      sort_column(Some("password")),
+      entities::Column::CreatedAt
+    ));
+    assert!(matches!(sort_column(None), entities::Column::CreatedAt));
+  }
+
+  #[test]
+  fn test_bulk_delete_partial_results() {
+    let admin = Uuid::from_u128(1);
+    let users = [user_with(2, UserRole::User)];
+    let ids = [Uuid::from_u128(2), Uuid::from_u128(3), Uuid::from_u128(2)];
+
+    let results = plan_bulk_delete(admin, &ids, &users, 1);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].status, BulkDeleteStatus::Deleted);
+    assert_eq!(results[1].status, BulkDeleteStatus::NotFound);
+  }
+
+  #[test]
+  fn test_bulk_delete_refuses_self() {
+    let admin = Uuid::from_u128(1);
+    let users = [user_with(1, UserRole::Admin), user_with(2, UserRole::Admin)];
+
+    let results = plan_bulk_delete(admin, &[admin], &users, 2);
+    assert_eq!(results[0].status, BulkDeleteStatus::Forbidden);
+    assert_eq!(
+      results[0].message.as_deref(),
+      Some("Cannot delete your own account")
+    );
+  }
+
+  #[test]
+  fn test_bulk_delete_refuses_last_admin() {
+    let admin = Uuid::from_u128(1);
+    let users = [user_with(2, UserRole::Admin), user_with(3, UserRole::Admin)];
+    let ids = [Uuid::from_u128(2), Uuid::from_u128(3)];
+
+    // The requester's token may be stale, only two admins are left in the database
+    let results = plan_bulk_delete(admin, &ids, &users, 2);
+    assert_eq!(results[0].status, BulkDeleteStatus::Deleted);
+    assert_eq!(results[1].status, BulkDeleteStatus::Forbidden);
+    assert_eq!(
+      results[1].message.as_deref(),
+      Some("Cannot delete the last remaining admin")
+    );
+  }
+
+  #[test]
+  fn test_last_admin_cannot_be_demoted() {
+    assert!(matches!(
+      ensure_not_last_admin(1),
+      Err(ApiError::Forbidden(_))
+    ));
+  }
+
+  #[test]
+  fn test_admin_can_be_demoted_when_others_remain() {
+    assert!(ensure_not_last_admin(2).is_ok());
+  }
+
+  #[tokio::test]
+  async fn test_last_active_admin_cannot_be_deactivated() {
+    let admin = user_with(1, UserRole::Admin);
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![admin.clone()]])
+      .append_query_results([[std::collections::BTreeMap::from([(
+        "num_items",
+        sea_orm::Value::from(1i64),
+      )])]])
+      .into_connection();
+
+    let result = update_status(
+      &db,
+      &TenantContext::default(),
+      admin.id,
+      UserStatus::Inactive,
+      admin.id,
+    )
+    .await;
+    match result {
+      Err(ApiError::Forbidden(message)) => {
+        assert_eq!(message, "Cannot deactivate the last active admin")
+      }
+      result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("FOR UPDATE"));
+    assert!(!log.contains("UPDATE \\\"users\\\""));
+  }
+
+  #[tokio::test]
+  async fn test_last_admin_cannot_be_demoted() {
+    let admin = user_with(1, UserRole::Admin);
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![admin.clone()]])
+      .append_query_results([[std::collections::BTreeMap::from([(
+        "num_items",
+        sea_orm::Value::from(1i64),
+      )])]])
+      .into_connection();
+
+    let result = update_role(
+      &db,
+      &TenantContext::default(),
+      admin.id,
+      UserRole::User,
+      admin.id,
+    )
+    .await;
+    assert!(matches!(result, Err(ApiError::Forbidden(_))));
+    // The admins are locked while counted, so concurrent demotions can't both pass the check
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("FOR UPDATE"));
+    assert!(!log.contains("UPDATE \\\"users\\\""));
+  }
+
+  #[tokio::test]
+  async fn test_user_is_deactivated() {
+    let user = user_with(2, UserRole::User);
+    let deactivated = entities::Model {
+      status: UserStatus::Inactive,
+      ..user.clone()
+    };
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![user.clone()], vec![deactivated]])
+      .into_connection();
+
+    let result = update_status(
+      &db,
+      &TenantContext::default(),
+      user.id,
+      UserStatus::Inactive,
+      Uuid::from_u128(1),
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.status, "Inactive");
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("UPDATE \\\"users\\\" SET \\\"status\\\""));
+    assert!(!log.contains("COUNT"));
+  }
+
+  #[tokio::test]
+  async fn test_patch_only_updates_given_fields() {
+    let updated = entities::Model {
+      email: "new@example.com".to_string(),
+      ..user()
+    };
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![user()], vec![updated]])
+      .into_connection();
+    let patch = UserPatch {
+      email: Some("New@Example.com".to_string()),
+      ..Default::default()
+    };
+
+    let result = update(
+      &db,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      Uuid::nil(),
+      patch,
+      true,
+      Uuid::nil(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.name, "User");
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("\\\"email\\\" = $"));
+    assert!(!log.contains("\\\"name\\\" = $"));
+  }
+
+  #[tokio::test]
+  async fn test_patch_cannot_demote_the_last_admin() {
+    let admin = user_with(1, UserRole::Admin);
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![admin.clone()], vec![admin.clone()]])
+      .into_connection();
+    // Roles only change through `update_role`, a role in the payload is ignored
+    let patch: UserPatch = serde_json::from_str(r#"{"role":"User"}"#).unwrap();
+
+    let result = update(
+      &db,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      admin.id,
+      patch,
+      true,
+      admin.id,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.role, "Admin");
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(!log.contains("\\\"role\\\" = $"));
+  }
+
+  #[tokio::test]
+  async fn test_role_change_is_audited() {
+    let user = user_with(2, UserRole::User);
+    let promoted = entities::Model {
+      role: UserRole::Admin,
+      ..user.clone()
+    };
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![user.clone()], vec![promoted]])
+      .append_exec_results([sea_orm::MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+      }])
+      .into_connection();
+
+    update_role(
+      &db,
+      &TenantContext::default(),
+      user.id,
+      UserRole::Admin,
+      Uuid::from_u128(1),
+    )
+    .await
+    .unwrap();
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("INSERT INTO \\\"audit_logs\\\""));
+    assert!(log.contains("\"role\": String(\"Admin\")"));
+  }
+
+  #[tokio::test]
+  async fn test_revoke_sessions_bumps_token_version() {
+    let user = user();
+    let exec = || sea_orm::MockExecResult {
+      last_insert_id: 0,
+      rows_affected: 1,
+    };
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_exec_results([exec(), exec(), exec()])
+      .into_connection();
+
+    revoke_sessions(&db, &TenantContext::default(), user.id, Uuid::from_u128(1))
+      .await
+      .unwrap();
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("\\\"token_version\\\" = \\\"token_version\\\" + $"));
+    assert!(log.contains("UPDATE \\\"api_keys\\\" SET \\\"revoked\\\" = $"));
+    assert!(log.contains("INSERT INTO \\\"audit_logs\\\""));
+  }
+
+  #[tokio::test]
+  async fn test_bulk_delete_revokes_the_sessions() {
+    let user = user();
+    let exec = || sea_orm::MockExecResult {
+      last_insert_id: 0,
+      rows_affected: 1,
+    };
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![user.clone()]])
+      .append_query_results([[std::collections::BTreeMap::from([(
+        "num_items",
+        sea_orm::Value::from(1i64),
+      )])]])
+      .append_exec_results([exec(), exec()])
+      .into_connection();
+
+    let results = bulk_delete(
+      &db,
+      &TenantContext::default(),
+      Uuid::from_u128(1),
+      vec![user.id],
+    )
+    .await
+    .unwrap();
+    assert_eq!(results[0].status, BulkDeleteStatus::Deleted);
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("\\\"deleted_at\\\" = $"));
+    assert!(log.contains("\\\"token_version\\\" = \\\"token_version\\\" + $"));
+  }
+
+  #[tokio::test]
+  async fn test_revoke_sessions_of_unknown_user_is_not_found() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([Vec::<entities::Model>::new()])
+      .into_connection();
+
+    let result = revoke_sessions(&db, &TenantContext::default(), Uuid::nil(), Uuid::nil()).await;
+    assert!(matches!(result, Err(ApiError::NotFound(_))));
+  }
+
+  #[tokio::test]
+  async fn test_set_avatar_stores_png_and_saves_location() {
+    let dir = std::env::temp_dir().join(format!("avatars-{}", Uuid::new_v4()));
+    let storage = crate::common::storage::LocalStorage::new(&dir);
+    let mut updated = user();
+    updated.avatar_url = Some(format!("{}/avatars/{}.png", dir.display(), Uuid::nil()));
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![user()], vec![updated]])
+      .into_connection();
+
+    let content = Bytes::from_static(b"\x89PNG\r\n\x1a\nimage");
+    let result = set_avatar(
+      &db,
+      &storage,
+      &TenantContext::default(),
+      Uuid::nil(),
+      "image/png",
+      content,
+      Uuid::nil(),
+    )
+    .await
+    .unwrap();
+    let location = result.avatar_url.unwrap();
+    assert!(location.ends_with(&format!("avatars/{}.png", Uuid::nil())));
+    assert_eq!(
+      tokio::fs::read(dir.join(format!("avatars/{}.png", Uuid::nil())))
+        .await
+        .unwrap(),
+      b"\x89PNG\r\n\x1a\nimage"
+    );
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("\\\"avatar_url\\\" = $"));
+
+    tokio::fs::remove_dir_all(dir).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_set_avatar_rejects_unsupported_content() {
+    let storage = crate::common::storage::LocalStorage::new(std::env::temp_dir());
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres).into_connection();
+
+    for (content_type, content) in [
+      ("image/gif", &b"GIF89a"[..]),
+      ("image/png", &b"\xff\xd8\xffjpeg"[..]),
+    ] {
+      let result = set_avatar(
+        &db,
+        &storage,
+        &TenantContext::default(),
+        Uuid::nil(),
+        content_type,
+        Bytes::copy_from_slice(content),
+        Uuid::nil(),
+      )
+      .await;
+      assert!(matches!(result, Err(ApiError::InvalidRequest(_))));
+    }
+    assert!(db.into_transaction_log().is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_create_rolls_back_when_insert_fails() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_errors([sea_orm::DbErr::Custom(
+        "duplicate key value violates unique constraint".to_string(),
+      )])
+      .into_connection();
+
+    let result = create(
+      &db,
+      &crate::common::config::Configuration::for_tests(),
+      &UserEvents::default(),
+      &TenantContext::default(),
+      "user@example.com".to_string(),
+      "password123".to_string(),
+      "User".to_string(),
+      None,
+    )
+    .await;
+    match result {
+      Err(ApiError::InvalidRequest(message)) => assert_eq!(message, "Email already exists"),
+      result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("INSERT INTO \\\"users\\\""));
+    assert!(log.contains("ROLLBACK"));
+    assert!(!log.contains("COMMIT"));
+  }
+
+  async fn cursor_page(
+    params: PaginationParams,
+    fetched: Vec<entities::Model>,
+  ) -> (CursorResponse<UserDto>, String) {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![user_with(10, UserRole::User)], fetched])
+      .into_connection();
+    let result = index(
+      &db,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      &params,
+      &UserFilter::default(),
+    )
+    .await
+    .unwrap();
+    let PaginatedResponse::Cursor(page) = result else {
+      panic!("expected a cursor page");
+    };
+    (page, format!("{:?}", db.into_transaction_log()))
+  }
+
+  fn ids(page: &CursorResponse<UserDto>) -> Vec<String> {
+    page.data.iter().map(|u| u.id.clone()).collect()
+  }
+
+  fn id(id: u128) -> String {
+    Uuid::from_u128(id).to_string()
+  }
+
+  /// Id of the item a cursor points at.
+  fn cursor_id(cursor: &Option<String>) -> Option<String> {
+    cursor
+      .as_deref()
+      .and_then(CursorPosition::decode)
+      .map(|position| position.id)
+  }
+
+  #[tokio::test]
+  async fn test_show_is_retried_after_connection_reset() {
+    let reset = DbErr::Query(sea_orm::RuntimeErr::SqlxError(sea_orm::sqlx::Error::Io(
+      std::io::ErrorKind::ConnectionReset.into(),
+    )));
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_errors([reset])
+      .append_query_results([vec![user()]])
+      .into_connection();
+
+    let found = show(
+      &db,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      Uuid::nil(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(found.email, "user@example.com");
+    assert_eq!(db.into_transaction_log().len(), 2);
+  }
+
+  #[tokio::test]
+  async fn test_user_of_another_tenant_is_not_found() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([Vec::<entities::Model>::new()])
+      .into_connection();
+    let tenant = TenantContext(Some("acme".to_string()));
+
+    let result = show(&db, &Configuration::for_tests(), &tenant, Uuid::nil()).await;
+    assert!(matches!(result, Err(ApiError::NotFound(_))));
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("\\\"tenant_id\\\" = $"));
+    assert!(log.contains("\"acme\""));
+  }
+
+  #[tokio::test]
+  async fn test_users_outside_of_tenants_only_list_each_other() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([[std::collections::BTreeMap::from([(
+        "num_items",
+        sea_orm::Value::from(0i64),
+      )])]])
+      .into_connection();
+
+    index(
+      &db,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      &PaginationParams::default(),
+      &UserFilter::default(),
+    )
+    .await
+    .unwrap();
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("\\\"tenant_id\\\" IS NULL"));
+    // Anonymized users are never listed
+    assert!(log.contains("\\\"status\\\" <> "));
+  }
+
+  #[tokio::test]
+  async fn test_cursor_forward_last_page() {
+    let params = PaginationParams {
+      cursor: Some(id(10)),
+      per_page: Some(2),
+      ..Default::default()
+    };
+    let (page, log) = cursor_page(params, vec![user_with(11, UserRole::User)]).await;
+
+    assert_eq!(ids(&page), vec![id(11)]);
+    assert!(!page.meta.has_next);
+    assert_eq!(page.meta.next_cursor, None);
+    assert!(page.meta.has_prev);
+    assert_eq!(cursor_id(&page.meta.prev_cursor), Some(id(11)));
+    assert!(log.contains("ORDER BY \\\"users\\\".\\\"created_at\\\" ASC"));
+  }
+
+  #[tokio::test]
+  async fn test_cursor_backward_page_is_reversed() {
+    let params = PaginationParams {
+      before: Some(id(10)),
+      per_page: Some(2),
+      ..Default::default()
+    };
+    // Fetched in descending order, one more than a page
+    let fetched = vec![
+      user_with(9, UserRole::User),
+      user_with(8, UserRole::User),
+      user_with(7, UserRole::User),
+    ];
+    let (page, log) = cursor_page(params, fetched).await;
+
+    assert_eq!(ids(&page), vec![id(8), id(9)]);
+    assert!(page.meta.has_prev);
+    assert_eq!(cursor_id(&page.meta.prev_cursor), Some(id(8)));
+    assert!(page.meta.has_next);
+    assert_eq!(cursor_id(&page.meta.next_cursor), Some(id(9)));
+    assert!(log.contains("ORDER BY \\\"users\\\".\\\"created_at\\\" DESC"));
+  }
+
+  #[tokio::test]
+  async fn test_cursor_backward_first_page() {
+    let params = PaginationParams {
+      before: Some(id(10)),
+      per_page: Some(2),
+      sort_dir: Some("desc".to_string()),
+      ..Default::default()
+    };
+    let (page, log) = cursor_page(params, vec![user_with(11, UserRole::User)]).await;
+
+    assert_eq!(ids(&page), vec![id(11)]);
+    assert!(!page.meta.has_prev);
+    assert_eq!(page.meta.prev_cursor, None);
+    assert!(page.meta.has_next);
+    assert_eq!(cursor_id(&page.meta.next_cursor), Some(id(11)));
+    assert!(log.contains("ORDER BY \\\"users\\\".\\\"created_at\\\" ASC"));
+  }
+
+  fn named(id: u128, name: &str) -> entities::Model {
+    entities::Model {
+      name: name.to_string(),
+      ..user_with(id, UserRole::User)
+    }
+  }
+
+  #[tokio::test]
+  async fn test_cursor_sorted_by_name_breaks_ties_by_id() {
+    // Users 1 and 2 are both named Ann, the first page starts after user 1
+    let params = PaginationParams {
+      cursor: Some(id(1)),
+      per_page: Some(1),
+      sort_by: Some("name".to_string()),
+      ..Default::default()
+    };
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([
+        vec![named(1, "Ann")],
+        vec![named(2, "Ann"), named(3, "Bob")],
+      ])
+      .into_connection();
+    let PaginatedResponse::Cursor(page) = index(
+      &db,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      &params,
+      &UserFilter::default(),
+    )
+    .await
+    .unwrap() else {
+      panic!("expected a cursor page");
+    };
+    assert_eq!(ids(&page), vec![id(2)]);
+
+    // The ties on the name are broken by the id, in the condition and the order
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("\\\"users\\\".\\\"name\\\" > $"));
+    assert!(log.contains("\\\"users\\\".\\\"id\\\" > $"));
+    assert!(log.contains("ORDER BY \\\"users\\\".\\\"name\\\" ASC, \\\"users\\\".\\\"id\\\" ASC"));
+
+    // The next cursor holds both sort keys of user 2, so the next page starts right after it,
+    // at the following Ann or at Bob, without looking it up
+    let next_cursor = page.meta.next_cursor.unwrap();
+    let position = CursorPosition::decode(&next_cursor).unwrap();
+    assert_eq!(position.sort, "name");
+    assert_eq!(position.key, serde_json::json!("Ann"));
+    assert_eq!(position.id, id(2));
+
+    let params = PaginationParams {
+      cursor: Some(next_cursor),
+      per_page: Some(1),
+      sort_by: Some("name".to_string()),
+      ..Default::default()
+    };
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([vec![named(3, "Bob")]])
+      .into_connection();
+    let PaginatedResponse::Cursor(page) = index(
+      &db,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      &params,
+      &UserFilter::default(),
+    )
+    .await
+    .unwrap() else {
+      panic!("expected a cursor page");
+    };
+    assert_eq!(ids(&page), vec![id(3)]);
+    assert!(!page.meta.has_next);
+
+    let log = db.into_transaction_log();
+    assert_eq!(log.len(), 1);
+    let log = format!("{:?}", log);
+    assert!(log.contains("String(Some(\"Ann\"))"));
+    assert!(log.contains(&id(2)));
+  }
+
+  #[tokio::test]
+  async fn test_cursor_of_another_sort_is_rejected() {
+    let cursor = CursorPosition {
+      sort: "email".to_string(),
+      key: serde_json::json!("ann@example.com"),
+      id: id(1),
+    }
+    .encode();
+    let params = PaginationParams {
+      cursor: Some(cursor),
+      sort_by: Some("name".to_string()),
+      ..Default::default()
+    };
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres).into_connection();
+    assert!(matches!(
+      index(
+        &db,
+        &Configuration::for_tests(),
+        &TenantContext::default(),
+        &params,
+        &UserFilter::default()
+      )
+      .await,
+      Err(ApiError::InvalidRequest(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_corrupt_cursors_are_invalid_requests() {
+    let position = |key: serde_json::Value, id: &str| {
+      CursorPosition {
+        sort: "name".to_string(),
+        key,
+        id: id.to_string(),
+      }
+      .encode()
+    };
+    let cursors = [
+      "not a cursor!".to_string(),
+      general_purpose::URL_SAFE_NO_PAD.encode(b"{\"sort\":\"name\"}"),
+      position(serde_json::json!("Ann"), "not-a-uuid"),
+      position(serde_json::json!(42), &id(1)),
+    ];
+
+    for cursor in cursors {
+      let params = PaginationParams {
+        cursor: Some(cursor.clone()),
+        sort_by: Some("name".to_string()),
+        ..Default::default()
+      };
+      let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres).into_connection();
+      let result = index(
+        &db,
+        &Configuration::for_tests(),
+        &TenantContext::default(),
+        &params,
+        &UserFilter::default(),
+      )
+      .await;
+      assert!(
+        matches!(&result, Err(ApiError::InvalidRequest(message)) if message == "Invalid cursor"),
+        "{cursor} was accepted"
+      );
+      // Nothing is queried for a cursor that can't be decoded
+      assert!(db.into_transaction_log().is_empty());
+    }
+  }
+
+  #[tokio::test]
+  async fn test_cursor_total_is_opt_in() {
+    let params = PaginationParams {
+      cursor: Some(id(10)),
+      ..Default::default()
+    };
+    let (page, log) = cursor_page(params, vec![user_with(11, UserRole::User)]).await;
+    assert_eq!(page.meta.total, None);
+    assert!(!log.contains("COUNT(*)"));
+  }
+
+  #[tokio::test]
+  async fn test_cursor_total_is_counted_when_requested() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([
+        vec![user_with(10, UserRole::User)],
+        vec![user_with(11, UserRole::User)],
+      ])
+      .append_query_results([[std::collections::BTreeMap::from([(
+        "num_items",
+        sea_orm::Value::from(5i64),
+      )])]])
+      .into_connection();
+    let params = PaginationParams {
+      cursor: Some(id(10)),
+      include_total: true,
+      ..Default::default()
+    };
+    let PaginatedResponse::Cursor(page) = index(
+      &db,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      &params,
+      &UserFilter::default(),
+    )
+    .await
+    .unwrap() else {
+      panic!("expected a cursor page");
+    };
+    assert_eq!(page.meta.total, Some(5));
+    assert_eq!(page.data.len(), 1);
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("COUNT(*)"));
+  }
+
+  #[tokio::test]
+  async fn test_page_past_the_end_skips_the_query() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([[std::collections::BTreeMap::from([(
+        "num_items",
+        sea_orm::Value::from(45i64),
+      )])]])
+      .into_connection();
+    let params = PaginationParams {
+      page: Some(1_000_000_000),
+      ..Default::default()
+    };
+
+    let PaginatedResponse::Page(page) = index(
+      &db,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      &params,
+      &UserFilter::default(),
+    )
+    .await
+    .unwrap() else {
+      panic!("expected a page");
+    };
+    assert!(page.data.is_empty());
+    assert_eq!(page.meta.total, 45);
+    assert_eq!(page.meta.page, 1_000_000_000);
+    assert_eq!(page.meta.total_pages, 3);
+
+    // Only the count query ran
+    assert_eq!(db.into_transaction_log().len(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_filter_applies_to_the_count() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([[std::collections::BTreeMap::from([(
+        "num_items",
+        sea_orm::Value::from(0i64),
+      )])]])
+      .into_connection();
+    let filter = UserFilter {
+      status: Some(UserStatus::Banned),
+      role: Some(UserRole::Admin),
+    };
+
+    index(
+      &db,
+      &Configuration::for_tests(),
+      &TenantContext::default(),
+      &PaginationParams::default(),
+      &filter,
+    )
+    .await
+    .unwrap();
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("\\\"status\\\" = "));
+    assert!(log.contains("\\\"role\\\" = "));
+    assert!(log.contains("\"Banned\""));
+  }
+
+  #[tokio::test]
+  async fn test_cursor_and_before_are_exclusive() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres).into_connection();
+    let params = PaginationParams {
+      cursor: Some(id(10)),
+      before: Some(id(10)),
+      ..Default::default()
+    };
+    assert!(matches!(
+      index(
+        &db,
+        &Configuration::for_tests(),
+        &TenantContext::default(),
+        &params,
+        &UserFilter::default()
+      )
+      .await,
+      Err(ApiError::InvalidRequest(_))
+    ));
+  }
+
+  async fn run_import(
+    db: &DatabaseConnection,
+    csv: &'static str,
+  ) -> Result<Vec<UserImportResult>, ApiError> {
+    // Split in small chunks, as uploads arrive
+    let chunks: Vec<Result<Bytes, std::convert::Infallible>> = csv
+      .as_bytes()
+      .chunks(7)
+      .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+      .collect();
+    import(
+      db,
+      &crate::common::config::Configuration::for_tests(),
+      &TenantContext::default(),
+      futures_util::stream::iter(chunks),
+      Uuid::nil(),
+    )
+    .await
+  }
+
+  fn import_db(existing: Vec<entities::Model>) -> DatabaseConnection {
+    sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres)
+      .append_query_results([existing])
+      .append_exec_results([sea_orm::MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 2,
+      }])
+      .into_connection()
+  }
+
+  #[tokio::test]
+  async fn test_import_valid_csv() {
+    let db = import_db(vec![]);
+
+    let results = run_import(
+      &db,
+      "email,name,role\r\nadmin@example.com,\"Doe, Jane\",Admin\r\nuser@example.com,User,\r\n",
+    )
+    .await
+    .unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results
+      .iter()
+      .all(|result| result.status == UserImportStatus::Success && result.id.is_some()));
+    assert_eq!(results[0].email, "admin@example.com");
+    assert_eq!(results[1].row, 2);
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("INSERT INTO \\\"users\\\""));
+    assert!(log.contains("Doe, Jane"));
+    assert!(log.contains("COMMIT"));
+  }
+
+  #[tokio::test]
+  async fn test_import_reports_bad_rows() {
+    let db = import_db(vec![entities::Model {
+      email: "taken@example.com".to_string(),
+      ..user()
+    }]);
+
+    let csv = "name,email\n\
+      Bad,not-an-email\n\
+      Valid,user@example.com\n\
+      Again,user@example.com\n\
+      Taken,taken@example.com\n\
+      ,other@example.com";
+    let results = run_import(&db, csv).await.unwrap();
+    let statuses: Vec<UserImportStatus> = results.iter().map(|result| result.status).collect();
+    assert_eq!(
+      statuses,
+      vec![
+        UserImportStatus::ValidationError,
+        UserImportStatus::Success,
+        UserImportStatus::Conflict,
+        UserImportStatus::Conflict,
+        UserImportStatus::ValidationError,
+      ]
+    );
+    assert_eq!(
+      results[0].message.as_deref(),
+      Some("email: invalid email format")
+    );
+    assert_eq!(
+      results[4].message.as_deref(),
+      Some("name: must be between 1 and 100 characters")
+    );
+    assert_eq!(results[3].message.as_deref(), Some("Email already exists"));
+  }
+
+  #[test]
+  fn test_import_rejects_invalid_role() {
+    let row = UserImportRow {
+      email: "user@example.com".to_string(),
+      name: "User".to_string(),
+      role: "Owner".to_string(),
+    };
+    assert_eq!(
+      validate_import_row(&row),
+      Err("role: must be one of: Admin, User".to_string())
+    );
+  }
+
+  #[tokio::test]
+  async fn test_import_requires_header_columns() {
+    let db = sea_orm::MockDatabase::new(sea_orm::DbBackend::Postgres).into_connection();
+    assert!(matches!(
+      run_import(&db, "email,role\nuser@example.com,User\n").await,
+      Err(ApiError::InvalidRequest(_))
+    ));
+    assert!(matches!(
+      run_import(&db, "").await,
+      Err(ApiError::InvalidRequest(_))
+    ));
+  }
+}